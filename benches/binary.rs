@@ -0,0 +1,75 @@
+//! Compares loading a large source map from the binary cache format with
+//! `Cache::deserialize_binary`, and without loading it with `MappedCache::from_bytes`,
+//! against parsing its JSON with `consume`.
+//!
+//! Run with `cargo bench --bench binary`.
+extern crate criterion;
+extern crate js_source_mapper;
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+
+use js_source_mapper::{Cache, MappedCache, base64_vlq, consume};
+
+// A source map like a bundle's: 300 sources, 1000 names and long lines of 4 and 5 field
+// segments in generated order
+fn large_source_map() -> String {
+  let mut state = 0x2545_f491u32;
+  let mut random = move |range: u32| {
+    state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+    (state >> 8) % range
+  };
+  let push = |mappings: &mut String, value: i32| mappings.push_str(std::str::from_utf8(&base64_vlq::encode(value).unwrap()).unwrap());
+  let mut mappings = String::new();
+  let (mut source, mut original_line, mut original_column, mut name) = (0, 0, 0, 0);
+  for line in 0..2_000 {
+    if line > 0 {
+      mappings.push(';');
+    }
+    for segment in 0..250 {
+      if segment > 0 {
+        mappings.push(',');
+      }
+      let (next_source, next_line, next_column) = (random(300) as i32, random(5_000) as i32, random(120) as i32);
+      push(&mut mappings, 1 + random(40) as i32);
+      push(&mut mappings, next_source - source);
+      push(&mut mappings, next_line - original_line);
+      push(&mut mappings, next_column - original_column);
+      source = next_source;
+      original_line = next_line;
+      original_column = next_column;
+      if random(4) == 0 {
+        let next_name = random(1_000) as i32;
+        push(&mut mappings, next_name - name);
+        name = next_name;
+      }
+    }
+  }
+  let sources: Vec<String> = (0..300).map(|index| format!(r#""webpack:///src/module{}.js""#, index)).collect();
+  let names: Vec<String> = (0..1_000).map(|index| format!(r#""name{}""#, index)).collect();
+  format!(
+    r#"{{ "version": 3, "file": "bundle.js", "sources": [{}], "names": [{}], "mappings": "{}" }}"#,
+    sources.join(","), names.join(","), mappings
+  )
+}
+
+fn load(c: &mut Criterion) {
+  let json = large_source_map();
+  let cache = consume(&json).unwrap();
+  assert_eq!(cache.len(), 500_000);
+  let bytes = cache.serialize_binary();
+  println!("{} bytes of JSON, {} bytes of binary cache", json.len(), bytes.len());
+
+  let mut group = c.benchmark_group("load large map");
+  group.sample_size(20);
+  group.bench_function("consume", |b| b.iter(|| consume(black_box(&json)).unwrap()));
+  group.bench_function("deserialize_binary", |b| b.iter(|| Cache::deserialize_binary(black_box(&bytes)).unwrap()));
+  group.bench_function("MappedCache::from_bytes", |b| b.iter_batched(
+    || bytes.clone(),
+    |bytes| MappedCache::from_bytes(bytes).unwrap(),
+    BatchSize::LargeInput
+  ));
+  group.finish();
+}
+
+criterion_group!(benches, load);
+criterion_main!(benches);
@@ -0,0 +1,155 @@
+//! Compares decoding a large `mappings` field with `MappingsDecoder`, a single forward
+//! scan, against the previous decoder that split the field into lines and segments
+//! first. Also compares decoding its base 64 digits with the lookup table of
+//! `base64::decode` against the range comparisons it replaced.
+//!
+//! Run with `cargo bench --bench decode`.
+extern crate criterion;
+extern crate js_source_mapper;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use js_source_mapper::{Error, base64, base64_vlq};
+use js_source_mapper::mappings::{DecodedSegment, MappingsDecoder};
+
+// A mappings field like a minified bundle's: long lines of 4 and 5 field segments
+fn large_mappings() -> String {
+  let mut state = 0x2545_f491u32;
+  let mut random = move |range: u32| {
+    state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+    (state >> 8) % range
+  };
+  let mut mappings = String::new();
+  for line in 0..2_000 {
+    if line > 0 {
+      mappings.push(';');
+    }
+    for segment in 0..500 {
+      if segment > 0 {
+        mappings.push(',');
+      }
+      let field_count = if random(4) == 0 { 5 } else { 4 };
+      for _ in 0..field_count {
+        let value = random(400) as i32 - 200;
+        mappings.push_str(std::str::from_utf8(&base64_vlq::encode(value).unwrap()).unwrap());
+      }
+    }
+  }
+  mappings
+}
+
+// The decoder before the single forward scan, which found the end of each segment
+// before decoding its fields
+struct SplittingDecoder<'a> {
+  mappings: &'a [u8],
+  position: usize,
+  generated_line: u32,
+  failed: bool
+}
+
+impl<'a> Iterator for SplittingDecoder<'a> {
+  type Item = Result<DecodedSegment, Error>;
+
+  fn next(&mut self) -> Option<Result<DecodedSegment, Error>> {
+    while !self.failed && self.position <= self.mappings.len() {
+      let rest = &self.mappings[self.position..];
+      let length = rest.iter().position(|&byte| byte == b',' || byte == b';').unwrap_or(rest.len());
+      let segment = &rest[..length];
+      let generated_line = self.generated_line;
+      if rest.get(length) == Some(&b';') {
+        self.generated_line += 1;
+      }
+      self.position += length + 1;
+
+      if !segment.is_empty() {
+        let decoded = decode_segment(segment, generated_line);
+        self.failed = decoded.is_err();
+        return Some(decoded);
+      }
+    }
+    None
+  }
+}
+
+fn decode_segment(segment: &[u8], generated_line: u32) -> Result<DecodedSegment, Error> {
+  let mut decoded = DecodedSegment { generated_line, fields: [0; 5], field_count: 0 };
+  let mut field_count = 0;
+  let mut character_index = 0;
+  while character_index < segment.len() {
+    match base64_vlq::decode(&segment[character_index..]) {
+      Some((value, field_length)) => {
+        if field_count == decoded.fields.len() {
+          return Err(Error::from("Found more than five fields"));
+        }
+        decoded.fields[field_count] = value;
+        field_count += 1;
+        character_index += field_length;
+      },
+      None => return Err(Error::from("Invalid VLQ mapping field"))
+    }
+  }
+  match field_count {
+    2 | 3 => Err(Error::from("Invalid segment")),
+    _ => {
+      decoded.field_count = field_count as u8;
+      Ok(decoded)
+    }
+  }
+}
+
+// Sums the fields, so that nothing is optimized away
+fn sum_fields<I: Iterator<Item = Result<DecodedSegment, Error>>>(segments: I) -> i64 {
+  segments
+    .map(|segment| segment.unwrap().fields().iter().map(|&field| field as i64).sum::<i64>())
+    .sum()
+}
+
+fn decode_by_splitting(mappings: &str) -> i64 {
+  sum_fields(SplittingDecoder { mappings: mappings.as_bytes(), position: 0, generated_line: 1, failed: false })
+}
+
+fn decode_in_one_pass(mappings: &str) -> i64 {
+  sum_fields(MappingsDecoder::new(mappings))
+}
+
+fn decode(c: &mut Criterion) {
+  let mappings = large_mappings();
+  assert_eq!(decode_in_one_pass(&mappings), decode_by_splitting(&mappings));
+
+  let mut group = c.benchmark_group("decode");
+  group.throughput(Throughput::Bytes(mappings.len() as u64));
+  group.bench_function("split", |b| b.iter(|| decode_by_splitting(black_box(&mappings))));
+  group.bench_function("single pass", |b| b.iter(|| decode_in_one_pass(black_box(&mappings))));
+  group.finish();
+}
+
+// The base 64 digit decoder before the lookup table
+fn decode_digit_by_ranges(char_code: u8) -> Option<i32> {
+  match char_code {
+    b'A'..=b'Z' => Some((char_code - b'A') as i32),
+    b'a'..=b'z' => Some((char_code - b'a' + 26) as i32),
+    b'0'..=b'9' => Some((char_code - b'0' + 52) as i32),
+    b'+' | b'-' => Some(62),
+    b'/' | b'_' => Some(63),
+    _ => None
+  }
+}
+
+// Sums the digits, counting the separators and other invalid digits as -1
+fn sum_digits<F: Fn(u8) -> Option<i32>>(mappings: &str, decode_digit: F) -> i64 {
+  mappings.bytes().map(|char_code| decode_digit(char_code).unwrap_or(-1) as i64).sum()
+}
+
+fn decode_digits(c: &mut Criterion) {
+  let mappings = large_mappings();
+  assert_eq!(sum_digits(&mappings, base64::decode), sum_digits(&mappings, decode_digit_by_ranges));
+
+  let mut group = c.benchmark_group("decode base 64 digits");
+  group.throughput(Throughput::Bytes(mappings.len() as u64));
+  group.bench_function("ranges", |b| b.iter(|| sum_digits(black_box(&mappings), decode_digit_by_ranges)));
+  group.bench_function("lookup table", |b| b.iter(|| sum_digits(black_box(&mappings), base64::decode)));
+  group.finish();
+}
+
+criterion_group!(benches, decode, decode_digits);
+criterion_main!(benches);
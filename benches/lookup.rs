@@ -0,0 +1,66 @@
+//! Times lookups in a large generated source map, against a binary search of all
+//! mappings as lookups did before caches indexed their mappings by line.
+//!
+//! Run with `cargo bench --bench lookup`. `LOOKUP_BENCH_LINES` sets the number of
+//! generated lines, and `LOOKUP_BENCH_MAPPINGS_PER_LINE` the mappings on each, 20 unless
+//! set. A minified bundle is `LOOKUP_BENCH_LINES=1 LOOKUP_BENCH_MAPPINGS_PER_LINE=2000000`.
+extern crate js_source_mapper;
+
+use std::env;
+use std::hint::black_box;
+use std::time::Instant;
+
+use js_source_mapper::{Cache, CodePosition, Mapping};
+
+const LOOKUPS: usize = 2_000_000;
+
+fn search_all(mappings: &[Mapping], line: u32, column: u32) -> &Mapping {
+  let index = match mappings.binary_search_by(|mapping| (mapping.generated.line, mapping.generated.column).cmp(&(line, column))) {
+    Ok(index) | Err(index) => index
+  };
+  &mappings[index.min(mappings.len() - 1)]
+}
+
+fn main() {
+  let lines = env::var("LOOKUP_BENCH_LINES").ok().and_then(|lines| lines.parse().ok()).unwrap_or(100_000u32);
+  let mappings_per_line = env::var("LOOKUP_BENCH_MAPPINGS_PER_LINE").ok().and_then(|count| count.parse().ok()).unwrap_or(20u32);
+  let mut mappings = Vec::with_capacity((lines * mappings_per_line) as usize);
+  for line in 1..lines + 1 {
+    for column in 0..mappings_per_line {
+      mappings.push(Mapping {
+        generated: CodePosition { line, column: column * 7 },
+        original: CodePosition { line, column },
+        source: "app.js".into(),
+        name: "".into()
+      });
+    }
+  }
+  let cache = Cache::from_mappings(mappings, "").unwrap();
+
+  // A fixed linear congruential sequence, so every run looks up the same positions
+  let mut state = 0x2545_f491u64;
+  let positions: Vec<(u32, u32)> = (0..LOOKUPS).map(|_| {
+    state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+    ((state >> 33) as u32 % lines + 1, (state >> 13) as u32 % (mappings_per_line * 7))
+  }).collect();
+
+  for &(line, column) in positions.iter().take(10_000) {
+    assert_eq!(cache.mapping_for_generated_position(line, column), *search_all(cache.mappings(), line, column));
+  }
+
+  let start = Instant::now();
+  for &(line, column) in &positions {
+    black_box(search_all(cache.mappings(), black_box(line), black_box(column)));
+  }
+  let flat = start.elapsed();
+
+  let start = Instant::now();
+  for &(line, column) in &positions {
+    black_box(cache.mapping_for_generated_position(black_box(line), black_box(column)));
+  }
+  let indexed = start.elapsed();
+
+  println!("{} lookups in {} mappings", LOOKUPS, cache.mappings().len());
+  println!("binary search of all mappings: {:>8.1} ns/lookup", flat.as_nanos() as f64 / LOOKUPS as f64);
+  println!("line index:                    {:>8.1} ns/lookup", indexed.as_nanos() as f64 / LOOKUPS as f64);
+}
@@ -0,0 +1,83 @@
+//! Times parsing a large `mappings` field that is already in order, as bundlers emit
+//! them, which skips the sort, against the sort of the mappings that parsing did before.
+//! Also times sorting the mappings shuffled, which is done on rayon's thread pool with
+//! the `parallel` feature.
+//!
+//! Run with `cargo bench --bench parse`, and `--features parallel` for the parallel sort.
+extern crate criterion;
+extern crate js_source_mapper;
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+
+use js_source_mapper::{base64_vlq, parse_mappings_str, sort_mappings};
+
+// A mappings field in generated order: long lines of segments with increasing columns
+// and scattered original positions
+fn sorted_mappings() -> String {
+  let mut state = 0x2545_f491u32;
+  let mut random = move |range: u32| {
+    state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+    (state >> 8) % range
+  };
+  let push = |mappings: &mut String, value: i32| mappings.push_str(std::str::from_utf8(&base64_vlq::encode(value).unwrap()).unwrap());
+  let mut mappings = String::new();
+  let (mut original_line, mut original_column) = (0, 0);
+  for line in 0..2_000 {
+    if line > 0 {
+      mappings.push(';');
+    }
+    for segment in 0..500 {
+      if segment > 0 {
+        mappings.push(',');
+      }
+      let (next_line, next_column) = (random(5_000) as i32, random(120) as i32);
+      push(&mut mappings, 1 + random(40) as i32);
+      push(&mut mappings, 0);
+      push(&mut mappings, next_line - original_line);
+      push(&mut mappings, next_column - original_column);
+      original_line = next_line;
+      original_column = next_column;
+    }
+  }
+  mappings
+}
+
+fn parse(c: &mut Criterion) {
+  let mappings = sorted_mappings();
+  let sources = ["a.js".to_owned()];
+  let cache = parse_mappings_str(&mappings, &sources, &[], "").unwrap();
+  assert_eq!(cache.len(), 1_000_000);
+
+  let mut group = c.benchmark_group("parse sorted map");
+  group.sample_size(20);
+  group.bench_function("parse", |b| b.iter(|| parse_mappings_str(black_box(&mappings), &sources, &[], "").unwrap()));
+  group.bench_function("sort as before", |b| b.iter_batched(
+    || cache.mappings().to_vec(),
+    |mut mappings| {
+      sort_mappings(&mut mappings);
+      mappings
+    },
+    BatchSize::LargeInput
+  ));
+  group.finish();
+
+  // The mappings of each line in reverse, as out of order as maps get
+  let mut shuffled = cache.mappings().to_vec();
+  for line in shuffled.chunks_mut(500) {
+    line.reverse();
+  }
+  let mut group = c.benchmark_group("sort shuffled mappings");
+  group.sample_size(20);
+  group.bench_function("sort", |b| b.iter_batched(
+    || shuffled.clone(),
+    |mut mappings| {
+      sort_mappings(&mut mappings);
+      mappings
+    },
+    BatchSize::LargeInput
+  ));
+  group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
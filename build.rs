@@ -0,0 +1,22 @@
+// Generates the C header of the capi feature
+#[cfg(feature = "capi")]
+extern crate cbindgen;
+
+#[cfg(feature = "capi")]
+fn main() {
+  use std::env;
+
+  let crate_dir = env::var("CARGO_MANIFEST_DIR").expect("cargo sets CARGO_MANIFEST_DIR");
+  println!("cargo:rerun-if-changed=src/capi.rs");
+  println!("cargo:rerun-if-changed=cbindgen.toml");
+  let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir)).expect("cbindgen.toml should be valid");
+  cbindgen::Builder::new()
+    .with_crate(&crate_dir)
+    .with_config(config)
+    .generate()
+    .expect("the C header should generate")
+    .write_to_file(format!("{}/include/js_source_mapper.h", crate_dir));
+}
+
+#[cfg(not(feature = "capi"))]
+fn main() {}
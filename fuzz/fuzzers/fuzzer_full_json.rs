@@ -0,0 +1,27 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+
+extern crate js_source_mapper;
+
+use js_source_mapper::consume;
+
+fuzz_target!(|data: &[u8]| {
+    // The whole document is fuzzed, so the JSON layer, the tables, sourceRoot and
+    // version handling are explored along with the mappings
+    let json = String::from_utf8_lossy(data);
+    let cache = match consume(&json) {
+        Ok(cache) => cache,
+        _ => return
+    };
+
+    // Look up a few fixed positions, and the positions of the first mappings and just
+    // past them
+    for &(line, column) in &[(0, 0), (1, 0), (1, 5), (2, 2), (u32::max_value(), u32::max_value())] {
+        cache.mapping_for_generated_position(line, column);
+    }
+    for mapping in cache.mappings().iter().take(16) {
+        let found = cache.mapping_for_generated_position(mapping.generated.line, mapping.generated.column);
+        assert_eq!(found.generated, mapping.generated);
+        cache.mapping_for_generated_position(mapping.generated.line, mapping.generated.column.wrapping_add(1));
+    }
+});
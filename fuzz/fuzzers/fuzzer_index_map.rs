@@ -0,0 +1,79 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+#[macro_use] extern crate arbitrary;
+
+extern crate js_source_mapper;
+
+use js_source_mapper::{consume, Cache};
+
+const NO_MAPPINGS: &str = "Source Map contains no mappings";
+
+#[derive(Arbitrary, Debug)]
+struct FuzzSection {
+    line_gap: u8,
+    column: u8,
+    mappings: String
+}
+
+// The source map of a section, with mappings restricted to the characters they are
+// made of, so they need no escaping
+fn section_map(index: usize, mappings: &str) -> String {
+    let mappings: String = mappings.chars().filter(|c| c.is_ascii_alphanumeric() || "+/,;".contains(*c)).collect();
+    format!(r#"{{ "version": 3, "sources": ["section{}.js"], "names": ["name{}"], "mappings": "{}" }}"#, index, index, mappings)
+}
+
+fuzz_target!(|input: Vec<FuzzSection>| {
+    // Sections at fuzz-chosen offsets, each starting after the previous one
+    let mut offsets: Vec<(u32, u32)> = Vec::with_capacity(input.len());
+    for section in &input {
+        let gap = (section.line_gap % 8) as u32;
+        let offset = match offsets.last() {
+            Some(&(line, column)) if gap == 0 => (line, column + 1 + (section.column % 64) as u32),
+            Some(&(line, _)) => (line + gap, (section.column % 64) as u32),
+            None => (gap, (section.column % 64) as u32)
+        };
+        offsets.push(offset);
+    }
+    let maps: Vec<String> = input.iter().enumerate().map(|(index, section)| section_map(index, &section.mappings)).collect();
+    let sections: Vec<String> = maps.iter().zip(&offsets)
+        .map(|(map, &(line, column))| format!(r#"{{ "offset": {{ "line": {}, "column": {} }}, "map": {} }}"#, line, column, map))
+        .collect();
+    let json = format!(r#"{{ "version": 3, "file": "bundle.js", "sections": [{}] }}"#, sections.join(", "));
+
+    // Consuming the sections on their own must succeed exactly when the index map does.
+    // Sections without mappings are valid in an index map, while consume rejects them
+    let direct: Result<Vec<Cache>, _> = maps.iter().zip(&offsets)
+        .filter_map(|(map, &(line, column))| match consume(map) {
+            Err(ref err) if err.to_string() == NO_MAPPINGS => None,
+            result => Some(result.and_then(|cache| cache.offset_generated(line, column)))
+        })
+        .collect();
+    let cache = match (consume(&json), direct) {
+        (Ok(cache), Ok(direct)) => {
+            // Every mapping of a section is found at its generated position in the
+            // flattened cache
+            for section in &direct {
+                for mapping in section.mappings().iter().take(16) {
+                    let found = cache.mapping_for_generated_position(mapping.generated.line, mapping.generated.column);
+                    assert_eq!(found.generated, mapping.generated);
+                    assert!(cache.mappings_for_generated_line(mapping.generated.line).contains(mapping), "{:?} was not flattened", mapping);
+                }
+            }
+            cache
+        },
+        (Err(_), Err(_)) => return,
+        (Err(ref err), Ok(ref direct)) if direct.is_empty() && err.to_string() == NO_MAPPINGS => return,
+        (flattened, direct) => panic!("the index map gave {:?} but its sections gave {:?}", flattened.err(), direct.err())
+    };
+
+    // Mappings are in generated order, and lookups around them do not panic
+    for pair in cache.mappings().windows(2) {
+        assert!(pair[0].generated <= pair[1].generated, "{:?} is after {:?}", pair[0], pair[1]);
+    }
+    for mapping in cache.mappings().iter().take(16) {
+        cache.mapping_for_generated_position(mapping.generated.line, mapping.generated.column.wrapping_add(1));
+        cache.mapping_for_generated_position(mapping.generated.line + 1, 0);
+    }
+    cache.mapping_for_generated_position(0, 0);
+    cache.mapping_for_generated_position(u32::max_value(), u32::max_value());
+});
@@ -0,0 +1,73 @@
+#![no_main]
+#[macro_use] extern crate libfuzzer_sys;
+#[macro_use] extern crate arbitrary;
+
+extern crate js_source_mapper;
+
+use js_source_mapper::{consume, sort_mappings, CodePosition, Generator, Mapping};
+
+const SOURCES: [&str; 3] = ["a.js", "b.js", "c.js"];
+const NAMES: [&str; 2] = ["x", "y"];
+
+#[derive(Arbitrary, Debug)]
+struct FuzzMapping {
+    line_gap: u8,
+    column: u16,
+    original_line: u16,
+    original_column: u16,
+    source: Option<u8>,
+    name: Option<u8>
+}
+
+// Builds well-formed mappings, sorted like a cache sorts them
+fn to_mappings(input: &[FuzzMapping]) -> Vec<Mapping> {
+    let mut line = 1;
+    let mut mappings: Vec<Mapping> = input.iter().map(|m| {
+        line += (m.line_gap % 4) as u32;
+        let source = m.source.map(|index| SOURCES[index as usize % SOURCES.len()]);
+        Mapping {
+            generated: CodePosition { line: line, column: m.column as u32 },
+            original: match source {
+                Some(_) => CodePosition { line: m.original_line as u32 + 1, column: m.original_column as u32 },
+                None => CodePosition { line: 0, column: 0 }
+            },
+            source: source.unwrap_or("").into(),
+            name: match (source, m.name) {
+                (Some(_), Some(index)) => NAMES[index as usize % NAMES.len()].into(),
+                _ => "".into()
+            }
+        }
+    }).collect();
+    sort_mappings(&mut mappings);
+    mappings
+}
+
+fuzz_target!(|input: Vec<FuzzMapping>| {
+    let mappings = to_mappings(&input);
+    if mappings.is_empty() {
+        return;
+    }
+
+    let mut generator = Generator::new();
+    for mapping in &mappings {
+        generator.add_mapping(mapping.generated.clone(), mapping.original.clone(), &mapping.source, &mapping.name);
+    }
+    let json = generator.to_json().expect("well-formed mappings should encode");
+    let cache = consume(&json).expect("encoded mappings should decode");
+
+    // Every mapping must be found again at its generated position, and lookups around
+    // it must not panic
+    for mapping in &mappings {
+        let found = cache.mapping_for_generated_position(mapping.generated.line, mapping.generated.column);
+        assert_eq!(found.generated, mapping.generated);
+        assert!(mappings.contains(&found), "{:?} was not in the input", found);
+
+        cache.mapping_for_generated_position(mapping.generated.line, mapping.generated.column.wrapping_add(1));
+        cache.mapping_for_generated_position(mapping.generated.line + 1, 0);
+    }
+    cache.mapping_for_generated_position(0, 0);
+    cache.mapping_for_generated_position(u32::max_value(), u32::max_value());
+
+    // Encoding the decoded cache again must give the same source map
+    assert_eq!(cache.to_json().unwrap(), json);
+});
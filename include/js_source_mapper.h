@@ -0,0 +1,124 @@
+#ifndef JS_SOURCE_MAPPER_H
+#define JS_SOURCE_MAPPER_H
+
+/* Generated by cbindgen from src/capi.rs with the capi feature, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/*
+ * The outcome of a call that can fail.
+ */
+typedef enum jsm_status {
+  JSM_OK = 0,
+  /*
+   * A required pointer argument was null
+   */
+  JSM_NULL_ARGUMENT = 1,
+  /*
+   * The source map is not valid UTF-8
+   */
+  JSM_INVALID_UTF8 = 2,
+  /*
+   * The source map could not be parsed
+   */
+  JSM_INVALID_SOURCE_MAP = 3,
+} jsm_status;
+
+/*
+ * A parsed source map. Created by `jsm_consume` and freed with `jsm_cache_free`.
+ */
+typedef struct jsm_cache jsm_cache;
+
+/*
+ * The error of a failed call. Initialize it to zero before the call. On failure,
+ * `message` is a NUL-terminated UTF-8 string owned by the caller, to be freed with
+ * `jsm_string_free`. When the status is `JSM_INVALID_SOURCE_MAP`, `code` is the stable
+ * error code of the Rust crate, such as 2 for an unsupported version, and it is 0
+ * otherwise.
+ */
+typedef struct jsm_error {
+  enum jsm_status status;
+  char *message;
+  uint32_t code;
+} jsm_error;
+
+/*
+ * A mapping filled in by `jsm_lookup`. Lines start from 1 and columns from 0. `source`
+ * and `name` are NUL-terminated UTF-8 strings owned by the cache, valid until
+ * `jsm_cache_free`, and empty when absent.
+ */
+typedef struct jsm_mapping {
+  uint32_t generated_line;
+  uint32_t generated_column;
+  uint32_t original_line;
+  uint32_t original_column;
+  const char *source;
+  const char *name;
+} jsm_mapping;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ * Parses `len` bytes of source map JSON, which need not be NUL-terminated and are not
+ * retained. Returns null and fills in `err`, if not null, on failure.
+ *
+ * # Safety
+ *
+ * `json` must point to `len` readable bytes, and `err` must be null or point to a
+ * `jsm_error`.
+ */
+struct jsm_cache *jsm_consume(const char *json, size_t len, struct jsm_error *err);
+
+/*
+ * Fills in `out` with the mapping of a generated position. Returns true if the
+ * position maps to an original source, and false otherwise or if an argument is null.
+ *
+ * # Safety
+ *
+ * `cache` must be null or returned by `jsm_consume` and not freed, and `out` must be
+ * null or point to a `jsm_mapping`.
+ */
+bool jsm_lookup(const struct jsm_cache *cache,
+                uint32_t line,
+                uint32_t column,
+                struct jsm_mapping *out);
+
+/*
+ * Returns the message of a failed call, owned by `err`, or null if there is none.
+ *
+ * # Safety
+ *
+ * `err` must be null or point to a `jsm_error`.
+ */
+const char *jsm_error_message(const struct jsm_error *err);
+
+/*
+ * Frees a cache and the strings of the mappings looked up in it. Does nothing for null.
+ *
+ * # Safety
+ *
+ * `cache` must be null or returned by `jsm_consume` and not already freed.
+ */
+void jsm_cache_free(struct jsm_cache *cache);
+
+/*
+ * Frees a string allocated by this library, such as an error message. Does nothing for
+ * null.
+ *
+ * # Safety
+ *
+ * `string` must be null or allocated by this library and not already freed.
+ */
+void jsm_string_free(char *string);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* JS_SOURCE_MAPPER_H */
@@ -0,0 +1,38 @@
+"use strict";
+
+const assert = require("assert");
+const fs = require("fs");
+const path = require("path");
+const test = require("node:test");
+
+const { consume, consumeAsync } = require("..");
+
+const fixture = path.join(__dirname, "..", "..", "tests", "cli", "app.js.map");
+const expected = {
+  generated: { line: 1, column: 9 },
+  original: { line: 1, column: 9 },
+  source: "src/app.js",
+  name: "handleClick"
+};
+
+test("looks up positions in a map given as a string", () => {
+  const cache = consume(fs.readFileSync(fixture, "utf8"));
+  assert.deepStrictEqual(cache.mappingForGeneratedPosition(1, 9), expected);
+  assert.deepStrictEqual(cache.sources(), ["src/app.js"]);
+});
+
+test("looks up positions in a map given as a Buffer", () => {
+  const cache = consume(fs.readFileSync(fixture));
+  assert.deepStrictEqual(cache.mappingForGeneratedPosition(1, 9), expected);
+});
+
+test("parses maps off the JavaScript thread", async () => {
+  const cache = await consumeAsync(fs.readFileSync(fixture));
+  assert.deepStrictEqual(cache.mappingForGeneratedPosition(1, 9), expected);
+  await assert.rejects(consumeAsync("{}"));
+});
+
+test("throws for invalid maps", () => {
+  assert.throws(() => consume('{ "version": 2 }'), Error);
+  assert.throws(() => consume(Buffer.from([0x7b, 0xff, 0x7d])), /not valid UTF-8/);
+});
@@ -0,0 +1,29 @@
+export interface Position {
+  line: number
+  column: number
+}
+
+/** A mapping, with the same shape as the `--json` output of the command line tool. */
+export interface Mapping {
+  generated: Position
+  original: Position
+  source: string
+  name: string
+}
+
+/** A parsed source map, created by `consume` or `consumeAsync`. */
+export class Cache {
+  /** Returns the mapping of a generated position. Lines start from 1 and columns from 0. */
+  mappingForGeneratedPosition(line: number, column: number): Mapping
+  /** Returns the `sources` table of the source map. */
+  sources(): Array<string>
+}
+
+/**
+ * Parses a source map on the JavaScript thread. Buffers are parsed in place, while
+ * strings are copied out of the JavaScript heap first.
+ */
+export function consume(json: string | Buffer): Cache
+
+/** Parses a source map on the libuv thread pool. */
+export function consumeAsync(json: string | Buffer): Promise<Cache>
@@ -0,0 +1,26 @@
+// Builds the addon with cargo and copies it to js-source-mapper.node, where index.js
+// loads it from
+"use strict";
+
+const { execFileSync } = require("child_process");
+const fs = require("fs");
+const path = require("path");
+
+const release = process.argv.includes("--release");
+const args = ["build", "-p", "js-source-mapper-node", "--message-format=json-render-diagnostics"];
+if (release) {
+  args.push("--release");
+}
+const output = execFileSync("cargo", args, { cwd: __dirname, encoding: "utf8", stdio: ["ignore", "pipe", "inherit"] });
+
+// The library file name depends on the platform, so it is read from cargo's output
+const library = output.split("\n")
+  .filter((line) => line.startsWith("{"))
+  .map((line) => JSON.parse(line))
+  .filter((message) => message.reason === "compiler-artifact" && message.target.name === "js_source_mapper_node")
+  .flatMap((message) => message.filenames)
+  .find((file) => /\.(so|dylib|dll)$/.test(file));
+if (!library) {
+  throw new Error("cargo did not build the addon");
+}
+fs.copyFileSync(library, path.join(__dirname, "..", "js-source-mapper.node"));
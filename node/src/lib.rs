@@ -0,0 +1,103 @@
+// Node.js bindings, loaded as a native addon. See README.md for how to build them.
+
+use js_source_mapper::{consume, CodePosition};
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+
+/**
+ * A source map to parse, as a string or as a Buffer. Buffers are parsed in place,
+ * while strings are copied out of the JavaScript heap first.
+ */
+type Json = Either<String, Buffer>;
+
+#[napi(object)]
+pub struct Position {
+  pub line: u32,
+  pub column: u32
+}
+
+/**
+ * A mapping, with the same shape as the `--json` output of the command line tool.
+ */
+#[napi(object)]
+pub struct Mapping {
+  pub generated: Position,
+  pub original: Position,
+  pub source: String,
+  pub name: String
+}
+
+/**
+ * A parsed source map, created by `consume` or `consumeAsync`.
+ */
+#[napi]
+pub struct Cache {
+  cache: js_source_mapper::Cache
+}
+
+#[napi]
+impl Cache {
+  /**
+   * Returns the mapping of a generated position. Lines start from 1 and columns from 0.
+   */
+  #[napi]
+  pub fn mapping_for_generated_position(&self, line: u32, column: u32) -> Mapping {
+    let mapping = self.cache.mapping_for_generated_position(line, column);
+    let position = |position: CodePosition| Position { line: position.line, column: position.column };
+    Mapping {
+      generated: position(mapping.generated),
+      original: position(mapping.original),
+      source: mapping.source.to_string(),
+      name: mapping.name.to_string()
+    }
+  }
+
+  /**
+   * Returns the `sources` table of the source map.
+   */
+  #[napi]
+  pub fn sources(&self) -> Vec<String> {
+    self.cache.sources().to_vec()
+  }
+}
+
+fn parse(json: &Json) -> Result<js_source_mapper::Cache> {
+  let json = match *json {
+    Either::A(ref json) => json.as_str(),
+    Either::B(ref buffer) => std::str::from_utf8(buffer).map_err(|err| Error::from_reason(format!("the source map is not valid UTF-8: {}", err)))?
+  };
+  consume(json).map_err(|err| Error::from_reason(err.to_string()))
+}
+
+/**
+ * Parses a source map on the JavaScript thread.
+ */
+#[napi(js_name = "consume")]
+pub fn consume_json(json: Json) -> Result<Cache> {
+  parse(&json).map(|cache| Cache { cache })
+}
+
+pub struct ConsumeTask {
+  json: Json
+}
+
+impl Task for ConsumeTask {
+  type Output = js_source_mapper::Cache;
+  type JsValue = Cache;
+
+  fn compute(&mut self) -> Result<Self::Output> {
+    parse(&self.json)
+  }
+
+  fn resolve(&mut self, _env: Env, cache: Self::Output) -> Result<Self::JsValue> {
+    Ok(Cache { cache })
+  }
+}
+
+/**
+ * Parses a source map on the libuv thread pool, resolving to a `Cache`.
+ */
+#[napi(ts_return_type = "Promise<Cache>")]
+pub fn consume_async(json: Json) -> AsyncTask<ConsumeTask> {
+  AsyncTask::new(ConsumeTask { json })
+}
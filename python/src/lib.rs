@@ -0,0 +1,68 @@
+// Python bindings, imported as the js_source_mapper extension module. See README.md for
+// how to build them.
+
+use js_source_mapper::{consume as consume_json, CodePosition};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict};
+
+/**
+ * A parsed source map, created by `consume`.
+ */
+#[pyclass(frozen)]
+struct Cache {
+  cache: js_source_mapper::Cache
+}
+
+#[pymethods]
+impl Cache {
+  /**
+   * Returns the mapping of a generated position as a dict, with the same shape as the
+   * `--json` output of the command line tool. Lines start from 1 and columns from 0.
+   */
+  fn mapping_for_generated_position<'py>(&self, py: Python<'py>, line: u32, column: u32) -> PyResult<Bound<'py, PyDict>> {
+    let mapping = self.cache.mapping_for_generated_position(line, column);
+    let position = |position: CodePosition| -> PyResult<Bound<'py, PyDict>> {
+      let dict = PyDict::new(py);
+      dict.set_item("line", position.line)?;
+      dict.set_item("column", position.column)?;
+      Ok(dict)
+    };
+    let dict = PyDict::new(py);
+    dict.set_item("generated", position(mapping.generated)?)?;
+    dict.set_item("original", position(mapping.original)?)?;
+    dict.set_item("source", &*mapping.source)?;
+    dict.set_item("name", &*mapping.name)?;
+    Ok(dict)
+  }
+
+  /**
+   * Returns the `sources` table of the source map.
+   */
+  fn sources(&self) -> Vec<String> {
+    self.cache.sources().to_vec()
+  }
+}
+
+/**
+ * Parses a source map, given as a str or as bytes, releasing the GIL while it does.
+ * Raises ValueError for invalid maps.
+ */
+#[pyfunction]
+fn consume(py: Python<'_>, json: &Bound<'_, PyAny>) -> PyResult<Cache> {
+  // Both are parsed in place, without copying them out of the Python heap
+  let json: &str = match json.cast::<PyBytes>() {
+    Ok(bytes) => std::str::from_utf8(bytes.as_bytes()).map_err(|err| PyValueError::new_err(format!("the source map is not valid UTF-8: {}", err)))?,
+    Err(_) => json.extract()?
+  };
+  let cache = py.detach(|| consume_json(json)).map_err(|err| PyValueError::new_err(err.to_string()))?;
+  Ok(Cache { cache })
+}
+
+#[pymodule]
+#[pyo3(name = "js_source_mapper")]
+fn python_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+  module.add_class::<Cache>()?;
+  module.add_function(wrap_pyfunction!(consume, module)?)?;
+  Ok(())
+}
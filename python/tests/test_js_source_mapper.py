@@ -0,0 +1,47 @@
+import os
+from concurrent.futures import ThreadPoolExecutor
+
+import pytest
+
+from js_source_mapper import Cache, consume
+
+FIXTURE = os.path.join(os.path.dirname(__file__), "..", "..", "tests", "cli", "app.js.map")
+EXPECTED = {
+    "generated": {"line": 1, "column": 9},
+    "original": {"line": 1, "column": 9},
+    "source": "src/app.js",
+    "name": "handleClick",
+}
+
+
+def read_fixture(mode):
+    with open(FIXTURE, mode) as fixture:
+        return fixture.read()
+
+
+def test_looks_up_positions_in_a_map_given_as_a_str():
+    cache = consume(read_fixture("r"))
+    assert isinstance(cache, Cache)
+    assert cache.mapping_for_generated_position(1, 9) == EXPECTED
+    assert cache.sources() == ["src/app.js"]
+
+
+def test_looks_up_positions_in_a_map_given_as_bytes():
+    cache = consume(read_fixture("rb"))
+    assert cache.mapping_for_generated_position(1, 9) == EXPECTED
+
+
+def test_parses_maps_from_several_threads():
+    json = read_fixture("rb")
+    with ThreadPoolExecutor(max_workers=4) as executor:
+        caches = list(executor.map(consume, [json] * 8))
+    assert all(cache.mapping_for_generated_position(1, 9) == EXPECTED for cache in caches)
+
+
+def test_raises_for_invalid_maps():
+    with pytest.raises(ValueError):
+        consume('{ "version": 2 }')
+    with pytest.raises(ValueError, match="not valid UTF-8"):
+        consume(b"{\xff}")
+    with pytest.raises(TypeError):
+        consume(42)
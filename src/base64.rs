@@ -1,4 +1,11 @@
-static CHARACTER_MAP : &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+/*!
+ * Encoding and decoding of single base 64 digits, as used by the VLQs in source map mappings.
+ */
+
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+
+const CHARACTER_MAP : &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
 
 /**
  * Encode an integer in the range of 0 to 63 to a single base 64 digit.
@@ -21,10 +28,147 @@ fn it_encodes_some_numbers() {
   assert!(encode(65) == None);
 }
 
+// Maps every byte to its base 64 digit, or to -1 if it is not a base 64 digit.
+static DECODE_TABLE: [i8; 256] = build_decode_table();
+
+const fn build_decode_table() -> [i8; 256] {
+  let mut table = [-1i8; 256];
+  let mut digit = 0;
+  while digit < CHARACTER_MAP.len() {
+    table[CHARACTER_MAP[digit] as usize] = digit as i8;
+    digit += 1;
+  }
+  // The URL-safe alphabet replaces + and / with - and _
+  table[b'-' as usize] = 62;
+  table[b'_' as usize] = 63;
+  table
+}
+
 /**
  * Decode a single base 64 character code digit to an integer.
+ *
+ * Digits from the URL-safe alphabet are accepted as well, since some emitters use it
+ * even though the source map spec does not allow it.
  */
+#[inline]
 pub fn decode(char_code: u8) -> Option<i32> {
+  let digit = DECODE_TABLE[char_code as usize];
+  if digit < 0 {
+    // Invalid base64 digit.
+    return None;
+  }
+  Some(digit as i32)
+}
+
+/**
+ * Decode a slice of base 64 digits, appending the decoded integers to `out`.
+ *
+ * On failure, returns the offset of the first byte that is not a base 64 digit. The
+ * integers decoded before that byte are still appended to `out`.
+ */
+pub fn decode_slice(input: &[u8], out: &mut Vec<i32>) -> Result<(), usize> {
+  out.reserve(input.len());
+  for (offset, &char_code) in input.iter().enumerate() {
+    match decode(char_code) {
+      Some(digit) => out.push(digit),
+      None => return Err(offset)
+    }
+  }
+  Ok(())
+}
+
+/**
+ * Encode a slice of integers in the range of 0 to 63 to base 64 digits.
+ */
+pub fn encode_slice(values: &[i32]) -> Option<Vec<u8>> {
+  values.iter().map(|&value| encode(value)).collect()
+}
+
+/**
+ * Encode bytes as standard base 64 text with padding, as used in data URIs.
+ */
+pub fn encode_bytes(input: &[u8]) -> String {
+  let mut encoded = String::with_capacity(input.len().div_ceil(3) * 4);
+  for chunk in input.chunks(3) {
+    let bits = (chunk[0] as u32) << 16
+      | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+      | (*chunk.get(2).unwrap_or(&0) as u32);
+    for digit in 0..4 {
+      if digit <= chunk.len() {
+        encoded.push(CHARACTER_MAP[(bits >> (18 - 6 * digit) & 0b111111) as usize] as char);
+      } else {
+        encoded.push('=');
+      }
+    }
+  }
+  encoded
+}
+
+/**
+ * Decode standard base 64 text to bytes. Padding is optional, but must be correct when
+ * present. Returns None for invalid input.
+ */
+pub fn decode_bytes(input: &[u8]) -> Option<Vec<u8>> {
+  let unpadded = match input.iter().position(|&char_code| char_code == b'=') {
+    Some(padding_start) => {
+      let padding = &input[padding_start..];
+      if !input.len().is_multiple_of(4) || padding.len() > 2 || padding.iter().any(|&char_code| char_code != b'=') {
+        return None;
+      }
+      &input[..padding_start]
+    },
+    None => input
+  };
+  if unpadded.len() % 4 == 1 {
+    return None;
+  }
+
+  let mut decoded = Vec::with_capacity(unpadded.len() / 4 * 3 + 2);
+  for chunk in unpadded.chunks(4) {
+    let mut bits: u32 = 0;
+    for &char_code in chunk {
+      bits = bits << 6 | decode(char_code)? as u32;
+    }
+    bits <<= 6 * (4 - chunk.len()) as u32;
+    for byte in 0..chunk.len() - 1 {
+      decoded.push((bits >> (16 - 8 * byte)) as u8);
+    }
+  }
+  Some(decoded)
+}
+
+#[test]
+fn it_decodes_some_codepoints() {
+  assert!(decode('A' as u8) == Some(0));
+  assert!(decode('W' as u8) == Some(22));
+  assert!(decode('q' as u8) == Some(42));
+  assert!(decode('3' as u8) == Some(55));
+  assert!(decode('/' as u8) == Some(63));
+  assert!(decode('+' as u8) == Some(62));
+  assert!(decode('.' as u8) == None);
+  assert!(decode('ö' as u8) == None);
+}
+
+#[test]
+fn it_decodes_the_url_safe_alphabet() {
+  assert!(decode(b'-') == Some(62));
+  assert!(decode(b'_') == Some(63));
+  assert!(decode(b'=').is_none());
+}
+
+#[test]
+fn it_encodes_and_decodes_some_numbers() {
+  for x in 0..64 {
+    assert!(encode(x).and_then(decode) == Some(x));
+  }
+  for &x in CHARACTER_MAP {
+    assert!(decode(x).and_then(encode) == Some(x));
+  }
+}
+
+// The range-based decoder that the lookup table replaced
+#[cfg(test)]
+fn decode_by_ranges(char_code: u8) -> Option<i32> {
   // 0 - 25: ABCDEFGHIJKLMNOPQRSTUVWXYZ
   if ('A' as u8) <= char_code && char_code <= ('Z' as u8) {
     return Some((char_code - ('A' as u8)) as i32);
@@ -42,13 +186,13 @@ pub fn decode(char_code: u8) -> Option<i32> {
     return Some((char_code - ('0' as u8) + number_map_offset) as i32);
   }
 
-  // 62: +
-  if char_code == ('+' as u8) {
+  // 62: + (or - in the URL-safe alphabet)
+  if char_code == ('+' as u8) || char_code == ('-' as u8) {
     return Some(62);
   }
 
-  // 63: /
-  if char_code == ('/' as u8) {
+  // 63: / (or _ in the URL-safe alphabet)
+  if char_code == ('/' as u8) || char_code == ('_' as u8) {
     return Some(63);
   }
 
@@ -57,23 +201,40 @@ pub fn decode(char_code: u8) -> Option<i32> {
 }
 
 #[test]
-fn it_decodes_some_codepoints() {
-  assert!(decode('A' as u8) == Some(0));
-  assert!(decode('W' as u8) == Some(22));
-  assert!(decode('q' as u8) == Some(42));
-  assert!(decode('3' as u8) == Some(55));
-  assert!(decode('/' as u8) == Some(63));
-  assert!(decode('+' as u8) == Some(62));
-  assert!(decode('.' as u8) == None);
-  assert!(decode('ö' as u8) == None);
+fn it_decodes_every_byte_like_the_range_based_decoder() {
+  for byte in 0..=255u8 {
+    assert_eq!(decode(byte), decode_by_ranges(byte), "byte {}", byte);
+  }
 }
 
 #[test]
-fn it_encodes_and_decodes_some_numbers() {
-  for x in 0..64 {
-    assert!(encode(x).and_then(decode) == Some(x));
-  }
-  for &x in CHARACTER_MAP {
-    assert!(decode(x).and_then(encode) == Some(x));
+fn it_decodes_slices() {
+  let mut out = Vec::new();
+  assert_eq!(decode_slice(b"AZa9+/", &mut out), Ok(()));
+  assert_eq!(out, vec![0, 25, 26, 61, 62, 63]);
+}
+
+#[test]
+fn it_reports_the_offset_of_invalid_digits() {
+  let mut out = Vec::new();
+  assert_eq!(decode_slice(b"AB=C", &mut out), Err(2));
+  assert_eq!(out, vec![0, 1]);
+}
+
+#[test]
+fn it_encodes_slices() {
+  assert_eq!(encode_slice(&[0, 25, 26, 61, 62, 63]), Some(b"AZa9+/".to_vec()));
+  assert_eq!(encode_slice(&[0, 64]), None);
+}
+
+#[test]
+fn it_encodes_and_decodes_bytes() {
+  for &(bytes, text) in &[(&b""[..], ""), (b"f", "Zg=="), (b"fo", "Zm8="), (b"foo", "Zm9v"), (b"foob", "Zm9vYg=="), (b"\xff\xfe", "//4=")] {
+    assert_eq!(encode_bytes(bytes), text);
+    assert_eq!(decode_bytes(text.as_bytes()), Some(bytes.to_vec()));
   }
+  assert_eq!(decode_bytes(b"Zg"), Some(b"f".to_vec()));
+  assert_eq!(decode_bytes(b"Z"), None);
+  assert_eq!(decode_bytes(b"Zg=a"), None);
+  assert_eq!(decode_bytes(b"Zm9v="), None);
 }
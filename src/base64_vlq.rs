@@ -1,3 +1,10 @@
+/*!
+ * Encoding and decoding of the base 64 variable length quantities used in source map mappings.
+ */
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
 use base64;
 
 // A single base 64 digit can contain 6 bits of data. For the base 64 variable
@@ -35,9 +42,13 @@ fn it_defines_sane_constants() {
  * placed in the least significant bit.  For example, as decimals:
  *   1 becomes 2 (10 binary), -1 becomes 3 (11 binary)
  *   2 becomes 4 (100 binary), -2 becomes 5 (101 binary)
+ *
+ * The result takes 33 bits for the largest magnitudes, so it is returned as an
+ * i64 to support the full i32 range, including i32::MIN.
  */
 #[allow(dead_code)]
-pub fn to_vql(value: i32) -> i32 {
+pub fn to_vql(value: i32) -> i64 {
+  let value = value as i64;
   if value < 0 {
     ((-value) << 1) + 1
   } else {
@@ -53,13 +64,21 @@ fn it_converts_to_vql() {
   assert!(to_vql(-2) == 5);
 }
 
+#[test]
+fn it_converts_i32_boundaries_to_vql() {
+  assert_eq!(to_vql(i32::MAX), 0xFFFF_FFFE);
+  assert_eq!(to_vql(i32::MIN + 1), 0xFFFF_FFFF);
+  assert_eq!(to_vql(i32::MIN), 0x1_0000_0001);
+}
+
 /**
  * Converts to a two-complement value from a value where the sign bit is
  * placed in the least significant bit.  For example, as decimals:
  *   2 (10 binary) becomes 1, 3 (11 binary) becomes -1
  *   4 (100 binary) becomes 2, 5 (101 binary) becomes -2
  */
-pub fn from_vql(value: i32) -> i32 {
+#[allow(dead_code)]
+pub fn from_vql(value: i64) -> i64 {
   let is_neative = (value & 1) == 1;
   let shifted = value >> 1;
   if is_neative {
@@ -78,33 +97,43 @@ fn it_converts_from_vql() {
 }
 
 /**
- * Returns the base 64 VLQ encoded value.
+ * Returns the base 64 VLQ encoded value. Every i32 value, including i32::MIN,
+ * can be encoded, so this never returns None.
  */
-#[allow(dead_code)]
 pub fn encode(value: i32) -> Option<Vec<u8>> {
+  Some(encode64(value as i64))
+}
+
+/**
+ * Returns the base 64 VLQ encoded value of a 64-bit integer. Every i64 value,
+ * including i64::MIN, can be encoded.
+ */
+pub fn encode64(value: i64) -> Vec<u8> {
   let mut encoded: Vec<u8> = Vec::new();
-  let mut vlq = to_vql(value);
+  // Moving the sign into the least significant bit takes 65 bits for i64::MIN
+  let mut vlq: u128 = if value < 0 {
+    ((value as i128).unsigned_abs() << 1) + 1
+  } else {
+    (value as u128) << 1
+  };
 
   loop {
-    let mut digit = vlq & VLQ_BASE_MASK;
-    vlq = vlq >> VLQ_BASE_SHIFT;
+    let mut digit = (vlq & (VLQ_BASE_MASK as u128)) as i32;
+    vlq >>= VLQ_BASE_SHIFT;
     if vlq > 0 {
       // There are still more digits in this value, so we must make sure the
       // continuation bit is marked.
       digit |= VLQ_CONTINUATION_BIT;
     }
 
-    encoded.push(match base64::encode(digit) {
-      Some(x) => x,
-      None => return None
-    });
+    encoded.push(base64::encode(digit).expect("digit should be in the range of 0 to 63"));
 
-    if vlq <= 0 {
+    if vlq == 0 {
       break;
     }
   };
 
-  Some(encoded)
+  encoded
 }
 
 #[cfg(test)]
@@ -145,33 +174,73 @@ fn it_encodes_some_numbers() {
 
 /*
  * Decodes the next base 64 VLQ value from the given string and returns the
- * value and the rest of the string via the out parameter.
+ * value and the number of characters read. Returns None if the value does not
+ * fit in an i32, or if the input ends before the value does.
  */
+#[inline]
 pub fn decode(encoded: &[u8]) -> Option<(i32, usize)> {
-  let mut result: i32  = 0;
-  let mut shift: i32  = 0;
+  // Values of up to six digits fit in 30 bits, which covers nearly every field of real
+  // source maps, so only longer ones go through the 64-bit decoder
+  let mut result: u32 = 0;
+  for (index, &character) in encoded.iter().take(6).enumerate() {
+    let digit = base64::decode(character)?;
+    result |= ((digit & VLQ_BASE_MASK) as u32) << (index as i32 * VLQ_BASE_SHIFT);
+    if digit & VLQ_CONTINUATION_BIT == 0 {
+      let magnitude = (result >> 1) as i32;
+      return Some((if result & 1 == 1 { -magnitude } else { magnitude }, index + 1));
+    }
+  }
+
+  let (value, characters_read) = decode64(encoded)?;
+  if value < (i32::MIN as i64) || value > (i32::MAX as i64) {
+    return None;
+  }
+  Some((value as i32, characters_read))
+}
+
+// The largest number of digits needed for a 65 bit VLQ, the width of i64::MIN
+const VLQ_MAX_DIGITS_64: usize = 13;
+
+/**
+ * Decodes the next base 64 VLQ value as a 64-bit integer. Returns None if the
+ * value does not fit in an i64, or if the input ends before the value does.
+ */
+pub fn decode64(encoded: &[u8]) -> Option<(i64, usize)> {
+  let mut result: u128 = 0;
+  let mut shift: u32 = 0;
+  // An empty input is truncated as well
+  let mut continuation = true;
 
   let mut characters_read = 0;
   for &character in encoded {
     characters_read += 1;
-
-    let mut digit = match base64::decode(character) {
-      Some(x) => x,
-      None => return None
-    };
-    let continuation = (digit & VLQ_CONTINUATION_BIT) != 0;
-    digit &= VLQ_BASE_MASK;
-    result = result + (digit << shift);
-    shift += VLQ_BASE_SHIFT;
-    if shift >= 32 {
+    if characters_read > VLQ_MAX_DIGITS_64 {
       return None;
     }
+
+    let mut digit = base64::decode(character)?;
+    continuation = (digit & VLQ_CONTINUATION_BIT) != 0;
+    digit &= VLQ_BASE_MASK;
+    result += (digit as u128) << shift;
+    shift += VLQ_BASE_SHIFT as u32;
     if !continuation {
       break;
     }
   }
 
-  Some((from_vql(result), characters_read))
+  if continuation {
+    // The last digit asked for more digits, so the value was cut off
+    return None;
+  }
+
+  let is_negative = (result & 1) == 1;
+  let magnitude = (result >> 1) as i128;
+  let value = if is_negative { -magnitude } else { magnitude };
+  if value < (i64::MIN as i128) || value > (i64::MAX as i128) {
+    return None;
+  }
+
+  Some((value as i64, characters_read))
 }
 
 #[cfg(test)]
@@ -236,9 +305,55 @@ fn it_returns_sane_field_length() {
     assert!(result.0 == 2);
     assert!(result.1 == 1);
   }
+  {
+    let result = decode(b"gBA").unwrap();
+    assert!(result.0 == 16);
+    assert!(result.1 == 2);
+  }
+  // The continuation bit of the last digit is set, so the value is truncated
+  assert!(decode(b"g").is_none());
+  assert!(decode(&b"gBA"[..1]).is_none());
+  assert!(decode(b"").is_none());
 }
 
 #[test]
 fn it_does_not_panic_on_long_strings() {
   decode(b"00000000");
-}
\ No newline at end of file
+}
+
+#[test]
+fn it_encodes_i32_boundaries() {
+  assert_encodes_to!(i32::MAX, "+/////D");
+  assert_encodes_to!(i32::MIN + 1, "//////D");
+  assert_encodes_to!(i32::MIN, "hgggggE");
+}
+
+#[test]
+fn it_encodes_and_decodes_i32_boundaries() {
+  for &x in &[i32::MAX, i32::MAX - 1, i32::MIN + 1, i32::MIN] {
+    assert_eq!(decode(&encode(x).unwrap()).unwrap().0, x);
+  }
+}
+
+#[test]
+fn it_rejects_values_out_of_i32_range() {
+  assert_eq!(decode(&encode64(i32::MAX as i64 + 1)), None);
+  assert_eq!(decode(&encode64(i32::MIN as i64 - 1)), None);
+}
+
+#[test]
+fn it_encodes_and_decodes_i64_boundaries() {
+  for &x in &[i64::MAX, i64::MAX - 1, i64::MIN + 1, i64::MIN, i32::MAX as i64 + 1, i32::MIN as i64 - 1] {
+    let encoded = encode64(x);
+    assert_eq!(decode64(&encoded), Some((x, encoded.len())));
+  }
+  assert_eq!(encode64(i64::MIN).len(), VLQ_MAX_DIGITS_64);
+}
+
+#[test]
+fn it_rejects_values_out_of_i64_range() {
+  // 2^63, one past i64::MAX
+  assert_eq!(decode64(b"ggggggggggggQ"), None);
+  // Too many digits
+  assert_eq!(decode64(b"gggggggggggggA"), None);
+}
@@ -0,0 +1,257 @@
+use std::fs;
+use std::io;
+use std::io::Write;
+
+use serde_json;
+
+pub const USAGE: &str = "\
+Usage: js-source-mapper [<global options>] <command> [<options>] <arguments>
+
+Commands:
+       js-source-mapper [lookup] [--positions <file>] <map-file> [<line>:<column>...]
+       js-source-mapper [lookup] --context <lines> [--source-dir <dir>] <map-file> [<line>:<column>...]
+       js-source-mapper symbolicate [--file <name>] <map-file> < <stack-trace>
+       js-source-mapper dump [--source <source>] [--line <line>] [--format <format>] <map-file>
+       js-source-mapper stats <map-file>
+       js-source-mapper validate [--strict] <map-file>
+       js-source-mapper repl <map-file>
+       js-source-mapper flatten [-o <file>] [--passthrough] <index-map-file>
+       js-source-mapper rebase [--from <prefix> --to <prefix>...] [--strip-scheme]
+                               [--dry-run] [-o <file>] <map-file>
+       js-source-mapper diff [--verbose] <old-map-file> <new-map-file>
+       js-source-mapper extract-sources [--filter <text>] -o <directory> <map-file>
+       js-source-mapper compose [--source <source>] [-o <file>] <map-file> <intermediate-map-file>
+       js-source-mapper offset --lines <lines> [--first-line-columns <columns>] [-o <file>] <map-file>
+       js-source-mapper lookup-name [--source <source>] <map-file> <name>
+       js-source-mapper ignore-list [--add <glob>...] [--remove <glob>...] [-o <file>] <map-file>
+       js-source-mapper bench [--lookups <count>] [--seed <seed>] <map-file>
+       js-source-mapper strip [--sources-content] [--names] [--sources-matching <glob>...]
+                              [-o <file>] <map-file>
+       js-source-mapper coverage [--file <name>] [-o <file>] <map-file> <lcov-file>
+       js-source-mapper verify <bundle> <map-file>
+       js-source-mapper to-data-uri [--append <bundle>] [-o <file>] <map-file>
+       js-source-mapper extract [--pretty] [--strip <file>] [-o <file>] <bundle>
+       js-source-mapper serve [--host <host>] [--port <port>] <map-file-or-directory>...
+
+lookup prints the original position of each generated position in the source
+map, in the order they are given. Lines start from 1 and columns start from 0.
+Use - as the map file to read the source map from standard input. With
+--context, the original code around each position is printed as well, taken
+from the sourcesContent of the map or read from the source directory.
+
+symbolicate reads a Chrome, Node.js, Firefox or Safari stack trace from standard
+input and rewrites the frames in the generated file with their original
+positions, keeping the layout of the trace. Other lines are printed unchanged.
+
+dump prints every mapping in generated order, one per line. --format csv and
+--format tsv print a header row and one row per mapping instead, with the
+columns generated_line, generated_column, original_line, original_column,
+source and name.
+
+stats prints the number of mappings, generated lines, sources and names in the
+source map, the size of its embedded sources, and the sources with the most
+mappings.
+
+validate checks the source map and prints every problem found. It exits with 0
+when the map is valid, 1 when there are only warnings and 2 when there are
+errors.
+
+repl parses the source map once and then answers queries read from standard
+input, one per line: <line>:<column> looks up a position, source <source> lists
+the mappings into a source, and quit exits.
+
+flatten merges the sections of an index map into a regular source map, written
+to standard output or to the file given with -o.
+
+rebase rewrites the source paths and source root of a source map. Each --from
+prefix is replaced by the --to prefix that follows it; the first matching pair
+is used. --strip-scheme first removes URL schemes such as webpack:// from the
+paths.
+
+diff compares two source maps by generated position and prints how many
+mappings were added, removed or changed, and which sources were added or
+removed. It exits with 0 when the maps are identical and 1 when they differ.
+
+extract-sources writes the original sources embedded in sourcesContent to files
+under the output directory, at the paths given by the sources table. Sources
+with absolute paths or .. segments, which would escape the output directory,
+are refused.
+
+compose applies the map of an intermediate file to the map of the final file:
+mappings into the intermediate source are resolved through the intermediate map,
+and the composed map is written to standard output or to the file given with -o.
+The intermediate source defaults to the file field of the intermediate map.
+
+offset shifts the generated positions of a source map, for when content is
+prepended to the generated file: every mapping moves down by --lines, and the
+mappings on the first line also move right by --first-line-columns.
+
+lookup-name prints every mapping whose original name is the given identifier,
+in generated order, for instance to find where a function ended up in minified
+code.
+
+ignore-list prints the sources in the ignoreList of the source map. With --add
+or --remove, the sources matching the glob patterns are added to or removed from
+the list, in the order given, and the updated map is written to standard output
+or to the file given with -o. In patterns, * matches within a path segment, **
+matches across segments and ? matches one character.
+
+bench measures how long parsing the source map takes, and how long a number of
+lookups at random generated positions take. The positions are picked from the
+lines and columns covered by the mappings with a seeded generator, so runs with
+the same seed look up the same positions.
+
+strip writes a smaller copy of the source map without the sourcesContent, the
+names, or the mappings into the sources matching the glob patterns, and prints
+the size before and after.
+
+serve answers lookups over HTTP, for tools that cannot use the library. It loads
+the maps, and the .map files in the directories, keyed by their file name, their
+file field and their debugId. GET /lookup?map=<key>&line=<line>&column=<column>
+returns the lookup as a JSON object and GET /maps lists the keys. serve is only
+available when built with the serve feature.
+
+Every command also accepts a bundle in place of the map file, and reads the map
+referenced by its sourceMappingURL comment, which may be an inline data: URI.
+lookup, symbolicate, dump and repl print sources prefixed with the source root of
+the map, which --source-root replaces.
+
+Global options:
+  --map <file>        Read this source map instead of the one referenced by the
+                      sourceMappingURL comment of the bundle
+  --source-root <root>
+                      Use this source root instead of the one in the map. An
+                      empty root removes it. The map file is not modified
+  --json              Print errors as JSON. lookup prints one JSON object per
+                      position, or a JSON array with --positions, lookup-name
+                      prints a JSON array of mappings, and stats, validate and
+                      bench print the statistics, report or timings as a JSON
+                      object
+  -h, --help          Print this help
+
+Options:
+  --positions <file>  Also look up the positions in a file, one <line>:<column>
+                      per line. Blank lines and lines starting with # are skipped
+  --context <lines>   Print this many lines of original code around each position
+  --source-dir <dir>  Read the original sources missing from sourcesContent from
+                      this directory. Defaults to the current directory
+  --file <name>       Only symbolicate frames or remap coverage records in this
+                      generated file. Defaults to the file field of the source
+                      map, or all frames or records without one
+  --source <source>   Only dump or look up the mappings into this original source.
+                      With compose, the intermediate source to resolve
+  --line <line>       Only dump the mappings on this generated line
+  --format <format>   Dump the mappings as text, csv or tsv. Defaults to text
+  --strict            Treat validation warnings as errors
+  -o, --output <file> Write the source map, the coverage report or the data URI to
+                      this file instead of standard output
+  --passthrough       Let flatten copy source maps that are not index maps as is
+  --from <prefix>     Rebase the source paths starting with this prefix
+  --to <prefix>       The prefix replacing the preceding --from prefix
+  --strip-scheme      Remove URL schemes like webpack:// from the source paths
+  --dry-run           Print how each source would be rebased without writing
+  -v, --verbose       Print every mapping that differs, prefixed with + when it
+                      was added, - when it was removed and ~ when it changed
+  --filter <text>     Only extract the sources whose path contains this text
+  --lines <lines>     The number of lines prepended to the generated file
+  --first-line-columns <columns>
+                      The number of columns prepended to the first line of the
+                      generated file
+  --add <glob>        Add the sources matching the pattern to the ignoreList
+  --remove <glob>     Remove the sources matching the pattern from the ignoreList
+  --lookups <count>   The number of lookups bench runs. Defaults to 100000
+  --seed <seed>       The seed bench picks the lookup positions with. Defaults to 1
+  --sources-content   Strip the sourcesContent of the map
+  --names             Strip the names table and the names of the mappings
+  --sources-matching <glob>
+                      Strip the mappings into the sources matching the pattern
+  --append <bundle>   Print the bundle with the data URI as its sourceMappingURL
+  --pretty            Indent the extracted source map
+  --strip <file>      Also write the bundle without its inline map to this file
+  --host <host>       The address serve listens on. Defaults to 127.0.0.1
+  --port <port>       The port serve listens on. Defaults to 8080";
+
+// Exit codes
+pub const EXIT_OK: i32 = 0;
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+// validate exits with EXIT_FAILURE when there are only warnings
+pub const EXIT_INVALID: i32 = 2;
+// diff exits with EXIT_FAILURE when the maps differ
+pub const EXIT_DIFF_ERROR: i32 = 2;
+
+// Options that apply to every command
+#[derive(Default)]
+pub struct GlobalOptions {
+  // Print results and errors as JSON, for the commands that support it
+  pub json: bool,
+  pub map: Option<String>,
+  pub source_root: Option<String>
+}
+
+// Removes the global --json, --map and --source-root options from the arguments,
+// wherever they appear
+pub fn take_global_options(args: &[String]) -> Result<(Vec<String>, GlobalOptions), String> {
+  let mut globals = GlobalOptions::default();
+  let mut remaining: Vec<String> = Vec::new();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--json" => globals.json = true,
+      "--map" => globals.map = Some(args.next().ok_or("--map expects a map file")?.clone()),
+      flag if flag.starts_with("--map=") => globals.map = Some(flag["--map=".len()..].to_owned()),
+      "--source-root" => globals.source_root = Some(args.next().ok_or("--source-root expects a source root")?.clone()),
+      flag if flag.starts_with("--source-root=") => globals.source_root = Some(flag["--source-root=".len()..].to_owned()),
+      _ => remaining.push(arg.clone())
+    }
+  }
+  Ok((remaining, globals))
+}
+
+pub fn report_error(json: bool, message: &str) {
+  if json {
+    eprintln!("{}", serde_json::json!({ "error": message }));
+  } else {
+    eprintln!("error: {}", message);
+  }
+}
+
+// Reports invalid arguments along with the usage
+pub fn usage_error(json: bool, message: &str) -> i32 {
+  report_error(json, message);
+  eprintln!("{}", USAGE);
+  EXIT_USAGE
+}
+
+pub fn write_output(path: Option<&str>, contents: &str) -> Result<(), String> {
+  match path {
+    Some(path) => fs::write(path, contents).map_err(|err| format!("could not write {}: {}", path, err)),
+    None => {
+      let stdout = io::stdout();
+      let mut stdout = stdout.lock();
+      stdout.write_all(contents.as_bytes())
+        .and_then(|()| stdout.write_all(b"\n"))
+        .map_err(|err| format!("could not write the source map: {}", err))
+    }
+  }
+}
+
+// Prints the summary of a command that writes a map, on standard error if the map
+// itself is written to standard output
+pub fn print_summary(output: Option<&str>, summary: &str) {
+  if output.is_some() {
+    println!("{}", summary);
+  } else {
+    eprintln!("{}", summary);
+  }
+}
+
+#[test]
+fn it_takes_global_options() {
+  let args: Vec<String> = ["dump", "--source-root=", "a.map", "--map", "b.map", "--json", "--source", "a.js"].iter().map(|arg| arg.to_string()).collect();
+  let (remaining, globals) = take_global_options(&args).unwrap();
+  assert_eq!(remaining, ["dump", "a.map", "--source", "a.js"]);
+  assert!(globals.json);
+  assert_eq!(globals.map.as_deref(), Some("b.map"));
+  assert_eq!(globals.source_root.as_deref(), Some(""));
+}
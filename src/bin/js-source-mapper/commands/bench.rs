@@ -0,0 +1,205 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use js_source_mapper::{Cache, consume};
+use serde_json;
+
+use cli::{EXIT_FAILURE, EXIT_OK, GlobalOptions, report_error, usage_error};
+use maps::read_map;
+
+const DEFAULT_LOOKUPS: usize = 100_000;
+const DEFAULT_SEED: u64 = 1;
+
+struct BenchOptions {
+  map_file: String,
+  lookups: usize,
+  seed: u64
+}
+
+#[derive(Serialize, Debug)]
+struct ParseOutput {
+  seconds: f64,
+  bytes: usize,
+  mappings: usize,
+  bytes_per_second: f64
+}
+
+#[derive(Serialize, Debug)]
+struct LookupsOutput {
+  seconds: f64,
+  count: usize,
+  seed: u64,
+  lookups_per_second: f64
+}
+
+#[derive(Serialize, Debug)]
+struct BenchOutput {
+  parse: ParseOutput,
+  lookups: LookupsOutput
+}
+
+pub fn run(args: &[String], globals: &GlobalOptions) -> i32 {
+  let options = match parse_args(args) {
+    Ok(options) => options,
+    Err(err) => return usage_error(globals.json, &err)
+  };
+  let (json, name) = match read_map(&options.map_file, globals.map.as_deref()) {
+    Ok(map) => map,
+    Err(err) => {
+      report_error(globals.json, &err);
+      return EXIT_FAILURE;
+    }
+  };
+
+  let start = Instant::now();
+  let cache = match consume(&json) {
+    Ok(cache) => cache,
+    Err(err) => {
+      report_error(globals.json, &format!("could not parse {}: {}", name, err));
+      return EXIT_FAILURE;
+    }
+  };
+  let parse_time = start.elapsed();
+
+  let positions = random_positions(&cache, options.lookups, options.seed);
+  let start = Instant::now();
+  for &(line, column) in &positions {
+    black_box(cache.mapping_for_generated_position(line, column));
+  }
+  let lookup_time = start.elapsed();
+
+  let output = BenchOutput {
+    parse: ParseOutput {
+      seconds: parse_time.as_secs_f64(),
+      bytes: json.len(),
+      mappings: cache.mappings().len(),
+      bytes_per_second: per_second(json.len(), parse_time)
+    },
+    lookups: LookupsOutput {
+      seconds: lookup_time.as_secs_f64(),
+      count: positions.len(),
+      seed: options.seed,
+      lookups_per_second: per_second(positions.len(), lookup_time)
+    }
+  };
+  if globals.json {
+    println!("{}", serde_json::to_string(&output).expect("bench output should serialize"));
+  } else {
+    print!("{}", format_bench(&output));
+  }
+  EXIT_OK
+}
+
+fn parse_args(args: &[String]) -> Result<BenchOptions, String> {
+  let mut lookups = DEFAULT_LOOKUPS;
+  let mut seed = DEFAULT_SEED;
+  let mut positional: Vec<String> = Vec::new();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    let (flag, value) = match arg.find('=') {
+      Some(equals) if arg.starts_with("--") => (&arg[..equals], Some(arg[equals + 1..].to_owned())),
+      _ => (arg.as_str(), None)
+    };
+    match flag {
+      "--lookups" | "--seed" => {
+        let value = match value {
+          Some(value) => value,
+          None => args.next().ok_or_else(|| format!("{} expects a number", flag))?.clone()
+        };
+        let invalid = || format!("invalid {} {:?}, expected a non-negative number", flag, value);
+        if flag == "--lookups" {
+          lookups = value.parse().map_err(|_| invalid())?;
+        } else {
+          seed = value.parse().map_err(|_| invalid())?;
+        }
+      },
+      flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option {}", flag)),
+      _ => positional.push(arg.clone())
+    }
+  }
+  if positional.len() != 1 {
+    return Err("expected a map file".into());
+  }
+  Ok(BenchOptions { map_file: positional.remove(0), lookups, seed })
+}
+
+// Picks generated positions uniformly from the lines and columns covered by the
+// mappings, one past the last column so that lookups past the end are measured too
+fn random_positions(cache: &Cache, count: usize, seed: u64) -> Vec<(u32, u32)> {
+  let mappings = cache.mappings();
+  let last_line = mappings.last().map_or(1, |mapping| mapping.generated.line);
+  let last_column = mappings.iter().map(|mapping| mapping.generated.column).max().unwrap_or(0);
+  let mut random = SplitMix64(seed);
+  (0..count)
+    .map(|_| {
+      let line = 1 + random.below(u64::from(last_line)) as u32;
+      let column = random.below(u64::from(last_column) + 2) as u32;
+      (line, column)
+    })
+    .collect()
+}
+
+// A small seeded generator, so that runs look up the same positions
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+  fn next(&mut self) -> u64 {
+    self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = self.0;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+  }
+
+  // A number in 0..bound, which must not be 0
+  fn below(&mut self, bound: u64) -> u64 {
+    self.next() % bound
+  }
+}
+
+fn per_second(count: usize, time: Duration) -> f64 {
+  if time.is_zero() {
+    return 0.0;
+  }
+  count as f64 / time.as_secs_f64()
+}
+
+fn format_bench(output: &BenchOutput) -> String {
+  let parse = &output.parse;
+  let lookups = &output.lookups;
+  let nanoseconds_per_lookup = if lookups.count == 0 { 0.0 } else { lookups.seconds * 1e9 / lookups.count as f64 };
+  format!(
+    "parse: {:.3} ms for {} bytes and {} mappings ({:.1} MB/s)\nlookups: {:.3} ms for {} lookups with seed {} ({:.0} lookups/s, {:.0} ns per lookup)\n",
+    parse.seconds * 1000.0, parse.bytes, parse.mappings, parse.bytes_per_second / 1e6,
+    lookups.seconds * 1000.0, lookups.count, lookups.seed, lookups.lookups_per_second, nanoseconds_per_lookup
+  )
+}
+
+#[test]
+fn it_picks_reproducible_positions() {
+  let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA,UAAU;;AAAA" }"#).unwrap();
+  let positions = random_positions(&cache, 1000, 7);
+  assert_eq!(positions, random_positions(&cache, 1000, 7));
+  assert_ne!(positions, random_positions(&cache, 1000, 8));
+  assert!(positions.iter().all(|&(line, column)| (1..=3).contains(&line) && column <= 11));
+  assert!(positions.iter().any(|&(line, _)| line == 3));
+  assert!(positions.iter().any(|&(_, column)| column == 11));
+}
+
+#[test]
+fn it_formats_bench_results() {
+  let output = BenchOutput {
+    parse: ParseOutput { seconds: 0.002, bytes: 4000, mappings: 100, bytes_per_second: 2e6 },
+    lookups: LookupsOutput { seconds: 0.001, count: 1000, seed: 1, lookups_per_second: 1e6 }
+  };
+  assert_eq!(format_bench(&output), concat!(
+    "parse: 2.000 ms for 4000 bytes and 100 mappings (2.0 MB/s)\n",
+    "lookups: 1.000 ms for 1000 lookups with seed 1 (1000000 lookups/s, 1000 ns per lookup)\n"
+  ));
+
+  let args = |args: &[&str]| parse_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>());
+  let options = args(&["a.map", "--lookups", "500", "--seed=9"]).unwrap();
+  assert_eq!((options.lookups, options.seed), (500, 9));
+  assert_eq!(args(&["a.map"]).unwrap().lookups, DEFAULT_LOOKUPS);
+  assert!(args(&["a.map", "--lookups", "-1"]).is_err());
+}
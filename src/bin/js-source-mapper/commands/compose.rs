@@ -0,0 +1,107 @@
+use js_source_mapper::compose;
+
+use cli::{EXIT_FAILURE, EXIT_OK, GlobalOptions, print_summary, report_error, usage_error, write_output};
+use maps::{describe_input, load_with_sources_content};
+
+struct ComposeOptions {
+  map_file: String,
+  intermediate_map_file: String,
+  source: Option<String>,
+  output: Option<String>
+}
+
+pub fn run(args: &[String], globals: &GlobalOptions) -> i32 {
+  let options = match parse_args(args) {
+    Ok(options) => options,
+    Err(err) => return usage_error(globals.json, &err)
+  };
+  match compose_maps(&options, globals) {
+    Ok(summary) => {
+      print_summary(options.output.as_deref(), &summary);
+      EXIT_OK
+    },
+    Err(err) => {
+      report_error(globals.json, &err);
+      EXIT_FAILURE
+    }
+  }
+}
+
+fn parse_args(args: &[String]) -> Result<ComposeOptions, String> {
+  let mut source: Option<String> = None;
+  let mut output: Option<String> = None;
+  let mut positional: Vec<String> = Vec::new();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--source" => source = Some(args.next().ok_or("--source expects a source")?.clone()),
+      flag if flag.starts_with("--source=") => source = Some(flag["--source=".len()..].to_owned()),
+      "-o" | "--output" => output = Some(args.next().ok_or("--output expects a file")?.clone()),
+      flag if flag.starts_with("--output=") => output = Some(flag["--output=".len()..].to_owned()),
+      flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option {}", flag)),
+      _ => positional.push(arg.clone())
+    }
+  }
+  if positional.len() != 2 {
+    return Err("expected a map file and an intermediate map file".into());
+  }
+  let intermediate_map_file = positional.pop().unwrap();
+  let map_file = positional.pop().unwrap();
+  Ok(ComposeOptions { map_file, intermediate_map_file, source, output })
+}
+
+// Writes the composed map and returns a summary of what was done
+fn compose_maps(options: &ComposeOptions, globals: &GlobalOptions) -> Result<String, String> {
+  let outer = load_with_sources_content(&options.map_file, globals)?;
+  // Global options are about the map of the final file
+  let inner = load_with_sources_content(&options.intermediate_map_file, &GlobalOptions::default())?;
+  let source = match options.source {
+    Some(ref source) => source.as_str(),
+    None if !inner.file.is_empty() => inner.file.as_str(),
+    None => return Err(format!("{} has no file field, pass the intermediate source with --source", describe_input(&options.intermediate_map_file)))
+  };
+  // Find the intermediate source as it is spelled in the sources table, which may be
+  // a longer path than the file field of the intermediate map
+  let suffix = format!("/{}", source.trim_start_matches("./"));
+  let source = outer.sources().iter()
+    .find(|candidate| *candidate == source)
+    .or_else(|| outer.sources().iter().find(|candidate| candidate.ends_with(&suffix)))
+    .ok_or_else(|| format!("{} has no source {}", describe_input(&options.map_file), source))?;
+
+  let composition = compose(&outer, source, &inner);
+  let json = composition.cache.to_json().map_err(|err| format!("could not serialize the composed source map: {}", err))?;
+  write_output(options.output.as_deref(), &json)?;
+  let mut summary = format!("composed {} mappings, passed through {}", composition.composed, composition.passed_through);
+  if composition.unresolved > 0 {
+    summary.push_str(&format!(", {} not mapped by the intermediate map", composition.unresolved));
+  }
+  Ok(summary)
+}
+
+#[cfg(test)]
+use std::env;
+#[cfg(test)]
+use std::fs;
+#[cfg(test)]
+use std::process;
+
+#[test]
+fn it_composes_maps() {
+  let dir = env::temp_dir().join(format!("js-source-mapper-compose-{}", process::id()));
+  fs::create_dir_all(&dir).unwrap();
+  let path = |name: &str| dir.join(name).to_str().unwrap().to_owned();
+  fs::write(dir.join("final.map"), r#"{ "version": 3, "sources": ["dist/intermediate.js", "vendor.js"], "names": [], "mappings": "AAAA,ECAA,EDEA" }"#).unwrap();
+  fs::write(dir.join("intermediate.map"), r#"{ "version": 3, "file": "intermediate.js", "sources": ["src/app.ts"], "names": [], "mappings": "AAAA" }"#).unwrap();
+  let options = |source: Option<&str>| ComposeOptions {
+    map_file: path("final.map"),
+    intermediate_map_file: path("intermediate.map"),
+    source: source.map(|source| source.to_owned()),
+    output: Some(path("composed.map"))
+  };
+
+  assert_eq!(compose_maps(&options(None), &GlobalOptions::default()), Ok("composed 1 mappings, passed through 1, 1 not mapped by the intermediate map".into()));
+  assert_eq!(fs::read_to_string(dir.join("composed.map")).unwrap(), r#"{"version":3,"sources":["src/app.ts","vendor.js"],"names":[],"mappings":"AAAA,ECAA,E"}"#);
+  assert!(compose_maps(&options(Some("dist/intermediate.js")), &GlobalOptions::default()).is_ok());
+  assert!(compose_maps(&options(Some("other.js")), &GlobalOptions::default()).unwrap_err().contains("has no source other.js"));
+  fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,367 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use js_source_mapper::{Cache, Mapping};
+
+use cli::{EXIT_FAILURE, EXIT_OK, GlobalOptions, print_summary, report_error, usage_error, write_output};
+use maps::{load, read_input, resolve_sources};
+
+struct CoverageOptions {
+  map_file: String,
+  report_file: String,
+  file: Option<String>,
+  output: Option<String>
+}
+
+// An lcov record of the generated file, as read. Function hits are given by name
+#[derive(Default, Debug)]
+struct Record {
+  functions: Vec<(u32, String)>,
+  function_hits: BTreeMap<String, u64>,
+  lines: Vec<(u32, u64)>,
+  branches: Vec<(u32, u32, u32, Option<u64>)>
+}
+
+// The coverage of an original source. Branches are keyed by line, block and branch,
+// and have no count when their block was never run
+#[derive(Default, Debug, PartialEq)]
+struct SourceCoverage {
+  functions: BTreeMap<String, (u32, u64)>,
+  lines: BTreeMap<u32, u64>,
+  branches: BTreeMap<(u32, u32, u32), Option<u64>>
+}
+
+// The generated positions that are not mapped to an original source
+#[derive(Default, Debug, PartialEq)]
+struct Unmapped {
+  lines: usize,
+  functions: usize,
+  branches: usize
+}
+
+#[derive(Default, Debug)]
+struct Remapped {
+  sources: BTreeMap<String, SourceCoverage>,
+  unmapped: Unmapped,
+  // The records of other files, copied as is
+  other_records: Vec<String>,
+  generated_lines: usize
+}
+
+pub fn run(args: &[String], globals: &GlobalOptions) -> i32 {
+  let options = match parse_args(args) {
+    Ok(options) => options,
+    Err(err) => return usage_error(globals.json, &err)
+  };
+  let mut cache = match load(&options.map_file, globals) {
+    Ok(cache) => cache,
+    Err(err) => {
+      report_error(globals.json, &err);
+      return EXIT_FAILURE;
+    }
+  };
+  resolve_sources(&mut cache);
+  let file = options.file.clone().or_else(|| if cache.file.is_empty() { None } else { Some(cache.file.clone()) });
+
+  let result = read_input(&options.report_file)
+    .and_then(|report| remap(&cache, file.as_deref(), &report))
+    .and_then(|remapped| write_output(options.output.as_deref(), &format_lcov(&remapped)).map(|()| remapped));
+  match result {
+    Ok(remapped) => {
+      print_summary(options.output.as_deref(), &format_summary(&remapped));
+      EXIT_OK
+    },
+    Err(err) => {
+      report_error(globals.json, &err);
+      EXIT_FAILURE
+    }
+  }
+}
+
+fn parse_args(args: &[String]) -> Result<CoverageOptions, String> {
+  let mut file: Option<String> = None;
+  let mut output: Option<String> = None;
+  let mut positional: Vec<String> = Vec::new();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    let (flag, value) = match arg.find('=') {
+      Some(equals) if arg.starts_with("--") => (&arg[..equals], Some(arg[equals + 1..].to_owned())),
+      _ => (arg.as_str(), None)
+    };
+    match flag {
+      "--file" | "-o" | "--output" => {
+        let value = match value {
+          Some(value) => value,
+          None => args.next().ok_or_else(|| format!("{} expects a value", flag))?.clone()
+        };
+        if flag == "--file" {
+          file = Some(value);
+        } else {
+          output = Some(value);
+        }
+      },
+      flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option {}", flag)),
+      _ => positional.push(arg.clone())
+    }
+  }
+  if positional.len() != 2 {
+    return Err("expected a map file and a coverage report".into());
+  }
+  if positional.iter().all(|input| input == "-") {
+    return Err("only one of the map and the coverage report can be read from standard input".into());
+  }
+  let report_file = positional.pop().unwrap();
+  Ok(CoverageOptions { map_file: positional.remove(0), report_file, file, output })
+}
+
+// Reads the lcov report and remaps the records of the generated file, or of every
+// file when its name is not known
+fn remap(cache: &Cache, file: Option<&str>, report: &str) -> Result<Remapped, String> {
+  let mut remapped = Remapped::default();
+  let mut record = Record::default();
+  let mut record_lines: Vec<&str> = Vec::new();
+  let mut in_file = true;
+  for (index, line) in report.lines().enumerate() {
+    let line = line.trim_end();
+    if line.is_empty() {
+      continue;
+    }
+    record_lines.push(line);
+    let invalid = || format!("invalid lcov on line {}: {:?}", index + 1, line);
+    let (key, value) = line.split_once(':').unwrap_or((line, ""));
+    let numbers = |count: usize| -> Result<Vec<&str>, String> {
+      let fields: Vec<&str> = value.splitn(count, ',').collect();
+      if fields.len() == count { Ok(fields) } else { Err(invalid()) }
+    };
+    match key {
+      "SF" => in_file = file.is_none_or(|file| is_file(value, file)),
+      "FN" => {
+        let fields = numbers(2)?;
+        record.functions.push((fields[0].parse().map_err(|_| invalid())?, fields[1].to_owned()));
+      },
+      "FNDA" => {
+        let fields = numbers(2)?;
+        *record.function_hits.entry(fields[1].to_owned()).or_insert(0) += fields[0].parse::<u64>().map_err(|_| invalid())?;
+      },
+      "DA" => {
+        let fields = numbers(2)?;
+        // Some tools append a checksum to the count
+        let count = fields[1].split(',').next().unwrap_or("");
+        record.lines.push((fields[0].parse().map_err(|_| invalid())?, count.parse().map_err(|_| invalid())?));
+      },
+      "BRDA" => {
+        let fields = numbers(4)?;
+        let taken = if fields[3] == "-" { None } else { Some(fields[3].parse().map_err(|_| invalid())?) };
+        record.branches.push((
+          fields[0].parse().map_err(|_| invalid())?,
+          fields[1].parse().map_err(|_| invalid())?,
+          fields[2].parse().map_err(|_| invalid())?,
+          taken
+        ));
+      },
+      "end_of_record" => {
+        if in_file {
+          remap_record(cache, &record, &mut remapped);
+        } else {
+          remapped.other_records.push(record_lines.join("\n"));
+        }
+        record = Record::default();
+        record_lines.clear();
+        in_file = true;
+      },
+      // The totals are recomputed, and the test name is not kept
+      _ => {}
+    }
+  }
+  if !record_lines.is_empty() {
+    return Err("the coverage report ends in the middle of a record, expected end_of_record".into());
+  }
+  Ok(remapped)
+}
+
+fn is_file(path: &str, file: &str) -> bool {
+  let file = file.trim_start_matches("./");
+  path == file || (path.ends_with(file) && path[..path.len() - file.len()].ends_with(['/', '\\']))
+}
+
+// A line runs every original line mapped from it, as often as the line itself. The
+// functions and branches are placed at the first mapping of their line
+fn remap_record(cache: &Cache, record: &Record, remapped: &mut Remapped) {
+  let mapped = |line: u32| cache.mappings_for_generated_line(line).iter().filter(|mapping| !mapping.source.is_empty());
+  let first_mapped = |line: u32| -> Option<&Mapping> { mapped(line).next() };
+
+  for &(line, count) in &record.lines {
+    remapped.generated_lines += 1;
+    let mut any = false;
+    for mapping in mapped(line) {
+      let hits = remapped.sources.entry(mapping.source.to_string()).or_default().lines.entry(mapping.original.line).or_insert(0);
+      *hits = (*hits).max(count);
+      any = true;
+    }
+    if !any {
+      remapped.unmapped.lines += 1;
+    }
+  }
+
+  for (line, name) in &record.functions {
+    let mapping = match first_mapped(*line) {
+      Some(mapping) => mapping,
+      None => {
+        remapped.unmapped.functions += 1;
+        continue;
+      }
+    };
+    // The original name of the function is mapped from its name in the minified code
+    let original_name = mapped(*line).find(|mapping| !mapping.name.is_empty()).map_or(name.as_str(), |mapping| &*mapping.name);
+    let count = record.function_hits.get(name).cloned().unwrap_or(0);
+    let function = remapped.sources.entry(mapping.source.to_string()).or_default()
+      .functions.entry(original_name.to_owned()).or_insert((mapping.original.line, 0));
+    function.1 = function.1.max(count);
+  }
+
+  for &(line, block, branch, taken) in &record.branches {
+    let mapping = match first_mapped(line) {
+      Some(mapping) => mapping,
+      None => {
+        remapped.unmapped.branches += 1;
+        continue;
+      }
+    };
+    let total = remapped.sources.entry(mapping.source.to_string()).or_default()
+      .branches.entry((mapping.original.line, block, branch)).or_insert(None);
+    *total = match (*total, taken) {
+      (Some(total), Some(taken)) => Some(total + taken),
+      (total, taken) => total.or(taken)
+    };
+  }
+}
+
+// Writes the records of the other files, then a record per original source
+fn format_lcov(remapped: &Remapped) -> String {
+  let mut lcov = String::new();
+  for record in &remapped.other_records {
+    lcov.push_str(record);
+    lcov.push('\n');
+  }
+  for (source, coverage) in &remapped.sources {
+    writeln!(lcov, "SF:{}", source).unwrap();
+    let mut functions: Vec<(&String, &(u32, u64))> = coverage.functions.iter().collect();
+    functions.sort_by_key(|&(name, &(line, _))| (line, name));
+    for (name, (line, _)) in &functions {
+      writeln!(lcov, "FN:{},{}", line, name).unwrap();
+    }
+    for (name, (_, count)) in &functions {
+      writeln!(lcov, "FNDA:{},{}", count, name).unwrap();
+    }
+    writeln!(lcov, "FNF:{}\nFNH:{}", functions.len(), functions.iter().filter(|(_, &(_, count))| count > 0).count()).unwrap();
+    for (line, count) in &coverage.lines {
+      writeln!(lcov, "DA:{},{}", line, count).unwrap();
+    }
+    writeln!(lcov, "LF:{}\nLH:{}", coverage.lines.len(), coverage.lines.values().filter(|&&count| count > 0).count()).unwrap();
+    for ((line, block, branch), taken) in &coverage.branches {
+      match taken {
+        Some(taken) => writeln!(lcov, "BRDA:{},{},{},{}", line, block, branch, taken).unwrap(),
+        None => writeln!(lcov, "BRDA:{},{},{},-", line, block, branch).unwrap()
+      }
+    }
+    let hit = coverage.branches.values().filter(|taken| taken.is_some_and(|taken| taken > 0)).count();
+    writeln!(lcov, "BRF:{}\nBRH:{}\nend_of_record", coverage.branches.len(), hit).unwrap();
+  }
+  // write_output ends the report with a newline
+  lcov.pop();
+  lcov
+}
+
+fn format_summary(remapped: &Remapped) -> String {
+  let unmapped = &remapped.unmapped;
+  format!(
+    "remapped {} generated lines to {} sources; {} lines, {} functions and {} branches did not map to a source",
+    remapped.generated_lines, remapped.sources.len(), unmapped.lines, unmapped.functions, unmapped.branches
+  )
+}
+
+#[cfg(test)]
+use js_source_mapper::consume;
+
+#[test]
+fn it_remaps_lcov_reports() {
+  // Line 1 maps to a.js lines 1 and 2 and names x, line 2 maps to b.js line 1, and
+  // line 3 is not mapped
+  let cache = consume(r#"{ "version": 3, "file": "bundle.js", "sources": ["a.js", "b.js"], "names": ["handle"], "mappings": "AAAAA,EACA;ACDA;" }"#).unwrap();
+  let report = "\
+TN:
+SF:dist/bundle.js
+FN:1,a
+FN:3,b
+FNDA:4,a
+FNDA:0,b
+FNF:2
+FNH:1
+DA:1,4
+DA:2,0
+DA:3,1
+LF:3
+LH:2
+BRDA:2,0,0,-
+BRDA:2,0,1,-
+BRDA:3,1,0,1
+BRF:3
+BRH:1
+end_of_record
+SF:vendor.js
+DA:1,1
+end_of_record
+";
+  let remapped = remap(&cache, Some("bundle.js"), report).unwrap();
+  assert_eq!(remapped.unmapped, Unmapped { lines: 1, functions: 1, branches: 1 });
+  assert_eq!(remapped.other_records, ["SF:vendor.js\nDA:1,1\nend_of_record"]);
+  assert_eq!(format_lcov(&remapped), "\
+SF:vendor.js
+DA:1,1
+end_of_record
+SF:a.js
+FN:1,handle
+FNDA:4,handle
+FNF:1
+FNH:1
+DA:1,4
+DA:2,4
+LF:2
+LH:2
+BRF:0
+BRH:0
+end_of_record
+SF:b.js
+FNF:0
+FNH:0
+DA:1,0
+LF:1
+LH:0
+BRDA:1,0,0,-
+BRDA:1,0,1,-
+BRF:2
+BRH:0
+end_of_record");
+  assert_eq!(
+    format_summary(&remapped),
+    "remapped 3 generated lines to 2 sources; 1 lines, 1 functions and 1 branches did not map to a source"
+  );
+
+  // Without a file name, every record is remapped
+  assert!(remap(&cache, None, report).unwrap().other_records.is_empty());
+  assert!(remap(&cache, None, "SF:a.js\nDA:x,1\nend_of_record").is_err());
+  assert!(remap(&cache, None, "SF:a.js\nDA:1,1").is_err());
+}
+
+#[test]
+fn it_parses_coverage_arguments() {
+  let args = |args: &[&str]| parse_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>());
+  let options = args(&["bundle.js.map", "coverage.lcov", "-o", "original.lcov", "--file=bundle.js"]).unwrap();
+  assert_eq!((options.map_file.as_str(), options.report_file.as_str()), ("bundle.js.map", "coverage.lcov"));
+  assert_eq!(options.output.as_deref(), Some("original.lcov"));
+  assert_eq!(options.file.as_deref(), Some("bundle.js"));
+  assert!(args(&["bundle.js.map"]).is_err());
+  assert!(args(&["-", "-"]).is_err());
+  assert!(is_file("/home/ci/dist/bundle.js", "bundle.js"));
+  assert!(!is_file("dist/app-bundle.js", "bundle.js"));
+}
@@ -0,0 +1,141 @@
+use std::collections::HashSet;
+use std::io;
+use std::io::Write;
+
+use js_source_mapper::{Cache, Mapping, diff};
+
+use cli::{EXIT_DIFF_ERROR, EXIT_FAILURE, EXIT_OK, GlobalOptions, report_error, usage_error};
+use format::{format_original, write_mapping};
+use maps::load;
+
+struct DiffOptions {
+  verbose: bool,
+  old_map_file: String,
+  new_map_file: String
+}
+
+pub fn run(args: &[String], globals: &GlobalOptions) -> i32 {
+  let options = match parse_args(args) {
+    Ok(options) => options,
+    // Usage errors exit with EXIT_USAGE, which is also EXIT_DIFF_ERROR
+    Err(err) => return usage_error(globals.json, &err)
+  };
+  if globals.map.is_some() {
+    report_error(globals.json, "diff does not support --map, pass both maps instead");
+    return EXIT_DIFF_ERROR;
+  }
+  let caches = load(&options.old_map_file, globals)
+    .and_then(|old| load(&options.new_map_file, globals).map(|new| (old, new)));
+  let (old, new) = match caches {
+    Ok(caches) => caches,
+    Err(err) => {
+      report_error(globals.json, &err);
+      return EXIT_DIFF_ERROR;
+    }
+  };
+
+  let stdout = io::stdout();
+  match write_diff(&old, &new, options.verbose, io::BufWriter::new(stdout.lock())) {
+    Ok(true) => EXIT_OK,
+    Ok(false) => EXIT_FAILURE,
+    Err(ref err) if err.kind() == io::ErrorKind::BrokenPipe => EXIT_FAILURE,
+    Err(err) => {
+      report_error(globals.json, &format!("could not write the differences: {}", err));
+      EXIT_DIFF_ERROR
+    }
+  }
+}
+
+fn parse_args(args: &[String]) -> Result<DiffOptions, String> {
+  let mut verbose = false;
+  let mut positional: Vec<String> = Vec::new();
+  for arg in args {
+    match arg.as_str() {
+      "-v" | "--verbose" => verbose = true,
+      flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option {}", flag)),
+      _ => positional.push(arg.clone())
+    }
+  }
+  if positional.len() != 2 {
+    return Err("expected an old and a new map file".into());
+  }
+  let new_map_file = positional.pop().unwrap();
+  let old_map_file = positional.pop().unwrap();
+  Ok(DiffOptions { verbose, old_map_file, new_map_file })
+}
+
+// Writes the differences between the caches and returns true if they are identical
+fn write_diff<W: Write>(old: &Cache, new: &Cache, verbose: bool, mut output: W) -> io::Result<bool> {
+  let changes = diff(old, new);
+  let old_sources: HashSet<&str> = old.sources().iter().map(|source| source.as_str()).collect();
+  let new_sources: HashSet<&str> = new.sources().iter().map(|source| source.as_str()).collect();
+  let added_sources: Vec<&str> = new.sources().iter().map(|source| source.as_str()).filter(|source| !old_sources.contains(source)).collect();
+  let removed_sources: Vec<&str> = old.sources().iter().map(|source| source.as_str()).filter(|source| !new_sources.contains(source)).collect();
+
+  if changes.is_empty() && added_sources.is_empty() && removed_sources.is_empty() {
+    writeln!(output, "source maps are identical ({} mappings)", changes.unchanged)?;
+    output.flush()?;
+    return Ok(true);
+  }
+
+  writeln!(output, "mappings: {} added, {} removed, {} changed, {} unchanged",
+    changes.added.len(), changes.removed.len(), changes.changed.len(), changes.unchanged)?;
+  if !added_sources.is_empty() {
+    writeln!(output, "sources added: {}", added_sources.join(", "))?;
+  }
+  if !removed_sources.is_empty() {
+    writeln!(output, "sources removed: {}", removed_sources.join(", "))?;
+  }
+
+  if verbose {
+    // Interleave the three lists in generated order
+    let mut entries: Vec<(&Mapping, char, Option<&Mapping>)> = Vec::new();
+    entries.extend(changes.added.iter().map(|mapping| (mapping, '+', None)));
+    entries.extend(changes.removed.iter().map(|mapping| (mapping, '-', None)));
+    entries.extend(changes.changed.iter().map(|change| (&change.new, '~', Some(&change.old))));
+    entries.sort_by_key(|&(mapping, _, _)| (mapping.generated.line, mapping.generated.column));
+    for (mapping, prefix, old) in entries {
+      write!(output, "{} ", prefix)?;
+      match old {
+        Some(old) => {
+          let mut line: Vec<u8> = Vec::new();
+          write_mapping(&mut line, mapping)?;
+          line.pop();
+          output.write_all(&line)?;
+          let was = if old.source.is_empty() { "unmapped".to_owned() } else { format_original(old) };
+          writeln!(output, " (was {})", was)?;
+        },
+        None => write_mapping(&mut output, mapping)?
+      }
+    }
+  }
+  output.flush()?;
+  Ok(false)
+}
+
+#[cfg(test)]
+use js_source_mapper::consume;
+
+#[test]
+fn it_diffs_source_maps() {
+  let old = consume(r#"{ "version": 3, "sources": ["a.js", "b.js"], "names": ["x"], "mappings": "AAAA,CAAC,CCAAA" }"#).unwrap();
+  let new = consume(r#"{ "version": 3, "sources": ["a.js", "c.js"], "names": ["x"], "mappings": "AAAA,CAAE,EAAC;ACAA" }"#).unwrap();
+  let write = |old: &Cache, new: &Cache, verbose: bool| {
+    let mut output: Vec<u8> = Vec::new();
+    let identical = write_diff(old, new, verbose, &mut output).unwrap();
+    (identical, String::from_utf8(output).unwrap())
+  };
+
+  assert_eq!(write(&old, &old, true), (true, "source maps are identical (3 mappings)\n".into()));
+  assert_eq!(write(&old, &new, false), (false, "\
+mappings: 2 added, 1 removed, 1 changed, 1 unchanged
+sources added: c.js
+sources removed: b.js
+".into()));
+  assert_eq!(write(&old, &new, true).1.lines().skip(3).collect::<Vec<_>>(), vec![
+    "~ 1:1 -> a.js:1:2 (was a.js:1:1)",
+    "- 1:2 -> b.js:1:1 (x)",
+    "+ 1:3 -> a.js:1:3",
+    "+ 2:0 -> c.js:1:3"
+  ]);
+}
@@ -0,0 +1,154 @@
+use std::io;
+use std::io::Write;
+
+use js_source_mapper::{Cache, write_mappings_csv};
+
+use cli::{EXIT_FAILURE, EXIT_OK, GlobalOptions, report_error, usage_error};
+use format::write_mapping;
+use maps::{load, resolve_sources};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum DumpFormat {
+  Text,
+  Csv,
+  Tsv
+}
+
+struct DumpOptions {
+  map_file: String,
+  source: Option<String>,
+  line: Option<u32>,
+  format: DumpFormat
+}
+
+pub fn run(args: &[String], globals: &GlobalOptions) -> i32 {
+  let options = match parse_args(args) {
+    Ok(options) => options,
+    Err(err) => return usage_error(globals.json, &err)
+  };
+  let mut cache = match load(&options.map_file, globals) {
+    Ok(cache) => cache,
+    Err(err) => {
+      report_error(globals.json, &err);
+      return EXIT_FAILURE;
+    }
+  };
+  resolve_sources(&mut cache);
+
+  let stdout = io::stdout();
+  match dump(&cache, &options, io::BufWriter::new(stdout.lock())) {
+    Ok(()) => EXIT_OK,
+    // The reader went away, e.g. when piping into head
+    Err(ref err) if err.kind() == io::ErrorKind::BrokenPipe => EXIT_OK,
+    Err(err) => {
+      report_error(globals.json, &format!("could not write the mappings: {}", err));
+      EXIT_FAILURE
+    }
+  }
+}
+
+fn parse_args(args: &[String]) -> Result<DumpOptions, String> {
+  let mut source: Option<String> = None;
+  let mut line: Option<String> = None;
+  let mut format = DumpFormat::Text;
+  let mut positional: Vec<String> = Vec::new();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--source" => source = Some(args.next().ok_or("--source expects a source")?.clone()),
+      flag if flag.starts_with("--source=") => source = Some(flag["--source=".len()..].to_owned()),
+      "--line" => line = Some(args.next().ok_or("--line expects a line")?.clone()),
+      flag if flag.starts_with("--line=") => line = Some(flag["--line=".len()..].to_owned()),
+      "--format" => format = parse_format(args.next().ok_or("--format expects a format")?)?,
+      flag if flag.starts_with("--format=") => format = parse_format(&flag["--format=".len()..])?,
+      flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option {}", flag)),
+      _ => positional.push(arg.clone())
+    }
+  }
+  if positional.len() != 1 {
+    return Err("expected a map file".into());
+  }
+  let line = match line {
+    Some(line) => match line.parse() {
+      Ok(line) if line >= 1 => Some(line),
+      _ => return Err(format!("invalid line {:?}, lines start from 1", line))
+    },
+    None => None
+  };
+  Ok(DumpOptions { map_file: positional.remove(0), source, line, format })
+}
+
+fn parse_format(format: &str) -> Result<DumpFormat, String> {
+  match format {
+    "text" => Ok(DumpFormat::Text),
+    "csv" => Ok(DumpFormat::Csv),
+    "tsv" => Ok(DumpFormat::Tsv),
+    _ => Err(format!("unknown format {:?}, expected text, csv or tsv", format))
+  }
+}
+
+// Writes the mappings one at a time, so that large maps are not formatted into memory
+fn dump<W: Write>(cache: &Cache, options: &DumpOptions, mut output: W) -> io::Result<()> {
+  let mappings = match options.line {
+    Some(line) => cache.mappings_for_generated_line(line),
+    None => cache.mappings()
+  };
+  let mappings = mappings.iter()
+    .filter(|mapping| options.source.as_ref().is_none_or(|source| **source == *mapping.source));
+  match options.format {
+    DumpFormat::Text => {
+      for mapping in mappings {
+        write_mapping(&mut output, mapping)?;
+      }
+      output.flush()
+    },
+    DumpFormat::Csv => write_mappings_csv(mappings, output, b','),
+    DumpFormat::Tsv => write_mappings_csv(mappings, output, b'\t')
+  }
+}
+
+#[cfg(test)]
+use js_source_mapper::consume;
+
+#[test]
+fn it_dumps_mappings() {
+  let cache = consume(r#"{ "version": 3, "sources": ["a.js", "b.js"], "names": ["x"], "mappings": "AAAAA,C;ACAA,EDCA" }"#).unwrap();
+  let dump_with = |source: Option<&str>, line: Option<u32>| {
+    let options = DumpOptions { map_file: "a.map".into(), source: source.map(|source| source.into()), line, format: DumpFormat::Text };
+    let mut output = Vec::new();
+    dump(&cache, &options, &mut output).unwrap();
+    String::from_utf8(output).unwrap()
+  };
+  assert_eq!(dump_with(None, None), "1:0 -> a.js:1:0 (x)\n1:1\n2:0 -> b.js:1:0\n2:2 -> a.js:2:0\n");
+  assert_eq!(dump_with(Some("a.js"), None), "1:0 -> a.js:1:0 (x)\n2:2 -> a.js:2:0\n");
+  assert_eq!(dump_with(None, Some(2)), "2:0 -> b.js:1:0\n2:2 -> a.js:2:0\n");
+  assert_eq!(dump_with(None, Some(3)), "");
+}
+
+#[test]
+fn it_dumps_mappings_as_csv() {
+  let cache = consume(r#"{ "version": 3, "sources": ["a.js", "b,c.js"], "names": ["x"], "mappings": "AAAAA,C;ACAA,EDCA" }"#).unwrap();
+  let dump_as = |format: DumpFormat, source: Option<&str>| {
+    let options = DumpOptions { map_file: "a.map".into(), source: source.map(|source| source.into()), line: None, format };
+    let mut output = Vec::new();
+    dump(&cache, &options, &mut output).unwrap();
+    String::from_utf8(output).unwrap()
+  };
+  assert_eq!(dump_as(DumpFormat::Csv, None), concat!(
+    "generated_line,generated_column,original_line,original_column,source,name\n",
+    "1,0,1,0,a.js,x\n",
+    "1,1,,,,\n",
+    "2,0,1,0,\"b,c.js\",\n",
+    "2,2,2,0,a.js,\n"
+  ));
+  assert_eq!(dump_as(DumpFormat::Tsv, Some("b,c.js")), concat!(
+    "generated_line\tgenerated_column\toriginal_line\toriginal_column\tsource\tname\n",
+    "2\t0\t1\t0\tb,c.js\t\n"
+  ));
+
+  let args = |args: &[&str]| parse_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>());
+  assert_eq!(args(&["--format", "tsv", "a.map"]).unwrap().format, DumpFormat::Tsv);
+  assert_eq!(args(&["--format=csv", "a.map"]).unwrap().format, DumpFormat::Csv);
+  assert_eq!(args(&["a.map"]).unwrap().format, DumpFormat::Text);
+  assert!(args(&["--format", "json", "a.map"]).is_err());
+}
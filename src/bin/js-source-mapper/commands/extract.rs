@@ -0,0 +1,189 @@
+use js_source_mapper::{decode_data_uri, source_mapping_url};
+use serde_json;
+
+use cli::{EXIT_FAILURE, EXIT_OK, GlobalOptions, print_summary, report_error, usage_error, write_output};
+use maps::{describe_input, read_input};
+
+struct ExtractOptions {
+  bundle_file: String,
+  pretty: bool,
+  strip: Option<String>,
+  output: Option<String>
+}
+
+pub fn run(args: &[String], globals: &GlobalOptions) -> i32 {
+  let options = match parse_args(args) {
+    Ok(options) => options,
+    Err(err) => return usage_error(globals.json, &err)
+  };
+  if globals.map.is_some() {
+    return usage_error(globals.json, "extract does not support --map, the map is read from the bundle");
+  }
+  match extract(&options) {
+    Ok(summary) => {
+      print_summary(options.output.as_deref(), &summary);
+      EXIT_OK
+    },
+    Err(err) => {
+      report_error(globals.json, &err);
+      EXIT_FAILURE
+    }
+  }
+}
+
+fn parse_args(args: &[String]) -> Result<ExtractOptions, String> {
+  let mut pretty = false;
+  let mut strip: Option<String> = None;
+  let mut output: Option<String> = None;
+  let mut positional: Vec<String> = Vec::new();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    let (flag, value) = match arg.find('=') {
+      Some(equals) if arg.starts_with("--") => (&arg[..equals], Some(arg[equals + 1..].to_owned())),
+      _ => (arg.as_str(), None)
+    };
+    match flag {
+      "--strip" | "-o" | "--output" => {
+        let value = match value {
+          Some(value) => value,
+          None => args.next().ok_or_else(|| format!("{} expects a file", flag))?.clone()
+        };
+        if flag == "--strip" {
+          strip = Some(value);
+        } else {
+          output = Some(value);
+        }
+      },
+      "--pretty" => pretty = true,
+      flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option {}", flag)),
+      _ => positional.push(arg.clone())
+    }
+  }
+  if positional.len() != 1 {
+    return Err("expected a bundle".into());
+  }
+  if strip.is_some() && strip == output {
+    return Err("--strip and -o must name different files".into());
+  }
+  Ok(ExtractOptions { bundle_file: positional.remove(0), pretty, strip, output })
+}
+
+// Writes the inline map, and the stripped bundle, returning a summary
+fn extract(options: &ExtractOptions) -> Result<String, String> {
+  let bundle = read_input(&options.bundle_file)?;
+  let name = describe_input(&options.bundle_file);
+  let json = inline_map(&bundle, name)?;
+  serde_json::from_str::<serde_json::Value>(&json).map_err(|err| format!("the inline source map of {} is not valid JSON: {}", name, err))?;
+  let json = if options.pretty { pretty_print(&json) } else { json };
+  write_output(options.output.as_deref(), &json)?;
+
+  let mut summary = format!("extracted a {} byte source map from {}", json.len(), name);
+  if let Some(ref strip) = options.strip {
+    let stripped = without_inline_map(&bundle);
+    write_output(Some(strip), &stripped)?;
+    summary.push_str(&format!(", and wrote {} without it to {}", name, strip));
+  }
+  Ok(summary)
+}
+
+fn inline_map(bundle: &str, name: &str) -> Result<String, String> {
+  let url = source_mapping_url(bundle).ok_or_else(|| format!("could not find a sourceMappingURL comment in {}", name))?;
+  if !url.get(..5).is_some_and(|scheme| scheme.eq_ignore_ascii_case("data:")) {
+    return Err(format!("the source map of {} is not inline, its sourceMappingURL is {}", name, url));
+  }
+  decode_data_uri(url).map_err(|err| format!("could not decode the inline source map of {}: {}", name, err))
+}
+
+// Removes the comment of the inline map, which is the last sourceMappingURL comment
+fn without_inline_map(bundle: &str) -> String {
+  let mut lines: Vec<&str> = bundle.split_inclusive('\n').collect();
+  if let Some(index) = lines.iter().rposition(|line| source_mapping_url(line).is_some()) {
+    lines.remove(index);
+  }
+  lines.concat()
+}
+
+// Indents valid JSON by two spaces, keeping the order of the keys, which parsing it
+// into a serde_json::Value would not
+fn pretty_print(json: &str) -> String {
+  let mut pretty = String::with_capacity(json.len() * 2);
+  let mut depth = 0;
+  let mut in_string = false;
+  let mut escaped = false;
+  let mut characters = json.chars().peekable();
+  let newline = |pretty: &mut String, depth: usize| {
+    pretty.push('\n');
+    pretty.extend(std::iter::repeat_n(' ', depth * 2));
+  };
+  while let Some(character) = characters.next() {
+    if in_string {
+      pretty.push(character);
+      match character {
+        _ if escaped => escaped = false,
+        '\\' => escaped = true,
+        '"' => in_string = false,
+        _ => {}
+      }
+      continue;
+    }
+    match character {
+      ' ' | '\t' | '\n' | '\r' => {},
+      '"' => {
+        in_string = true;
+        pretty.push(character);
+      },
+      '{' | '[' => {
+        pretty.push(character);
+        // Empty objects and arrays stay on one line
+        while characters.peek().is_some_and(|next| next.is_ascii_whitespace()) {
+          characters.next();
+        }
+        if characters.peek().is_some_and(|&next| next == '}' || next == ']') {
+          pretty.push(characters.next().unwrap());
+        } else {
+          depth += 1;
+          newline(&mut pretty, depth);
+        }
+      },
+      '}' | ']' => {
+        depth -= 1;
+        newline(&mut pretty, depth);
+        pretty.push(character);
+      },
+      ',' => {
+        pretty.push(character);
+        newline(&mut pretty, depth);
+      },
+      ':' => pretty.push_str(": "),
+      _ => pretty.push(character)
+    }
+  }
+  pretty
+}
+
+#[test]
+fn it_extracts_inline_maps() {
+  let bundle = "a();\n//# sourceMappingURL=data:application/json;base64,eyJ2ZXJzaW9uIjozfQ==\n";
+  assert_eq!(inline_map(bundle, "a.js").unwrap(), r#"{"version":3}"#);
+  assert_eq!(
+    inline_map("a();\n//# sourceMappingURL=a.js.map", "a.js"),
+    Err("the source map of a.js is not inline, its sourceMappingURL is a.js.map".into())
+  );
+  assert!(inline_map("a();", "a.js").is_err());
+  assert_eq!(without_inline_map(bundle), "a();\n");
+  assert_eq!(without_inline_map("a();\n//# sourceMappingURL=data:,{}\nb();"), "a();\nb();");
+}
+
+#[test]
+fn it_pretty_prints_json() {
+  assert_eq!(
+    pretty_print(r#"{"version":3,"sources":["a.js", "b\",{}.js"],"names":[ ],"x":{}}"#),
+    "{\n  \"version\": 3,\n  \"sources\": [\n    \"a.js\",\n    \"b\\\",{}.js\"\n  ],\n  \"names\": [],\n  \"x\": {}\n}"
+  );
+
+  let args = |args: &[&str]| parse_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>());
+  let options = args(&["a.js", "--pretty", "-o", "a.js.map", "--strip", "a.stripped.js"]).unwrap();
+  assert!(options.pretty);
+  assert_eq!((options.output.as_deref(), options.strip.as_deref()), (Some("a.js.map"), Some("a.stripped.js")));
+  assert!(args(&["a.js", "-o", "x", "--strip", "x"]).is_err());
+}
@@ -0,0 +1,183 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use js_source_mapper::Cache;
+
+use cli::{EXIT_FAILURE, EXIT_OK, GlobalOptions, report_error, usage_error};
+use maps::{load_with_sources_content, strip_scheme};
+
+struct ExtractSourcesOptions {
+  map_file: String,
+  output: String,
+  filter: Option<String>
+}
+
+#[derive(Default)]
+struct ExtractedSources {
+  written: usize,
+  // Sources without an entry in sourcesContent
+  missing: Vec<String>,
+  // Sources whose path would escape the output directory
+  refused: Vec<String>
+}
+
+pub fn run(args: &[String], globals: &GlobalOptions) -> i32 {
+  let options = match parse_args(args) {
+    Ok(options) => options,
+    Err(err) => return usage_error(globals.json, &err)
+  };
+  let cache = match load_with_sources_content(&options.map_file, globals) {
+    Ok(cache) => cache,
+    Err(err) => {
+      report_error(globals.json, &err);
+      return EXIT_FAILURE;
+    }
+  };
+
+  match extract_sources(&cache, &options) {
+    Ok(extracted) => {
+      for source in &extracted.missing {
+        eprintln!("warning: {} has no embedded content", source);
+      }
+      for source in &extracted.refused {
+        eprintln!("warning: refusing to extract {}, its path escapes the output directory", source);
+      }
+      println!("extracted {} sources to {}", extracted.written, options.output);
+      EXIT_OK
+    },
+    Err(err) => {
+      report_error(globals.json, &err);
+      EXIT_FAILURE
+    }
+  }
+}
+
+fn parse_args(args: &[String]) -> Result<ExtractSourcesOptions, String> {
+  let mut output: Option<String> = None;
+  let mut filter: Option<String> = None;
+  let mut positional: Vec<String> = Vec::new();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "-o" | "--output" => output = Some(args.next().ok_or("--output expects a directory")?.clone()),
+      flag if flag.starts_with("--output=") => output = Some(flag["--output=".len()..].to_owned()),
+      "--filter" => filter = Some(args.next().ok_or("--filter expects a text")?.clone()),
+      flag if flag.starts_with("--filter=") => filter = Some(flag["--filter=".len()..].to_owned()),
+      flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option {}", flag)),
+      _ => positional.push(arg.clone())
+    }
+  }
+  if positional.len() != 1 {
+    return Err("expected a map file".into());
+  }
+  let output = output.ok_or("expected an output directory, given with -o")?;
+  Ok(ExtractSourcesOptions { map_file: positional.remove(0), output, filter })
+}
+
+fn extract_sources(cache: &Cache, options: &ExtractSourcesOptions) -> Result<ExtractedSources, String> {
+  let mut extracted = ExtractedSources::default();
+  let mut seen: HashSet<&str> = HashSet::new();
+  let root = Path::new(&options.output);
+  for source in cache.sources() {
+    if !seen.insert(source) || options.filter.as_ref().is_some_and(|filter| !source.contains(filter.as_str())) {
+      continue;
+    }
+    let content = match cache.source_content(source) {
+      Some(content) => content,
+      None => {
+        extracted.missing.push(source.clone());
+        continue;
+      }
+    };
+    let relative = match sanitize_source_path(source) {
+      Some(relative) => relative,
+      None => {
+        extracted.refused.push(source.clone());
+        continue;
+      }
+    };
+
+    let path = root.join(relative);
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent).map_err(|err| format!("could not create {}: {}", parent.display(), err))?;
+    }
+    fs::write(&path, content).map_err(|err| format!("could not write {}: {}", path.display(), err))?;
+    extracted.written += 1;
+  }
+  Ok(extracted)
+}
+
+// Turns a source like "webpack:///./src/app.js?v=1" into "src/app.js", or returns None
+// if the path is absolute or climbs out of the directory it is extracted to
+fn sanitize_source_path(source: &str) -> Option<PathBuf> {
+  let has_scheme = strip_scheme(source).len() != source.len();
+  let path = match source.find(['?', '#']) {
+    Some(end) if has_scheme => &source[..end],
+    _ => source
+  };
+  let path = strip_scheme(path);
+  if !has_scheme && path.starts_with(['/', '\\']) {
+    return None;
+  }
+
+  let mut relative = PathBuf::new();
+  for segment in path.split(['/', '\\']) {
+    match segment {
+      "" | "." => {},
+      ".." => return None,
+      // Drive letters like "C:"
+      segment if segment.contains(':') => return None,
+      segment => relative.push(segment)
+    }
+  }
+  if relative.as_os_str().is_empty() { None } else { Some(relative) }
+}
+
+#[cfg(test)]
+use std::env;
+#[cfg(test)]
+use std::process;
+#[cfg(test)]
+use js_source_mapper::consume_with_sources_content;
+
+#[test]
+fn it_sanitizes_source_paths() {
+  assert_eq!(sanitize_source_path("src/app.js"), Some(PathBuf::from("src").join("app.js")));
+  assert_eq!(sanitize_source_path("./src//app.js"), Some(PathBuf::from("src").join("app.js")));
+  assert_eq!(sanitize_source_path("webpack:///./src/app.js?v=1"), Some(PathBuf::from("src").join("app.js")));
+  assert_eq!(sanitize_source_path("webpack://app/src/app.js"), Some(PathBuf::from("app").join("src").join("app.js")));
+  assert_eq!(sanitize_source_path("/etc/passwd"), None);
+  assert_eq!(sanitize_source_path("../../etc/passwd"), None);
+  assert_eq!(sanitize_source_path("src/../../app.js"), None);
+  assert_eq!(sanitize_source_path("C:\\src\\app.js"), None);
+  assert_eq!(sanitize_source_path(""), None);
+}
+
+#[test]
+fn it_extracts_embedded_sources() {
+  let dir = env::temp_dir().join(format!("js-source-mapper-extract-{}", process::id()));
+  let cache = consume_with_sources_content(r#"{
+    "version": 3,
+    "sources": ["webpack:///./src/app.js", "src/lib/util.js", "../secret.js", "vendor.js"],
+    "sourcesContent": ["app();", "util();", "secret();", null],
+    "names": [],
+    "mappings": "AAAA,CCAA,CCAA,CCAA"
+  }"#).unwrap();
+  let options = |filter: Option<&str>| ExtractSourcesOptions {
+    map_file: String::new(),
+    output: dir.to_str().unwrap().to_owned(),
+    filter: filter.map(|filter| filter.to_owned())
+  };
+
+  let extracted = extract_sources(&cache, &options(None)).unwrap();
+  assert_eq!(extracted.written, 2);
+  assert_eq!(extracted.missing, vec!["vendor.js"]);
+  assert_eq!(extracted.refused, vec!["../secret.js"]);
+  assert_eq!(fs::read_to_string(dir.join("src").join("app.js")).unwrap(), "app();");
+  assert_eq!(fs::read_to_string(dir.join("src").join("lib").join("util.js")).unwrap(), "util();");
+  assert!(!dir.parent().unwrap().join("secret.js").exists());
+
+  assert_eq!(extract_sources(&cache, &options(Some("lib/"))).unwrap().written, 1);
+  fs::remove_dir_all(&dir).unwrap();
+}
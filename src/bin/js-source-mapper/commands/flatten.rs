@@ -0,0 +1,98 @@
+use js_source_mapper::consume_with_sources_content;
+use serde_json;
+
+use cli::{EXIT_FAILURE, EXIT_OK, GlobalOptions, print_summary, report_error, usage_error, write_output};
+use maps::read_map;
+
+struct FlattenOptions {
+  map_file: String,
+  output: Option<String>,
+  passthrough: bool
+}
+
+pub fn run(args: &[String], globals: &GlobalOptions) -> i32 {
+  let options = match parse_args(args) {
+    Ok(options) => options,
+    Err(err) => return usage_error(globals.json, &err)
+  };
+  match flatten(&options, globals) {
+    Ok(summary) => {
+      print_summary(options.output.as_deref(), &summary);
+      EXIT_OK
+    },
+    Err(err) => {
+      report_error(globals.json, &err);
+      EXIT_FAILURE
+    }
+  }
+}
+
+fn parse_args(args: &[String]) -> Result<FlattenOptions, String> {
+  let mut output: Option<String> = None;
+  let mut passthrough = false;
+  let mut positional: Vec<String> = Vec::new();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "-o" | "--output" => output = Some(args.next().ok_or("--output expects a file")?.clone()),
+      flag if flag.starts_with("--output=") => output = Some(flag["--output=".len()..].to_owned()),
+      "--passthrough" => passthrough = true,
+      flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option {}", flag)),
+      _ => positional.push(arg.clone())
+    }
+  }
+  if positional.len() != 1 {
+    return Err("expected an index map file".into());
+  }
+  Ok(FlattenOptions { map_file: positional.remove(0), output, passthrough })
+}
+
+// Writes the flattened map and returns a summary of what was done
+fn flatten(options: &FlattenOptions, globals: &GlobalOptions) -> Result<String, String> {
+  let (json, name) = read_map(&options.map_file, globals.map.as_deref())?;
+  let sections = serde_json::from_str::<serde_json::Value>(&json).ok()
+    .and_then(|source_map| source_map.get("sections").and_then(|sections| sections.as_array()).map(|sections| sections.len()));
+  let sections = match sections {
+    Some(sections) => sections,
+    None if options.passthrough => {
+      write_output(options.output.as_deref(), &json)?;
+      return Ok(format!("{} is not an index map, copied it unchanged", name));
+    },
+    None => return Err(format!("{} is not an index map; pass --passthrough to copy regular source maps unchanged", name))
+  };
+
+  let mut cache = consume_with_sources_content(&json).map_err(|err| format!("could not parse {}: {}", name, err))?;
+  if let Some(ref source_root) = globals.source_root {
+    cache.source_root = source_root.clone();
+  }
+  let flattened = cache.to_json().map_err(|err| format!("could not serialize the flattened source map: {}", err))?;
+  write_output(options.output.as_deref(), &flattened)?;
+  Ok(format!("merged {} sections into {} mappings", sections, cache.mappings().len()))
+}
+
+#[cfg(test)]
+use std::env;
+#[cfg(test)]
+use std::fs;
+#[cfg(test)]
+use std::process;
+
+#[test]
+fn it_flattens_index_maps() {
+  let dir = env::temp_dir().join(format!("js-source-mapper-flatten-{}", process::id()));
+  fs::create_dir_all(&dir).unwrap();
+  let path = |name: &str| dir.join(name).to_str().unwrap().to_owned();
+  fs::write(dir.join("index.map"), r#"{ "version": 3, "sections": [
+    { "offset": { "line": 0, "column": 0 }, "map": { "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA" } },
+    { "offset": { "line": 0, "column": 10 }, "map": { "version": 3, "sources": ["b.js"], "names": [], "mappings": "AAAA" } }
+  ] }"#).unwrap();
+  fs::write(dir.join("plain.map"), r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA" }"#).unwrap();
+  let options = |input: &str, passthrough: bool| FlattenOptions { map_file: path(input), output: Some(path("out.map")), passthrough };
+
+  assert_eq!(flatten(&options("index.map", false), &GlobalOptions::default()), Ok("merged 2 sections into 2 mappings".into()));
+  assert_eq!(fs::read_to_string(dir.join("out.map")).unwrap(), r#"{"version":3,"sources":["a.js","b.js"],"names":[],"mappings":"AAAA,UCAA"}"#);
+  assert!(flatten(&options("plain.map", false), &GlobalOptions::default()).unwrap_err().contains("is not an index map"));
+  assert!(flatten(&options("plain.map", true), &GlobalOptions::default()).is_ok());
+  assert_eq!(fs::read_to_string(dir.join("out.map")).unwrap(), fs::read_to_string(dir.join("plain.map")).unwrap());
+  fs::remove_dir_all(&dir).unwrap();
+}
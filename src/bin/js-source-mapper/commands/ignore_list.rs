@@ -0,0 +1,145 @@
+use js_source_mapper::Cache;
+
+use cli::{EXIT_FAILURE, EXIT_OK, GlobalOptions, print_summary, report_error, usage_error, write_output};
+use glob::source_matches;
+use maps::load_with_sources_content;
+
+// An --add or --remove pattern, in the order given
+struct Edit {
+  add: bool,
+  pattern: String
+}
+
+struct IgnoreListOptions {
+  map_file: String,
+  edits: Vec<Edit>,
+  output: Option<String>
+}
+
+pub fn run(args: &[String], globals: &GlobalOptions) -> i32 {
+  let options = match parse_args(args) {
+    Ok(options) => options,
+    Err(err) => return usage_error(globals.json, &err)
+  };
+  let mut cache = match load_with_sources_content(&options.map_file, globals) {
+    Ok(cache) => cache,
+    Err(err) => {
+      report_error(globals.json, &err);
+      return EXIT_FAILURE;
+    }
+  };
+
+  if options.edits.is_empty() {
+    for source in cache.ignored_sources() {
+      println!("{}", source);
+    }
+    return EXIT_OK;
+  }
+
+  for pattern in edit_ignore_list(&mut cache, &options.edits) {
+    eprintln!("warning: {} matches no sources", pattern);
+  }
+  let result = cache.to_json()
+    .map_err(|err| format!("could not serialize the source map: {}", err))
+    .and_then(|json| write_output(options.output.as_deref(), &json));
+  match result {
+    Ok(()) => {
+      let summary = format!("{} of {} sources are ignore-listed", cache.ignored_sources().len(), distinct_sources(&cache));
+      print_summary(options.output.as_deref(), &summary);
+      EXIT_OK
+    },
+    Err(err) => {
+      report_error(globals.json, &err);
+      EXIT_FAILURE
+    }
+  }
+}
+
+fn parse_args(args: &[String]) -> Result<IgnoreListOptions, String> {
+  let mut edits: Vec<Edit> = Vec::new();
+  let mut output: Option<String> = None;
+  let mut positional: Vec<String> = Vec::new();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    let (flag, value) = match arg.find('=') {
+      Some(equals) if arg.starts_with("--") => (&arg[..equals], Some(arg[equals + 1..].to_owned())),
+      _ => (arg.as_str(), None)
+    };
+    match flag {
+      "--add" | "--remove" | "-o" | "--output" => {
+        let value = match value {
+          Some(value) => value,
+          None => args.next().ok_or_else(|| format!("{} expects a value", flag))?.clone()
+        };
+        match flag {
+          "--add" => edits.push(Edit { add: true, pattern: value }),
+          "--remove" => edits.push(Edit { add: false, pattern: value }),
+          _ => output = Some(value)
+        }
+      },
+      flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option {}", flag)),
+      _ => positional.push(arg.clone())
+    }
+  }
+  if positional.len() != 1 {
+    return Err("expected a map file".into());
+  }
+  if edits.is_empty() && output.is_some() {
+    return Err("-o expects --add or --remove patterns".into());
+  }
+  Ok(IgnoreListOptions { map_file: positional.remove(0), edits, output })
+}
+
+// Applies the edits in order, returning the patterns that matched no sources
+fn edit_ignore_list<'a>(cache: &mut Cache, edits: &'a [Edit]) -> Vec<&'a str> {
+  let sources: Vec<String> = cache.sources().to_vec();
+  let mut unmatched: Vec<&str> = Vec::new();
+  for edit in edits {
+    let mut matched = false;
+    for source in &sources {
+      if source_matches(&edit.pattern, source) {
+        cache.set_ignored(source, edit.add);
+        matched = true;
+      }
+    }
+    if !matched {
+      unmatched.push(&edit.pattern);
+    }
+  }
+  unmatched
+}
+
+fn distinct_sources(cache: &Cache) -> usize {
+  let mut sources: Vec<&String> = cache.sources().iter().collect();
+  sources.sort();
+  sources.dedup();
+  sources.len()
+}
+
+#[cfg(test)]
+use js_source_mapper::consume;
+
+#[test]
+fn it_edits_the_ignore_list() {
+  let mut cache = consume(r#"{ "version": 3, "sources": ["src/app.js", "webpack:///node_modules/a.js", "node_modules/b/index.js", "vendor.js"], "names": [], "mappings": "AAAA,CCAA,CCAA,CCAA", "ignoreList": [3] }"#).unwrap();
+  let edit = |add, pattern: &str| Edit { add, pattern: pattern.into() };
+  let edits = [edit(true, "node_modules/**"), edit(false, "vendor.js"), edit(true, "lib/**")];
+  assert_eq!(edit_ignore_list(&mut cache, &edits), ["lib/**"]);
+  assert_eq!(cache.ignored_sources(), ["webpack:///node_modules/a.js", "node_modules/b/index.js"]);
+  assert_eq!(
+    cache.to_json().unwrap(),
+    r#"{"version":3,"sources":["src/app.js","webpack:///node_modules/a.js","node_modules/b/index.js","vendor.js"],"names":[],"mappings":"AAAA,CCAA,CCAA,CCAA","ignoreList":[1,2]}"#
+  );
+  assert_eq!(distinct_sources(&cache), 4);
+}
+
+#[test]
+fn it_parses_ignore_list_arguments() {
+  let args = |args: &[&str]| parse_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>());
+  let options = args(&["a.map", "--add", "node_modules/**", "--remove=vendor.js", "-o", "out.map"]).unwrap();
+  assert_eq!(options.edits.iter().map(|edit| (edit.add, edit.pattern.as_str())).collect::<Vec<_>>(), [(true, "node_modules/**"), (false, "vendor.js")]);
+  assert_eq!(options.output.as_deref(), Some("out.map"));
+  assert!(args(&["a.map"]).unwrap().edits.is_empty());
+  assert!(args(&["a.map", "-o", "out.map"]).is_err());
+  assert!(args(&["--add", "x"]).is_err());
+}
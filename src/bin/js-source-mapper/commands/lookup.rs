@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use js_source_mapper::{Cache, CodePosition};
+
+use cli::{EXIT_FAILURE, EXIT_OK, GlobalOptions, report_error, usage_error};
+use format::{format_lookup, json_lookup, parse_position};
+use maps::{load, load_with_sources_content, resolve_sources, strip_scheme};
+
+struct LookupOptions {
+  map_file: String,
+  positions: Vec<String>,
+  positions_file: Option<String>,
+  context: Option<usize>,
+  source_dir: Option<String>
+}
+
+// A position to look up, with where it came from for error messages
+struct Query {
+  location: Option<String>,
+  position: String
+}
+
+pub fn run(args: &[String], globals: &GlobalOptions) -> i32 {
+  let options = match parse_args(args) {
+    Ok(options) => options,
+    Err(err) => return usage_error(globals.json, &err)
+  };
+  if options.context.is_some() && globals.json {
+    return usage_error(true, "--context cannot be combined with --json");
+  }
+
+  // The original code is only needed to print context
+  let loaded = if options.context.is_some() {
+    load_with_sources_content(&options.map_file, globals)
+  } else {
+    load(&options.map_file, globals)
+  };
+  let mut cache = match loaded {
+    Ok(cache) => cache,
+    Err(err) => {
+      report_error(globals.json, &err);
+      return EXIT_FAILURE;
+    }
+  };
+  resolve_sources(&mut cache);
+
+  let mut queries: Vec<Query> = options.positions.iter()
+    .map(|position| Query { location: None, position: position.clone() })
+    .collect();
+  if let Some(ref path) = options.positions_file {
+    match read_positions(path) {
+      Ok(positions) => queries.extend(positions),
+      Err(err) => {
+        report_error(globals.json, &err);
+        return EXIT_FAILURE;
+      }
+    }
+  }
+
+  let mut status = EXIT_OK;
+  let mut json_results: Vec<String> = Vec::new();
+  let mut original_sources: HashMap<String, Option<String>> = HashMap::new();
+  for query in &queries {
+    let result = parse_position(&query.position).map(|(line, column)| (line, column, cache.mapping_for_generated_position(line, column)));
+    match result {
+      Ok((line, column, mapping)) => {
+        if mapping.source.is_empty() {
+          status = EXIT_FAILURE;
+        }
+        if globals.json {
+          json_results.push(json_lookup(line, column, &mapping));
+        } else {
+          match format_lookup(line, column, &mapping) {
+            Ok(output) => println!("{}", output),
+            Err(err) => report_error(false, &err)
+          }
+          if let (Some(context), false) = (options.context, mapping.source.is_empty()) {
+            let text = original_sources.entry(mapping.source.to_string())
+              .or_insert_with(|| read_original_source(&cache, &mapping.source, options.source_dir.as_deref()));
+            match *text {
+              Some(ref text) => println!("{}", format_context(text, &mapping.original, context)),
+              None => report_error(false, &format!("the original code of {} is not available", mapping.source))
+            }
+          }
+        }
+      },
+      Err(err) => {
+        match query.location {
+          Some(ref location) => report_error(globals.json, &format!("{}: {}", location, err)),
+          None => report_error(globals.json, &err)
+        }
+        status = EXIT_FAILURE;
+      }
+    }
+  }
+
+  if options.positions_file.is_some() && globals.json {
+    println!("[{}]", json_results.join(","));
+  } else {
+    for result in json_results {
+      println!("{}", result);
+    }
+  }
+  status
+}
+
+fn parse_args(args: &[String]) -> Result<LookupOptions, String> {
+  let mut positions_file: Option<String> = None;
+  let mut context: Option<String> = None;
+  let mut source_dir: Option<String> = None;
+  let mut positional: Vec<String> = Vec::new();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--positions" => positions_file = Some(args.next().ok_or("--positions expects a file")?.clone()),
+      flag if flag.starts_with("--positions=") => positions_file = Some(flag["--positions=".len()..].to_owned()),
+      "--context" => context = Some(args.next().ok_or("--context expects a number of lines")?.clone()),
+      flag if flag.starts_with("--context=") => context = Some(flag["--context=".len()..].to_owned()),
+      "--source-dir" => source_dir = Some(args.next().ok_or("--source-dir expects a directory")?.clone()),
+      flag if flag.starts_with("--source-dir=") => source_dir = Some(flag["--source-dir=".len()..].to_owned()),
+      flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option {}", flag)),
+      _ => positional.push(arg.clone())
+    }
+  }
+  if positional.is_empty() {
+    return Err("expected a map file".into());
+  }
+  let map_file = positional.remove(0);
+  if positional.is_empty() && positions_file.is_none() {
+    return Err("expected at least one position".into());
+  }
+  let context = match context {
+    Some(context) => Some(context.parse().map_err(|_| format!("invalid number of context lines {:?}", context))?),
+    None => None
+  };
+  Ok(LookupOptions { map_file, positions: positional, positions_file, context, source_dir })
+}
+
+// Reads one position per line, skipping blank lines and # comments
+fn read_positions(path: &str) -> Result<Vec<Query>, String> {
+  let contents = fs::read_to_string(path).map_err(|err| format!("could not read {}: {}", path, err))?;
+  Ok(parse_positions_file(path, &contents))
+}
+
+fn parse_positions_file(path: &str, contents: &str) -> Vec<Query> {
+  (1..).zip(contents.lines())
+    .map(|(line_number, line)| (line_number, line.trim()))
+    .filter(|&(_, line)| !line.is_empty() && !line.starts_with('#'))
+    .map(|(line_number, line)| Query { location: Some(format!("{}:{}", path, line_number)), position: line.to_owned() })
+    .collect()
+}
+
+// Returns the text of an original source, from the sourcesContent of the map or else
+// from the source directory
+fn read_original_source(cache: &Cache, source: &str, source_dir: Option<&str>) -> Option<String> {
+  if let Some(content) = cache.source_content(source) {
+    return Some(content.to_owned());
+  }
+  let path = Path::new(source_dir.unwrap_or(".")).join(strip_scheme(source));
+  fs::read_to_string(path).ok()
+}
+
+// Formats the lines around an original position like a compiler diagnostic, marking the
+// line with > and the column with a caret
+fn format_context(text: &str, original: &CodePosition, context: usize) -> String {
+  let lines: Vec<&str> = text.lines().collect();
+  let target = original.line as usize;
+  if target > lines.len() {
+    return format!("  line {} is past the end of the source, which has {} lines", target, lines.len());
+  }
+
+  let first = target.saturating_sub(context).max(1);
+  let last = (target + context).min(lines.len());
+  let width = last.to_string().len();
+  let mut output: Vec<String> = Vec::new();
+  for number in first..=last {
+    let code = lines[number - 1];
+    if number != target {
+      output.push(format!("  {:>width$} | {}", number, code, width = width));
+      continue;
+    }
+    output.push(format!("> {:>width$} | {}", number, code, width = width));
+    // Keep tabs so that the caret lines up however wide they are displayed
+    let indent: String = code.chars()
+      .take(original.column as usize)
+      .map(|character| if character == '\t' { '\t' } else { ' ' })
+      .collect();
+    output.push(format!("  {:>width$} | {}^", "", indent, width = width));
+  }
+  output.join("\n")
+}
+
+#[cfg(test)]
+use std::env;
+#[cfg(test)]
+use std::process;
+#[cfg(test)]
+use js_source_mapper::consume_with_sources_content;
+
+#[test]
+fn it_parses_arguments() {
+  let args = |args: &[&str]| -> Vec<String> { args.iter().map(|arg| arg.to_string()).collect() };
+  let options = parse_args(&args(&["-", "1:0", "2:0"])).unwrap();
+  assert_eq!(options.map_file, "-");
+  assert_eq!(options.positions, ["1:0", "2:0"]);
+  assert!(parse_args(&args(&["a.map"])).is_err());
+
+  let options = parse_args(&args(&["a.map", "--positions", "positions.txt"])).unwrap();
+  assert_eq!(options.map_file, "a.map");
+  assert_eq!(options.positions_file, Some("positions.txt".into()));
+  assert!(options.positions.is_empty());
+  assert!(parse_args(&args(&["--jsn", "a.map", "1:0"])).is_err());
+}
+
+#[test]
+fn it_reads_positions_files() {
+  let queries = parse_positions_file("positions.txt", "# from the incident\n1:0\n\n  2:5  \nbad\n");
+  let positions: Vec<(&str, &str)> = queries.iter()
+    .map(|query| (query.location.as_ref().unwrap().as_str(), query.position.as_str()))
+    .collect();
+  assert_eq!(positions, [("positions.txt:2", "1:0"), ("positions.txt:4", "2:5"), ("positions.txt:5", "bad")]);
+}
+
+#[test]
+fn it_formats_original_code_context() {
+  let text = "function f() {\n\tif (x) {\n\t\tg();\n\t}\n}\n";
+  let context = |text, line, column, lines| -> Vec<String> {
+    format_context(text, &CodePosition { line, column }, lines).lines().map(|line| line.to_owned()).collect()
+  };
+  assert_eq!(context(text, 3, 2, 1), ["  2 | \tif (x) {", "> 3 | \t\tg();", "    | \t\t^", "  4 | \t}"]);
+  assert_eq!(context(text, 1, 9, 0), ["> 1 | function f() {", "    |          ^"]);
+  // Columns past the end of the line point just after it
+  assert_eq!(context("a\nb", 2, 7, 1), ["  1 | a", "> 2 | b", "    |  ^"]);
+  assert_eq!(context(text, 9, 0, 2), ["  line 9 is past the end of the source, which has 5 lines"]);
+}
+
+#[test]
+fn it_reads_original_sources() {
+  let dir = env::temp_dir().join(format!("js-source-mapper-context-{}", process::id()));
+  fs::create_dir_all(dir.join("src")).unwrap();
+  fs::write(dir.join("src").join("b.js"), "b();").unwrap();
+  let cache = consume_with_sources_content(r#"{ "version": 3, "sources": ["a.js", "webpack:///src/b.js"], "sourcesContent": ["a();"], "names": [], "mappings": "AAAA,CCAA" }"#).unwrap();
+  let source_dir = dir.to_str();
+
+  assert_eq!(read_original_source(&cache, "a.js", source_dir), Some("a();".into()));
+  assert_eq!(read_original_source(&cache, "webpack:///src/b.js", source_dir), Some("b();".into()));
+  assert_eq!(read_original_source(&cache, "c.js", source_dir), None);
+  fs::remove_dir_all(&dir).unwrap();
+
+  let args = |args: &[&str]| -> Vec<String> { args.iter().map(|arg| arg.to_string()).collect() };
+  assert_eq!(parse_args(&args(&["--context", "3", "a.map", "1:0"])).unwrap().context, Some(3));
+  assert!(parse_args(&args(&["--context=-1", "a.map", "1:0"])).is_err());
+}
@@ -0,0 +1,140 @@
+use std::io;
+use std::io::Write;
+
+use js_source_mapper::{Cache, Mapping};
+use serde_json;
+
+use cli::{EXIT_FAILURE, EXIT_OK, GlobalOptions, report_error, usage_error};
+use format::{PositionOutput, write_mapping};
+use maps::{load, resolve_sources};
+
+struct LookupNameOptions {
+  map_file: String,
+  name: String,
+  source: Option<String>
+}
+
+// The same shape as the mappings written by Cache::mappings_to_json
+#[derive(Serialize, Debug)]
+struct MappingOutput<'a> {
+  generated: PositionOutput,
+  original: PositionOutput,
+  source: &'a str,
+  name: &'a str
+}
+
+pub fn run(args: &[String], globals: &GlobalOptions) -> i32 {
+  let options = match parse_args(args) {
+    Ok(options) => options,
+    Err(err) => return usage_error(globals.json, &err)
+  };
+  let mut cache = match load(&options.map_file, globals) {
+    Ok(cache) => cache,
+    Err(err) => {
+      report_error(globals.json, &err);
+      return EXIT_FAILURE;
+    }
+  };
+  resolve_sources(&mut cache);
+
+  let mappings = mappings_named(&cache, &options.name, options.source.as_deref());
+  let stdout = io::stdout();
+  let result = if globals.json {
+    writeln!(stdout.lock(), "{}", json_mappings(&mappings))
+  } else {
+    write_mappings(&mappings, io::BufWriter::new(stdout.lock()))
+  };
+  match result {
+    Ok(()) => {},
+    Err(ref err) if err.kind() == io::ErrorKind::BrokenPipe => return EXIT_OK,
+    Err(err) => {
+      report_error(globals.json, &format!("could not write the mappings: {}", err));
+      return EXIT_FAILURE;
+    }
+  }
+  if mappings.is_empty() {
+    report_error(globals.json, &format!("no mappings are named {}", options.name));
+    return EXIT_FAILURE;
+  }
+  EXIT_OK
+}
+
+fn parse_args(args: &[String]) -> Result<LookupNameOptions, String> {
+  let mut source: Option<String> = None;
+  let mut positional: Vec<String> = Vec::new();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--source" => source = Some(args.next().ok_or("--source expects a source")?.clone()),
+      flag if flag.starts_with("--source=") => source = Some(flag["--source=".len()..].to_owned()),
+      flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option {}", flag)),
+      _ => positional.push(arg.clone())
+    }
+  }
+  if positional.len() != 2 {
+    return Err("expected a map file and a name".into());
+  }
+  let name = positional.pop().unwrap();
+  if name.is_empty() {
+    return Err("the name must not be empty".into());
+  }
+  Ok(LookupNameOptions { map_file: positional.remove(0), name, source })
+}
+
+// The mappings with the name, in generated order
+fn mappings_named<'a>(cache: &'a Cache, name: &str, source: Option<&str>) -> Vec<&'a Mapping> {
+  cache.mappings().iter()
+    .filter(|mapping| &*mapping.name == name)
+    .filter(|mapping| source.is_none_or(|source| &*mapping.source == source))
+    .collect()
+}
+
+fn write_mappings<W: Write>(mappings: &[&Mapping], mut output: W) -> io::Result<()> {
+  for mapping in mappings {
+    write_mapping(&mut output, mapping)?;
+  }
+  output.flush()
+}
+
+fn json_mappings(mappings: &[&Mapping]) -> String {
+  let output: Vec<MappingOutput> = mappings.iter()
+    .map(|mapping| MappingOutput {
+      generated: PositionOutput { line: mapping.generated.line, column: mapping.generated.column },
+      original: PositionOutput { line: mapping.original.line, column: mapping.original.column },
+      source: &mapping.source,
+      name: &mapping.name
+    })
+    .collect();
+  serde_json::to_string(&output).expect("mappings should serialize")
+}
+
+#[cfg(test)]
+use js_source_mapper::consume;
+
+#[test]
+fn it_finds_mappings_by_name() {
+  let cache = consume(r#"{ "version": 3, "sources": ["a.js", "b.js"], "names": ["x", "y"], "mappings": "AAAAA,EAAEC,ECAAD;AAAA" }"#).unwrap();
+  let positions = |name: &str, source: Option<&str>| -> Vec<(u32, u32)> {
+    mappings_named(&cache, name, source).iter().map(|mapping| (mapping.generated.line, mapping.generated.column)).collect()
+  };
+  assert_eq!(positions("x", None), [(1, 0), (1, 4)]);
+  assert_eq!(positions("x", Some("b.js")), [(1, 4)]);
+  assert_eq!(positions("y", None), [(1, 2)]);
+  assert!(positions("z", None).is_empty());
+
+  let mut output = Vec::new();
+  write_mappings(&mappings_named(&cache, "x", None), &mut output).unwrap();
+  assert_eq!(String::from_utf8(output).unwrap(), "1:0 -> a.js:1:0 (x)\n1:4 -> b.js:1:2 (x)\n");
+  assert_eq!(json_mappings(&mappings_named(&cache, "y", None)), r#"[{"generated":{"line":1,"column":2},"original":{"line":1,"column":2},"source":"a.js","name":"y"}]"#);
+}
+
+#[test]
+fn it_parses_lookup_name_arguments() {
+  let args = |args: &[&str]| -> Vec<String> { args.iter().map(|arg| arg.to_string()).collect() };
+  let options = parse_args(&args(&["a.map", "handleSubmit", "--source", "src/form.ts"])).unwrap();
+  assert_eq!(options.map_file, "a.map");
+  assert_eq!(&*options.name, "handleSubmit");
+  assert_eq!(options.source.as_deref(), Some("src/form.ts"));
+  assert!(parse_args(&args(&["a.map"])).is_err());
+  assert!(parse_args(&args(&["a.map", ""])).is_err());
+}
@@ -0,0 +1,59 @@
+mod bench;
+mod compose;
+mod coverage;
+mod diff;
+mod dump;
+mod extract;
+mod extract_sources;
+mod flatten;
+mod ignore_list;
+mod lookup;
+mod lookup_name;
+mod offset;
+mod rebase;
+mod repl;
+#[cfg(feature = "serve")]
+mod serve;
+mod stats;
+mod strip;
+mod symbolicate;
+mod to_data_uri;
+mod validate;
+mod verify;
+
+use cli::GlobalOptions;
+
+// A command of the command line tool, named by its first argument
+pub struct Command {
+  pub name: &'static str,
+  // Whether the command supports the global --json option
+  pub json: bool,
+  // Runs the command with the remaining arguments and returns the exit code
+  pub run: fn(&[String], &GlobalOptions) -> i32
+}
+
+// The first command is run when the first argument does not name one
+pub const COMMANDS: &[Command] = &[
+  Command { name: "lookup", json: true, run: lookup::run },
+  Command { name: "symbolicate", json: false, run: symbolicate::run },
+  Command { name: "dump", json: false, run: dump::run },
+  Command { name: "stats", json: true, run: stats::run },
+  Command { name: "validate", json: true, run: validate::run },
+  Command { name: "repl", json: false, run: repl::run },
+  Command { name: "flatten", json: false, run: flatten::run },
+  Command { name: "rebase", json: false, run: rebase::run },
+  Command { name: "diff", json: false, run: diff::run },
+  Command { name: "extract-sources", json: false, run: extract_sources::run },
+  Command { name: "compose", json: false, run: compose::run },
+  Command { name: "offset", json: false, run: offset::run },
+  Command { name: "lookup-name", json: true, run: lookup_name::run },
+  Command { name: "ignore-list", json: false, run: ignore_list::run },
+  Command { name: "bench", json: true, run: bench::run },
+  Command { name: "strip", json: false, run: strip::run },
+  Command { name: "coverage", json: false, run: coverage::run },
+  Command { name: "verify", json: false, run: verify::run },
+  Command { name: "to-data-uri", json: false, run: to_data_uri::run },
+  Command { name: "extract", json: false, run: extract::run },
+  #[cfg(feature = "serve")]
+  Command { name: "serve", json: false, run: serve::run }
+];
@@ -0,0 +1,116 @@
+use cli::{EXIT_FAILURE, EXIT_OK, GlobalOptions, print_summary, report_error, usage_error, write_output};
+use maps::load_with_sources_content;
+
+struct OffsetOptions {
+  map_file: String,
+  lines: u32,
+  first_line_columns: u32,
+  output: Option<String>
+}
+
+pub fn run(args: &[String], globals: &GlobalOptions) -> i32 {
+  let options = match parse_args(args) {
+    Ok(options) => options,
+    Err(err) => return usage_error(globals.json, &err)
+  };
+  match offset(&options, globals) {
+    Ok(summary) => {
+      print_summary(options.output.as_deref(), &summary);
+      EXIT_OK
+    },
+    Err(err) => {
+      report_error(globals.json, &err);
+      EXIT_FAILURE
+    }
+  }
+}
+
+fn parse_args(args: &[String]) -> Result<OffsetOptions, String> {
+  let mut lines: Option<String> = None;
+  let mut first_line_columns: Option<String> = None;
+  let mut output: Option<String> = None;
+  let mut positional: Vec<String> = Vec::new();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--lines" => lines = Some(args.next().ok_or("--lines expects a number of lines")?.clone()),
+      flag if flag.starts_with("--lines=") => lines = Some(flag["--lines=".len()..].to_owned()),
+      "--first-line-columns" => first_line_columns = Some(args.next().ok_or("--first-line-columns expects a number of columns")?.clone()),
+      flag if flag.starts_with("--first-line-columns=") => first_line_columns = Some(flag["--first-line-columns=".len()..].to_owned()),
+      "-o" | "--output" => output = Some(args.next().ok_or("--output expects a file")?.clone()),
+      flag if flag.starts_with("--output=") => output = Some(flag["--output=".len()..].to_owned()),
+      // Negative numbers are values, and are rejected below with a clearer message
+      flag if flag.starts_with('-') && flag != "-" && flag.parse::<i64>().is_err() => return Err(format!("unknown option {}", flag)),
+      _ => positional.push(arg.clone())
+    }
+  }
+  if positional.len() != 1 {
+    return Err("expected a map file".into());
+  }
+
+  fn parse_offset(name: &str, value: Option<String>) -> Result<u32, String> {
+    let value = match value {
+      Some(value) => value,
+      None => return Ok(0)
+    };
+    match value.parse::<i64>() {
+      Ok(offset) if offset < 0 => Err(format!("{} {} would move mappings before the start of the generated file", name, offset)),
+      Ok(offset) if offset <= u32::MAX as i64 => Ok(offset as u32),
+      _ => Err(format!("invalid {} {:?}", name, value))
+    }
+  }
+  if lines.is_none() && first_line_columns.is_none() {
+    return Err("expected --lines or --first-line-columns".into());
+  }
+  Ok(OffsetOptions {
+    map_file: positional.remove(0),
+    lines: parse_offset("--lines", lines)?,
+    first_line_columns: parse_offset("--first-line-columns", first_line_columns)?,
+    output
+  })
+}
+
+// Writes the shifted map and returns a summary of what was done
+fn offset(options: &OffsetOptions, globals: &GlobalOptions) -> Result<String, String> {
+  let cache = load_with_sources_content(&options.map_file, globals)?;
+  let mappings = cache.mappings();
+  let shifted = cache.offset_generated(options.lines, options.first_line_columns)
+    .map_err(|_| "the offset moves mappings past the largest supported position".to_owned())?;
+  let json = shifted.to_json().map_err(|err| format!("could not serialize the shifted source map: {}", err))?;
+  write_output(options.output.as_deref(), &json)?;
+  Ok(format!(
+    "shifted {} mappings by {} lines, and {} mappings on the first line by {} columns",
+    mappings.len(),
+    options.lines,
+    mappings.iter().filter(|mapping| mapping.generated.line == 1).count(),
+    options.first_line_columns
+  ))
+}
+
+#[cfg(test)]
+use std::env;
+#[cfg(test)]
+use std::fs;
+#[cfg(test)]
+use std::process;
+
+#[test]
+fn it_offsets_generated_positions() {
+  let args = |args: &[&str]| parse_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>());
+  let options = args(&["--lines", "120", "--first-line-columns=14", "a.map"]).unwrap();
+  assert_eq!((options.lines, options.first_line_columns), (120, 14));
+  assert!(args(&["--lines", "-3", "a.map"]).err().unwrap().contains("before the start of the generated file"));
+  assert!(args(&["--lines", "3x", "a.map"]).is_err());
+  assert!(args(&["a.map"]).is_err());
+
+  let dir = env::temp_dir().join(format!("js-source-mapper-offset-{}", process::id()));
+  fs::create_dir_all(&dir).unwrap();
+  let path = |name: &str| dir.join(name).to_str().unwrap().to_owned();
+  fs::write(dir.join("a.map"), r#"{ "version": 3, "sources": ["a.js"], "sourcesContent": ["a();"], "names": [], "mappings": "AAAA,CAAC;AACA" }"#).unwrap();
+  let options = |lines, first_line_columns| OffsetOptions { map_file: path("a.map"), lines, first_line_columns, output: Some(path("shifted.map")) };
+
+  assert_eq!(offset(&options(2, 14), &GlobalOptions::default()), Ok("shifted 3 mappings by 2 lines, and 2 mappings on the first line by 14 columns".into()));
+  assert_eq!(fs::read_to_string(dir.join("shifted.map")).unwrap(), r#"{"version":3,"sources":["a.js"],"sourcesContent":["a();"],"names":[],"mappings":";;cAAA,CAAC;AACA"}"#);
+  assert!(offset(&options(u32::MAX, 0), &GlobalOptions::default()).is_err());
+  fs::remove_dir_all(&dir).unwrap();
+}
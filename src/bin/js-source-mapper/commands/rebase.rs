@@ -0,0 +1,146 @@
+use std::collections::HashSet;
+
+use js_source_mapper::Cache;
+
+use cli::{EXIT_FAILURE, EXIT_OK, GlobalOptions, report_error, usage_error, write_output};
+use maps::{load_with_sources_content, strip_scheme};
+
+struct RebaseOptions {
+  map_file: String,
+  prefixes: Vec<(String, String)>,
+  strip_scheme: bool,
+  dry_run: bool,
+  output: Option<String>
+}
+
+pub fn run(args: &[String], globals: &GlobalOptions) -> i32 {
+  let options = match parse_args(args) {
+    Ok(options) => options,
+    Err(err) => return usage_error(globals.json, &err)
+  };
+  let mut cache = match load_with_sources_content(&options.map_file, globals) {
+    Ok(cache) => cache,
+    Err(err) => {
+      report_error(globals.json, &err);
+      return EXIT_FAILURE;
+    }
+  };
+
+  if options.dry_run {
+    for line in rebase_preview(&cache, &options) {
+      println!("{}", line);
+    }
+    return EXIT_OK;
+  }
+
+  cache.source_root = rebase_path(&cache.source_root, &options);
+  cache.map_sources(|source| rebase_path(source, &options));
+  let result = cache.to_json()
+    .map_err(|err| format!("could not serialize the rebased source map: {}", err))
+    .and_then(|json| write_output(options.output.as_deref(), &json));
+  match result {
+    Ok(()) => EXIT_OK,
+    Err(err) => {
+      report_error(globals.json, &err);
+      EXIT_FAILURE
+    }
+  }
+}
+
+fn parse_args(args: &[String]) -> Result<RebaseOptions, String> {
+  let mut prefixes: Vec<(String, String)> = Vec::new();
+  let mut from: Option<String> = None;
+  let mut strip_scheme = false;
+  let mut dry_run = false;
+  let mut output: Option<String> = None;
+  let mut positional: Vec<String> = Vec::new();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    let (flag, value) = match arg.find('=') {
+      Some(equals) if arg.starts_with("--") => (&arg[..equals], Some(arg[equals + 1..].to_owned())),
+      _ => (arg.as_str(), None)
+    };
+    match flag {
+      "--from" | "--to" | "-o" | "--output" => {
+        let value = match value {
+          Some(value) => value,
+          None => args.next().ok_or_else(|| format!("{} expects a value", flag))?.clone()
+        };
+        match flag {
+          "--from" if from.is_some() => return Err("--from must be followed by --to".into()),
+          "--from" => from = Some(value),
+          "--to" => prefixes.push((from.take().ok_or("--to must follow a --from")?, value)),
+          _ => output = Some(value)
+        }
+      },
+      "--strip-scheme" => strip_scheme = true,
+      "--dry-run" => dry_run = true,
+      flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option {}", flag)),
+      _ => positional.push(arg.clone())
+    }
+  }
+  if from.is_some() {
+    return Err("--from must be followed by --to".into());
+  }
+  if prefixes.is_empty() && !strip_scheme {
+    return Err("expected --from and --to prefixes or --strip-scheme".into());
+  }
+  if positional.len() != 1 {
+    return Err("expected a map file".into());
+  }
+  Ok(RebaseOptions { map_file: positional.remove(0), prefixes, strip_scheme, dry_run, output })
+}
+
+// Strips the URL scheme if asked to, then replaces the first matching prefix
+fn rebase_path(path: &str, options: &RebaseOptions) -> String {
+  let path = if options.strip_scheme { strip_scheme(path) } else { path };
+  for (from, to) in &options.prefixes {
+    if let Some(rest) = path.strip_prefix(from.as_str()) {
+      return format!("{}{}", to, rest);
+    }
+  }
+  path.to_owned()
+}
+
+// Lists how the source root and each distinct source would be rebased
+fn rebase_preview(cache: &Cache, options: &RebaseOptions) -> Vec<String> {
+  let mut seen: HashSet<&str> = HashSet::new();
+  let root = if cache.source_root.is_empty() { None } else { Some(cache.source_root.as_str()) };
+  root.into_iter().chain(cache.sources().iter().map(|source| source.as_str()))
+    .filter(|path| seen.insert(path))
+    .map(|path| {
+      let rebased = rebase_path(path, options);
+      let label = if Some(path) == root { "sourceRoot " } else { "" };
+      if rebased == path {
+        format!("{}{} (unchanged)", label, path)
+      } else {
+        format!("{}{} -> {}", label, path, rebased)
+      }
+    })
+    .collect()
+}
+
+#[cfg(test)]
+use js_source_mapper::consume;
+
+#[test]
+fn it_rebases_source_paths() {
+  let args = |args: &[&str]| parse_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>());
+  let options = args(&["--from", "/home/ci/build/", "--to", "./", "--from=/home/ci/", "--to=../", "--strip-scheme", "a.map"]).unwrap();
+  assert_eq!(rebase_path("/home/ci/build/src/a.js", &options), "./src/a.js");
+  assert_eq!(rebase_path("/home/ci/lib/b.js", &options), "../lib/b.js");
+  assert_eq!(rebase_path("webpack:///home/ci/build/c.js", &options), "home/ci/build/c.js");
+  assert_eq!(rebase_path("webpack://app/d.js", &options), "app/d.js");
+  assert_eq!(rebase_path("vendor/e.js", &options), "vendor/e.js");
+  assert!(args(&["--from", "/a/", "a.map"]).is_err());
+  assert!(args(&["--to", "/a/", "a.map"]).is_err());
+  assert!(args(&["a.map"]).is_err());
+
+  let cache = consume(r#"{ "version": 3, "sourceRoot": "webpack://", "sources": ["/home/ci/build/a.js", "b.js", "b.js"], "names": [], "mappings": "AAAA,CCAA,CCAA" }"#).unwrap();
+  let options = args(&["--from", "/home/ci/build/", "--to", "./", "--strip-scheme", "--dry-run", "a.map"]).unwrap();
+  assert_eq!(rebase_preview(&cache, &options), vec![
+    "sourceRoot webpack:// -> ",
+    "/home/ci/build/a.js -> ./a.js",
+    "b.js (unchanged)"
+  ]);
+}
@@ -0,0 +1,109 @@
+use std::io;
+use std::io::{BufRead, IsTerminal, Write};
+
+use js_source_mapper::Cache;
+
+use cli::{EXIT_FAILURE, EXIT_OK, GlobalOptions, report_error, usage_error};
+use format::{format_lookup, parse_position, write_mapping};
+use maps::{load, resolve_sources};
+
+const REPL_HELP: &str = "\
+<line>:<column>   Look up the original position of a generated position
+source <source>   List the mappings into an original source
+help              Print this help
+quit              Exit";
+
+pub fn run(args: &[String], globals: &GlobalOptions) -> i32 {
+  let map_file = match args {
+    [map_file] if map_file != "-" => map_file,
+    _ => return usage_error(globals.json, "expected a map file, the queries are read from standard input")
+  };
+  let mut cache = match load(map_file, globals) {
+    Ok(cache) => cache,
+    Err(err) => {
+      report_error(globals.json, &err);
+      return EXIT_FAILURE;
+    }
+  };
+  resolve_sources(&mut cache);
+
+  let stdin = io::stdin();
+  let prompt = stdin.is_terminal();
+  if prompt {
+    println!("Loaded {} mappings. Type help for the commands.", cache.mappings().len());
+  }
+  let stdout = io::stdout();
+  match repl(&cache, stdin.lock(), stdout.lock(), prompt) {
+    Ok(()) => EXIT_OK,
+    Err(ref err) if err.kind() == io::ErrorKind::BrokenPipe => EXIT_OK,
+    Err(err) => {
+      report_error(globals.json, &err.to_string());
+      EXIT_FAILURE
+    }
+  }
+}
+
+// Answers queries until quit or the end of the input. Invalid queries are reported and
+// the session continues.
+fn repl<R: BufRead, W: Write>(cache: &Cache, mut input: R, mut output: W, prompt: bool) -> io::Result<()> {
+  let mut line = String::new();
+  loop {
+    if prompt {
+      write!(output, "> ")?;
+      output.flush()?;
+    }
+    line.clear();
+    if input.read_line(&mut line)? == 0 {
+      break;
+    }
+
+    let query = line.trim();
+    if query.is_empty() {
+      continue;
+    }
+    if query == "quit" || query == "exit" {
+      break;
+    }
+    if query == "help" {
+      writeln!(output, "{}", REPL_HELP)?;
+    } else if let Some(source) = query.strip_prefix("source ") {
+      let source = source.trim();
+      let mut found = false;
+      for mapping in cache.mappings().iter().filter(|mapping| &*mapping.source == source) {
+        write_mapping(&mut output, mapping)?;
+        found = true;
+      }
+      if !found {
+        writeln!(output, "error: no mappings into {:?}", source)?;
+      }
+    } else {
+      let result = parse_position(query)
+        .and_then(|(line, column)| format_lookup(line, column, &cache.mapping_for_generated_position(line, column)));
+      match result {
+        Ok(result) => writeln!(output, "{}", result)?,
+        Err(err) => writeln!(output, "error: {}", err)?
+      }
+    }
+    output.flush()?;
+  }
+  output.flush()
+}
+
+#[cfg(test)]
+use js_source_mapper::consume;
+
+#[test]
+fn it_answers_repl_queries() {
+  let cache = consume(r#"{ "version": 3, "sources": ["a.js", "b.js"], "names": ["x"], "mappings": "AAAAA,C;ACAA,EDCA" }"#).unwrap();
+  let input = "1:0\n\nnonsense\n  source a.js\nsource c.js\n2:0\nquit\n1:0\n";
+  let mut output = Vec::new();
+  repl(&cache, input.as_bytes(), &mut output, false).unwrap();
+  assert_eq!(String::from_utf8(output).unwrap(), concat!(
+    "1:0 -> a.js:1:0 (x)\n",
+    "error: invalid position \"nonsense\", expected <line>:<column>\n",
+    "1:0 -> a.js:1:0 (x)\n",
+    "2:2 -> a.js:2:0\n",
+    "error: no mappings into \"c.js\"\n",
+    "2:0 -> b.js:1:0\n"
+  ));
+}
@@ -0,0 +1,264 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::thread;
+
+use js_source_mapper::{Cache, consume};
+use serde_json;
+use tiny_http::{Header, Method, Response, Server};
+
+use cli::{EXIT_FAILURE, GlobalOptions, report_error, usage_error};
+use format::{json_lookup, parse_position};
+use maps::{load_json_with, resolve_sources};
+
+struct ServeOptions {
+  host: String,
+  port: u16,
+  inputs: Vec<String>
+}
+
+// The loaded caches, and the keys they are requested by
+#[derive(Default)]
+struct LoadedMaps {
+  caches: Vec<Cache>,
+  keys: BTreeMap<String, usize>
+}
+
+// Only the debug ID is read from the raw JSON, the rest is parsed by consume
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct DebugId {
+  #[serde(rename = "debugId", alias = "debug_id")]
+  debug_id: String
+}
+
+pub fn run(args: &[String], globals: &GlobalOptions) -> i32 {
+  let options = match parse_args(args) {
+    Ok(options) => options,
+    Err(err) => return usage_error(globals.json, &err)
+  };
+  if globals.map.is_some() {
+    return usage_error(globals.json, "serve does not support --map, pass the maps instead");
+  }
+  let maps = match load_maps(&options.inputs, globals) {
+    Ok(maps) => maps,
+    Err(err) => {
+      report_error(globals.json, &err);
+      return EXIT_FAILURE;
+    }
+  };
+
+  let address = format!("{}:{}", options.host, options.port);
+  let server = match Server::http(&address) {
+    Ok(server) => server,
+    Err(err) => {
+      report_error(globals.json, &format!("could not listen on {}: {}", address, err));
+      return EXIT_FAILURE;
+    }
+  };
+  eprintln!("serving {} maps on http://{}", maps.caches.len(), address);
+
+  // The caches are only read, so the workers share them without locking
+  let workers = thread::available_parallelism().map_or(4, |workers| workers.get());
+  thread::scope(|scope| {
+    for _ in 0..workers {
+      scope.spawn(|| {
+        while let Ok(request) = server.recv() {
+          let (status, body) = if *request.method() == Method::Get {
+            respond(&maps, request.url())
+          } else {
+            (405, error_body("only GET requests are supported"))
+          };
+          let content_type = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).expect("the header should be valid");
+          // The client may have gone away, which only affects its own request
+          let _ = request.respond(Response::from_string(body).with_status_code(status).with_header(content_type));
+        }
+      });
+    }
+  });
+  // The workers only stop when the server can no longer accept connections
+  EXIT_FAILURE
+}
+
+fn parse_args(args: &[String]) -> Result<ServeOptions, String> {
+  let mut host = "127.0.0.1".to_owned();
+  let mut port: u16 = 8080;
+  let mut inputs: Vec<String> = Vec::new();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    let (flag, value) = match arg.find('=') {
+      Some(equals) if arg.starts_with("--") => (&arg[..equals], Some(arg[equals + 1..].to_owned())),
+      _ => (arg.as_str(), None)
+    };
+    match flag {
+      "--host" | "--port" => {
+        let value = match value {
+          Some(value) => value,
+          None => args.next().ok_or_else(|| format!("{} expects a value", flag))?.clone()
+        };
+        if flag == "--host" {
+          host = value;
+        } else {
+          port = value.parse().map_err(|_| format!("invalid port {:?}", value))?;
+        }
+      },
+      flag if flag.starts_with('-') => return Err(format!("unknown option {}", flag)),
+      _ => inputs.push(arg.clone())
+    }
+  }
+  if inputs.is_empty() {
+    return Err("expected at least one map file or directory".into());
+  }
+  Ok(ServeOptions { host, port, inputs })
+}
+
+// Loads the maps, and the .map files in the directories
+fn load_maps(inputs: &[String], globals: &GlobalOptions) -> Result<LoadedMaps, String> {
+  let mut maps = LoadedMaps::default();
+  for input in inputs {
+    if !Path::new(input).is_dir() {
+      load_map(&mut maps, input, globals)?;
+      continue;
+    }
+    let entries = fs::read_dir(input).map_err(|err| format!("could not read {}: {}", input, err))?;
+    let mut paths: Vec<String> = entries
+      .filter_map(|entry| entry.ok())
+      .map(|entry| entry.path())
+      .filter(|path| path.extension().is_some_and(|extension| extension == "map") && path.is_file())
+      .map(|path| path.display().to_string())
+      .collect();
+    paths.sort();
+    for path in paths {
+      load_map(&mut maps, &path, globals)?;
+    }
+  }
+  Ok(maps)
+}
+
+// Adds a map under its file name, its file field and its debug ID
+fn load_map(maps: &mut LoadedMaps, input: &str, globals: &GlobalOptions) -> Result<(), String> {
+  let (mut cache, json) = load_json_with(input, globals, consume)?;
+  resolve_sources(&mut cache);
+
+  let file_name = Path::new(input).file_name().map(|file_name| file_name.to_string_lossy().into_owned());
+  let debug_id = serde_json::from_str::<DebugId>(&json).map(|id| id.debug_id).unwrap_or_default();
+  let keys: Vec<String> = file_name.into_iter()
+    .chain(Some(cache.file.clone()))
+    .chain(Some(debug_id))
+    .filter(|key| !key.is_empty())
+    .collect();
+
+  let index = maps.caches.len();
+  maps.caches.push(cache);
+  for key in keys {
+    match maps.keys.get(&key) {
+      Some(&other) if other != index => eprintln!("warning: {} is already the key of another map, ignoring it for {}", key, input),
+      _ => { maps.keys.insert(key, index); }
+    }
+  }
+  Ok(())
+}
+
+// Answers a GET request, returning the status code and the JSON body
+fn respond(maps: &LoadedMaps, url: &str) -> (u16, String) {
+  let (path, query) = match url.find('?') {
+    Some(question) => (&url[..question], &url[question + 1..]),
+    None => (url, "")
+  };
+  match path {
+    "/maps" => {
+      let keys: Vec<&String> = maps.keys.keys().collect();
+      (200, serde_json::to_string(&keys).expect("the keys should serialize"))
+    },
+    "/lookup" => match lookup(maps, query) {
+      Ok(body) => (200, body),
+      Err((status, err)) => (status, error_body(&err))
+    },
+    _ => (404, error_body(&format!("no such endpoint {}, expected /lookup or /maps", path)))
+  }
+}
+
+fn lookup(maps: &LoadedMaps, query: &str) -> Result<String, (u16, String)> {
+  let mut params: BTreeMap<String, String> = BTreeMap::new();
+  for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+    let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+    let decode = |text: &str| percent_decode(text).ok_or_else(|| (400, format!("invalid percent encoding in {:?}", pair)));
+    params.insert(decode(key)?, decode(value)?);
+  }
+  let param = |name: &str| params.get(name).ok_or_else(|| (400, format!("missing the {} parameter", name)));
+
+  let key = param("map")?;
+  let cache = match maps.keys.get(key) {
+    Some(&index) => &maps.caches[index],
+    None => return Err((404, format!("no map is loaded as {}", key)))
+  };
+  let (line, column) = parse_position(&format!("{}:{}", param("line")?, param("column")?)).map_err(|err| (400, err))?;
+  Ok(json_lookup(line, column, &cache.mapping_for_generated_position(line, column)))
+}
+
+fn error_body(message: &str) -> String {
+  serde_json::json!({ "error": message }).to_string()
+}
+
+// Decodes a query string component, where + is a space
+fn percent_decode(text: &str) -> Option<String> {
+  let mut bytes: Vec<u8> = Vec::with_capacity(text.len());
+  let mut input = text.bytes();
+  while let Some(byte) = input.next() {
+    match byte {
+      b'%' => {
+        let high = (input.next()? as char).to_digit(16)?;
+        let low = (input.next()? as char).to_digit(16)?;
+        bytes.push((high * 16 + low) as u8);
+      },
+      b'+' => bytes.push(b' '),
+      _ => bytes.push(byte)
+    }
+  }
+  String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+use std::env;
+#[cfg(test)]
+use std::process;
+
+#[test]
+fn it_answers_requests() {
+  let mut maps = LoadedMaps::default();
+  maps.caches.push(consume(r#"{ "version": 3, "file": "app.js", "sources": ["a.js"], "names": ["x"], "mappings": "AAAAA,EAAE" }"#).unwrap());
+  maps.keys.insert("app.js".into(), 0);
+  maps.keys.insert("app js.map".into(), 0);
+
+  assert_eq!(respond(&maps, "/maps"), (200, r#"["app js.map","app.js"]"#.into()));
+  assert_eq!(
+    respond(&maps, "/lookup?map=app.js&line=1&column=0"),
+    (200, r#"{"generated":{"line":1,"column":0},"original":{"line":1,"column":0},"source":"a.js","name":"x","matched":true}"#.into())
+  );
+  assert_eq!(respond(&maps, "/lookup?line=1&column=3&map=app+js.map").0, 200);
+  assert_eq!(respond(&maps, "/lookup?map=app%20js.map&line=1&column=3").0, 200);
+  assert_eq!(respond(&maps, "/lookup?map=app.js&line=1"), (400, r#"{"error":"missing the column parameter"}"#.into()));
+  assert_eq!(respond(&maps, "/lookup?map=app.js&line=0&column=0").0, 400);
+  assert_eq!(respond(&maps, "/lookup?map=app%2.js&line=1&column=0").0, 400);
+  assert_eq!(respond(&maps, "/lookup?map=other.js&line=1&column=0").0, 404);
+  assert_eq!(respond(&maps, "/").0, 404);
+}
+
+#[test]
+fn it_loads_maps_by_key() {
+  let dir = env::temp_dir().join(format!("js-source-mapper-serve-{}", process::id()));
+  fs::create_dir_all(&dir).unwrap();
+  fs::write(dir.join("a.js.map"), r#"{ "version": 3, "file": "a.js", "debugId": "85314830-023f-4cf1-a267-535f4e37bb17", "sources": ["a.js"], "names": [], "mappings": "AAAA" }"#).unwrap();
+  fs::write(dir.join("b.js.map"), r#"{ "version": 3, "file": "a.js", "sources": ["a.js"], "names": [], "mappings": "AAAA" }"#).unwrap();
+  fs::write(dir.join("notes.txt"), "").unwrap();
+
+  let maps = load_maps(&[dir.display().to_string()], &GlobalOptions::default()).unwrap();
+  assert_eq!(maps.caches.len(), 2);
+  assert_eq!(maps.keys.iter().map(|(key, &index)| (key.as_str(), index)).collect::<Vec<_>>(), [
+    ("85314830-023f-4cf1-a267-535f4e37bb17", 0),
+    ("a.js", 0),
+    ("a.js.map", 0),
+    ("b.js.map", 1)
+  ]);
+  fs::remove_dir_all(&dir).unwrap();
+}
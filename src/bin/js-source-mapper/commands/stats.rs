@@ -0,0 +1,164 @@
+use std::collections::{HashMap, HashSet};
+
+use js_source_mapper::Cache;
+use serde_json;
+
+use cli::{EXIT_FAILURE, EXIT_OK, GlobalOptions, report_error, usage_error};
+use maps::load_with_sources_content;
+
+// The number of sources listed by stats
+const TOP_SOURCES: usize = 10;
+
+struct StatsOptions {
+  map_file: String
+}
+
+#[derive(Serialize, Debug)]
+struct StatsOutput<'a> {
+  mappings: usize,
+  generated_lines: u32,
+  sources: TableStats,
+  names: TableStats,
+  sources_content: SourcesContentStats,
+  source_root: Option<&'a str>,
+  file: Option<&'a str>,
+  top_sources: Vec<SourceStats<'a>>
+}
+
+#[derive(Serialize, Debug)]
+struct TableStats {
+  declared: usize,
+  referenced: usize
+}
+
+#[derive(Serialize, Debug)]
+struct SourcesContentStats {
+  sources: usize,
+  bytes: usize
+}
+
+#[derive(Serialize, Debug)]
+struct SourceStats<'a> {
+  source: &'a str,
+  mappings: usize
+}
+
+pub fn run(args: &[String], globals: &GlobalOptions) -> i32 {
+  let options = match parse_args(args) {
+    Ok(options) => options,
+    Err(err) => return usage_error(globals.json, &err)
+  };
+  let cache = match load_with_sources_content(&options.map_file, globals) {
+    Ok(cache) => cache,
+    Err(err) => {
+      report_error(globals.json, &err);
+      return EXIT_FAILURE;
+    }
+  };
+
+  let output = stats(&cache);
+  if globals.json {
+    println!("{}", serde_json::to_string(&output).expect("stats output should serialize"));
+  } else {
+    print!("{}", format_stats(&output));
+  }
+  EXIT_OK
+}
+
+fn parse_args(args: &[String]) -> Result<StatsOptions, String> {
+  let mut positional: Vec<String> = Vec::new();
+  for arg in args {
+    match arg.as_str() {
+      flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option {}", flag)),
+      _ => positional.push(arg.clone())
+    }
+  }
+  if positional.len() != 1 {
+    return Err("expected a map file".into());
+  }
+  Ok(StatsOptions { map_file: positional.remove(0) })
+}
+
+fn stats(cache: &Cache) -> StatsOutput<'_> {
+  let mut mappings_per_source: HashMap<&str, usize> = HashMap::new();
+  let mut referenced_names: HashSet<&str> = HashSet::new();
+  for mapping in cache.mappings() {
+    if !mapping.source.is_empty() {
+      *mappings_per_source.entry(&mapping.source).or_insert(0) += 1;
+    }
+    if !mapping.name.is_empty() {
+      referenced_names.insert(&mapping.name);
+    }
+  }
+
+  let mut sources_content = SourcesContentStats { sources: 0, bytes: 0 };
+  let mut seen: HashSet<&str> = HashSet::new();
+  for source in cache.sources() {
+    if let Some(content) = cache.source_content(source).filter(|_| seen.insert(source)) {
+      sources_content.sources += 1;
+      sources_content.bytes += content.len();
+    }
+  }
+
+  let mut top_sources: Vec<SourceStats> = mappings_per_source.iter()
+    .map(|(&source, &mappings)| SourceStats { source, mappings })
+    .collect();
+  top_sources.sort_by(|a, b| b.mappings.cmp(&a.mappings).then_with(|| a.source.cmp(b.source)));
+  top_sources.truncate(TOP_SOURCES);
+
+  StatsOutput {
+    mappings: cache.mappings().len(),
+    generated_lines: cache.mappings().last().map_or(0, |mapping| mapping.generated.line),
+    sources: TableStats { declared: cache.sources().len(), referenced: mappings_per_source.len() },
+    names: TableStats { declared: cache.names().len(), referenced: referenced_names.len() },
+    sources_content,
+    source_root: if cache.source_root.is_empty() { None } else { Some(&cache.source_root) },
+    file: if cache.file.is_empty() { None } else { Some(&cache.file) },
+    top_sources
+  }
+}
+
+fn format_stats(stats: &StatsOutput) -> String {
+  let mut output = String::new();
+  output += &format!("mappings:         {}\n", stats.mappings);
+  output += &format!("generated lines:  {}\n", stats.generated_lines);
+  output += &format!("sources:          {} declared, {} referenced\n", stats.sources.declared, stats.sources.referenced);
+  output += &format!("names:            {} declared, {} referenced\n", stats.names.declared, stats.names.referenced);
+  if stats.sources_content.sources == 0 {
+    output += "sources content:  none\n";
+  } else {
+    output += &format!("sources content:  {} sources, {} bytes\n", stats.sources_content.sources, stats.sources_content.bytes);
+  }
+  output += &format!("source root:      {}\n", stats.source_root.unwrap_or("none"));
+  output += &format!("file:             {}\n", stats.file.unwrap_or("none"));
+  if !stats.top_sources.is_empty() {
+    output += "top sources by mappings:\n";
+    for source in &stats.top_sources {
+      output += &format!("  {:>8}  {}\n", source.mappings, source.source);
+    }
+  }
+  output
+}
+
+#[cfg(test)]
+use js_source_mapper::consume_with_sources_content;
+
+#[test]
+fn it_computes_stats() {
+  let cache = consume_with_sources_content(r#"{
+    "version": 3,
+    "file": "bundle.min.js",
+    "sources": ["a.js", "b.js", "unused.js"],
+    "sourcesContent": ["let a;", null, "let unused;"],
+    "names": ["x", "y"],
+    "mappings": "AAAAA,C;ACAA,EDCA;;AAAA"
+  }"#).unwrap();
+  let stats = stats(&cache);
+  assert_eq!(serde_json::to_string(&stats).unwrap(), concat!(
+    r#"{"mappings":5,"generated_lines":4,"sources":{"declared":3,"referenced":2},"names":{"declared":2,"referenced":1},"#,
+    r#""sources_content":{"sources":2,"bytes":17},"source_root":null,"file":"bundle.min.js","#,
+    r#""top_sources":[{"source":"a.js","mappings":3},{"source":"b.js","mappings":1}]}"#
+  ));
+  assert!(format_stats(&stats).contains("sources:          3 declared, 2 referenced\n"));
+  assert!(format_stats(&stats).ends_with("top sources by mappings:\n         3  a.js\n         1  b.js\n"));
+}
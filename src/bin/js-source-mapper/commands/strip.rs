@@ -0,0 +1,149 @@
+use js_source_mapper::{Cache, consume_with_sources_content};
+
+use cli::{EXIT_FAILURE, EXIT_OK, GlobalOptions, print_summary, report_error, usage_error, write_output};
+use glob::source_matches;
+use maps::load_json_with;
+
+struct StripOptions {
+  map_file: String,
+  sources_content: bool,
+  names: bool,
+  sources_matching: Vec<String>,
+  output: Option<String>
+}
+
+pub fn run(args: &[String], globals: &GlobalOptions) -> i32 {
+  let options = match parse_args(args) {
+    Ok(options) => options,
+    Err(err) => return usage_error(globals.json, &err)
+  };
+  let (cache, json) = match load_json_with(&options.map_file, globals, consume_with_sources_content) {
+    Ok(loaded) => loaded,
+    Err(err) => {
+      report_error(globals.json, &err);
+      return EXIT_FAILURE;
+    }
+  };
+
+  let (stripped, unmatched) = strip(cache, &options);
+  for pattern in unmatched {
+    eprintln!("warning: {} matches no sources", pattern);
+  }
+  let serialized = if options.sources_content { stripped.to_json_without_sources_content() } else { stripped.to_json() };
+  let result = serialized
+    .map_err(|err| format!("could not serialize the stripped source map: {}", err))
+    .and_then(|stripped_json| write_output(options.output.as_deref(), &stripped_json).map(|()| stripped_json.len()));
+  match result {
+    Ok(size) => {
+      print_summary(options.output.as_deref(), &format_sizes(json.len(), size));
+      EXIT_OK
+    },
+    Err(err) => {
+      report_error(globals.json, &err);
+      EXIT_FAILURE
+    }
+  }
+}
+
+fn parse_args(args: &[String]) -> Result<StripOptions, String> {
+  let mut sources_content = false;
+  let mut names = false;
+  let mut sources_matching: Vec<String> = Vec::new();
+  let mut output: Option<String> = None;
+  let mut positional: Vec<String> = Vec::new();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    let (flag, value) = match arg.find('=') {
+      Some(equals) if arg.starts_with("--") => (&arg[..equals], Some(arg[equals + 1..].to_owned())),
+      _ => (arg.as_str(), None)
+    };
+    match flag {
+      "--sources-matching" | "-o" | "--output" => {
+        let value = match value {
+          Some(value) => value,
+          None => args.next().ok_or_else(|| format!("{} expects a value", flag))?.clone()
+        };
+        if flag == "--sources-matching" {
+          sources_matching.push(value);
+        } else {
+          output = Some(value);
+        }
+      },
+      "--sources-content" => sources_content = true,
+      "--names" => names = true,
+      flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option {}", flag)),
+      _ => positional.push(arg.clone())
+    }
+  }
+  if !sources_content && !names && sources_matching.is_empty() {
+    return Err("expected --sources-content, --names or --sources-matching".into());
+  }
+  if positional.len() != 1 {
+    return Err("expected a map file".into());
+  }
+  Ok(StripOptions { map_file: positional.remove(0), sources_content, names, sources_matching, output })
+}
+
+// Drops the names and the mappings into matching sources, returning the stripped
+// cache and the patterns that matched no sources. The sourcesContent is dropped when
+// serializing.
+fn strip(mut cache: Cache, options: &StripOptions) -> (Cache, Vec<&str>) {
+  if options.names {
+    cache.clear_names();
+  }
+  let unmatched: Vec<&str> = options.sources_matching.iter()
+    .filter(|pattern| !cache.sources().iter().any(|source| source_matches(pattern, source)))
+    .map(|pattern| pattern.as_str())
+    .collect();
+  if options.sources_matching.len() > unmatched.len() {
+    let matches = |source: &str| options.sources_matching.iter().any(|pattern| source_matches(pattern, source));
+    cache = cache.filter_sources(|source| !matches(source), true);
+  }
+  (cache, unmatched)
+}
+
+fn format_sizes(before: usize, after: usize) -> String {
+  let saved = if before == 0 { 0.0 } else { 100.0 * (before as f64 - after as f64) / before as f64 };
+  format!("stripped {} bytes to {} bytes ({:.1}% smaller)", before, after, saved)
+}
+
+#[cfg(test)]
+use js_source_mapper::consume;
+
+#[test]
+fn it_strips_source_maps() {
+  let json = r#"{ "version": 3, "sources": ["src/app.js", "webpack:///node_modules/a.js"], "sourcesContent": ["app();", "a();"], "names": ["x", "y"], "mappings": "AAAAA,ECAAC;ADCAD" }"#;
+  let options = |names, sources_matching: &[&str]| StripOptions {
+    map_file: "a.map".into(),
+    sources_content: false,
+    names,
+    sources_matching: sources_matching.iter().map(|pattern| pattern.to_string()).collect(),
+    output: None
+  };
+
+  let names = options(true, &[]);
+  let (stripped, unmatched) = strip(consume_with_sources_content(json).unwrap(), &names);
+  assert!(unmatched.is_empty());
+  assert_eq!(stripped.to_json().unwrap(), r#"{"version":3,"sources":["src/app.js","webpack:///node_modules/a.js"],"sourcesContent":["app();","a();"],"names":[],"mappings":"AAAA,ECAA;ADCA"}"#);
+
+  let sources = options(false, &["node_modules/**", "lib/**"]);
+  let (stripped, unmatched) = strip(consume_with_sources_content(json).unwrap(), &sources);
+  assert_eq!(unmatched, ["lib/**"]);
+  assert_eq!(stripped.to_json_without_sources_content().unwrap(), r#"{"version":3,"sources":["src/app.js"],"names":["x"],"mappings":"AAAAA,E;AACAA"}"#);
+
+  let (stripped, _) = strip(consume(json).unwrap(), &options(false, &["lib/**"]));
+  assert_eq!(stripped.mappings().len(), 3);
+}
+
+#[test]
+fn it_parses_strip_arguments() {
+  let args = |args: &[&str]| parse_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>());
+  let options = args(&["a.map", "--names", "--sources-content", "--sources-matching", "vendor/**", "-o", "slim.map"]).unwrap();
+  assert!(options.names && options.sources_content);
+  assert_eq!(options.sources_matching, ["vendor/**"]);
+  assert_eq!(options.output.as_deref(), Some("slim.map"));
+  assert!(args(&["a.map"]).is_err());
+  assert!(args(&["--names"]).is_err());
+
+  assert_eq!(format_sizes(1000, 250), "stripped 1000 bytes to 250 bytes (75.0% smaller)");
+}
@@ -0,0 +1,112 @@
+use std::io;
+use std::io::{BufRead, Write};
+
+use js_source_mapper::{Cache, StackFrame};
+
+use cli::{EXIT_FAILURE, EXIT_OK, GlobalOptions, report_error, usage_error};
+use maps::{load, resolve_sources};
+
+struct SymbolicateOptions {
+  map_file: String,
+  file: Option<String>
+}
+
+pub fn run(args: &[String], globals: &GlobalOptions) -> i32 {
+  let options = match parse_args(args) {
+    Ok(options) => options,
+    Err(err) => return usage_error(globals.json, &err)
+  };
+  let mut cache = match load(&options.map_file, globals) {
+    Ok(cache) => cache,
+    Err(err) => {
+      report_error(globals.json, &err);
+      return EXIT_FAILURE;
+    }
+  };
+  resolve_sources(&mut cache);
+  let file = options.file.or_else(|| if cache.file.is_empty() { None } else { Some(cache.file.clone()) });
+
+  let stdin = io::stdin();
+  let stdout = io::stdout();
+  match symbolicate(&cache, file.as_deref(), stdin.lock(), stdout.lock()) {
+    Ok(()) => EXIT_OK,
+    Err(err) => {
+      report_error(globals.json, &format!("could not symbolicate the stack trace: {}", err));
+      EXIT_FAILURE
+    }
+  }
+}
+
+fn parse_args(args: &[String]) -> Result<SymbolicateOptions, String> {
+  let mut file: Option<String> = None;
+  let mut positional: Vec<String> = Vec::new();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--file" => file = Some(args.next().ok_or("--file expects a file name")?.clone()),
+      flag if flag.starts_with("--file=") => file = Some(flag["--file=".len()..].to_owned()),
+      flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option {}", flag)),
+      _ => positional.push(arg.clone())
+    }
+  }
+  if positional.len() != 1 {
+    return Err("expected a map file".into());
+  }
+  let map_file = positional.remove(0);
+  if map_file == "-" {
+    return Err("the stack trace is read from standard input, so the map must be read from a file".into());
+  }
+  Ok(SymbolicateOptions { map_file, file })
+}
+
+// Copies the stack trace line by line, rewriting the frames in the file
+fn symbolicate<R: BufRead, W: Write>(cache: &Cache, file: Option<&str>, mut input: R, mut output: W) -> io::Result<()> {
+  let mut line: Vec<u8> = Vec::new();
+  loop {
+    line.clear();
+    if input.read_until(b'\n', &mut line)? == 0 {
+      break;
+    }
+    let content_length = line.len() - if line.ends_with(b"\r\n") { 2 } else if line.ends_with(b"\n") { 1 } else { 0 };
+    let symbolicated = std::str::from_utf8(&line[..content_length]).ok()
+      .and_then(StackFrame::parse)
+      .filter(|frame| file.is_none_or(|file| frame.is_in_file(file)))
+      .and_then(|frame| frame.symbolicate(cache));
+    match symbolicated {
+      Some(frame) => {
+        output.write_all(frame.as_bytes())?;
+        output.write_all(&line[content_length..])?;
+      },
+      None => output.write_all(&line)?
+    }
+  }
+  output.flush()
+}
+
+#[cfg(test)]
+use js_source_mapper::consume;
+
+#[test]
+fn it_symbolicates_stack_traces() {
+  let cache = consume(r#"{ "version": 3, "file": "bundle.min.js", "sources": ["src/app.js"], "names": ["handleClick"], "mappings": "AAyDYA" }"#).unwrap();
+  let trace = concat!(
+    "TypeError: Cannot read properties of undefined (reading 'id')\r\n",
+    "    at r (https://cdn.example.com/bundle.min.js:1:1)\r\n",
+    "    at https://cdn.example.com/vendor.js:1:1\r\n",
+    "    at new Promise (<anonymous>)"
+  );
+
+  let mut output = Vec::new();
+  symbolicate(&cache, Some("bundle.min.js"), trace.as_bytes(), &mut output).unwrap();
+  assert_eq!(String::from_utf8(output).unwrap(), concat!(
+    "TypeError: Cannot read properties of undefined (reading 'id')\r\n",
+    "    at handleClick (src/app.js:58:13)\r\n",
+    "    at https://cdn.example.com/vendor.js:1:1\r\n",
+    "    at new Promise (<anonymous>)"
+  ));
+
+  let mut output = Vec::new();
+  symbolicate(&cache, None, trace.as_bytes(), &mut output).unwrap();
+  // Without a file, the vendor.js frame is looked up in the map as well
+  assert_eq!(String::from_utf8(output).unwrap().matches("    at handleClick (src/app.js:58:13)\r\n").count(), 2);
+}
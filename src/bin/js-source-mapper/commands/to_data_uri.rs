@@ -0,0 +1,178 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Read, Write};
+
+use js_source_mapper::base64::encode_bytes;
+use js_source_mapper::source_mapping_url;
+
+use cli::{EXIT_FAILURE, EXIT_OK, GlobalOptions, report_error, usage_error};
+use maps::{describe_input, read_input};
+
+const DATA_URI_PREFIX: &str = "data:application/json;charset=utf-8;base64,";
+// A multiple of 3 bytes, so that only the last chunk is padded
+const CHUNK_SIZE: usize = 3 * 16 * 1024;
+
+struct ToDataUriOptions {
+  map_file: String,
+  append: Option<String>,
+  output: Option<String>
+}
+
+pub fn run(args: &[String], globals: &GlobalOptions) -> i32 {
+  let options = match parse_args(args) {
+    Ok(options) => options,
+    Err(err) => return usage_error(globals.json, &err)
+  };
+  if globals.map.is_some() {
+    return usage_error(globals.json, "to-data-uri does not support --map, pass the map instead");
+  }
+  match to_data_uri(&options) {
+    Ok(()) => EXIT_OK,
+    Err(err) => {
+      report_error(globals.json, &err);
+      EXIT_FAILURE
+    }
+  }
+}
+
+fn parse_args(args: &[String]) -> Result<ToDataUriOptions, String> {
+  let mut append: Option<String> = None;
+  let mut output: Option<String> = None;
+  let mut positional: Vec<String> = Vec::new();
+  let mut args = args.iter();
+  while let Some(arg) = args.next() {
+    let (flag, value) = match arg.find('=') {
+      Some(equals) if arg.starts_with("--") => (&arg[..equals], Some(arg[equals + 1..].to_owned())),
+      _ => (arg.as_str(), None)
+    };
+    match flag {
+      "--append" | "-o" | "--output" => {
+        let value = match value {
+          Some(value) => value,
+          None => args.next().ok_or_else(|| format!("{} expects a value", flag))?.clone()
+        };
+        if flag == "--append" {
+          append = Some(value);
+        } else {
+          output = Some(value);
+        }
+      },
+      flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option {}", flag)),
+      _ => positional.push(arg.clone())
+    }
+  }
+  if positional.len() != 1 {
+    return Err("expected a map file".into());
+  }
+  let map_file = positional.remove(0);
+  if map_file == "-" && append.as_deref() == Some("-") {
+    return Err("only one of the map and the bundle can be read from standard input".into());
+  }
+  if output.as_deref() == Some(map_file.as_str()) {
+    return Err("the output must not overwrite the map".into());
+  }
+  Ok(ToDataUriOptions { map_file, append, output })
+}
+
+// The map is encoded as it is read, so that large maps are not held in memory as JSON
+// and base 64 at once
+fn to_data_uri(options: &ToDataUriOptions) -> Result<(), String> {
+  let bundle = match options.append {
+    Some(ref bundle) => Some(read_input(bundle)?),
+    None => None
+  };
+  let map: Box<dyn Read> = if options.map_file == "-" {
+    Box::new(io::stdin())
+  } else {
+    Box::new(File::open(&options.map_file).map_err(|err| format!("could not read {}: {}", options.map_file, err))?)
+  };
+  let output: Box<dyn Write> = match options.output {
+    Some(ref path) => Box::new(File::create(path).map_err(|err| format!("could not write {}: {}", path, err))?),
+    None => Box::new(io::stdout())
+  };
+  let mut output = BufWriter::new(output);
+
+  let result = match bundle {
+    Some(ref bundle) => output.write_all(without_source_mapping_url(bundle).as_bytes())
+      .and_then(|()| output.write_all(b"//# sourceMappingURL="))
+      .map_err(|err| format!("could not write the bundle: {}", err)),
+    None => Ok(())
+  };
+  result
+    .and_then(|()| write_data_uri(map, &mut output).map_err(|err| format!("could not encode {}: {}", describe_input(&options.map_file), err)))
+    .and_then(|()| output.write_all(b"\n").and_then(|()| output.flush()).map_err(|err| format!("could not write the data URI: {}", err)))
+}
+
+fn write_data_uri<R: Read, W: Write>(mut map: R, output: &mut W) -> io::Result<()> {
+  output.write_all(DATA_URI_PREFIX.as_bytes())?;
+  let mut chunk = vec![0; CHUNK_SIZE];
+  loop {
+    let length = read_chunk(&mut map, &mut chunk)?;
+    if length == 0 {
+      return Ok(());
+    }
+    output.write_all(encode_bytes(&chunk[..length]).as_bytes())?;
+    if length < CHUNK_SIZE {
+      return Ok(());
+    }
+  }
+}
+
+// Fills the chunk unless the input ends first, returning the number of bytes read
+fn read_chunk<R: Read>(input: &mut R, chunk: &mut [u8]) -> io::Result<usize> {
+  let mut length = 0;
+  while length < chunk.len() {
+    match input.read(&mut chunk[length..]) {
+      Ok(0) => break,
+      Ok(read) => length += read,
+      Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {},
+      Err(err) => return Err(err)
+    }
+  }
+  Ok(length)
+}
+
+// Removes the sourceMappingURL comments of the bundle, and ends it with a newline
+fn without_source_mapping_url(bundle: &str) -> String {
+  let mut code: String = bundle.split_inclusive('\n')
+    .filter(|line| source_mapping_url(line).is_none())
+    .collect();
+  if !code.is_empty() && !code.ends_with('\n') {
+    code.push('\n');
+  }
+  code
+}
+
+#[cfg(test)]
+use js_source_mapper::decode_data_uri;
+
+#[test]
+fn it_encodes_maps_as_data_uris() {
+  let json = r#"{"version":3,"sources":["a.js"],"names":[],"mappings":"AAAA"}"#.repeat(CHUNK_SIZE / 10);
+  let mut output = Vec::new();
+  write_data_uri(json.as_bytes(), &mut output).unwrap();
+  let uri = String::from_utf8(output).unwrap();
+  assert!(uri.starts_with(DATA_URI_PREFIX));
+  assert_eq!(decode_data_uri(&uri).unwrap(), json);
+
+  // Short reads must not cause padding in the middle of the data
+  let mut output = Vec::new();
+  let (start, rest) = json.as_bytes().split_at(5);
+  write_data_uri(start.chain(rest), &mut output).unwrap();
+  assert_eq!(decode_data_uri(&String::from_utf8(output).unwrap()).unwrap(), json);
+}
+
+#[test]
+fn it_replaces_source_mapping_url_comments() {
+  assert_eq!(without_source_mapping_url("a();\n//# sourceMappingURL=a.js.map\n"), "a();\n");
+  assert_eq!(without_source_mapping_url("a();\r\n//@ sourceMappingURL=a.js.map"), "a();\r\n");
+  assert_eq!(without_source_mapping_url("a(\"//# sourceMappingURL=x\");"), "a(\"//# sourceMappingURL=x\");\n");
+  assert_eq!(without_source_mapping_url(""), "");
+
+  let args = |args: &[&str]| parse_args(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>());
+  let options = args(&["a.js.map", "--append", "a.js", "-o", "demo.js"]).unwrap();
+  assert_eq!((options.append.as_deref(), options.output.as_deref()), (Some("a.js"), Some("demo.js")));
+  assert!(args(&["-", "--append=-"]).is_err());
+  assert!(args(&["a.js.map", "-o", "a.js.map"]).is_err());
+  assert!(args(&[]).is_err());
+}
@@ -0,0 +1,133 @@
+use js_source_mapper::{Finding, Severity, validate};
+use serde_json;
+
+use cli::{EXIT_FAILURE, EXIT_INVALID, EXIT_OK, GlobalOptions, report_error, usage_error};
+use maps::read_map;
+
+struct ValidateOptions {
+  strict: bool,
+  map_file: String
+}
+
+#[derive(Serialize, Debug)]
+struct ValidationOutput<'a> {
+  valid: bool,
+  errors: usize,
+  warnings: usize,
+  findings: Vec<FindingOutput<'a>>
+}
+
+#[derive(Serialize, Debug)]
+struct FindingOutput<'a> {
+  severity: &'a str,
+  line: Option<u32>,
+  segment: Option<u32>,
+  message: &'a str
+}
+
+pub fn run(args: &[String], globals: &GlobalOptions) -> i32 {
+  let options = match parse_args(args) {
+    Ok(options) => options,
+    Err(err) => return usage_error(globals.json, &err)
+  };
+  let (json, name) = match read_map(&options.map_file, globals.map.as_deref()) {
+    Ok(result) => result,
+    Err(err) => {
+      report_error(globals.json, &err);
+      return EXIT_INVALID;
+    }
+  };
+
+  let mut findings = validate(&json);
+  if options.strict {
+    for finding in &mut findings {
+      finding.severity = Severity::Error;
+    }
+  }
+  let errors = findings.iter().filter(|finding| finding.severity == Severity::Error).count();
+  let warnings = findings.len() - errors;
+
+  if globals.json {
+    println!("{}", json_validation(&findings));
+  } else {
+    for finding in &findings {
+      println!("{}", format_finding(finding));
+    }
+    match (errors, warnings) {
+      (0, 0) => println!("{} is valid", name),
+      _ => println!("{} errors, {} warnings", errors, warnings)
+    }
+  }
+
+  if errors > 0 {
+    EXIT_INVALID
+  } else if warnings > 0 {
+    EXIT_FAILURE
+  } else {
+    EXIT_OK
+  }
+}
+
+fn parse_args(args: &[String]) -> Result<ValidateOptions, String> {
+  let mut strict = false;
+  let mut positional: Vec<String> = Vec::new();
+  for arg in args {
+    match arg.as_str() {
+      "--strict" => strict = true,
+      flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option {}", flag)),
+      _ => positional.push(arg.clone())
+    }
+  }
+  if positional.len() != 1 {
+    return Err("expected a map file".into());
+  }
+  Ok(ValidateOptions { strict, map_file: positional.remove(0) })
+}
+
+fn severity_name(severity: Severity) -> &'static str {
+  match severity {
+    Severity::Error => "error",
+    Severity::Warning => "warning"
+  }
+}
+
+// Formats a finding like "error: line 2, segment 0: Reference to source index 1 ..."
+fn format_finding(finding: &Finding) -> String {
+  match (finding.line, finding.segment) {
+    (Some(line), Some(segment)) => format!("{}: line {}, segment {}: {}", severity_name(finding.severity), line, segment, finding.message),
+    (Some(line), None) => format!("{}: line {}: {}", severity_name(finding.severity), line, finding.message),
+    _ => format!("{}: {}", severity_name(finding.severity), finding.message)
+  }
+}
+
+fn json_validation(findings: &[Finding]) -> String {
+  let errors = findings.iter().filter(|finding| finding.severity == Severity::Error).count();
+  let output = ValidationOutput {
+    valid: findings.is_empty(),
+    errors,
+    warnings: findings.len() - errors,
+    findings: findings.iter().map(|finding| FindingOutput {
+      severity: severity_name(finding.severity),
+      line: finding.line,
+      segment: finding.segment,
+      message: &finding.message
+    }).collect()
+  };
+  serde_json::to_string(&output).expect("validation output should serialize")
+}
+
+#[test]
+fn it_reports_validation_findings() {
+  let findings = validate(r#"{ "version": 3, "sources": ["a.js"], "sourcesContent": [], "names": [], "mappings": "AAAA;ACAA" }"#);
+  let lines: Vec<String> = findings.iter().map(format_finding).collect();
+  assert_eq!(lines, [
+    "warning: sourcesContent has 0 entries, but there are 1 sources",
+    "error: line 2, segment 0: Reference to source index 1 when source list length is 1"
+  ]);
+  assert_eq!(json_validation(&findings), concat!(
+    r#"{"valid":false,"errors":1,"warnings":1,"findings":["#,
+    r#"{"severity":"warning","line":null,"segment":null,"message":"sourcesContent has 0 entries, but there are 1 sources"},"#,
+    r#"{"severity":"error","line":2,"segment":0,"message":"Reference to source index 1 when source list length is 1"}]}"#
+  ));
+  assert_eq!(json_validation(&[]), r#"{"valid":true,"errors":0,"warnings":0,"findings":[]}"#);
+}
@@ -0,0 +1,204 @@
+use std::fs;
+use std::path::Path;
+
+use js_source_mapper::{Cache, consume, decode_data_uri, source_mapping_url};
+
+use cli::{EXIT_FAILURE, EXIT_OK, GlobalOptions, report_error, usage_error};
+use maps::{read_input, resolve_map_url};
+
+// The number of positions printed for each kind of violation
+const SHOWN_POSITIONS: usize = 10;
+
+struct VerifyOptions {
+  bundle_file: String,
+  map_file: String
+}
+
+// The mappings that do not fit the bundle, as generated line, column and the length
+// of the line in UTF-16 code units
+#[derive(Default, Debug, PartialEq)]
+struct MappingViolations {
+  missing_lines: Vec<(u32, u32)>,
+  past_line_end: Vec<(u32, u32, usize)>
+}
+
+pub fn run(args: &[String], globals: &GlobalOptions) -> i32 {
+  let options = match parse_args(args) {
+    Ok(options) => options,
+    Err(err) => return usage_error(globals.json, &err)
+  };
+  if globals.map.is_some() {
+    return usage_error(globals.json, "verify does not support --map, pass the map after the bundle instead");
+  }
+  let loaded = read_input(&options.bundle_file).and_then(|bundle| {
+    let json = read_input(&options.map_file)?;
+    let cache = consume(&json).map_err(|err| format!("could not parse {}: {}", options.map_file, err))?;
+    Ok((bundle, json, cache))
+  });
+  let (bundle, json, cache) = match loaded {
+    Ok(loaded) => loaded,
+    Err(err) => {
+      report_error(globals.json, &err);
+      return EXIT_FAILURE;
+    }
+  };
+
+  let violations = check_mappings(&cache, &bundle);
+  let mut problems = format_violations(&violations);
+  problems.extend(check_file(&cache, &options.bundle_file));
+  problems.extend(check_source_mapping_url(&bundle, &options.bundle_file, &options.map_file, &json));
+
+  for problem in &problems {
+    println!("{}", problem);
+  }
+  if problems.is_empty() {
+    println!("{} mappings fit the {} lines of {}", cache.mappings().len(), line_lengths(&bundle).len(), options.bundle_file);
+    EXIT_OK
+  } else {
+    println!("{} does not match {}", options.map_file, options.bundle_file);
+    EXIT_FAILURE
+  }
+}
+
+fn parse_args(args: &[String]) -> Result<VerifyOptions, String> {
+  let mut positional: Vec<String> = Vec::new();
+  for arg in args {
+    match arg.as_str() {
+      flag if flag.starts_with('-') && flag != "-" => return Err(format!("unknown option {}", flag)),
+      _ => positional.push(arg.clone())
+    }
+  }
+  if positional.len() != 2 {
+    return Err("expected a bundle and a map file".into());
+  }
+  if positional.iter().all(|input| input == "-") {
+    return Err("only one of the bundle and the map can be read from standard input".into());
+  }
+  let map_file = positional.pop().unwrap();
+  Ok(VerifyOptions { bundle_file: positional.remove(0), map_file })
+}
+
+// The length of each line of the bundle in UTF-16 code units, which generated
+// columns count
+fn line_lengths(bundle: &str) -> Vec<usize> {
+  bundle.split('\n')
+    .map(|line| line.strip_suffix('\r').unwrap_or(line).encode_utf16().count())
+    .collect()
+}
+
+// Mappings may point just past the end of a line, but not further
+fn check_mappings(cache: &Cache, bundle: &str) -> MappingViolations {
+  let lengths = line_lengths(bundle);
+  let mut violations = MappingViolations::default();
+  for mapping in cache.mappings() {
+    let (line, column) = (mapping.generated.line, mapping.generated.column);
+    match lengths.get(line as usize - 1) {
+      None => violations.missing_lines.push((line, column)),
+      Some(&length) if column as usize > length => violations.past_line_end.push((line, column, length)),
+      Some(_) => {}
+    }
+  }
+  violations
+}
+
+fn format_violations(violations: &MappingViolations) -> Vec<String> {
+  let mut problems = Vec::new();
+  if !violations.missing_lines.is_empty() {
+    let positions = violations.missing_lines.iter().map(|&(line, column)| format!("{}:{}", line, column));
+    problems.push(format!(
+      "{} mappings are on lines past the end of the bundle: {}",
+      violations.missing_lines.len(), list_positions(positions)
+    ));
+  }
+  if !violations.past_line_end.is_empty() {
+    let positions = violations.past_line_end.iter()
+      .map(|&(line, column, length)| format!("{}:{} (the line is {} columns long)", line, column, length));
+    problems.push(format!(
+      "{} mappings are past the end of their line: {}",
+      violations.past_line_end.len(), list_positions(positions)
+    ));
+  }
+  problems
+}
+
+fn list_positions<I: ExactSizeIterator<Item=String>>(positions: I) -> String {
+  let count = positions.len();
+  let mut list: Vec<String> = positions.take(SHOWN_POSITIONS).collect();
+  if count > SHOWN_POSITIONS {
+    list.push(format!("and {} more", count - SHOWN_POSITIONS));
+  }
+  list.join(", ")
+}
+
+fn file_name(path: &str) -> &str {
+  let path = match path.find(['?', '#']) {
+    Some(end) => &path[..end],
+    None => path
+  };
+  path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+fn check_file(cache: &Cache, bundle_file: &str) -> Option<String> {
+  if cache.file.is_empty() || bundle_file == "-" || file_name(&cache.file) == file_name(bundle_file) {
+    return None;
+  }
+  Some(format!("the file field of the map is {}, but the bundle is {}", cache.file, file_name(bundle_file)))
+}
+
+// An inline map must have the same JSON as the map. A local URL must resolve to the
+// map file, and other URLs must end with its name
+fn check_source_mapping_url(bundle: &str, bundle_file: &str, map_file: &str, json: &str) -> Option<String> {
+  let url = source_mapping_url(bundle)?;
+  if url.get(..5).is_some_and(|scheme| scheme.eq_ignore_ascii_case("data:")) {
+    return match decode_data_uri(url) {
+      Ok(inline) if inline.trim() == json.trim() => None,
+      Ok(_) => Some(format!("the bundle has an inline source map, which differs from {}", map_file)),
+      Err(err) => Some(format!("the inline source map of the bundle could not be decoded: {}", err))
+    };
+  }
+  if map_file == "-" {
+    return None;
+  }
+  let matches = match resolve_map_url(bundle_file, url) {
+    Some(path) => match (fs::canonicalize(&path), fs::canonicalize(map_file)) {
+      (Ok(path), Ok(map_path)) => path == map_path,
+      _ => path == Path::new(map_file)
+    },
+    None => file_name(url) == file_name(map_file)
+  };
+  if matches { None } else { Some(format!("the sourceMappingURL of the bundle is {}, which does not refer to {}", url, map_file)) }
+}
+
+#[test]
+fn it_checks_mappings_against_the_bundle() {
+  let cache = consume(r#"{ "version": 3, "file": "dist/app.min.js", "sources": ["a.js"], "names": [], "mappings": "AAAA,EAAE,EAAE;AACA;AACA" }"#).unwrap();
+  assert_eq!(check_mappings(&cache, "a(1)\r\n\u{1f600}\n"), MappingViolations::default());
+  assert_eq!(check_mappings(&cache, "a\nb"), MappingViolations {
+    missing_lines: vec![(3, 0)],
+    past_line_end: vec![(1, 2, 1), (1, 4, 1)]
+  });
+  assert_eq!(format_violations(&check_mappings(&cache, "a\nb")), [
+    "1 mappings are on lines past the end of the bundle: 3:0",
+    "2 mappings are past the end of their line: 1:2 (the line is 1 columns long), 1:4 (the line is 1 columns long)"
+  ]);
+  assert_eq!(list_positions((1..13).map(|line| line.to_string())), "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, and 2 more");
+
+  assert_eq!(check_file(&cache, "build/app.min.js"), None);
+  assert_eq!(check_file(&cache, "app.js"), Some("the file field of the map is dist/app.min.js, but the bundle is app.js".into()));
+}
+
+#[test]
+fn it_checks_the_source_mapping_url() {
+  let json = r#"{"version":3,"sources":[],"names":[],"mappings":""}"#;
+  let url = |url: &str| format!("a();\n//# sourceMappingURL={}\n", url);
+  assert_eq!(check_source_mapping_url("a();", "dist/app.js", "dist/app.js.map", json), None);
+  assert_eq!(check_source_mapping_url(&url("app.js.map"), "dist/app.js", "dist/app.js.map", json), None);
+  assert_eq!(check_source_mapping_url(&url("https://cdn.example.com/app.js.map?v=1"), "dist/app.js", "maps/app.js.map", json), None);
+  assert_eq!(
+    check_source_mapping_url(&url("vendor.js.map"), "dist/app.js", "dist/app.js.map", json),
+    Some("the sourceMappingURL of the bundle is vendor.js.map, which does not refer to dist/app.js.map".into())
+  );
+  let inline = url("data:application/json;charset=utf-8,%7B%22version%22%3A3%2C%22sources%22%3A%5B%5D%2C%22names%22%3A%5B%5D%2C%22mappings%22%3A%22%22%7D");
+  assert_eq!(check_source_mapping_url(&inline, "app.js", "app.js.map", json), None);
+  assert!(check_source_mapping_url(&inline, "app.js", "app.js.map", r#"{"version":3}"#).is_some());
+}
@@ -0,0 +1,102 @@
+use std::io;
+use std::io::Write;
+
+use js_source_mapper::{CodePosition, Mapping};
+use serde_json;
+
+// Parses a generated position like "1:283744"
+pub fn parse_position(position: &str) -> Result<(u32, u32), String> {
+  let position: CodePosition = position.parse().map_err(|err: js_source_mapper::Error| err.to_string())?;
+  Ok((position.line, position.column))
+}
+
+pub fn format_lookup(line: u32, column: u32, mapping: &Mapping) -> Result<String, String> {
+  if mapping.source.is_empty() {
+    return Err(format!("{}:{} is not mapped to an original position", line, column));
+  }
+  Ok(format!("{}:{} -> {}", line, column, format_original(mapping)))
+}
+
+// Formats a mapping like "src/app.js:57:12 (handleClick)"
+pub fn format_original(mapping: &Mapping) -> String {
+  let original = format!("{}:{}:{}", mapping.source, mapping.original.line, mapping.original.column);
+  if mapping.name.is_empty() {
+    original
+  } else {
+    format!("{} ({})", original, mapping.name)
+  }
+}
+
+// Writes a mapping like "1:283744 -> src/app.js:57:12 (handleClick)", or "1:5" if unmapped
+pub fn write_mapping<W: Write>(output: &mut W, mapping: &Mapping) -> io::Result<()> {
+  let generated = &mapping.generated;
+  if mapping.source.is_empty() {
+    writeln!(output, "{}:{}", generated.line, generated.column)
+  } else if mapping.name.is_empty() {
+    writeln!(output, "{}:{} -> {}:{}:{}", generated.line, generated.column, mapping.source, mapping.original.line, mapping.original.column)
+  } else {
+    writeln!(output, "{}:{} -> {}:{}:{} ({})", generated.line, generated.column, mapping.source, mapping.original.line, mapping.original.column, mapping.name)
+  }
+}
+
+#[derive(Serialize, Debug)]
+pub struct PositionOutput {
+  pub line: u32,
+  pub column: u32
+}
+
+#[derive(Serialize, Debug)]
+struct LookupOutput<'a> {
+  generated: PositionOutput,
+  original: Option<PositionOutput>,
+  source: Option<&'a str>,
+  name: Option<&'a str>,
+  matched: bool
+}
+
+// Formats a lookup as a JSON object, with null original fields if unmapped
+pub fn json_lookup(line: u32, column: u32, mapping: &Mapping) -> String {
+  let matched = !mapping.source.is_empty();
+  let output = LookupOutput {
+    generated: PositionOutput { line, column },
+    original: if matched { Some(PositionOutput { line: mapping.original.line, column: mapping.original.column }) } else { None },
+    source: if matched { Some(&mapping.source) } else { None },
+    name: if mapping.name.is_empty() { None } else { Some(&mapping.name) },
+    matched
+  };
+  serde_json::to_string(&output).expect("lookup output should serialize")
+}
+
+#[cfg(test)]
+use js_source_mapper::consume;
+
+#[test]
+fn it_parses_positions() {
+  assert_eq!(parse_position("10:42"), Ok((10, 42)));
+  assert!(parse_position("0:42").is_err());
+  assert!(parse_position("10").is_err());
+  assert!(parse_position("10:").is_err());
+  assert!(parse_position("a:1").is_err());
+}
+
+#[test]
+fn it_formats_lookups() {
+  let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": ["x"], "mappings": "AAAAA,EAAE,C" }"#).unwrap();
+  let lookup = |line, column| format_lookup(line, column, &cache.mapping_for_generated_position(line, column));
+  assert_eq!(lookup(1, 0), Ok("1:0 -> a.js:1:0 (x)".into()));
+  assert_eq!(lookup(1, 2), Ok("1:2 -> a.js:1:2".into()));
+  assert!(lookup(1, 3).is_err());
+}
+
+#[test]
+fn it_formats_lookups_as_json() {
+  let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": ["x"], "mappings": "AAAAA,EAAE,C" }"#).unwrap();
+  assert_eq!(
+    json_lookup(1, 0, &cache.mapping_for_generated_position(1, 0)),
+    r#"{"generated":{"line":1,"column":0},"original":{"line":1,"column":0},"source":"a.js","name":"x","matched":true}"#
+  );
+  assert_eq!(
+    json_lookup(1, 3, &cache.mapping_for_generated_position(1, 3)),
+    r#"{"generated":{"line":1,"column":3},"original":null,"source":null,"name":null,"matched":false}"#
+  );
+}
@@ -0,0 +1,56 @@
+use maps::strip_scheme;
+
+// Matches a path against a glob: * matches within a path segment, ** matches across
+// segments, including none when followed by a slash, and ? matches one character
+pub fn glob_matches(pattern: &str, path: &str) -> bool {
+  let pattern: Vec<char> = pattern.chars().collect();
+  let path: Vec<char> = path.chars().collect();
+  glob_matches_from(&pattern, &path)
+}
+
+fn glob_matches_from(pattern: &[char], path: &[char]) -> bool {
+  match pattern.first() {
+    None => path.is_empty(),
+    Some('*') if pattern.get(1) == Some(&'*') => {
+      let rest = &pattern[2..];
+      if let Some(after_slash) = rest.strip_prefix(&['/']) {
+        if glob_matches_from(after_slash, path) {
+          return true;
+        }
+      }
+      (0..=path.len()).any(|skipped| glob_matches_from(rest, &path[skipped..]))
+    },
+    Some('*') => {
+      let segment = path.iter().position(|&c| c == '/').unwrap_or(path.len());
+      (0..=segment).any(|skipped| glob_matches_from(&pattern[1..], &path[skipped..]))
+    },
+    Some('?') => path.first().is_some_and(|&c| c != '/') && glob_matches_from(&pattern[1..], &path[1..]),
+    Some(&c) => path.first() == Some(&c) && glob_matches_from(&pattern[1..], &path[1..])
+  }
+}
+
+// Matches a source against a glob, with or without its URL scheme
+pub fn source_matches(pattern: &str, source: &str) -> bool {
+  glob_matches(pattern, source) || glob_matches(pattern, strip_scheme(source))
+}
+
+#[test]
+fn it_matches_globs() {
+  assert!(glob_matches("node_modules/**", "node_modules/react/index.js"));
+  assert!(!glob_matches("node_modules/**", "src/node_modules.js"));
+  assert!(glob_matches("**/node_modules/**", "node_modules/react/index.js"));
+  assert!(glob_matches("**/node_modules/**", "packages/app/node_modules/react/index.js"));
+  assert!(glob_matches("src/*.js", "src/app.js"));
+  assert!(!glob_matches("src/*.js", "src/lib/app.js"));
+  assert!(glob_matches("src/?.js", "src/a.js"));
+  assert!(!glob_matches("src/?.js", "src/ab.js"));
+  assert!(glob_matches("vendor.js", "vendor.js"));
+  assert!(!glob_matches("vendor.js", "vendor.jsx"));
+}
+
+#[test]
+fn it_matches_sources_without_their_scheme() {
+  assert!(source_matches("node_modules/**", "webpack:///node_modules/react/index.js"));
+  assert!(source_matches("webpack://**", "webpack:///node_modules/react/index.js"));
+  assert!(!source_matches("src/**", "webpack:///node_modules/react/index.js"));
+}
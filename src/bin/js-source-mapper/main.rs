@@ -0,0 +1,44 @@
+extern crate js_source_mapper;
+#[macro_use] extern crate serde_derive;
+extern crate serde_json;
+#[cfg(feature = "serve")]
+extern crate tiny_http;
+
+mod cli;
+mod commands;
+mod format;
+mod glob;
+mod maps;
+
+use std::env;
+use std::process;
+
+use cli::{EXIT_OK, USAGE, take_global_options, usage_error};
+use commands::COMMANDS;
+
+fn main() {
+  let args: Vec<String> = env::args().skip(1).collect();
+  process::exit(run(&args));
+}
+
+// Runs the command named by the first argument, or lookup if it does not name one
+fn run(args: &[String]) -> i32 {
+  if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+    println!("{}", USAGE);
+    return EXIT_OK;
+  }
+  let (args, globals) = match take_global_options(args) {
+    Ok(result) => result,
+    Err(err) => return usage_error(args.iter().any(|arg| arg == "--json"), &err)
+  };
+
+  let named = args.first().and_then(|name| COMMANDS.iter().find(|command| command.name == name));
+  let (command, args) = match named {
+    Some(command) => (command, &args[1..]),
+    None => (&COMMANDS[0], &args[..])
+  };
+  if globals.json && !command.json {
+    return usage_error(true, &format!("{} does not support --json", command.name));
+  }
+  (command.run)(args, &globals)
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use js_source_mapper::{Cache, Error, consume, consume_with_sources_content, decode_data_uri, source_mapping_url};
+
+use cli::GlobalOptions;
+
+pub fn load(input: &str, globals: &GlobalOptions) -> Result<Cache, String> {
+  load_with(input, globals, consume)
+}
+
+// Like load, but keeps the sourcesContent of the map, which is skipped by default
+pub fn load_with_sources_content(input: &str, globals: &GlobalOptions) -> Result<Cache, String> {
+  load_with(input, globals, consume_with_sources_content)
+}
+
+pub fn load_with(input: &str, globals: &GlobalOptions, parse: fn(&str) -> Result<Cache, Error>) -> Result<Cache, String> {
+  load_json_with(input, globals, parse).map(|(cache, _)| cache)
+}
+
+// Like load_with, but also returns the source map JSON the cache was parsed from
+pub fn load_json_with(input: &str, globals: &GlobalOptions, parse: fn(&str) -> Result<Cache, Error>) -> Result<(Cache, String), String> {
+  let (json, name) = read_map(input, globals.map.as_deref())?;
+  let mut cache = parse(&json).map_err(|err| format!("could not parse {}: {}", name, err))?;
+  if let Some(ref source_root) = globals.source_root {
+    cache.source_root = source_root.clone();
+  }
+  Ok((cache, json))
+}
+
+// Replaces the sources with their resolved paths, for commands that print source paths
+pub fn resolve_sources(cache: &mut Cache) {
+  if cache.source_root.is_empty() {
+    return;
+  }
+  let resolved: HashMap<String, String> = cache.sources().iter()
+    .map(|source| (source.clone(), cache.resolved_source(source)))
+    .collect();
+  cache.source_root.clear();
+  cache.map_sources(|source| resolved.get(source).cloned().unwrap_or_else(|| source.to_owned()));
+}
+
+// Reads the source map JSON for an input, which is either a source map or a bundle whose
+// sourceMappingURL comment refers to one. The map option bypasses the discovery. Returns
+// the JSON along with a name for the source map in messages.
+pub fn read_map(input: &str, map: Option<&str>) -> Result<(String, String), String> {
+  let path = map.unwrap_or(input);
+  let contents = read_input(path)?;
+  let name = if path == "-" { "source map from stdin".to_owned() } else { path.to_owned() };
+  if map.is_some() {
+    return Ok((contents, name));
+  }
+
+  let is_bundle = [".js", ".mjs", ".cjs"].iter().any(|extension| path.ends_with(extension));
+  let url = match source_mapping_url(&contents) {
+    Some(url) if is_bundle || !contents.trim_start().starts_with('{') => url,
+    None if is_bundle => return Err(format!("could not find a sourceMappingURL comment in {}", describe_input(path))),
+    _ => return Ok((contents, name))
+  };
+
+  if url.get(..5).is_some_and(|scheme| scheme.eq_ignore_ascii_case("data:")) {
+    let json = decode_data_uri(url).map_err(|err| format!("could not decode the inline source map of {}: {}", describe_input(path), err))?;
+    return Ok((json, format!("inline source map of {}", describe_input(path))));
+  }
+
+  let map_path = resolve_map_url(path, url).ok_or_else(|| format!(
+    "the sourceMappingURL of {} is {}, which is not a local file; download it and pass it with --map",
+    describe_input(path), url
+  ))?;
+  let json = fs::read_to_string(&map_path).map_err(|err| format!(
+    "could not read {} (the sourceMappingURL {} of {}): {}",
+    map_path.display(), url, describe_input(path), err
+  ))?;
+  Ok((json, map_path.display().to_string()))
+}
+
+pub fn read_input(path: &str) -> Result<String, String> {
+  if path == "-" {
+    let mut contents = String::new();
+    io::stdin().read_to_string(&mut contents).map_err(|err| format!("could not read source map from stdin: {}", err))?;
+    return Ok(contents);
+  }
+  fs::read_to_string(path).map_err(|err| format!("could not read {}: {}", path, err))
+}
+
+pub fn describe_input(path: &str) -> &str {
+  if path == "-" { "stdin" } else { path }
+}
+
+// Resolves a sourceMappingURL against the directory of the bundle, or returns None for
+// URLs that do not refer to local files
+pub fn resolve_map_url(bundle: &str, url: &str) -> Option<PathBuf> {
+  let url = match url.find(['?', '#']) {
+    Some(end) => &url[..end],
+    None => url
+  };
+  if let Some(path) = url.strip_prefix("file://") {
+    return Some(PathBuf::from(path));
+  }
+  if url.contains("://") || url.starts_with("//") {
+    return None;
+  }
+  let directory = if bundle == "-" { None } else { Path::new(bundle).parent() };
+  Some(directory.map_or_else(|| PathBuf::from(url), |directory| directory.join(url)))
+}
+
+// "webpack:///src/app.js" and "webpack://src/app.js" both become "src/app.js"
+pub fn strip_scheme(path: &str) -> &str {
+  let scheme_end = match path.find("://") {
+    Some(end) => end,
+    None => return path
+  };
+  let scheme = &path[..scheme_end];
+  let is_scheme = scheme.starts_with(|character: char| character.is_ascii_alphabetic())
+    && scheme.chars().all(|character| character.is_ascii_alphanumeric() || "+-.".contains(character));
+  if is_scheme { path[scheme_end + 3..].trim_start_matches('/') } else { path }
+}
+
+#[cfg(test)]
+use std::env;
+#[cfg(test)]
+use std::process;
+
+#[test]
+fn it_resolves_source_mapping_urls() {
+  assert_eq!(resolve_map_url("dist/bundle.min.js", "bundle.min.js.map?v=2"), Some(PathBuf::from("dist/bundle.min.js.map")));
+  assert_eq!(resolve_map_url("dist/bundle.min.js", "../maps/bundle.map"), Some(PathBuf::from("dist/../maps/bundle.map")));
+  assert_eq!(resolve_map_url("bundle.min.js", "/srv/maps/bundle.map"), Some(PathBuf::from("/srv/maps/bundle.map")));
+  assert_eq!(resolve_map_url("-", "bundle.map"), Some(PathBuf::from("bundle.map")));
+  assert_eq!(resolve_map_url("dist/bundle.min.js", "file:///srv/maps/bundle.map"), Some(PathBuf::from("/srv/maps/bundle.map")));
+  assert_eq!(resolve_map_url("dist/bundle.min.js", "https://cdn.example.com/bundle.map"), None);
+  assert_eq!(resolve_map_url("dist/bundle.min.js", "//cdn.example.com/bundle.map"), None);
+}
+
+#[test]
+fn it_reads_maps_referenced_by_bundles() {
+  let dir = env::temp_dir().join(format!("js-source-mapper-{}", process::id()));
+  fs::create_dir_all(&dir).unwrap();
+  let map = r#"{"version":3,"sources":["a.js"],"names":[],"mappings":"AAAA"}"#;
+  fs::write(dir.join("bundle.js.map"), map).unwrap();
+  fs::write(dir.join("bundle.js"), "a();\n//# sourceMappingURL=bundle.js.map\n").unwrap();
+  fs::write(dir.join("inline.js"), "a();\n//# sourceMappingURL=data:application/json;base64,e30=\n").unwrap();
+  fs::write(dir.join("missing.js"), "a();\n//# sourceMappingURL=missing.js.map\n").unwrap();
+  fs::write(dir.join("remote.js"), "a();\n//# sourceMappingURL=https://cdn.example.com/remote.js.map\n").unwrap();
+  let path = |name: &str| dir.join(name).to_str().unwrap().to_owned();
+
+  assert_eq!(read_map(&path("bundle.js"), None).unwrap().0, map);
+  assert_eq!(read_map(&path("bundle.js.map"), None).unwrap().0, map);
+  assert_eq!(read_map(&path("inline.js"), None).unwrap().0, "{}");
+  assert_eq!(read_map(&path("inline.js"), Some(&path("bundle.js.map"))).unwrap().0, map);
+  assert!(read_map(&path("missing.js"), None).unwrap_err().contains("the sourceMappingURL missing.js.map of"));
+  assert!(read_map(&path("remote.js"), None).unwrap_err().contains("https://cdn.example.com/remote.js.map"));
+  assert!(read_map(&path("bundle.js.map"), Some(&path("bundle.js.map"))).is_ok());
+  fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn it_overrides_the_source_root() {
+  let dir = env::temp_dir().join(format!("js-source-mapper-source-root-{}", process::id()));
+  fs::create_dir_all(&dir).unwrap();
+  let path = dir.join("a.map").to_str().unwrap().to_owned();
+  let map = r#"{ "version": 3, "sourceRoot": "https://cdn.example.com/src", "sources": ["app.js"], "names": [], "mappings": "AAAA" }"#;
+  fs::write(&path, map).unwrap();
+  let source = |source_root: Option<&str>| {
+    let globals = GlobalOptions { source_root: source_root.map(|root| root.to_owned()), ..GlobalOptions::default() };
+    let mut cache = load(&path, &globals).unwrap();
+    resolve_sources(&mut cache);
+    cache.mapping_for_generated_position(1, 0).source
+  };
+
+  assert_eq!(&*source(None), "https://cdn.example.com/src/app.js");
+  assert_eq!(&*source(Some("/home/me/checkout/")), "/home/me/checkout/app.js");
+  assert_eq!(&*source(Some("")), "app.js");
+  assert_eq!(fs::read_to_string(&path).unwrap(), map);
+  fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,582 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+#[cfg(feature = "mmap")]
+extern crate memmap2;
+#[cfg(feature = "mmap")]
+use std::fs::File;
+#[cfg(feature = "mmap")]
+use std::path::Path;
+
+use consume::{Cache, CodePosition, Interner, Mapping};
+use error::{Error, ErrorCode};
+use lookup::MappingLookup;
+
+// Identifies a serialized cache
+const MAGIC: &[u8] = b"JSMC";
+
+// Bumped whenever the layout below changes
+const FORMAT_VERSION: u32 = 2;
+
+// Marks a mapping without a source or name
+const NO_INDEX: u32 = u32::MAX;
+
+const HEADER_LENGTH: usize = 24;
+const MAPPING_LENGTH: usize = 24;
+const STRING_INDEX_ENTRY_LENGTH: usize = 8;
+
+// The binary format, with all integers stored as little-endian u32:
+//
+//   header:        magic, format version, number of mappings, number of
+//                  sources, number of names, reserved
+//   mappings:      generated line and column, original line and column,
+//                  source index, name index
+//   string index:  offset and length in bytes of the source root, each
+//                  source and each name within the string data
+//   string data:   UTF-8 bytes of all strings
+//
+// The mappings are fixed-width records sorted by generated position, so they
+// can be searched directly in a memory-mapped file without parsing.
+
+impl Cache {
+  /**
+   * Serializes the cache into a compact binary form that is much faster to load than
+   * the source map JSON. Use `Cache::deserialize_binary` to load it again, or
+   * `MappedCache` to query it without loading it.
+   *
+   * The binary form holds the mappings, the `sources` and `names` tables and the source
+   * root. It leaves out the source contents, the `file` field, the `ignoreList`, the
+   * declared positions of the sources and names `drop_unreferenced` removed, and the
+   * `SourceResolver`. A loaded cache therefore has no source contents or file, ignores
+   * no sources, and declares its tables as `sources` and `names` return them.
+   *
+   * # Panics
+   *
+   * The counts, offsets and lengths of the binary form are 32-bit, so this panics if the
+   * cache has 2^32 or more mappings, sources or names, or more than 4 GiB of strings.
+   */
+  pub fn serialize_binary(&self) -> Vec<u8> {
+    let mut sources: Vec<&str> = self.sources().iter().map(|source| source.as_str()).collect();
+    let mut names: Vec<&str> = self.names().iter().map(|name| name.as_str()).collect();
+    let mut source_indices: HashMap<&str, u32> = HashMap::new();
+    let mut name_indices: HashMap<&str, u32> = HashMap::new();
+    for (index, &source) in sources.iter().enumerate().rev() {
+      source_indices.insert(source, binary_u32(index, "source index"));
+    }
+    for (index, &name) in names.iter().enumerate().rev() {
+      name_indices.insert(name, binary_u32(index, "name index"));
+    }
+
+    let mut mapping_bytes: Vec<u8> = Vec::with_capacity(self.mappings().len() * MAPPING_LENGTH);
+    for mapping in self.mappings() {
+      write_u32(&mut mapping_bytes, mapping.generated.line);
+      write_u32(&mut mapping_bytes, mapping.generated.column);
+      write_u32(&mut mapping_bytes, mapping.original.line);
+      write_u32(&mut mapping_bytes, mapping.original.column);
+      write_u32(&mut mapping_bytes, table_index(&mapping.source, &mut sources, &mut source_indices));
+      write_u32(&mut mapping_bytes, table_index(&mapping.name, &mut names, &mut name_indices));
+    }
+
+    let mut string_index: Vec<u8> = Vec::new();
+    let mut string_data: Vec<u8> = Vec::new();
+    let strings = Some(self.source_root.as_str()).into_iter()
+      .chain(sources.iter().cloned())
+      .chain(names.iter().cloned());
+    for string in strings {
+      write_u32(&mut string_index, binary_u32(string_data.len(), "string offset"));
+      write_u32(&mut string_index, binary_u32(string.len(), "string length"));
+      string_data.extend_from_slice(string.as_bytes());
+    }
+
+    let mut bytes: Vec<u8> = Vec::with_capacity(HEADER_LENGTH + mapping_bytes.len() + string_index.len() + string_data.len());
+    bytes.extend_from_slice(MAGIC);
+    write_u32(&mut bytes, FORMAT_VERSION);
+    write_u32(&mut bytes, binary_u32(self.mappings().len(), "mapping count"));
+    write_u32(&mut bytes, binary_u32(sources.len(), "source count"));
+    write_u32(&mut bytes, binary_u32(names.len(), "name count"));
+    write_u32(&mut bytes, 0);
+    bytes.extend_from_slice(&mapping_bytes);
+    bytes.extend_from_slice(&string_index);
+    bytes.extend_from_slice(&string_data);
+    bytes
+  }
+
+  /**
+   * Loads a cache serialized with `Cache::serialize_binary`.
+   *
+   * Fails if the data was serialized by an incompatible version of this crate.
+   *
+   * # Examples
+   *
+   * ```
+   * use js_source_mapper::{consume, Cache};
+   *
+   * let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA" }"#).unwrap();
+   * let bytes = cache.serialize_binary();
+   * let loaded = Cache::deserialize_binary(&bytes).unwrap();
+   * assert!(&*loaded.mapping_for_generated_position(1, 0).source == "a.js");
+   * ```
+   */
+  pub fn deserialize_binary(bytes: &[u8]) -> Result<Cache, Error> {
+    let view = BinaryView::new(bytes)?;
+
+    let sources: Vec<String> = (0..view.source_count).map(|index| view.string(1 + index).to_owned()).collect();
+    let names: Vec<String> = (0..view.name_count).map(|index| view.string(1 + view.source_count + index).to_owned()).collect();
+
+    let mut interner = Interner::default();
+    let shared_sources: Vec<Arc<str>> = sources.iter().map(|source| interner.intern(source)).collect();
+    let shared_names: Vec<Arc<str>> = names.iter().map(|name| interner.intern(name)).collect();
+    let empty = interner.intern("");
+
+    let mut mappings: Vec<Mapping> = Vec::with_capacity(view.mapping_count);
+    for index in 0..view.mapping_count {
+      let record = view.record(index);
+      mappings.push(Mapping {
+        generated: CodePosition { line: record[0], column: record[1] },
+        original: CodePosition { line: record[2], column: record[3] },
+        source: table_entry(&shared_sources, record[4], &empty),
+        name: table_entry(&shared_names, record[5], &empty)
+      });
+    }
+
+    Ok(Cache::from_parts(mappings, sources, names, view.string(0).to_owned()))
+  }
+
+  /**
+   * Opens a cache serialized with `Cache::serialize_binary` by memory-mapping the file.
+   *
+   * The header and string tables are validated when opening, and the mapping records
+   * are checked to be in generated order; lookups search them in the file directly. The
+   * file must not be modified while it is open.
+   */
+  #[cfg(feature = "mmap")]
+  pub fn open_mmap<P: AsRef<Path>>(path: P) -> Result<MappedCache, Error> {
+    let file = File::open(path)?;
+    // Safety: the caller guarantees that the file is not modified while it is mapped
+    let mmap = unsafe { memmap2::Mmap::map(&file) }?;
+    MappedCache::new(Backing::Mmap(mmap))
+  }
+}
+
+enum Backing {
+  Owned(Vec<u8>),
+  #[cfg(feature = "mmap")]
+  Mmap(memmap2::Mmap)
+}
+
+impl Backing {
+  fn bytes(&self) -> &[u8] {
+    match *self {
+      Backing::Owned(ref bytes) => bytes,
+      #[cfg(feature = "mmap")]
+      Backing::Mmap(ref mmap) => mmap
+    }
+  }
+}
+
+/**
+ * A cache in the binary format of `Cache::serialize_binary`, queried without
+ * deserializing it.
+ *
+ * Mappings are searched in the serialized bytes, and their sources and names are only
+ * resolved for the mappings that are returned. With the `mmap` feature,
+ * `Cache::open_mmap` creates a `MappedCache` backed by a memory-mapped file, keeping the
+ * mappings out of the heap entirely.
+ */
+pub struct MappedCache {
+  backing: Backing,
+  mapping_count: usize,
+  source_count: usize,
+  name_count: usize
+}
+
+impl MappedCache {
+  /**
+   * Creates a mapped cache from bytes produced by `Cache::serialize_binary`.
+   */
+  pub fn from_bytes(bytes: Vec<u8>) -> Result<MappedCache, Error> {
+    MappedCache::new(Backing::Owned(bytes))
+  }
+
+  fn new(backing: Backing) -> Result<MappedCache, Error> {
+    let (mapping_count, source_count, name_count) = {
+      let view = BinaryView::new(backing.bytes())?;
+      (view.mapping_count, view.source_count, view.name_count)
+    };
+    Ok(MappedCache {
+      backing,
+      mapping_count,
+      source_count,
+      name_count
+    })
+  }
+
+  fn view(&self) -> BinaryView<'_> {
+    BinaryView {
+      bytes: self.backing.bytes(),
+      mapping_count: self.mapping_count,
+      source_count: self.source_count,
+      name_count: self.name_count
+    }
+  }
+
+  /**
+   * The path prefix of mapping source paths.
+   */
+  pub fn source_root(&self) -> &str {
+    self.view().string(0)
+  }
+
+  /**
+   * Returns the original source, line, column and name information for the generated
+   * source's line and column positions provided, like
+   * `Cache::mapping_for_generated_position`.
+   */
+  pub fn mapping_for_generated_position(&self, line: u32, column: u32) -> Mapping {
+    let view = self.view();
+    if view.mapping_count == 0 {
+      return Mapping {
+        generated: CodePosition { line, column },
+        original: CodePosition { line: 0, column: 0 },
+        source: "".into(),
+        name: "".into()
+      };
+    }
+
+    // Binary search over the raw records, with the same semantics as Cache
+    let mut low = 0;
+    let mut high = view.mapping_count;
+    while low < high {
+      let middle = low + (high - low) / 2;
+      let record = view.record(middle);
+      match (record[0], record[1]).cmp(&(line, column)) {
+        Ordering::Less => low = middle + 1,
+        Ordering::Greater => high = middle,
+        Ordering::Equal => {
+          low = middle;
+          break;
+        }
+      }
+    }
+    let index = if low >= view.mapping_count { view.mapping_count - 1 } else { low };
+
+    let record = view.record(index);
+    Mapping {
+      generated: CodePosition { line: record[0], column: record[1] },
+      original: CodePosition { line: record[2], column: record[3] },
+      source: view.table_string(record[4], 1).into(),
+      name: view.table_string(record[5], 1 + view.source_count).into()
+    }
+  }
+}
+
+impl MappingLookup for MappedCache {
+  fn mapping_for_generated_position(&self, line: u32, column: u32) -> Option<Mapping> {
+    if self.mapping_count == 0 {
+      None
+    } else {
+      Some(MappedCache::mapping_for_generated_position(self, line, column))
+    }
+  }
+}
+
+// A validated view over the bytes of a serialized cache
+struct BinaryView<'a> {
+  bytes: &'a [u8],
+  mapping_count: usize,
+  source_count: usize,
+  name_count: usize
+}
+
+impl<'a> BinaryView<'a> {
+  fn new(bytes: &'a [u8]) -> Result<BinaryView<'a>, Error> {
+    if bytes.len() < HEADER_LENGTH || &bytes[..MAGIC.len()] != MAGIC {
+      return Err(Error::invalid(ErrorCode::InvalidBinaryCache, "Not a serialized source map cache"));
+    }
+    let format_version = read_u32(bytes, 4);
+    if format_version != FORMAT_VERSION {
+      return Err(Error::invalid(ErrorCode::InvalidBinaryCache, format!(
+        "Unsupported cache format version {}, expected version {}",
+        format_version, FORMAT_VERSION
+      )));
+    }
+
+    let view = BinaryView {
+      bytes,
+      mapping_count: read_u32(bytes, 8) as usize,
+      source_count: read_u32(bytes, 12) as usize,
+      name_count: read_u32(bytes, 16) as usize
+    };
+
+    // The counts come from the file, so the sizes of the sections they give are checked
+    // for overflow, which 32-bit targets run into, before they are checked to fit. The
+    // offsets computed from them afterwards are all within bytes
+    let truncated = || Error::invalid(ErrorCode::InvalidBinaryCache, "Serialized cache is truncated");
+    let string_count = view.source_count.checked_add(view.name_count)
+      .and_then(|count| count.checked_add(1))
+      .ok_or_else(truncated)?;
+    let string_index_start = view.mapping_count.checked_mul(MAPPING_LENGTH)
+      .and_then(|length| length.checked_add(HEADER_LENGTH))
+      .filter(|&start| start <= bytes.len())
+      .ok_or_else(truncated)?;
+    let string_data_start = string_count.checked_mul(STRING_INDEX_ENTRY_LENGTH)
+      .and_then(|length| length.checked_add(string_index_start))
+      .filter(|&start| start <= bytes.len())
+      .ok_or_else(truncated)?;
+
+    for index in 0..string_count {
+      let (offset, length) = view.string_bounds(index);
+      let end = string_data_start.checked_add(offset).and_then(|start| start.checked_add(length));
+      match end {
+        Some(end) if end <= bytes.len() => {
+          if ::std::str::from_utf8(&bytes[end - length..end]).is_err() {
+            return Err(Error::invalid(ErrorCode::InvalidBinaryCache, "Serialized cache contains invalid UTF-8"));
+          }
+        },
+        _ => return Err(truncated())
+      }
+    }
+
+    // Lookups binary search the records, which only finds the right ones in generated order,
+    // and resolve their sources and names without checking the indices again
+    let mut previous = (0, 0);
+    for record in bytes[HEADER_LENGTH..string_index_start].chunks_exact(MAPPING_LENGTH) {
+      let generated = (read_u32(record, 0), read_u32(record, 4));
+      if generated < previous {
+        return Err(Error::invalid(ErrorCode::InvalidBinaryCache, "Serialized cache has mappings out of generated order"));
+      }
+      previous = generated;
+      check_table_index(read_u32(record, 16), "source", view.source_count)?;
+      check_table_index(read_u32(record, 20), "name", view.name_count)?;
+    }
+
+    Ok(view)
+  }
+
+  fn string_index_start(&self) -> usize {
+    HEADER_LENGTH + self.mapping_count * MAPPING_LENGTH
+  }
+
+  fn record(&self, index: usize) -> [u32; 6] {
+    let start = HEADER_LENGTH + index * MAPPING_LENGTH;
+    let mut record = [0; 6];
+    for (field, value) in record.iter_mut().enumerate() {
+      *value = read_u32(self.bytes, start + field * 4);
+    }
+    record
+  }
+
+  fn string_bounds(&self, index: usize) -> (usize, usize) {
+    let entry = self.string_index_start() + index * STRING_INDEX_ENTRY_LENGTH;
+    (read_u32(self.bytes, entry) as usize, read_u32(self.bytes, entry + 4) as usize)
+  }
+
+  fn string(&self, index: usize) -> &'a str {
+    let string_count = 1 + self.source_count + self.name_count;
+    let start = self.string_index_start() + string_count * STRING_INDEX_ENTRY_LENGTH;
+    let (offset, length) = self.string_bounds(index);
+    let bytes = &self.bytes[start + offset..start + offset + length];
+    // Validated in BinaryView::new
+    ::std::str::from_utf8(bytes).expect("strings should be valid UTF-8")
+  }
+
+  // Resolves an index into the sources or names table, which start at `first`. Indices
+  // are checked against the tables in BinaryView::new
+  fn table_string(&self, index: u32, first: usize) -> &'a str {
+    if index == NO_INDEX {
+      return "";
+    }
+    self.string(first + index as usize)
+  }
+}
+
+fn table_index<'a>(value: &'a str, table: &mut Vec<&'a str>, indices: &mut HashMap<&'a str, u32>) -> u32 {
+  if value.is_empty() {
+    return NO_INDEX;
+  }
+  *indices.entry(value).or_insert_with(|| {
+    table.push(value);
+    binary_u32(table.len() - 1, "table index")
+  })
+}
+
+fn check_table_index(index: u32, kind: &str, count: usize) -> Result<(), Error> {
+  if index != NO_INDEX && index as usize >= count {
+    return Err(Error::invalid(ErrorCode::InvalidBinaryCache, format!("Serialized cache references {} index {} when {} list length is {}", kind, index, kind, count)));
+  }
+  Ok(())
+}
+
+// Mappings share the entries of the tables, and the empty string when they have none
+fn table_entry(table: &[Arc<str>], index: u32, empty: &Arc<str>) -> Arc<str> {
+  if index == NO_INDEX {
+    return empty.clone();
+  }
+  // Checked against the table in BinaryView::new
+  table[index as usize].clone()
+}
+
+// Converts a count, offset or length for the 32-bit fields of the binary form, which
+// serialize_binary documents that it panics for
+fn binary_u32(value: usize, kind: &str) -> u32 {
+  u32::try_from(value).unwrap_or_else(|_| panic!("The {} {} does not fit in a binary cache", kind, value))
+}
+
+fn write_u32(bytes: &mut Vec<u8>, value: u32) {
+  bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+  u32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]])
+}
+
+#[cfg(test)]
+use consume::consume;
+
+#[cfg(test)]
+fn example_cache() -> Cache {
+  consume(r#"{
+    "version": 3,
+    "file": "foo.js",
+    "sources": ["source1.js", "source1.js", "source3.js"],
+    "names": ["name1", "name2"],
+    "mappings": ";EAACA,C;;IAEEC;;MEEE",
+    "sourceRoot": "http://example.com"
+  }"#).unwrap()
+}
+
+#[test]
+fn it_round_trips_binary_caches() {
+  let cache = example_cache();
+  let loaded = Cache::deserialize_binary(&cache.serialize_binary()).unwrap();
+  assert_eq!(loaded.mappings(), cache.mappings());
+  assert_eq!(loaded.sources(), cache.sources());
+  assert_eq!(loaded.names(), cache.names());
+  assert_eq!(loaded.source_root, cache.source_root);
+}
+
+#[test]
+fn it_leaves_out_what_the_binary_form_does_not_hold() {
+  let mut cache = consume(r#"{ "version": 3, "file": "a.min.js", "sources": ["a.js", "b.js"], "names": [], "mappings": "AAAA", "ignoreList": [0] }"#).unwrap();
+  cache.drop_unreferenced();
+  let loaded = Cache::deserialize_binary(&cache.serialize_binary()).unwrap();
+  assert_eq!(cache.declared_sources(), [Some("a.js"), None]);
+  assert_eq!(loaded.declared_sources(), [Some("a.js")]);
+  assert!(cache.is_ignored("a.js") && !loaded.is_ignored("a.js"));
+  assert_eq!((cache.file.as_str(), loaded.file.as_str()), ("a.min.js", ""));
+}
+
+#[test]
+fn it_rejects_other_format_versions() {
+  let mut bytes = example_cache().serialize_binary();
+  bytes[4] = 1;
+  match Cache::deserialize_binary(&bytes) {
+    Err(s) => assert_eq!(s.to_string(), "Unsupported cache format version 1, expected version 2"),
+    _ => panic!("Other format versions should be rejected")
+  }
+}
+
+#[test]
+fn it_rejects_truncated_and_foreign_data() {
+  let bytes = example_cache().serialize_binary();
+  for length in 0..bytes.len() {
+    assert!(Cache::deserialize_binary(&bytes[..length]).is_err());
+    assert!(MappedCache::from_bytes(bytes[..length].to_vec()).is_err());
+  }
+  assert!(Cache::deserialize_binary(br#"{"version":3}"#).is_err());
+}
+
+#[test]
+fn it_rejects_unsorted_mappings() {
+  let mut bytes = example_cache().serialize_binary();
+  let (first, second) = bytes[HEADER_LENGTH..HEADER_LENGTH + 2 * MAPPING_LENGTH].split_at_mut(MAPPING_LENGTH);
+  first.swap_with_slice(second);
+  match Cache::deserialize_binary(&bytes) {
+    Err(err) => {
+      assert_eq!(err.code(), ErrorCode::InvalidBinaryCache);
+      assert_eq!(err.to_string(), "Serialized cache has mappings out of generated order");
+    },
+    _ => panic!("Unsorted mappings should be rejected")
+  }
+  assert!(MappedCache::from_bytes(bytes).is_err());
+}
+
+#[test]
+fn it_rejects_source_and_name_indices_outside_the_tables() {
+  let bytes = example_cache().serialize_binary();
+  // The source and name index of the first mapping, which has both
+  for &(offset, expected) in &[
+    (16, "Serialized cache references source index 7 when source list length is 3"),
+    (20, "Serialized cache references name index 7 when name list length is 2")
+  ] {
+    let mut corrupt = bytes.clone();
+    corrupt[HEADER_LENGTH + offset..HEADER_LENGTH + offset + 4].copy_from_slice(&7u32.to_le_bytes());
+    for result in [Cache::deserialize_binary(&corrupt).map(|_| ()), MappedCache::from_bytes(corrupt).map(|_| ())] {
+      match result {
+        Err(err) => {
+          assert_eq!(err.code(), ErrorCode::InvalidBinaryCache);
+          assert_eq!(err.to_string(), expected);
+        },
+        _ => panic!("Indices outside the tables should be rejected")
+      }
+    }
+  }
+}
+
+#[test]
+fn it_rejects_counts_larger_than_the_data() {
+  let bytes = example_cache().serialize_binary();
+  // The number of mappings, sources and names, alone and all at once
+  for fields in [&[8][..], &[12], &[16], &[8, 12, 16]] {
+    let mut corrupt = bytes.clone();
+    for &offset in fields {
+      corrupt[offset..offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    }
+    match Cache::deserialize_binary(&corrupt) {
+      Err(err) => assert_eq!(err.to_string(), "Serialized cache is truncated"),
+      _ => panic!("Counts larger than the data should be rejected")
+    }
+    assert!(MappedCache::from_bytes(corrupt).is_err());
+  }
+}
+
+// Caches large enough to reach the limit take too much memory for a test
+#[cfg(target_pointer_width = "64")]
+#[test]
+#[should_panic(expected = "The string offset 4294967296 does not fit in a binary cache")]
+fn it_panics_for_sizes_past_the_32_bit_fields() {
+  binary_u32(1 << 32, "string offset");
+}
+
+#[test]
+fn it_looks_up_mappings_without_deserializing() {
+  let cache = example_cache();
+  let mapped = MappedCache::from_bytes(cache.serialize_binary()).unwrap();
+  assert_eq!(mapped.source_root(), "http://example.com");
+  for line in 0..8 {
+    for column in 0..8 {
+      assert_eq!(
+        mapped.mapping_for_generated_position(line, column),
+        cache.mapping_for_generated_position(line, column)
+      );
+    }
+  }
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn it_opens_memory_mapped_caches() {
+  use std::io::Write;
+
+  let cache = example_cache();
+  let path = ::std::env::temp_dir().join(format!("js-source-mapper-{}.cache", ::std::process::id()));
+  File::create(&path).unwrap().write_all(&cache.serialize_binary()).unwrap();
+
+  let mapped = Cache::open_mmap(&path).unwrap();
+  assert_eq!(mapped.mapping_for_generated_position(4, 4), cache.mapping_for_generated_position(4, 4));
+
+  drop(mapped);
+  ::std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,251 @@
+// The C API built with the capi feature. The build script generates its header,
+// include/js_source_mapper.h, with cbindgen. The types follow C naming.
+#![allow(non_camel_case_types)]
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+use std::str;
+
+use consume::{Cache, consume};
+
+/**
+ * The outcome of a call that can fail.
+ */
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum jsm_status {
+  // Only set by callers, when initializing a jsm_error
+  #[allow(dead_code)]
+  JSM_OK = 0,
+  /**
+   * A required pointer argument was null
+   */
+  JSM_NULL_ARGUMENT = 1,
+  /**
+   * The source map is not valid UTF-8
+   */
+  JSM_INVALID_UTF8 = 2,
+  /**
+   * The source map could not be parsed
+   */
+  JSM_INVALID_SOURCE_MAP = 3
+}
+
+/**
+ * The error of a failed call. Initialize it to zero before the call. On failure,
+ * `message` is a NUL-terminated UTF-8 string owned by the caller, to be freed with
+ * `jsm_string_free`. When the status is `JSM_INVALID_SOURCE_MAP`, `code` is the stable
+ * error code of the Rust crate, such as 2 for an unsupported version, and it is 0
+ * otherwise.
+ */
+#[repr(C)]
+pub struct jsm_error {
+  pub status: jsm_status,
+  pub message: *mut c_char,
+  pub code: u32
+}
+
+/**
+ * A parsed source map. Created by `jsm_consume` and freed with `jsm_cache_free`.
+ */
+pub struct jsm_cache {
+  cache: Cache,
+  // NUL-terminated copies of the sources and names, which lookups point into
+  strings: HashMap<String, CString>
+}
+
+/**
+ * A mapping filled in by `jsm_lookup`. Lines start from 1 and columns from 0. `source`
+ * and `name` are NUL-terminated UTF-8 strings owned by the cache, valid until
+ * `jsm_cache_free`, and empty when absent.
+ */
+#[repr(C)]
+pub struct jsm_mapping {
+  pub generated_line: u32,
+  pub generated_column: u32,
+  pub original_line: u32,
+  pub original_column: u32,
+  pub source: *const c_char,
+  pub name: *const c_char
+}
+
+static EMPTY: &[u8] = b"\0";
+
+fn set_error(err: *mut jsm_error, status: jsm_status, code: u32, message: &str) {
+  if err.is_null() {
+    return;
+  }
+  let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+  unsafe {
+    (*err).status = status;
+    (*err).message = message.into_raw();
+    (*err).code = code;
+  }
+}
+
+fn c_string(value: &str) -> CString {
+  // Sources and names are JSON strings, which may escape NUL bytes
+  CString::new(value.replace('\0', "")).unwrap_or_default()
+}
+
+/**
+ * Parses `len` bytes of source map JSON, which need not be NUL-terminated and are not
+ * retained. Returns null and fills in `err`, if not null, on failure.
+ *
+ * # Safety
+ *
+ * `json` must point to `len` readable bytes, and `err` must be null or point to a
+ * `jsm_error`.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn jsm_consume(json: *const c_char, len: usize, err: *mut jsm_error) -> *mut jsm_cache {
+  if json.is_null() {
+    set_error(err, jsm_status::JSM_NULL_ARGUMENT, 0, "json is null");
+    return ptr::null_mut();
+  }
+  let bytes = slice::from_raw_parts(json as *const u8, len);
+  let json = match str::from_utf8(bytes) {
+    Ok(json) => json,
+    Err(utf8_err) => {
+      set_error(err, jsm_status::JSM_INVALID_UTF8, 0, &format!("the source map is not valid UTF-8: {}", utf8_err));
+      return ptr::null_mut();
+    }
+  };
+  let cache = match consume(json) {
+    Ok(cache) => cache,
+    Err(parse_err) => {
+      set_error(err, jsm_status::JSM_INVALID_SOURCE_MAP, parse_err.code() as u32, &parse_err.to_string());
+      return ptr::null_mut();
+    }
+  };
+  let strings = cache.sources().iter().chain(cache.names())
+    .map(|value| (value.clone(), c_string(value)))
+    .collect();
+  Box::into_raw(Box::new(jsm_cache { cache, strings }))
+}
+
+/**
+ * Fills in `out` with the mapping of a generated position. Returns true if the
+ * position maps to an original source, and false otherwise or if an argument is null.
+ *
+ * # Safety
+ *
+ * `cache` must be null or returned by `jsm_consume` and not freed, and `out` must be
+ * null or point to a `jsm_mapping`.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn jsm_lookup(cache: *const jsm_cache, line: u32, column: u32, out: *mut jsm_mapping) -> bool {
+  if cache.is_null() || out.is_null() {
+    return false;
+  }
+  let cache = &*cache;
+  let mapping = cache.cache.mapping_for_generated_position(line, column);
+  let string = |value: &str| cache.strings.get(value).map_or(EMPTY.as_ptr() as *const c_char, |string| string.as_ptr());
+  *out = jsm_mapping {
+    generated_line: mapping.generated.line,
+    generated_column: mapping.generated.column,
+    original_line: mapping.original.line,
+    original_column: mapping.original.column,
+    source: string(&mapping.source),
+    name: string(&mapping.name)
+  };
+  !mapping.source.is_empty()
+}
+
+/**
+ * Returns the message of a failed call, owned by `err`, or null if there is none.
+ *
+ * # Safety
+ *
+ * `err` must be null or point to a `jsm_error`.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn jsm_error_message(err: *const jsm_error) -> *const c_char {
+  if err.is_null() { ptr::null() } else { (*err).message }
+}
+
+/**
+ * Frees a cache and the strings of the mappings looked up in it. Does nothing for null.
+ *
+ * # Safety
+ *
+ * `cache` must be null or returned by `jsm_consume` and not already freed.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn jsm_cache_free(cache: *mut jsm_cache) {
+  if !cache.is_null() {
+    drop(Box::from_raw(cache));
+  }
+}
+
+/**
+ * Frees a string allocated by this library, such as an error message. Does nothing for
+ * null.
+ *
+ * # Safety
+ *
+ * `string` must be null or allocated by this library and not already freed.
+ */
+#[no_mangle]
+pub unsafe extern "C" fn jsm_string_free(string: *mut c_char) {
+  if !string.is_null() {
+    drop(CString::from_raw(string));
+  }
+}
+
+#[cfg(test)]
+use std::ffi::CStr;
+#[cfg(test)]
+use error::ErrorCode;
+
+#[test]
+fn it_looks_up_positions_through_the_c_api() {
+  let json = r#"{ "version": 3, "sources": ["a.js"], "names": ["x"], "mappings": "AAAAA,EAAE" }"#;
+  unsafe {
+    let mut err = jsm_error { status: jsm_status::JSM_OK, message: ptr::null_mut(), code: 0 };
+    let cache = jsm_consume(json.as_ptr() as *const c_char, json.len(), &mut err);
+    assert!(!cache.is_null());
+    assert!(jsm_error_message(&err).is_null());
+
+    let mut mapping: jsm_mapping = std::mem::zeroed();
+    assert!(jsm_lookup(cache, 1, 0, &mut mapping));
+    assert_eq!((mapping.original_line, mapping.original_column), (1, 0));
+    assert_eq!(CStr::from_ptr(mapping.source).to_str(), Ok("a.js"));
+    assert_eq!(CStr::from_ptr(mapping.name).to_str(), Ok("x"));
+    assert!(jsm_lookup(cache, 1, 2, &mut mapping));
+    assert_eq!(CStr::from_ptr(mapping.name).to_str(), Ok(""));
+    assert!(!jsm_lookup(ptr::null(), 1, 0, &mut mapping));
+    assert!(!jsm_lookup(cache, 1, 0, ptr::null_mut()));
+    jsm_cache_free(cache);
+  }
+}
+
+#[test]
+fn it_reports_errors_through_the_c_api() {
+  unsafe {
+    let mut err = jsm_error { status: jsm_status::JSM_OK, message: ptr::null_mut(), code: 0 };
+    assert!(jsm_consume(ptr::null(), 0, &mut err).is_null());
+    assert_eq!((err.status, err.code), (jsm_status::JSM_NULL_ARGUMENT, 0));
+    jsm_string_free(err.message);
+
+    let invalid = b"{\xff}";
+    let mut err = jsm_error { status: jsm_status::JSM_OK, message: ptr::null_mut(), code: 0 };
+    assert!(jsm_consume(invalid.as_ptr() as *const c_char, invalid.len(), &mut err).is_null());
+    assert_eq!(err.status, jsm_status::JSM_INVALID_UTF8);
+    jsm_string_free(err.message);
+
+    let json = r#"{ "version": 2, "sources": [], "names": [], "mappings": "" }"#;
+    let mut err = jsm_error { status: jsm_status::JSM_OK, message: ptr::null_mut(), code: 0 };
+    assert!(jsm_consume(json.as_ptr() as *const c_char, json.len(), &mut err).is_null());
+    assert_eq!((err.status, err.code), (jsm_status::JSM_INVALID_SOURCE_MAP, ErrorCode::UnsupportedVersion as u32));
+    assert!(!CStr::from_ptr(jsm_error_message(&err)).to_bytes().is_empty());
+    jsm_string_free(err.message);
+
+    assert!(jsm_consume(json.as_ptr() as *const c_char, json.len(), ptr::null_mut()).is_null());
+    jsm_cache_free(ptr::null_mut());
+    jsm_string_free(ptr::null_mut());
+  }
+}
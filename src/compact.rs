@@ -0,0 +1,255 @@
+use core::fmt;
+use core::iter;
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+
+use consume::{Mapping, MappingTables, SegmentState, parse_regular_source_map, sort_mappings};
+use error::{Error, ErrorCode};
+use lookup::MappingLookup;
+use mappings::MappingsDecoder;
+use scan;
+
+// The number of segments between checkpoints, unless another interval is given
+const CHECKPOINT_INTERVAL: usize = 64;
+
+// Every segment is validated when the cache is created, so decoding it again cannot fail
+const VALIDATED: &str = "the mappings were validated when the cache was created";
+
+/**
+ * A cache for looking up a few positions in enormous source maps, which keeps the
+ * mappings encoded rather than holding a `Mapping` for each segment.
+ *
+ * The `mappings` field is kept as it is, along with the offset of each generated line
+ * and, every few segments, a checkpoint of the state the following segments are relative
+ * to. A lookup decodes the generated line from the closest checkpoint before it, so it
+ * costs about as much as decoding that line, where a `Cache` does a binary search.
+ * Lookups give the same answers as those of a `Cache`, and both implement
+ * `MappingLookup` for code that works with either.
+ *
+ * Index maps are not supported, since their sections cannot be looked up in one encoded
+ * `mappings` field.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::{CompactCache, consume};
+ *
+ * let json = r#"{ "version": 3, "sources": ["a.js"], "names": ["run"], "mappings": "AAAA,EACEA;AACA" }"#;
+ * let compact = CompactCache::from_json(json).unwrap();
+ * let cache = consume(json).unwrap();
+ * assert!(compact.mapping_for_generated_position(1, 3) == cache.mapping_for_generated_position(1, 3));
+ * assert!(compact.mappings_for_generated_line(1).len() == 2);
+ * ```
+ */
+pub struct CompactCache {
+  mappings: String,
+  // The offset in mappings of each generated line, starting from line 1
+  line_starts: Vec<usize>,
+  checkpoints: Vec<Checkpoint>,
+  tables: MappingTables,
+  mapping_count: usize,
+  // The last mapping in the canonical order, which positions past all mappings resolve to
+  last: Mapping,
+  /** The path prefix of mapping source paths */
+  pub source_root: String,
+  /** The generated file the source map belongs to, or an empty string if not specified */
+  pub file: String
+}
+
+// A position where decoding can resume, with the state the segments after it are
+// relative to
+#[derive(Clone, Copy, Debug)]
+struct Checkpoint {
+  offset: usize,
+  generated_line: u32,
+  state: SegmentState
+}
+
+impl CompactCache {
+  /**
+   * Parses a source map into a compact cache, validating it like `consume`.
+   */
+  pub fn from_json(source_map_json: &str) -> Result<CompactCache, Error> {
+    CompactCache::with_checkpoint_interval(source_map_json, CHECKPOINT_INTERVAL)
+  }
+
+  /**
+   * Like `from_json`, but with a checkpoint every `segments` segments rather than every
+   * 64. Fewer checkpoints take less memory, and more checkpoints leave lookups fewer
+   * segments of earlier lines to decode.
+   */
+  pub fn with_checkpoint_interval(source_map_json: &str, segments: usize) -> Result<CompactCache, Error> {
+    let (header, mut mappings) = parse_regular_source_map(source_map_json)?;
+    let tables = MappingTables::new(&header.sources, &header.names);
+    let interval = segments.max(1);
+
+    // Every segment is decoded once, to validate it and to find the checkpoints
+    let mut checkpoints = Vec::new();
+    let mut state = SegmentState::default();
+    let mut mapping_count = 0;
+    let mut last: Option<Mapping> = None;
+    let mut decoder = MappingsDecoder::new(&mappings);
+    loop {
+      if mapping_count % interval == 0 {
+        checkpoints.push(Checkpoint { offset: decoder.offset(), generated_line: decoder.generated_line(), state });
+      }
+      let segment = match decoder.next() {
+        Some(segment) => segment?,
+        None => break
+      };
+      let mapping = state.apply(&segment, &tables)?;
+      mapping_count += 1;
+      if last.as_ref().is_none_or(|last| mapping > *last) {
+        last = Some(mapping);
+      }
+    }
+    let last = last.ok_or_else(|| Error::invalid(ErrorCode::NoMappings, "Source Map contains no mappings"))?;
+
+    let line_starts = iter::once(0)
+      .chain(scan::line_ends(mappings.as_bytes()))
+      .collect();
+    mappings.shrink_to_fit();
+    checkpoints.shrink_to_fit();
+
+    Ok(CompactCache {
+      mappings,
+      line_starts,
+      checkpoints,
+      tables,
+      mapping_count,
+      last,
+      source_root: header.source_root.unwrap_or_default(),
+      file: header.file.unwrap_or_default()
+    })
+  }
+
+  /**
+   * Returns the mapping for a generated position, like
+   * `Cache::mapping_for_generated_position`. Only the mappings of the line are decoded,
+   * unless the position is past the last of them.
+   */
+  pub fn mapping_for_generated_position(&self, line: u32, column: u32) -> Mapping {
+    let mut mappings = self.decode_line(line);
+    let index = match mappings.binary_search_by(|mapping| (mapping.generated.line, mapping.generated.column).cmp(&(line, column))) {
+      Ok(index) | Err(index) => index
+    };
+    if index < mappings.len() {
+      return mappings.swap_remove(index);
+    }
+    // Past the last mapping of the line, the position resolves to the first mapping of a
+    // later line, as it does in a cache
+    for next_line in line.saturating_add(1)..=self.line_starts.len() as u32 {
+      if let Some(first) = self.decode_line(next_line).into_iter().next() {
+        return first;
+      }
+    }
+    self.last.clone()
+  }
+
+  /**
+   * Returns the mappings of a generated line, decoded and in the canonical order, like
+   * `Cache::mappings_for_generated_line`.
+   */
+  pub fn mappings_for_generated_line(&self, line: u32) -> Vec<Mapping> {
+    self.decode_line(line)
+  }
+
+  /**
+   * Returns the number of mappings.
+   */
+  pub fn len(&self) -> usize {
+    self.mapping_count
+  }
+
+  /**
+   * Returns true if there are no mappings, which is never the case, since a source map
+   * without mappings cannot be parsed.
+   */
+  pub fn is_empty(&self) -> bool {
+    self.mapping_count == 0
+  }
+
+  fn decode_line(&self, line: u32) -> Vec<Mapping> {
+    if line == 0 || line as usize > self.line_starts.len() {
+      return Vec::new();
+    }
+    let start = self.line_starts[line as usize - 1];
+    let end = self.line_starts.get(line as usize).map_or(self.mappings.len(), |&next_start| next_start - 1);
+    if self.mappings.as_bytes()[start..end].iter().all(|&byte| byte == b',') {
+      return Vec::new();
+    }
+
+    // The first checkpoint is at offset 0, so there is always one before the line
+    let checkpoint = self.checkpoints[self.checkpoints.partition_point(|checkpoint| checkpoint.offset <= start) - 1];
+    let mut state = checkpoint.state;
+    let mut mappings = Vec::new();
+    for segment in MappingsDecoder::resume(&self.mappings, checkpoint.offset, checkpoint.generated_line) {
+      let segment = segment.expect(VALIDATED);
+      if segment.generated_line > line {
+        break;
+      }
+      let mapping = state.apply(&segment, &self.tables).expect(VALIDATED);
+      if segment.generated_line == line {
+        mappings.push(mapping);
+      }
+    }
+    sort_mappings(&mut mappings);
+    mappings
+  }
+}
+
+impl fmt::Debug for CompactCache {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    formatter.debug_struct("CompactCache")
+      .field("mapping_count", &self.mapping_count)
+      .field("line_count", &self.line_starts.len())
+      .field("checkpoint_count", &self.checkpoints.len())
+      .field("source_root", &self.source_root)
+      .field("file", &self.file)
+      .field("last_mapping", &self.last)
+      .finish()
+  }
+}
+
+impl MappingLookup for CompactCache {
+  fn mapping_for_generated_position(&self, line: u32, column: u32) -> Option<Mapping> {
+    Some(CompactCache::mapping_for_generated_position(self, line, column))
+  }
+}
+
+#[cfg(test)]
+use consume::consume;
+
+#[test]
+fn it_answers_like_a_cache() {
+  // Segments out of order, empty lines and segments, and names carried across lines
+  let json = r#"{ "version": 3, "sources": ["a.js", "b.js"], "names": ["x", "y"], "mappings": ";EAAEA,AAAA,,CCCCC;;;AACAD,IAAI,EAAE;AAAA" }"#;
+  let cache = consume(json).unwrap();
+  for interval in 1..6 {
+    let compact = CompactCache::with_checkpoint_interval(json, interval).unwrap();
+    assert_eq!(compact.len(), cache.len());
+    for line in 0..9 {
+      assert_eq!(compact.mappings_for_generated_line(line), cache.mappings_for_generated_line(line), "line {}", line);
+      for column in 0..12 {
+        assert_eq!(compact.mapping_for_generated_position(line, column), cache.mapping_for_generated_position(line, column), "{}:{}", line, column);
+      }
+    }
+    assert_eq!(compact.mapping_for_generated_position(u32::MAX, u32::MAX), cache.mapping_for_generated_position(u32::MAX, u32::MAX));
+  }
+}
+
+#[test]
+fn it_rejects_what_consume_rejects() {
+  let invalid = [
+    r#"{ "version": 3, "sources": [], "names": [], "mappings": "AAAA" }"#,
+    r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAAC" }"#,
+    r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": ";;" }"#,
+    r#"{ "version": 2, "sources": ["a.js"], "names": [], "mappings": "AAAA" }"#,
+    r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA,A!" }"#
+  ];
+  for json in &invalid {
+    assert_eq!(CompactCache::from_json(json).unwrap_err().code(), consume(json).unwrap_err().code(), "{}", json);
+  }
+  let index_map = r#"{ "version": 3, "sections": [] }"#;
+  assert_eq!(CompactCache::from_json(index_map).unwrap_err().code(), ErrorCode::Other);
+}
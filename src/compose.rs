@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use consume::{Cache, Interner, Mapping, join_source_root};
+
+/**
+ * The result of composing two caches with `compose`.
+ */
+#[derive(Debug)]
+pub struct Composition {
+  /** The composed cache */
+  pub cache: Cache,
+  /** The number of mappings re-resolved through the inner cache */
+  pub composed: usize,
+  /** The number of mappings into other sources, which were kept as they were */
+  pub passed_through: usize,
+  /** The number of mappings into the intermediate source that the inner cache does not map, which lost their source */
+  pub unresolved: usize
+}
+
+/**
+ * Composes two source maps, as needed when a file goes through several build steps,
+ * such as a TypeScript compiler followed by a minifier.
+ *
+ * `outer` maps the final generated file to its sources, one of which is the
+ * intermediate file `source`. `inner` maps that intermediate file to its own sources.
+ * Every mapping of `outer` into `source` is re-resolved through `inner`, using the
+ * closest mapping of `inner` at or before its original position on the same line.
+ * Mappings into other sources are kept as they are.
+ *
+ * Names are taken from `inner`, or from `outer` when `inner` has none. Embedded source
+ * contents and ignored sources are carried over from both caches. If the caches have
+ * different source roots, the roots are joined into the source paths.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::{compose, consume};
+ *
+ * // bundle.min.js -> bundle.js
+ * let outer = consume(r#"{ "version": 3, "sources": ["bundle.js"], "names": [], "mappings": "AAAA,KAEI" }"#).unwrap();
+ * // bundle.js -> app.ts
+ * let inner = consume(r#"{ "version": 3, "sources": ["app.ts"], "names": [], "mappings": "AAAA;;IAUE" }"#).unwrap();
+ *
+ * let composition = compose(&outer, "bundle.js", &inner);
+ * assert!(composition.composed == 2);
+ * let mapping = composition.cache.mapping_for_generated_position(1, 5);
+ * assert!(&*mapping.source == "app.ts" && mapping.original.line == 11);
+ * ```
+ */
+pub fn compose(outer: &Cache, source: &str, inner: &Cache) -> Composition {
+  let same_root = outer.source_root == inner.source_root;
+  let rooted = |root: &str, path: &str| if same_root { path.to_owned() } else { join_source_root(root, path) };
+  let is_intermediate = |path: &str| path == source || join_source_root(&outer.source_root, path) == source;
+
+  let mut composed = 0;
+  let mut passed_through = 0;
+  let mut unresolved = 0;
+  let mut sources_content: HashMap<String, String> = HashMap::new();
+  let mut ignored: Vec<String> = Vec::new();
+  let mut interner = Interner::default();
+  let empty = interner.intern("");
+  let mut rooted_source = |root: &str, path: &Arc<str>| if same_root { path.clone() } else { interner.intern(&join_source_root(root, path)) };
+
+  let mappings: Vec<Mapping> = outer.mappings().iter().map(|mapping| {
+    if mapping.source.is_empty() {
+      return mapping.clone();
+    }
+    if !is_intermediate(&mapping.source) {
+      passed_through += 1;
+      return Mapping { source: rooted_source(&outer.source_root, &mapping.source), ..mapping.clone() };
+    }
+
+    match original_mapping(inner, mapping.original.line, mapping.original.column) {
+      Some(original) => {
+        composed += 1;
+        Mapping {
+          generated: mapping.generated.clone(),
+          original: original.original.clone(),
+          source: rooted_source(&inner.source_root, &original.source),
+          name: if original.name.is_empty() { mapping.name.clone() } else { original.name.clone() }
+        }
+      },
+      None => {
+        unresolved += 1;
+        Mapping { source: empty.clone(), name: empty.clone(), ..mapping.clone() }
+      }
+    }
+  }).collect();
+
+  // The intermediate source is no longer referenced
+  for (path, content) in outer.sources_content() {
+    if !is_intermediate(path) {
+      sources_content.insert(rooted(&outer.source_root, path), content.clone());
+    }
+  }
+  for (path, content) in inner.sources_content() {
+    sources_content.insert(rooted(&inner.source_root, path), content.clone());
+  }
+  for cache in &[outer, inner] {
+    ignored.extend(cache.ignored_sources().into_iter().map(|path| rooted(&cache.source_root, path)));
+  }
+
+  let source_root = if same_root { outer.source_root.clone() } else { "".into() };
+  let mut cache = Cache::from_mappings(mappings, source_root).expect("the outer cache has mappings");
+  cache.file = outer.file.clone();
+  cache.set_sources_content(sources_content);
+  for path in ignored {
+    cache.set_ignored(&path, true);
+  }
+
+  Composition { cache, composed, passed_through, unresolved }
+}
+
+// Finds the closest mapping at or before a position on the same generated line
+fn original_mapping(cache: &Cache, line: u32, column: u32) -> Option<&Mapping> {
+  let mappings = cache.mappings_for_generated_line(line);
+  let end = mappings.partition_point(|mapping| mapping.generated.column <= column);
+  mappings[..end].last().filter(|mapping| !mapping.source.is_empty())
+}
+
+#[cfg(test)]
+use consume::{consume, consume_with_sources_content};
+
+#[test]
+fn it_composes_mappings_into_the_intermediate_source() {
+  // Line 1 of the minified file maps into bundle.js and into vendor.js
+  let outer = consume(r#"{ "version": 3, "sources": ["bundle.js", "vendor.js"], "names": ["x"], "mappings": "AAAA,EAAEA,ECAA,EDEE,EADG" }"#).unwrap();
+  let inner = consume(r#"{ "version": 3, "sources": ["src/app.ts"], "names": ["handler"], "mappings": "AAAA,EAAE;;AAEAA" }"#).unwrap();
+
+  let composition = compose(&outer, "bundle.js", &inner);
+  assert_eq!((composition.composed, composition.passed_through, composition.unresolved), (3, 1, 1));
+
+  let original = |column| {
+    let mapping = composition.cache.mapping_for_generated_position(1, column);
+    (mapping.source, mapping.original.line, mapping.original.column, mapping.name)
+  };
+  assert_eq!(original(0), ("src/app.ts".into(), 1, 0, "".into()));
+  // bundle.js:1:2 resolves through the inner mapping at column 2 and keeps the outer name
+  assert_eq!(original(2), ("src/app.ts".into(), 1, 2, "x".into()));
+  assert_eq!(original(4), ("vendor.js".into(), 1, 2, "".into()));
+  // bundle.js:3:4 resolves through the inner mapping at column 0 and takes its name
+  assert_eq!(original(6), ("src/app.ts".into(), 3, 2, "handler".into()));
+  // bundle.js:2:7 has no inner mapping on its line
+  assert_eq!(&*original(8).0, "");
+  assert_eq!(composition.cache.sources(), ["src/app.ts", "vendor.js"]);
+}
+
+#[test]
+fn it_carries_over_roots_contents_and_ignored_sources() {
+  let mut outer = consume_with_sources_content(r#"{ "version": 3, "file": "out.js", "sourceRoot": "/dist", "sources": ["bundle.js", "lib.js"], "sourcesContent": ["bundle();", "lib();"], "names": [], "mappings": "AAAA,CCAA" }"#).unwrap();
+  outer.set_ignored("lib.js", true);
+  let inner = consume_with_sources_content(r#"{ "version": 3, "sources": ["app.ts"], "sourcesContent": ["app();"], "names": [], "mappings": "AAAA" }"#).unwrap();
+
+  let cache = compose(&outer, "/dist/bundle.js", &inner).cache;
+  assert_eq!(cache.file, "out.js");
+  assert_eq!(cache.source_root, "");
+  assert_eq!(cache.sources(), ["app.ts", "/dist/lib.js"]);
+  assert_eq!(cache.source_content("app.ts"), Some("app();"));
+  assert_eq!(cache.source_content("/dist/lib.js"), Some("lib();"));
+  assert_eq!(cache.source_content("/dist/bundle.js"), None);
+  assert_eq!(cache.ignored_sources(), ["/dist/lib.js"]);
+}
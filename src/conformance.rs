@@ -0,0 +1,125 @@
+/*!
+ * Runs conformance test cases in the format of the tc39/source-map-tests suite.
+ *
+ * The cases in tests/source-map-tests are run by default. Set `SOURCE_MAP_TESTS_DIR` to
+ * run the cases of another checkout of the suite instead.
+ */
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+extern crate serde_json;
+
+use consume::consume;
+
+// Cases that are expected to fail, with the reason. A run that fails prints the entries
+// to add here for the cases that failed
+const UNSUPPORTED: &[(&str, &str)] = &[];
+
+#[allow(non_snake_case)]
+#[derive(Deserialize, Debug)]
+struct TestSuite {
+  tests: Vec<TestCase>
+}
+
+#[allow(non_snake_case)]
+#[derive(Deserialize, Debug)]
+struct TestCase {
+  name: String,
+  sourceMapFile: String,
+  sourceMapIsValid: bool,
+  #[serde(default)]
+  testActions: Vec<serde_json::Value>
+}
+
+fn suite_dir() -> PathBuf {
+  match env::var_os("SOURCE_MAP_TESTS_DIR") {
+    Some(dir) => PathBuf::from(dir),
+    None => Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("source-map-tests")
+  }
+}
+
+fn run_case(dir: &Path, case: &TestCase) -> Result<(), String> {
+  let path = dir.join("resources").join(&case.sourceMapFile);
+  let json = fs::read_to_string(&path).map_err(|err| format!("could not read {}: {}", path.display(), err))?;
+
+  let cache = match (consume(&json), case.sourceMapIsValid) {
+    (Ok(cache), true) => cache,
+    (Err(_), false) => return Ok(()),
+    (Ok(_), false) => return Err("invalid source map was accepted".into()),
+    (Err(err), true) => return Err(format!("valid source map was rejected: {}", err))
+  };
+
+  for action in &case.testActions {
+    match action["actionType"].as_str() {
+      Some("checkMapping") => {
+        let field = |name: &str| action[name].as_u64().map(|value| value as u32).ok_or(format!("missing {}", name));
+        let (line, column) = (field("generatedLine")? + 1, field("generatedColumn")?);
+        let mapping = cache.mapping_for_generated_position(line, column);
+        if (mapping.generated.line, mapping.generated.column) != (line, column) {
+          return Err(format!("no mapping at {}:{}", line, column));
+        }
+
+        let source = if mapping.source.is_empty() { None } else { Some(cache.resolved_source(&mapping.source)) };
+        let expected_source = action["originalSource"].as_str().map(|source| source.to_owned());
+        if source != expected_source {
+          return Err(format!("expected source {:?} at {}:{}, found {:?}", expected_source, line, column, source));
+        }
+        if source.is_some() {
+          let original = (mapping.original.line - 1, mapping.original.column);
+          let expected = (field("originalLine")?, field("originalColumn")?);
+          if original != expected {
+            return Err(format!("expected original position {:?} at {}:{}, found {:?}", expected, line, column, original));
+          }
+        }
+
+        let name = if mapping.name.is_empty() { None } else { Some(&*mapping.name) };
+        if name != action["mappedName"].as_str() {
+          return Err(format!("expected name {:?} at {}:{}, found {:?}", action["mappedName"], line, column, name));
+        }
+      },
+      Some("checkIgnoreList") => {
+        let expected: Vec<&str> = action["present"].as_array()
+          .map(|present| present.iter().filter_map(|source| source.as_str()).collect())
+          .unwrap_or_default();
+        if cache.ignored_sources() != expected {
+          return Err(format!("expected ignore list {:?}, found {:?}", expected, cache.ignored_sources()));
+        }
+      },
+      other => return Err(format!("unsupported action type {:?}", other))
+    }
+  }
+  Ok(())
+}
+
+#[test]
+fn it_passes_the_conformance_suite() {
+  let dir = suite_dir();
+  let descriptor = fs::read_to_string(dir.join("source-map-spec-tests.json")).unwrap();
+  let suite: TestSuite = serde_json::from_str(&descriptor).unwrap();
+
+  let mut failures: Vec<String> = Vec::new();
+  let mut entries: Vec<String> = Vec::new();
+  for case in &suite.tests {
+    let unsupported = UNSUPPORTED.iter().find(|&&(name, _)| name == case.name);
+    match (run_case(&dir, case), unsupported) {
+      (Ok(()), None) => println!("pass  {}", case.name),
+      (Ok(()), Some(_)) => {
+        println!("pass  {} (listed as unsupported)", case.name);
+        failures.push(format!("{}: passes, remove it from UNSUPPORTED", case.name));
+      },
+      (Err(err), None) => {
+        println!("FAIL  {}: {}", case.name, err);
+        entries.push(format!("  ({:?}, {:?}),", case.name, err));
+        failures.push(format!("{}: {}", case.name, err));
+      },
+      (Err(_), Some(&(_, reason))) => println!("skip  {}: {}", case.name, reason)
+    }
+  }
+
+  if !entries.is_empty() {
+    println!("\nUNSUPPORTED entries for the failed cases:\n{}", entries.join("\n"));
+  }
+  assert!(failures.is_empty(), "{} conformance cases failed:\n{}", failures.len(), failures.join("\n"));
+}
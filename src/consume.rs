@@ -1,28 +1,118 @@
-use std::cmp::Ordering;
+use core::cmp::Ordering;
+use core::convert::TryFrom;
+use core::fmt;
+use core::mem;
+use core::ops::ControlFlow;
+use core::slice;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "std")]
+use std::sync::Arc;
+// HashMap needs std for its random state, and the tables are only ever looked up by key
+#[cfg(not(feature = "std"))]
+use alloc::collections::{BTreeMap as HashMap, BTreeSet as HashSet};
+#[cfg(not(feature = "std"))]
+use alloc::{borrow::ToOwned, string::String, sync::Arc, vec::Vec};
+#[cfg(all(test, not(feature = "std")))]
+use alloc::string::ToString;
 
 extern crate serde;
-extern crate serde_json;
+#[cfg(feature = "parallel")]
+extern crate rayon;
+#[cfg(feature = "parallel")]
+use self::rayon::slice::ParallelSliceMut;
 
-use base64_vlq;
+use error::{Error, ErrorCode};
+use fetch::Fetch;
+use line_index::LineIndex;
+use mappings::{DecodedSegment, MappingsDecoder};
+use scan;
+#[cfg(all(feature = "serde-json", not(feature = "mini-json")))]
+use json as front_end;
+#[cfg(any(feature = "mini-json", not(feature = "serde-json")))]
+use mini_json as front_end;
+use parser::Parser;
+use resolve::SourceResolver;
 
-static SOURCE_MAP_VERSION: u32 = 3;
+const SOURCE_MAP_VERSION: u32 = 3;
 
-#[allow(non_snake_case)]
-#[derive(Deserialize, Debug)]
-struct SourceMap {
-  version: u32,
-  sources: Vec<String>,
-  names: Vec<String>,
-  sourceRoot: Option<String>,
-  mappings: String,
-  file: Option<String>
+/**
+ * The fields of a regular source map other than its mappings, parsed by `parse_header`.
+ */
+#[derive(Clone, Eq, PartialEq, Deserialize, Debug)]
+pub struct RawSourceMap {
+  /** The revision of the source map format, which `consume` requires to be 3 */
+  pub version: u32,
+  /** The generated file the source map belongs to */
+  pub file: Option<String>,
+  /** The path prefix of the sources */
+  #[serde(rename = "sourceRoot")]
+  pub source_root: Option<String>,
+  /** The `sources` table */
+  pub sources: Vec<String>,
+  /** The `names` table */
+  pub names: Vec<String>,
+  /** The indices of the ignored sources, from `ignoreList` or `x_google_ignoreList` */
+  #[serde(rename = "ignoreList", alias = "x_google_ignoreList", default)]
+  pub ignore_list: Vec<u32>,
+  /** Whether there is a `sourcesContent` field, which is skipped rather than parsed */
+  #[serde(rename = "sourcesContent", default, deserialize_with = "is_present")]
+  pub has_sources_content: bool
+}
 
-  // We skip this. Keeping megabytes of data that we do not care about
-  // in memory seems reckless to caches.
-  //sourcesContent: Option<vec<String>>,
+// Skips a field that may be null, returning whether it has a value
+pub(crate) fn is_present<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+  <Option<serde::de::IgnoredAny> as serde::Deserialize>::deserialize(deserializer).map(|value| value.is_some())
 }
 
-#[derive(Clone, Eq, PartialEq, Debug)]
+// Hands out one shared allocation per distinct string, so that the many mappings
+// referring to a source or name do not each hold a copy of it
+#[derive(Default, Debug)]
+pub(crate) struct Interner {
+  strings: HashSet<Arc<str>>
+}
+
+impl Interner {
+  pub(crate) fn intern(&mut self, string: &str) -> Arc<str> {
+    if let Some(interned) = self.strings.get(string) {
+      return interned.clone();
+    }
+    let interned: Arc<str> = Arc::from(string);
+    self.strings.insert(interned.clone());
+    interned
+  }
+}
+
+// A regular source map or an index map, as the JSON front-end parses it
+#[derive(Debug)]
+pub(crate) enum Document {
+  SourceMap(RawSourceMap, String),
+  IndexMap(IndexMap)
+}
+
+// An index map, which concatenates the source maps of its sections
+#[derive(Debug)]
+pub(crate) struct IndexMap {
+  pub(crate) version: u32,
+  pub(crate) file: Option<String>,
+  pub(crate) sections: Vec<Section>
+}
+
+#[derive(Debug)]
+pub(crate) struct Section {
+  // The 0-based line and column of the section in the generated file
+  pub(crate) offset: (u32, u32),
+  // The JSON of the embedded map
+  pub(crate) map: Option<String>,
+  pub(crate) url: Option<String>
+}
+
+/**
+ * A position in a code file. Positions are ordered by line, then by column.
+ */
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CodePosition {
   /** Line number in a code file, starting from 1 */
   pub line: u32,
@@ -30,23 +120,208 @@ pub struct CodePosition {
   pub column: u32
 }
 
-#[derive(Clone, Eq, PartialEq, Debug)]
+/**
+ * Formats a position as `line:column`, the format `FromStr` parses.
+ */
+impl fmt::Display for CodePosition {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    write!(formatter, "{}:{}", self.line, self.column)
+  }
+}
+
+/**
+ * Parses a position like `1523:88`, or `1523,88`. The line must be at least 1.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::CodePosition;
+ *
+ * let position: CodePosition = "1523:88".parse().unwrap();
+ * assert!(position == CodePosition { line: 1523, column: 88 });
+ * assert!(position.to_string() == "1523:88");
+ * assert!("0:88".parse::<CodePosition>().is_err());
+ * ```
+ */
+impl FromStr for CodePosition {
+  type Err = Error;
+
+  fn from_str(position: &str) -> Result<CodePosition, Error> {
+    let (line, column) = position.split_once(':').or_else(|| position.split_once(','))
+      .ok_or_else(|| Error::invalid(ErrorCode::InvalidPosition, format!("invalid position {:?}, expected <line>:<column>", position)))?;
+    let line: u32 = line.parse().map_err(|_| Error::invalid(ErrorCode::InvalidPosition, format!("invalid position {:?}, the line is not a number", position)))?;
+    let column: u32 = column.parse().map_err(|_| Error::invalid(ErrorCode::InvalidPosition, format!("invalid position {:?}, the column is not a number", position)))?;
+    if line < 1 {
+      return Err(Error::invalid(ErrorCode::InvalidPosition, format!("invalid position {:?}, lines start from 1", position)));
+    }
+    Ok(CodePosition { line, column })
+  }
+}
+
+/**
+ * A mapping from a generated position to an original one. Mappings are ordered in the
+ * canonical order used by caches, see `compare_mappings`, which follows the order of
+ * the fields.
+ *
+ * The source and name are shared strings. The mappings of a parsed cache that refer to
+ * the same source or name share one allocation, and cloning a mapping only bumps two
+ * reference counts. Compare them to string slices through a dereference:
+ *
+ * ```
+ * use js_source_mapper::consume;
+ *
+ * let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": ["run"], "mappings": "AAAAA" }"#).unwrap();
+ * let mapping = cache.mapping_for_generated_position(1, 0);
+ * assert!(&*mapping.source == "a.js" && &*mapping.name == "run");
+ * ```
+ */
+#[derive(Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Mapping {
   /** The position in the generated file */
   pub generated: CodePosition,
   /** The position in the corresponding original source file */
   pub original: CodePosition,
   /** The original source file */
-  pub source: String,
+  pub source: Arc<str>,
   /** The original source name of the function/class, if applicable */
-  pub name: String
+  pub name: Arc<str>
 }
 
-#[derive(Debug)]
+/**
+ * A parsed source map, created by `consume`.
+ *
+ * A `Cache` is `Send` and `Sync`, so one cache can be shared between threads behind an
+ * `Arc` and queried concurrently. Lookups only read it, and any caching added inside it
+ * must keep that guarantee by using `OnceLock` or `RwLock` rather than `Cell` or
+ * `RefCell`. Such caching must also be left out of the `PartialEq` implementation, which
+ * compares the mappings and the source root, so that caches parsed from maps that only
+ * differ in the order of their tables or segments are equal.
+ *
+ * The `Debug` output summarizes the cache with counts and its first and last mappings
+ * rather than listing every mapping.
+ */
+#[derive(Clone)]
 pub struct Cache {
+  // memory_usage accounts for every field, and has to change along with them
   generated_mappings: Vec<Mapping>,
+  sources: Vec<String>,
+  names: Vec<String>,
+  sources_content: HashMap<String, String>,
+  ignored_sources: HashSet<String>,
+  pub(crate) line_index: LineIndex,
+  declared: Option<DeclaredTables>,
+  pub(crate) resolver: Option<Arc<dyn SourceResolver>>,
   /** The path prefix of mapping source paths */
-  pub source_root: String
+  pub source_root: String,
+  /** The generated file the source map belongs to, or an empty string if not specified */
+  pub file: String
+}
+
+// The positions the entries of the tables were declared at, once drop_unreferenced has
+// removed some of them
+#[derive(Clone, Debug)]
+struct DeclaredTables {
+  sources: Vec<u32>,
+  source_count: usize,
+  names: Vec<u32>,
+  name_count: usize
+}
+
+impl fmt::Debug for Cache {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    formatter.debug_struct("Cache")
+      .field("mapping_count", &self.generated_mappings.len())
+      .field("source_count", &self.sources.len())
+      .field("name_count", &self.names.len())
+      .field("source_root", &self.source_root)
+      .field("file", &self.file)
+      .field("first_mapping", &self.generated_mappings.first())
+      .field("last_mapping", &self.generated_mappings.last())
+      .field("resolver", &self.resolver)
+      .finish()
+  }
+}
+
+impl PartialEq for Cache {
+  fn eq(&self, other: &Cache) -> bool {
+    self.generated_mappings == other.generated_mappings && self.source_root == other.source_root
+  }
+}
+
+/**
+ * A summary of a cache, without its mappings.
+ */
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CacheSummary {
+  /** The `sources` table of the source map */
+  pub sources: Vec<String>,
+  /** The `names` table of the source map */
+  pub names: Vec<String>,
+  /** The path prefix of mapping source paths */
+  pub source_root: String,
+  /** The number of mappings in the cache */
+  pub mapping_count: usize
+}
+
+/**
+ * The parts of a cache, returned by `Cache::into_parts`.
+ */
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CacheParts {
+  /** The mappings, in generated order */
+  pub mappings: Vec<Mapping>,
+  /** The `sources` table of the source map */
+  pub sources: Vec<String>,
+  /** The `names` table of the source map */
+  pub names: Vec<String>,
+  /** The path prefix of mapping source paths */
+  pub source_root: String,
+  /** The generated file the source map belongs to, or an empty string if not specified */
+  pub file: String
+}
+
+/**
+ * An estimate of the memory a cache holds, returned by `Cache::memory_usage`. Vectors
+ * are counted by their capacity and the other collections by their length, without the
+ * overhead of the allocator.
+ */
+#[derive(Clone, Copy, Eq, PartialEq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MemoryUsage {
+  /** The bytes of the vector of mappings */
+  pub mappings: usize,
+  /**
+   * The bytes of the sources and names of the mappings, counting each shared string
+   * once, and of the `sources` and `names` tables, the ignored sources, the source root
+   * and the file
+   */
+  pub strings: usize,
+  /** The bytes of the embedded contents of sources, kept by `consume_with_sources_content` */
+  pub sources_content: usize,
+  /**
+   * The bytes of the index of the mappings by generated line, and of the declared
+   * indices of the table entries kept by `Cache::drop_unreferenced`
+   */
+  pub index: usize,
+  /** The sum of the above and of the cache itself */
+  pub total: usize
+}
+
+/**
+ * The counts `consume_with_visitor` returns once it has visited the mappings.
+ */
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ConsumeSummary {
+  /** The number of mappings handed to the visitor */
+  pub mapping_count: usize,
+  /** The number of generated lines decoded, including lines without mappings */
+  pub line_count: u32,
+  /** Whether the visitor stopped the parse before the end of the mappings */
+  pub stopped_early: bool
 }
 
 /**
@@ -62,6 +337,13 @@ pub struct Cache {
  *   - sourcesContent: Optional. An array of contents of the original source files.
  *   - mappings: A string of base64 VLQs which contain the actual mappings.
  *   - file: Optional. The generated file this source map is associated with.
+ *   - ignoreList: Optional. Indices of sources that debuggers should hide, such as
+ *     third-party code.
+ *
+ * [Index maps][index-map], which consist of `sections` that each embed a source map at
+ * an offset of the generated file, are flattened into a single cache. Sections referring
+ * to their source map by `url` are only supported by `consume_with_fetcher` and
+ * `consume_from_generated`.
  *
  * Here is an example source map:
  *
@@ -77,174 +359,1234 @@ pub struct Cache {
  * ```
  *
  * [source-map-spec]: https://docs.google.com/document/d/1U1RGAehQwRypUTovF1KRlpiOFze0b-_2gc6fAH0KY0k/edit?pli=1#
+ * [index-map]: https://tc39.es/source-map/#index-map
+ */
+pub fn consume(source_map_json: &str) -> Result<Cache, Error> {
+  Parser::new().consume(source_map_json)
+}
+
+/**
+ * Like `consume`, but also retains the `sourcesContent` of the source map, so that the
+ * original source text can be looked up with `Cache::source_content` and is written back
+ * out by `Cache::to_json`.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::consume_with_sources_content;
+ *
+ * let cache = consume_with_sources_content(r#"{ "version": 3, "sources": ["a.js"], "sourcesContent": ["let a;"], "names": [], "mappings": "AAAA" }"#).unwrap();
+ * assert!(cache.source_content("a.js") == Some("let a;"));
+ * ```
+ */
+pub fn consume_with_sources_content(source_map_json: &str) -> Result<Cache, Error> {
+  let mut parser = Parser::new();
+  parser.sources_content = true;
+  parser.consume(source_map_json)
+}
+
+/**
+ * Parses a source map like `consume`, but hands each mapping to `visitor` as it is
+ * decoded instead of collecting them into a cache, for scanning maps too large to keep
+ * in memory. Mappings are visited in the order they are encoded, which is generated
+ * order for well-formed maps, and decoding stops as soon as `visitor` returns
+ * `ControlFlow::Break`.
+ *
+ * Index maps are flattened into a cache first, since their sections have to be
+ * checked for overlaps, so only regular source maps are streamed.
+ *
+ * # Examples
+ *
+ * ```
+ * use std::ops::ControlFlow;
+ * use js_source_mapper::consume_with_visitor;
+ *
+ * let json = r#"{ "version": 3, "sources": ["a.js", "b.js"], "names": [], "mappings": "AAAA,CCAA;ADCA" }"#;
+ * let mut in_b = 0;
+ * let summary = consume_with_visitor(json, |mapping| {
+ *   if &*mapping.source == "b.js" {
+ *     in_b += 1;
+ *   }
+ *   ControlFlow::Continue(())
+ * }).unwrap();
+ * assert!(in_b == 1 && summary.mapping_count == 3 && summary.line_count == 2);
+ *
+ * let summary = consume_with_visitor(json, |_| ControlFlow::Break(())).unwrap();
+ * assert!(summary.stopped_early && summary.mapping_count == 1);
+ * ```
  */
-pub fn consume(source_map_json: &str) -> Result<Cache, String> {
-  let source_map: SourceMap = match serde_json::from_str(source_map_json) {
-    Ok(x) => x,
-    Err(err) => return Err(format!("{}", err))
+pub fn consume_with_visitor<F: FnMut(&Mapping) -> ControlFlow<()>>(source_map_json: &str, mut visitor: F) -> Result<ConsumeSummary, Error> {
+  let (header, mappings) = match front_end::parse_document(source_map_json)? {
+    Document::SourceMap(header, mappings) => (header, mappings),
+    Document::IndexMap(index_map) => return visit_cache(&flatten_index_map(index_map, false, None, &mut Scratch::default())?, visitor)
   };
+  if header.version != SOURCE_MAP_VERSION {
+    return Err(Error::invalid(ErrorCode::UnsupportedVersion, "Only Source Map version 3 is implemented"))
+  }
+  decode_mappings(&mappings, &MappingTables::new(&header.sources, &header.names), |mapping| visitor(&mapping))
+}
 
-  parse_mappings(&source_map)
+fn visit_cache<F: FnMut(&Mapping) -> ControlFlow<()>>(cache: &Cache, mut visitor: F) -> Result<ConsumeSummary, Error> {
+  let mut summary = ConsumeSummary {
+    mapping_count: 0,
+    line_count: cache.generated_mappings.last().map_or(0, |mapping| mapping.generated.line),
+    stopped_early: false
+  };
+  for mapping in &cache.generated_mappings {
+    summary.mapping_count += 1;
+    if visitor(mapping).is_break() {
+      summary.stopped_early = true;
+      summary.line_count = mapping.generated.line;
+      break;
+    }
+  }
+  Ok(summary)
 }
 
-fn parse_mappings(source_map: &SourceMap) -> Result<Cache, String>{
-  if source_map.version != SOURCE_MAP_VERSION {
-    return Err("Only Source Map version 3 is implemented".into())
+// Parses a regular source map into its header and mappings, for representations of
+// source maps that keep the mappings encoded and so cannot flatten index maps
+pub(crate) fn parse_regular_source_map(source_map_json: &str) -> Result<(RawSourceMap, String), Error> {
+  match front_end::parse_document(source_map_json)? {
+    Document::SourceMap(header, _) if header.version != SOURCE_MAP_VERSION => Err(Error::invalid(ErrorCode::UnsupportedVersion, "Only Source Map version 3 is implemented")),
+    Document::SourceMap(header, mappings) => Ok((header, mappings)),
+    Document::IndexMap(_) => Err(Error::invalid(ErrorCode::Other, "Index maps are not supported here, consume them into a Cache instead"))
   }
+}
 
-  let sources_length = source_map.sources.len() as u32;
-  let names_length = source_map.names.len() as u32;
+// Parses a source map or an index map, which may have no mappings. Sections of index
+// maps that refer to their map by url are fetched with fetch, if given
+pub(crate) fn consume_json(source_map_json: &str, with_sources_content: bool, fetch: Option<&Fetch>, scratch: &mut Scratch) -> Result<Cache, Error> {
+  let (header, mappings) = match front_end::parse_document(source_map_json)? {
+    Document::SourceMap(header, mappings) => (header, mappings),
+    Document::IndexMap(index_map) => return flatten_index_map(index_map, with_sources_content, fetch, scratch)
+  };
+
+  let with_sources_content = with_sources_content && header.has_sources_content;
+  let mut cache = parse_mappings(header, &mappings, scratch)?;
+  if with_sources_content {
+    // sourcesContent is skipped by parse_document, since most callers never need it
+    let sources_content = front_end::parse_sources_content(source_map_json)?;
+    for (source, content) in cache.sources.iter().zip(sources_content) {
+      if let Some(content) = content {
+        cache.sources_content.entry(source.clone()).or_insert(content);
+      }
+    }
+  }
+  Ok(cache)
+}
+
+/**
+ * Parses the fields of a regular source map other than its mappings, for tools that
+ * only need its metadata. The mappings are skipped without being decoded or copied, and
+ * the version is not checked.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::parse_header;
+ *
+ * let header = parse_header(r#"{ "version": 3, "file": "app.js", "sources": ["a.js"], "sourcesContent": ["a();"], "names": [], "mappings": "AAAA" }"#).unwrap();
+ * assert!(header.file.as_deref() == Some("app.js"));
+ * assert!(header.sources == ["a.js"]);
+ * assert!(header.has_sources_content);
+ * ```
+ */
+pub fn parse_header(source_map_json: &str) -> Result<RawSourceMap, Error> {
+  front_end::parse_header(source_map_json)
+}
+
+fn flatten_index_map(index_map: IndexMap, with_sources_content: bool, fetch: Option<&Fetch>, scratch: &mut Scratch) -> Result<Cache, Error> {
+  if index_map.version != SOURCE_MAP_VERSION {
+    return Err(Error::invalid(ErrorCode::UnsupportedVersion, "Only Source Map version 3 is implemented"))
+  }
+
+  let mut sections: Vec<Cache> = Vec::with_capacity(index_map.sections.len());
+  let mut previous_offset: Option<(u32, u32)> = None;
+  for (index, section) in index_map.sections.iter().enumerate() {
+    let offset = section.offset;
+    if previous_offset.is_some_and(|previous| offset <= previous) {
+      return Err(Error::invalid(ErrorCode::InvalidIndexMap, format!("Invalid index map: section {} does not start after the previous section", index)));
+    }
+    previous_offset = Some(offset);
+
+    let cache = match (&section.map, &section.url, fetch) {
+      (Some(map), _, _) => consume_json(map, with_sources_content, fetch, scratch),
+      (None, Some(url), Some(fetch)) => fetch.consume(url, with_sources_content, scratch),
+      (None, Some(url), None) => return Err(Error::invalid(ErrorCode::InvalidIndexMap, format!("Invalid index map: section {} refers to {}, but sections with a url need a MapFetcher", index, url))),
+      (None, None, _) => return Err(Error::invalid(ErrorCode::InvalidIndexMap, format!("Invalid index map: section {} has no map", index)))
+    }.map_err(|err| err.in_context(&format!("Invalid index map: section {}", index)))?;
+    let cache = cache.offset_generated(offset.0, offset.1)
+      .map_err(|err| err.in_context(&format!("Invalid index map: section {}", index)))?;
+    sections.push(cache);
+  }
+
+  // A cache has a single source root, so differing roots are joined into the sources
+  let source_root = match sections.first() {
+    Some(first) if sections.iter().all(|section| section.source_root == first.source_root) => first.source_root.clone(),
+    _ => {
+      debug!("The sections of the index map have different source roots, which are joined into their sources");
+      for section in &mut sections {
+        let source_root = mem::take(&mut section.source_root);
+        section.map_sources(|source| join_source_root(&source_root, source));
+      }
+      "".into()
+    }
+  };
 
   let mut generated_mappings: Vec<Mapping> = Vec::new();
+  let mut sources: Vec<String> = Vec::new();
+  let mut names: Vec<String> = Vec::new();
+  let mut sources_content: HashMap<String, String> = HashMap::new();
+  let mut ignored_sources: HashSet<String> = HashSet::new();
+  let mut seen_sources: HashSet<String> = HashSet::new();
+  let mut seen_names: HashSet<String> = HashSet::new();
+  for section in sections {
+    sources.extend(section.sources.into_iter().filter(|source| seen_sources.insert(source.clone())));
+    names.extend(section.names.into_iter().filter(|name| seen_names.insert(name.clone())));
+    for (source, content) in section.sources_content {
+      sources_content.entry(source).or_insert(content);
+    }
+    ignored_sources.extend(section.ignored_sources);
+    generated_mappings.extend(section.generated_mappings);
+  }
+  sort_mappings(&mut generated_mappings);
+
+  Ok(Cache {
+    line_index: LineIndex::new(&generated_mappings),
+    generated_mappings,
+    sources,
+    names,
+    sources_content,
+    ignored_sources,
+    declared: None,
+    resolver: None,
+    source_root,
+    file: index_map.file.unwrap_or_default()
+  })
+}
 
-  let mut generated_line: u32 = 0;
-  let mut previous_original_line: u32 = 0;
-  let mut previous_original_column: u32 = 0;
-  let mut previous_source: u32 = 0;
-  let mut previous_name: u32 = 0;
-
-  for line in source_map.mappings.as_bytes().split(|&x| x == (';' as u8)) {
-    generated_line += 1;
-    let mut previous_generated_column: u32 = 0;
-
-    for segment in line.split(|&x| x == (',' as u8)) {
-      let segment_length = segment.len();
-      let mut fields: Vec<i32> = Vec::new();
-      let mut character_index = 0;
-      while character_index < segment_length {
-        match base64_vlq::decode(&segment[character_index..segment_length]) {
-          Some((value, field_length)) => {
-            fields.push(value);
-            character_index += field_length;
-          },
-          None => return Err("Invalid VLQ mapping field".into())
-        };
+// Places the entries of a table at the indices they were declared at
+fn declared_view<'a>(table: &'a [String], declared: Option<(&[u32], usize)>) -> Vec<Option<&'a str>> {
+  match declared {
+    Some((indices, count)) => {
+      let mut view = vec![None; count];
+      for (entry, &index) in table.iter().zip(indices) {
+        view[index as usize] = Some(entry.as_str());
       }
+      view
+    },
+    None => table.iter().map(|entry| Some(entry.as_str())).collect()
+  }
+}
 
-      if fields.len() < 1 {
-        continue;
+pub(crate) fn join_source_root(source_root: &str, source: &str) -> String {
+  if source_root.is_empty() || source_root.ends_with('/') {
+    format!("{}{}", source_root, source)
+  } else {
+    format!("{}/{}", source_root, source)
+  }
+}
+
+fn parse_mappings(header: RawSourceMap, mappings: &str, scratch: &mut Scratch) -> Result<Cache, Error> {
+  if header.version != SOURCE_MAP_VERSION {
+    return Err(Error::invalid(ErrorCode::UnsupportedVersion, "Only Source Map version 3 is implemented"))
+  }
+
+  // Indices outside the sources table cannot refer to anything, so they are skipped
+  let ignored_sources = header.ignore_list.iter()
+    .filter_map(|&index| {
+      let source = header.sources.get(index as usize).cloned();
+      if source.is_none() {
+        warn!("Skipping ignoreList index {}, which is outside the sources table of {} sources", index, header.sources.len());
       }
+      source
+    })
+    .collect();
+  // The tables of the header are moved into the cache rather than copied
+  let mut cache = decode_cache(mappings, header.sources, header.names, header.source_root.unwrap_or_default(), scratch)?;
+  cache.ignored_sources = ignored_sources;
+  cache.file = header.file.unwrap_or_default();
+  Ok(cache)
+}
+
+/**
+ * Parses the `mappings` field of a source map given its `sources` and `names` tables and
+ * its `sourceRoot`, for tools that hold the fields of a source map without its JSON.
+ *
+ * The mappings are validated like `consume` validates them, but unlike `consume`, an
+ * empty `mappings` field gives a cache without mappings rather than an error.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::parse_mappings_str;
+ *
+ * let sources = vec!["source.js".to_owned()];
+ * let names = vec!["name1".to_owned()];
+ * let cache = parse_mappings_str("AAAAA,EAAE;AACA", &sources, &names, "").unwrap();
+ * let mapping = cache.mapping_for_generated_position(1, 0);
+ * assert!(&*mapping.source == "source.js" && &*mapping.name == "name1");
+ * assert!(parse_mappings_str("AAAAA", &sources, &[], "").is_err());
+ * ```
+ */
+pub fn parse_mappings_str(mappings: &str, sources: &[String], names: &[String], source_root: &str) -> Result<Cache, Error> {
+  decode_cache(mappings, sources.to_vec(), names.to_vec(), source_root.into(), &mut Scratch::default())
+}
+
+fn decode_cache(mappings: &str, sources: Vec<String>, names: Vec<String>, source_root: String, scratch: &mut Scratch) -> Result<Cache, Error> {
+  // Segments are one more than separators, so counting them first saves growing the
+  // vector, and copying it, over and over. Empty segments are counted too, so the
+  // capacity left over is given back
+  let mut generated_mappings: Vec<Mapping> = Vec::with_capacity(scan::count_separators(mappings.as_bytes()) + 1);
+  // Bundlers emit the mappings in order, so they are only sorted when one is found out
+  // of order while decoding
+  let mut sorted = true;
+  let decoded = decode_mappings(mappings, scratch.tables(&sources, &names), |mapping| {
+    if sorted {
+      sorted = generated_mappings.last().is_none_or(|last| *last <= mapping);
+    }
+    generated_mappings.push(mapping);
+    ControlFlow::Continue(())
+  });
+  scratch.clear();
+  decoded?;
+  generated_mappings.shrink_to_fit();
+
+  if !sorted {
+    debug!("The mappings are out of order, and are sorted");
+    sort_mappings(&mut generated_mappings);
+  }
+
+  Ok(Cache {
+    line_index: LineIndex::new(&generated_mappings),
+    generated_mappings,
+    sources,
+    names,
+    sources_content: HashMap::new(),
+    ignored_sources: HashSet::new(),
+    declared: None,
+    resolver: None,
+    source_root,
+    file: "".into()
+  })
+}
+
+// Decodes a mappings field, handing each mapping to visit in the order they are
+// encoded, and stops as soon as visit breaks
+fn decode_mappings<F: FnMut(Mapping) -> ControlFlow<()>>(mappings: &str, tables: &MappingTables, mut visit: F) -> Result<ConsumeSummary, Error> {
+  let mut state = SegmentState::default();
+  let mut summary = ConsumeSummary { mapping_count: 0, line_count: 0, stopped_early: false };
+
+  let mut decoder = MappingsDecoder::new(mappings);
+  for segment in &mut decoder {
+    let segment = segment?;
+    let mapping = state.apply(&segment, tables)?;
+    summary.mapping_count += 1;
+    if visit(mapping).is_break() {
+      summary.stopped_early = true;
+      summary.line_count = segment.generated_line;
+      return Ok(summary);
+    }
+  }
+
+  summary.line_count = decoder.generated_line();
+  Ok(summary)
+}
+
+// The sources and names of a source map, shared by the mappings decoded from it so that
+// each is allocated only once
+#[derive(Default, Debug)]
+pub(crate) struct MappingTables {
+  sources: Vec<Arc<str>>,
+  names: Vec<Arc<str>>,
+  empty: Arc<str>
+}
+
+impl MappingTables {
+  pub(crate) fn new(sources: &[String], names: &[String]) -> MappingTables {
+    let mut tables = MappingTables::default();
+    tables.fill(sources, names, &mut Interner::default());
+    tables
+  }
+
+  // Replaces the tables, reusing the memory of the vectors
+  fn fill(&mut self, sources: &[String], names: &[String], interner: &mut Interner) {
+    self.sources.clear();
+    self.sources.extend(sources.iter().map(|source| interner.intern(source)));
+    self.names.clear();
+    self.names.extend(names.iter().map(|name| interner.intern(name)));
+    self.empty = interner.intern("");
+  }
+}
+
+// The buffers that decoding only needs while it runs, and the strings of the caches
+// decoded so far, which a Parser keeps between calls
+#[derive(Default, Debug)]
+pub(crate) struct Scratch {
+  interner: Interner,
+  tables: MappingTables
+}
 
-      if fields.len() == 2 {
-        return Err("Found a source, but no line and column".into());
+impl Scratch {
+  fn tables(&mut self, sources: &[String], names: &[String]) -> &MappingTables {
+    self.tables.fill(sources, names, &mut self.interner);
+    &self.tables
+  }
+
+  // Lets go of the strings that no cache refers to, keeping the others for the next
+  // source map to share, and the memory of the buffers
+  fn clear(&mut self) {
+    self.tables.sources.clear();
+    self.tables.names.clear();
+    self.interner.strings.retain(|string| Arc::strong_count(string) > 1);
+  }
+
+  #[cfg(test)]
+  pub(crate) fn shared_strings(&self) -> usize {
+    self.interner.strings.len()
+  }
+}
+
+// The fields of the last segment, which the fields of the next one are relative to
+#[derive(Clone, Copy, Default, Debug)]
+pub(crate) struct SegmentState {
+  generated_line: u32,
+  generated_column: u32,
+  original_line: u32,
+  original_column: u32,
+  source: u32,
+  name: u32
+}
+
+impl SegmentState {
+  // Turns a segment into a mapping, checking its indices against the tables
+  pub(crate) fn apply(&mut self, segment: &DecodedSegment, tables: &MappingTables) -> Result<Mapping, Error> {
+    let fields = segment.fields;
+    // Generated columns are relative to the previous segment on the same line
+    if segment.generated_line != self.generated_line {
+      self.generated_line = segment.generated_line;
+      self.generated_column = 0;
+    }
+
+    let mut mapping = Mapping {
+      generated: CodePosition {
+        line: segment.generated_line,
+        column: ((self.generated_column as i32) + fields[0]) as u32
+      },
+      original: CodePosition {
+        line: 0,
+        column: 0
+      },
+      source: tables.empty.clone(),
+      name: tables.empty.clone()
+    };
+
+    self.generated_column = mapping.generated.column;
+
+    if segment.field_count > 1 {
+      // Original source.
+      self.source = ((self.source as i32) + fields[1]) as u32;
+      match tables.sources.get(self.source as usize) {
+        Some(source) => mapping.source = source.clone(),
+        None => return Err(Error::invalid(ErrorCode::SourceIndexOutOfRange, format!("Invalid source map: reference to source index {} when source list length is {}", self.source, tables.sources.len())))
       }
 
-      if fields.len() == 3 {
-        return Err("Found a source and line, but no column".into());
+      // Original line.
+      self.original_line = ((self.original_line as i32) + fields[2]) as u32;
+      // Lines are stored 0-based
+      mapping.original.line = self.original_line.checked_add(1).ok_or_else(|| Error::invalid(ErrorCode::InvalidPosition, "Line number overflowed"))?;
+
+      // Original column.
+      self.original_column = ((self.original_column as i32) + fields[3]) as u32;
+      mapping.original.column = self.original_column;
+
+      if segment.field_count > 4 {
+        // Original name.
+        self.name = ((self.name as i32) + fields[4]) as u32;
+        match tables.names.get(self.name as usize) {
+          Some(name) => mapping.name = name.clone(),
+          None => return Err(Error::invalid(ErrorCode::NameIndexOutOfRange, format!("Invalid source map: reference to name index {} when name list length is {}", self.name, tables.names.len())))
+        }
       }
+    }
 
-      let mut mapping = Mapping {
-        generated: CodePosition {
-          line: generated_line,
-          column: ((previous_generated_column as i32) + fields[0]) as u32
-        },
-        original: CodePosition {
-          line: 0,
-          column: 0
-        },
+    Ok(mapping)
+  }
+}
+
+/**
+ * Compares two mappings in the canonical order used by caches: by generated position,
+ * then by original position, then by source and name.
+ */
+pub fn compare_mappings(a: &Mapping, b: &Mapping) -> Ordering {
+  a.cmp(b)
+}
+
+/**
+ * Sorts mappings into the canonical order used by caches, see `compare_mappings`.
+ *
+ * With the `parallel` feature, the sort is spread over the threads of rayon's global
+ * pool. Mappings that compare equal are identical, so the order is the same either way.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::{sort_mappings, is_sorted_by_generated, Mapping, CodePosition};
+ *
+ * let mapping = |line, column| Mapping {
+ *   generated: CodePosition { line, column },
+ *   original: CodePosition { line: 1, column: 0 },
+ *   source: "a.js".into(),
+ *   name: "".into()
+ * };
+ * let mut mappings = vec![mapping(2, 0), mapping(1, 5), mapping(1, 0)];
+ * sort_mappings(&mut mappings);
+ * assert!(is_sorted_by_generated(&mappings));
+ * ```
+ */
+pub fn sort_mappings(mappings: &mut [Mapping]) {
+  #[cfg(feature = "parallel")]
+  mappings.par_sort_by(compare_mappings);
+  #[cfg(not(feature = "parallel"))]
+  mappings.sort_by(compare_mappings);
+}
+
+/**
+ * Removes consecutive identical mappings. On canonically sorted mappings, this removes
+ * all duplicates.
+ */
+pub fn dedup_mappings(mappings: &mut Vec<Mapping>) {
+  mappings.dedup();
+}
+
+/**
+ * Returns true if the mappings are ordered by generated position, which is what lookups
+ * on a cache rely on.
+ */
+pub fn is_sorted_by_generated(mappings: &[Mapping]) -> bool {
+  mappings.windows(2).all(|pair| {
+    (pair[0].generated.line, pair[0].generated.column) <= (pair[1].generated.line, pair[1].generated.column)
+  })
+}
+
+impl Cache {
+  /**
+   * Returns the original source, line, column and name information for the generated
+   * source's line and column positions provided.
+   *
+   * # Arguments
+   *
+   * * line: The line number in the generated source.
+   * * column: The column number in the generated source.
+   *
+   * # Examples
+   *
+   * ```
+   * use js_source_mapper::consume;
+   *
+   * let cache = consume(r#"{ "version": 3, "file": "foo.js", "sources": ["source.js"], "names": ["name1", "name1", "name3"], "mappings": ";EAACA;;IAEEA;;MAEEE", "sourceRoot": "http://example.com" }"#).unwrap();
+   *
+   * println!("{:?}", cache.mapping_for_generated_position(2, 2));
+   * // => Mapping {
+   * //   generated: CodePosition { line: 2, column: 2 },
+   * //   original: CodePosition { line: 1, column: 1 },
+   * //   source: "source.js"
+   * //   name: "name1"
+   * // }
+   * ```
+   *
+   */
+  pub fn mapping_for_generated_position(&self, line: u32, column: u32) -> Mapping {
+    let mappings = &self.generated_mappings;
+    if mappings.is_empty() {
+      // Only reachable for caches derived from another cache, e.g. by filtering, and for
+      // those parse_mappings_str gives for empty mappings
+      return Mapping {
+        generated: CodePosition { line, column },
+        original: CodePosition { line: 0, column: 0 },
         source: "".into(),
         name: "".into()
       };
+    }
+    // Only the mappings of the line are searched. Past its last mapping, the position
+    // resolves to the first mapping of a later line, like a search of all mappings would
+    let index = self.line_index.search(mappings, line, column);
+    mappings[index.min(mappings.len() - 1)].clone()
+  }
+
+  /**
+   * Creates a cache directly from mappings, without going through JSON.
+   *
+   * The mappings are sorted into the canonical order, and the `sources` and `names`
+   * tables are built from the mappings in the order they are first referenced. Like
+   * `consume`, this fails if there are no mappings.
+   *
+   * # Examples
+   *
+   * ```
+   * use js_source_mapper::{Cache, Mapping, CodePosition};
+   *
+   * let cache = Cache::from_mappings(vec![Mapping {
+   *   generated: CodePosition { line: 1, column: 0 },
+   *   original: CodePosition { line: 3, column: 2 },
+   *   source: "a.js".into(),
+   *   name: "".into()
+   * }], "").unwrap();
+   * assert!(cache.mapping_for_generated_position(1, 0).original.line == 3);
+   * ```
+   */
+  pub fn from_mappings<S: Into<String>>(mut mappings: Vec<Mapping>, source_root: S) -> Result<Cache, Error> {
+    if mappings.is_empty() {
+      return Err(Error::invalid(ErrorCode::NoMappings, "Source Map contains no mappings"));
+    }
+
+    for mapping in &mappings {
+      if mapping.generated.line < 1 {
+        return Err(Error::invalid(ErrorCode::InvalidPosition, format!("Invalid mapping: generated line {} is before the first line", mapping.generated.line)));
+      }
+      if !mapping.source.is_empty() && mapping.original.line < 1 {
+        return Err(Error::invalid(ErrorCode::InvalidPosition, format!("Invalid mapping: original line {} is before the first line", mapping.original.line)));
+      }
+    }
 
-      previous_generated_column = mapping.generated.column;
+    sort_mappings(&mut mappings);
 
-      if fields.len() > 1 {
-        // Original source.
-        previous_source = ((previous_source as i32) + fields[1]) as u32;
-        if previous_source < sources_length {
-          mapping.source = source_map.sources[previous_source as usize].to_owned();
-        } else {
-          return Err(format!("Invalid source map: reference to source index {} when source list length is {}", previous_source, sources_length));
-        }
+    fn table<'a, I: Iterator<Item = &'a str>>(entries: I) -> Vec<String> {
+      let mut seen: HashSet<&str> = HashSet::new();
+      entries
+        .filter(|entry| !entry.is_empty() && seen.insert(entry))
+        .map(|entry| entry.to_owned())
+        .collect()
+    }
+
+    Ok(Cache {
+      line_index: LineIndex::new(&mappings),
+      sources: table(mappings.iter().map(|mapping| &*mapping.source)),
+      names: table(mappings.iter().map(|mapping| &*mapping.name)),
+      generated_mappings: mappings,
+      sources_content: HashMap::new(),
+      ignored_sources: HashSet::new(),
+      declared: None,
+      resolver: None,
+      source_root: source_root.into(),
+      file: "".into()
+    })
+  }
+
+  #[cfg(feature = "std")]
+  pub(crate) fn from_parts(generated_mappings: Vec<Mapping>, sources: Vec<String>, names: Vec<String>, source_root: String) -> Cache {
+    Cache {
+      line_index: LineIndex::new(&generated_mappings),
+      generated_mappings,
+      sources,
+      names,
+      sources_content: HashMap::new(),
+      ignored_sources: HashSet::new(),
+      declared: None,
+      resolver: None,
+      source_root,
+      file: "".into()
+    }
+  }
+
+  /**
+   * Returns all mappings in generated order, without copying them.
+   *
+   * # Examples
+   *
+   * ```
+   * use js_source_mapper::consume;
+   *
+   * let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA,CAAC;AACA" }"#).unwrap();
+   * let lines: Vec<u32> = cache.mappings().iter().map(|mapping| mapping.generated.line).collect();
+   * assert!(lines == [1, 1, 2]);
+   * ```
+   */
+  pub fn mappings(&self) -> &[Mapping] {
+    &self.generated_mappings
+  }
+
+  /**
+   * Consumes the cache and returns its mappings in generated order, without copying
+   * them, for transforming them after parsing. `Cache::from_mappings` builds a cache
+   * from the transformed mappings.
+   *
+   * # Examples
+   *
+   * ```
+   * use js_source_mapper::{Cache, consume};
+   *
+   * let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA;AACA" }"#).unwrap();
+   * let mut mappings = cache.into_mappings();
+   * for mapping in &mut mappings {
+   *   mapping.source = format!("src/{}", mapping.source).into();
+   * }
+   * let cache = Cache::from_mappings(mappings, "").unwrap();
+   * assert!(cache.sources() == ["src/a.js"]);
+   * ```
+   */
+  pub fn into_mappings(self) -> Vec<Mapping> {
+    self.generated_mappings
+  }
+
+  /**
+   * Consumes the cache and returns its mappings along with its tables, source root and
+   * file, without copying them. The sources content and the ignored sources are dropped.
+   */
+  pub fn into_parts(self) -> CacheParts {
+    CacheParts {
+      mappings: self.generated_mappings,
+      sources: self.sources,
+      names: self.names,
+      source_root: self.source_root,
+      file: self.file
+    }
+  }
 
-        // Original line.
-        previous_original_line = ((previous_original_line as i32) + fields[2]) as u32;
-        // Lines are stored 0-based
-        mapping.original.line = previous_original_line.checked_add(1).ok_or("Line number overflowed")?;
-
-        // Original column.
-        previous_original_column = ((previous_original_column as i32) + fields[3]) as u32;
-        mapping.original.column = previous_original_column;
-
-        if fields.len() > 4 {
-          // Original name.
-          previous_name = ((previous_name as i32) + fields[4]) as u32;
-          if previous_name < names_length {
-            mapping.name = source_map.names[previous_name as usize].to_owned();
-          } else {
-            return Err(format!("Invalid source map: reference to name index {} when name list length is {}", previous_name, names_length));
-          }
+  /**
+   * Returns the number of mappings.
+   */
+  pub fn len(&self) -> usize {
+    self.generated_mappings.len()
+  }
+
+  /**
+   * Returns true if there are no mappings, which is only the case for caches derived
+   * from another cache, e.g. by `filter_sources`, and for caches `parse_mappings_str`
+   * gives for an empty `mappings` field.
+   */
+  pub fn is_empty(&self) -> bool {
+    self.generated_mappings.is_empty()
+  }
+
+  /**
+   * Returns the mappings on a generated line, in generated order. The mappings are found
+   * through an index of the lines, so this is cheap even for large caches.
+   */
+  pub fn mappings_for_generated_line(&self, line: u32) -> &[Mapping] {
+    let (start, end) = self.line_index.line_range(&self.generated_mappings, line);
+    &self.generated_mappings[start..end]
+  }
+
+  /**
+   * Returns the `sources` table of the source map, in declaration order.
+   */
+  pub fn sources(&self) -> &[String] {
+    &self.sources
+  }
+
+  /**
+   * Returns the `names` table of the source map, in declaration order.
+   */
+  pub fn names(&self) -> &[String] {
+    &self.names
+  }
+
+  /**
+   * Removes the sources and names that no mapping refers to from the tables, along with
+   * the contents and ignore flags of the removed sources. Large bundles often declare
+   * thousands of them, for modules that tree shaking left out.
+   *
+   * The remaining entries keep their order, and of entries declared more than once only
+   * the first is kept. `sources` and `names` return the compacted tables, which `to_json`
+   * writes out, while `declared_sources` and `declared_names` place each remaining entry
+   * at the index it was declared at.
+   *
+   * # Examples
+   *
+   * ```
+   * use js_source_mapper::consume;
+   *
+   * let mut cache = consume(r#"{ "version": 3, "sources": ["a.js", "shaken.js", "b.js"], "names": ["x"], "mappings": "AAAA,CEAA" }"#).unwrap();
+   * cache.drop_unreferenced();
+   * assert!(cache.sources() == ["a.js", "b.js"] && cache.names().is_empty());
+   * assert!(cache.declared_sources() == [Some("a.js"), None, Some("b.js")]);
+   * assert!(cache.declared_names() == [None]);
+   * ```
+   */
+  pub fn drop_unreferenced(&mut self) {
+    // The indices of the entries to keep, in declaration order
+    fn referenced<'a, I: Iterator<Item = &'a str>>(table: &[String], referenced: I) -> Vec<usize> {
+      let referenced: HashSet<&str> = referenced.filter(|entry| !entry.is_empty()).collect();
+      let mut seen: HashSet<&str> = HashSet::new();
+      (0..table.len()).filter(|&index| referenced.contains(table[index].as_str()) && seen.insert(&table[index])).collect()
+    }
+
+    fn retain(table: &mut Vec<String>, kept: &[usize]) {
+      let mut entries = mem::take(table);
+      *table = kept.iter().map(|&index| mem::take(&mut entries[index])).collect();
+    }
+
+    // Entries already dropped earlier are not declared at their current index
+    fn declared_at(kept: &[usize], previous: Option<&[u32]>) -> Vec<u32> {
+      kept.iter().map(|&index| previous.map_or(index as u32, |previous| previous[index])).collect()
+    }
+
+    let kept_sources = referenced(&self.sources, self.generated_mappings.iter().map(|mapping| &*mapping.source));
+    let kept_names = referenced(&self.names, self.generated_mappings.iter().map(|mapping| &*mapping.name));
+    self.declared = Some(DeclaredTables {
+      sources: declared_at(&kept_sources, self.declared.as_ref().map(|declared| &declared.sources[..])),
+      source_count: self.declared.as_ref().map_or(self.sources.len(), |declared| declared.source_count),
+      names: declared_at(&kept_names, self.declared.as_ref().map(|declared| &declared.names[..])),
+      name_count: self.declared.as_ref().map_or(self.names.len(), |declared| declared.name_count)
+    });
+    retain(&mut self.sources, &kept_sources);
+    retain(&mut self.names, &kept_names);
+
+    let sources: HashSet<&str> = self.sources.iter().map(|source| source.as_str()).collect();
+    self.sources_content.retain(|source, _| sources.contains(source.as_str()));
+    self.ignored_sources.retain(|source| sources.contains(source.as_str()));
+  }
+
+  /**
+   * Returns the `sources` table as the source map declared it, with `None` at the
+   * indices of the sources `drop_unreferenced` removed. The sources of `sources` are the
+   * entries that are not `None`, in the same order. Until `drop_unreferenced` is called,
+   * this is `sources` as it is.
+   */
+  pub fn declared_sources(&self) -> Vec<Option<&str>> {
+    declared_view(&self.sources, self.declared.as_ref().map(|declared| (&declared.sources[..], declared.source_count)))
+  }
+
+  /**
+   * Returns the `names` table as the source map declared it, with `None` at the indices
+   * of the names `drop_unreferenced` removed, like `declared_sources`.
+   */
+  pub fn declared_names(&self) -> Vec<Option<&str>> {
+    declared_view(&self.names, self.declared.as_ref().map(|declared| (&declared.names[..], declared.name_count)))
+  }
+
+  /**
+   * Returns the original text of a source, if the cache was created with
+   * `consume_with_sources_content` and the source map embeds it.
+   */
+  pub fn source_content(&self, source: &str) -> Option<&str> {
+    self.sources_content.get(source).map(|content| content.as_str())
+  }
+
+  #[cfg(feature = "std")]
+  pub(crate) fn sources_content(&self) -> &HashMap<String, String> {
+    &self.sources_content
+  }
+
+  #[cfg(feature = "std")]
+  pub(crate) fn set_sources_content(&mut self, sources_content: HashMap<String, String>) {
+    self.sources_content = sources_content;
+  }
+
+  /**
+   * Returns true if the source is in the `ignoreList` of the source map.
+   */
+  pub fn is_ignored(&self, source: &str) -> bool {
+    self.ignored_sources.contains(source)
+  }
+
+  /**
+   * Returns the sources in the `ignoreList` of the source map, in the order of the
+   * `sources` table.
+   */
+  pub fn ignored_sources(&self) -> Vec<&str> {
+    let mut seen: HashSet<&str> = HashSet::new();
+    self.sources.iter()
+      .map(|source| source.as_str())
+      .filter(|source| self.ignored_sources.contains(*source) && seen.insert(source))
+      .collect()
+  }
+
+  /**
+   * Adds a source to or removes it from the `ignoreList`.
+   */
+  pub fn set_ignored(&mut self, source: &str, ignored: bool) {
+    if ignored {
+      self.ignored_sources.insert(source.to_owned());
+    } else {
+      self.ignored_sources.remove(source);
+    }
+  }
+
+  /**
+   * Returns a summary of the cache, which can be serialized with the `serde` feature.
+   */
+  pub fn summary(&self) -> CacheSummary {
+    CacheSummary {
+      sources: self.sources.clone(),
+      names: self.names.clone(),
+      source_root: self.source_root.clone(),
+      mapping_count: self.generated_mappings.len()
+    }
+  }
+
+  /**
+   * Estimates the memory the cache holds, for capacity planning. It follows the
+   * representation of the cache: mappings that share a source or name share one string,
+   * which is counted once.
+   *
+   * # Examples
+   *
+   * ```
+   * use std::mem;
+   * use js_source_mapper::{Cache, Mapping, consume};
+   *
+   * let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA;AACA" }"#).unwrap();
+   * let usage = cache.memory_usage();
+   * assert!(usage.mappings >= 2 * mem::size_of::<Mapping>());
+   * assert!(usage.total == usage.mappings + usage.strings + usage.sources_content + usage.index + mem::size_of::<Cache>());
+   * ```
+   */
+  pub fn memory_usage(&self) -> MemoryUsage {
+    let string_size = |string: &String| mem::size_of::<String>() + string.capacity();
+    let table_size = |table: &Vec<String>| table.capacity() * mem::size_of::<String>() + table.iter().map(|entry| entry.capacity()).sum::<usize>();
+
+    // A shared string is allocated with its two reference counts
+    let mut shared: HashSet<*const u8> = HashSet::new();
+    let mut strings = 0;
+    for mapping in &self.generated_mappings {
+      for string in [&mapping.source, &mapping.name] {
+        if shared.insert(string.as_ptr()) {
+          strings += 2 * mem::size_of::<usize>() + string.len();
         }
       }
+    }
+    strings += table_size(&self.sources) + table_size(&self.names);
+    strings += self.ignored_sources.iter().map(string_size).sum::<usize>();
+    strings += self.source_root.capacity() + self.file.capacity();
+
+    let mut usage = MemoryUsage {
+      mappings: self.generated_mappings.capacity() * mem::size_of::<Mapping>(),
+      strings,
+      sources_content: self.sources_content.iter().map(|(source, content)| string_size(source) + string_size(content)).sum(),
+      index: self.line_index.memory_usage() + self.declared.as_ref().map_or(0, |declared| {
+        (declared.sources.capacity() + declared.names.capacity()) * mem::size_of::<u32>()
+      }),
+      total: mem::size_of::<Cache>()
+    };
+    usage.total += usage.mappings + usage.strings + usage.sources_content + usage.index;
+    usage
+  }
+
+  /**
+   * Rewrites every source path with the given function.
+   *
+   * The function is called once per entry in the `sources` table, and every mapping
+   * referring to that entry is updated to the rewritten path, so subsequent lookups and
+   * `to_json` output use the new names.
+   *
+   * # Examples
+   *
+   * ```
+   * use js_source_mapper::consume;
+   *
+   * let mut cache = consume(r#"{ "version": 3, "sources": ["foo.js"], "names": [], "mappings": "AAAA" }"#).unwrap();
+   * cache.map_sources(|source| source.to_uppercase());
+   * assert!(&*cache.mapping_for_generated_position(1, 0).source == "FOO.JS");
+   * ```
+   */
+  pub fn map_sources<F: FnMut(&str) -> String>(&mut self, mut f: F) {
+    let mut rewritten: HashMap<String, String> = HashMap::new();
+    for source in &mut self.sources {
+      let new_source = f(source);
+      rewritten.insert(source.clone(), new_source.clone());
+      *source = new_source;
+    }
+
+    self.sources_content = mem::take(&mut self.sources_content).into_iter()
+      .map(|(source, content)| (rewritten.get(&source).cloned().unwrap_or(source), content))
+      .collect();
+    self.ignored_sources = mem::take(&mut self.ignored_sources).into_iter()
+      .map(|source| rewritten.get(&source).cloned().unwrap_or(source))
+      .collect();
+
+    let mut interner = Interner::default();
+    let interned: HashMap<&str, Arc<str>> = rewritten.iter()
+      .map(|(source, new_source)| (source.as_str(), interner.intern(new_source)))
+      .collect();
+    for mapping in &mut self.generated_mappings {
+      if let Some(new_source) = interned.get(&*mapping.source) {
+        mapping.source = new_source.clone();
+      }
+    }
+  }
+
+  /**
+   * Replaces the `from_prefix` of every source path starting with it by `to_prefix`.
+   *
+   * The source root is rewritten the same way when it starts with `from_prefix`. Sources
+   * that do not start with the prefix are left untouched.
+   *
+   * # Examples
+   *
+   * ```
+   * use js_source_mapper::consume;
+   *
+   * let mut cache = consume(r#"{ "version": 3, "sources": ["/home/runner/work/app/src/foo.js"], "names": [], "mappings": "AAAA" }"#).unwrap();
+   * cache.rebase_sources("/home/runner/work/app/", "./");
+   * assert!(cache.sources() == ["./src/foo.js"]);
+   * ```
+   */
+  pub fn rebase_sources(&mut self, from_prefix: &str, to_prefix: &str) {
+    fn rebase(path: &str, from_prefix: &str, to_prefix: &str) -> String {
+      match path.strip_prefix(from_prefix) {
+        Some(rest) => format!("{}{}", to_prefix, rest),
+        None => path.to_owned()
+      }
+    }
+
+    self.source_root = rebase(&self.source_root, from_prefix, to_prefix);
+    self.map_sources(|source| rebase(source, from_prefix, to_prefix));
+  }
+
+  /**
+   * Removes the names from every mapping and empties the `names` table.
+   *
+   * The original positions are kept, so lookups still resolve to the same source
+   * locations, but `to_json` writes segments without a name field.
+   *
+   * # Examples
+   *
+   * ```
+   * use js_source_mapper::consume;
+   *
+   * let mut cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": ["x"], "mappings": "AAAAA" }"#).unwrap();
+   * cache.clear_names();
+   * assert!(cache.names().is_empty());
+   * # #[cfg(feature = "std")]
+   * assert!(cache.to_json().unwrap().contains(r#""mappings":"AAAA""#));
+   * ```
+   */
+  pub fn clear_names(&mut self) {
+    self.names.clear();
+    if let Some(ref mut declared) = self.declared {
+      declared.names.clear();
+    }
+    let empty: Arc<str> = Arc::from("");
+    for mapping in &mut self.generated_mappings {
+      mapping.name = empty.clone();
+    }
+  }
 
-      generated_mappings.push(mapping);
+  /**
+   * Returns a new cache containing only the mappings into sources accepted by `predicate`.
+   *
+   * The `sources` and `names` tables of the new cache only contain the entries that are
+   * still referenced, in their original order. Mappings without a source are kept if
+   * `keep_unmapped` is true. Mappings into the sources that are removed are replaced by
+   * mappings without a source at the same generated positions, so that lookups in the
+   * removed regions return a mapping without a source rather than falling through to a
+   * retained mapping.
+   *
+   * # Examples
+   *
+   * ```
+   * use js_source_mapper::consume;
+   *
+   * let cache = consume(r#"{ "version": 3, "sources": ["app.js", "sdk.js"], "names": [], "mappings": "AAAA;ACAA" }"#).unwrap();
+   * let filtered = cache.filter_sources(|source| source != "sdk.js", true);
+   * assert!(filtered.sources() == ["app.js"]);
+   * assert!(filtered.mapping_for_generated_position(2, 0).source.is_empty());
+   * ```
+   */
+  pub fn filter_sources<P: Fn(&str) -> bool>(&self, predicate: P, keep_unmapped: bool) -> Cache {
+    let empty: Arc<str> = Arc::from("");
+    let generated_mappings: Vec<Mapping> = self.generated_mappings.iter()
+      .filter_map(|mapping| if mapping.source.is_empty() {
+        Some(mapping.clone()).filter(|_| keep_unmapped)
+      } else if predicate(&mapping.source) {
+        Some(mapping.clone())
+      } else {
+        Some(Mapping {
+          generated: mapping.generated.clone(),
+          original: CodePosition { line: 0, column: 0 },
+          source: empty.clone(),
+          name: empty.clone()
+        })
+      })
+      .collect();
+
+    fn compact<'a, I: Iterator<Item = &'a str>>(table: &[String], referenced: I) -> Vec<String> {
+      let referenced: HashSet<&str> = referenced.collect();
+      let mut seen: HashSet<&str> = HashSet::new();
+      table.iter()
+        .filter(|entry| referenced.contains(entry.as_str()) && seen.insert(entry.as_str()))
+        .cloned()
+        .collect()
+    }
+
+    let sources = compact(&self.sources, generated_mappings.iter().map(|mapping| &*mapping.source));
+    let sources_content = sources.iter()
+      .filter_map(|source| self.sources_content.get(source).map(|content| (source.clone(), content.clone())))
+      .collect();
+    let ignored_sources = sources.iter()
+      .filter(|source| self.ignored_sources.contains(*source))
+      .cloned()
+      .collect();
+
+    Cache {
+      line_index: LineIndex::new(&generated_mappings),
+      sources,
+      names: compact(&self.names, generated_mappings.iter().map(|mapping| &*mapping.name)),
+      sources_content,
+      ignored_sources,
+      declared: None,
+      resolver: self.resolver.clone(),
+      generated_mappings,
+      source_root: self.source_root.clone(),
+      file: self.file.clone()
     }
   }
 
-  if generated_mappings.len() < 1 {
-    return Err("Source Map contains no mappings".to_owned());
+  /**
+   * Returns a copy of the cache with all generated positions shifted, as needed when the
+   * generated file is concatenated after other content into a bundle.
+   *
+   * # Arguments
+   *
+   * * line_offset: The number of lines preceding the generated file in the bundle.
+   * * first_line_column_offset: The number of columns preceding the generated file on the
+   *   line where it starts. Only mappings on the first line of the generated file are
+   *   shifted by this amount.
+   *
+   * Returns an `InvalidPosition` error if a shifted position would not fit in a `u32`.
+   *
+   * # Examples
+   *
+   * ```
+   * use js_source_mapper::consume;
+   *
+   * let cache = consume(r#"{ "version": 3, "sources": ["source.js"], "names": [], "mappings": "CAAA;EACA" }"#).unwrap();
+   * let shifted = cache.offset_generated(10, 4).unwrap();
+   *
+   * // The first line moves down and right...
+   * assert!(shifted.mapping_for_generated_position(11, 5).original.line == 1);
+   * // ...while later lines only move down.
+   * assert!(shifted.mapping_for_generated_position(12, 2).original.line == 2);
+   * ```
+   */
+  pub fn offset_generated(&self, line_offset: u32, first_line_column_offset: u32) -> Result<Cache, Error> {
+    let generated_mappings = self.generated_mappings.iter().map(|mapping| {
+      let mut mapping = mapping.clone();
+      let column = match mapping.generated.line {
+        1 => mapping.generated.column.checked_add(first_line_column_offset),
+        _ => Some(mapping.generated.column)
+      };
+      match (mapping.generated.line.checked_add(line_offset), column) {
+        (Some(line), Some(column)) => {
+          mapping.generated = CodePosition { line, column };
+          Ok(mapping)
+        },
+        _ => Err(Error::invalid(ErrorCode::InvalidPosition, format!(
+          "Offsetting by {} lines and {} columns moves the mapping at {} past the largest supported position",
+          line_offset, first_line_column_offset, mapping.generated
+        )))
+      }
+    }).collect::<Result<Vec<Mapping>, Error>>()?;
+
+    Ok(Cache {
+      line_index: LineIndex::new(&generated_mappings),
+      generated_mappings,
+      sources: self.sources.clone(),
+      names: self.names.clone(),
+      sources_content: self.sources_content.clone(),
+      ignored_sources: self.ignored_sources.clone(),
+      declared: self.declared.clone(),
+      resolver: self.resolver.clone(),
+      source_root: self.source_root.clone(),
+      file: self.file.clone()
+    })
+  }
+}
+
+/**
+ * Iterates over the mappings in generated order, like `Cache::mappings`.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::consume;
+ *
+ * let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": ["x"], "mappings": "AAAAA,CAAC,CAACA" }"#).unwrap();
+ * let named = (&cache).into_iter().filter(|mapping| !mapping.name.is_empty()).count();
+ * assert!(named == 2);
+ * for mapping in &cache {
+ *   assert!(&*mapping.source == "a.js");
+ * }
+ * ```
+ */
+impl<'a> IntoIterator for &'a Cache {
+  type Item = &'a Mapping;
+  type IntoIter = slice::Iter<'a, Mapping>;
+
+  fn into_iter(self) -> slice::Iter<'a, Mapping> {
+    self.generated_mappings.iter()
   }
+}
+
+/**
+ * Takes the mappings of a cache, like `Cache::into_mappings`.
+ */
+impl From<Cache> for Vec<Mapping> {
+  fn from(cache: Cache) -> Vec<Mapping> {
+    cache.into_mappings()
+  }
+}
+
+/**
+ * Parses a source map from its JSON, like `consume`.
+ *
+ * # Examples
+ *
+ * ```
+ * use std::convert::TryFrom;
+ * use js_source_mapper::Cache;
+ *
+ * let json = r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AACA" }"#;
+ * let cache = Cache::try_from(json).unwrap();
+ * assert!(cache.mapping_for_generated_position(1, 0).original.line == 2);
+ * ```
+ */
+impl<'a> TryFrom<&'a str> for Cache {
+  type Error = Error;
 
-  fn sort_key(mapping: &Mapping) -> (u32, u32) {
-    (mapping.generated.line, mapping.generated.column)
+  fn try_from(source_map_json: &'a str) -> Result<Cache, Error> {
+    consume(source_map_json)
   }
-  generated_mappings.sort_by(|a, b| sort_key(a).cmp(&sort_key(b)));
-
-  Ok(Cache {
-    generated_mappings: generated_mappings,
-    source_root: match &source_map.sourceRoot {
-      &Some(ref x) => x.to_owned(),
-      &None => "".into()
-    }
-  })
 }
 
+/**
+ * Parses a source map from its JSON, like `consume`.
+ *
+ * # Examples
+ *
+ * ```
+ * # #[cfg(feature = "std")] {
+ * use std::error::Error;
+ * use js_source_mapper::Cache;
+ *
+ * fn sources(json: &str) -> Result<Vec<String>, Box<dyn Error>> {
+ *   let cache: Cache = json.parse()?;
+ *   Ok(cache.sources().to_vec())
+ * }
+ *
+ * assert!(sources(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AACA" }"#).unwrap() == ["a.js"]);
+ * assert!(sources("{").is_err());
+ * # }
+ * ```
+ */
+impl FromStr for Cache {
+  type Err = Error;
 
-impl Cache {
-  /**
-   * Returns the original source, line, column and name information for the generated
-   * source's line and column positions provided.
-   *
-   * # Arguments
-   *
-   * * line: The line number in the generated source.
-   * * column: The column number in the generated source.
-   *
-   * # Examples
-   *
-   * ```
-   * use js_source_mapper::consume;
-   *
-   * let cache = consume(r#"{ "version": 3, "file": "foo.js", "sources": ["source.js"], "names": ["name1", "name1", "name3"], "mappings": ";EAACA;;IAEEA;;MAEEE", "sourceRoot": "http://example.com" }"#).unwrap();
-   *
-   * println!("{:?}", cache.mapping_for_generated_position(2, 2));
-   * // => Mapping {
-   * //   generated: CodePosition { line: 2, column: 2 },
-   * //   original: CodePosition { line: 1, column: 1 },
-   * //   source: "source.js"
-   * //   name: "name1"
-   * // }
-   * ```
-   *
-   */
-
-  pub fn mapping_for_generated_position(&self, line: u32, column: u32) -> Mapping {
-    let matcher = |mapping: &Mapping| -> Ordering {
-      (mapping.generated.line, mapping.generated.column).cmp(&(line, column))
-    };
-    let mappings = &self.generated_mappings;
-    match mappings.binary_search_by(matcher) {
-      Ok(index) => &self.generated_mappings[index],
-      Err(index) => &self.generated_mappings[if index >= mappings.len() { mappings.len() - 1 } else { index }]
-    }.clone()
+  fn from_str(source_map_json: &str) -> Result<Cache, Error> {
+    consume(source_map_json)
   }
 }
 
+#[cfg(all(test, feature = "serde", feature = "serde-json"))]
+extern crate serde_json;
+
 #[cfg(test)]
 macro_rules! assert_equal_mappings(
   ($a:expr, $b:expr) => (
     if $a != $b {
-      panic!(format!("\n\n{:?}\n\n!=\n\n{:?}\n\n", $a, $b));
+      panic!("\n\n{:?}\n\n!=\n\n{:?}\n\n", $a, $b);
     }
   );
 );
@@ -364,7 +1706,7 @@ fn test_source_map_issue_72_duplicate_names() {
 
 #[test]
 fn it_allows_omitting_source_root() {
-  let cache_result: Result<Cache, String> = consume(r#"{
+  let cache_result: Result<Cache, Error> = consume(r#"{
     "version": 3,
     "file": "foo.js",
     "sources": ["source.js"],
@@ -373,7 +1715,7 @@ fn it_allows_omitting_source_root() {
   }"#);
   match cache_result {
     Ok(_) => {},
-    Err(s) => panic!(format!("Error due to omitting: '{}'", s))
+    Err(s) => panic!("Error due to omitting: '{}'", s)
   }
 }
 
@@ -448,7 +1790,7 @@ fn it_does_not_panic_on_invalid_bit_shifts() {
     "mappings": "00000001",
     "sourceRoot": "http://example.com"
   }"#) {
-    Err(s) => assert!(s == "Invalid VLQ mapping field"),
+    Err(s) => assert!(s.to_string() == "Truncated VLQ mapping field at the end of segment \"00000001\" on generated line 1"),
     _ => panic!("Invalid source map should fail to consume")
   };
 }
@@ -464,7 +1806,712 @@ fn it_does_not_panic_from_add_overflow() {
     "mappings": "BBDDDDDDBBBBBBBc;*;ZZBBBBBBBBBBv",
     "sourceRoot": "http://example.com"
   }"#) {
-    Err(s) => assert!(s == "Line number overflowed"),
+    Err(s) => assert!(s.to_string() == r#"Found more than five fields in segment "BBDDDDDDBBBBBBBc" on generated line 1"#),
+    _ => panic!("Invalid source map should fail to consume")
+  };
+  // An original line of -1 overflows once it is made 1-based
+  match consume(r#"{ "version": 3, "sources": ["source.js"], "names": [], "mappings": "AADA" }"#) {
+    Err(s) => assert!(s.to_string() == "Line number overflowed"),
+    _ => panic!("Invalid source map should fail to consume")
+  };
+}
+
+#[test]
+fn it_offsets_generated_positions() {
+  let cache = consume(r#"{
+    "version": 3,
+    "file": "foo.js",
+    "sources": ["source.js"],
+    "names": [],
+    "mappings": "CAAA,EAAC;EACA"
+  }"#).unwrap();
+  let shifted = cache.offset_generated(3, 10).unwrap();
+
+  let positions: Vec<(u32, u32)> = shifted.generated_mappings.iter()
+    .map(|m| (m.generated.line, m.generated.column))
+    .collect();
+  assert_eq!(positions, vec![(4, 11), (4, 13), (5, 2)]);
+
+  // Original positions are untouched
+  assert_eq!(shifted.mapping_for_generated_position(4, 13).original, CodePosition { line: 1, column: 1 });
+  assert_eq!(shifted.mapping_for_generated_position(5, 2).original, CodePosition { line: 2, column: 1 });
+}
+
+#[test]
+fn it_only_applies_column_offset_to_the_first_line() {
+  let cache = consume(r#"{
+    "version": 3,
+    "file": "foo.js",
+    "sources": ["source.js"],
+    "names": [],
+    "mappings": ";CAAA"
+  }"#).unwrap();
+  let shifted = cache.offset_generated(0, 10).unwrap();
+  let mapping = &shifted.generated_mappings[0];
+  assert_eq!(mapping.generated, CodePosition { line: 2, column: 1 });
+}
+
+#[test]
+fn it_rejects_offsets_past_the_largest_position() {
+  let cache = consume(r#"{ "version": 3, "sources": ["source.js"], "names": [], "mappings": "CAAA;EACA" }"#).unwrap();
+  // The largest offsets that still fit
+  let shifted = cache.offset_generated(u32::MAX - 2, u32::MAX - 1).unwrap();
+  assert_eq!(shifted.mappings().last().unwrap().generated, CodePosition { line: u32::MAX, column: 2 });
+  for &(lines, columns) in &[(u32::MAX - 1, 0), (0, u32::MAX)] {
+    let err = cache.offset_generated(lines, columns).unwrap_err();
+    assert_eq!(err.code(), ErrorCode::InvalidPosition);
+  }
+
+  let index_map = r#"{
+    "version": 3,
+    "sections": [{ "offset": { "line": 4294967295, "column": 0 }, "map": { "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA" } }]
+  }"#;
+  let err = consume(index_map).unwrap_err();
+  assert_eq!(err.code(), ErrorCode::InvalidPosition);
+  assert!(err.to_string().starts_with("Invalid index map: section 0: "), "{}", err);
+}
+
+#[test]
+fn it_resolves_lookups_by_rebased_source_names() {
+  let mut cache = consume(r#"{
+    "version": 3,
+    "file": "foo.js",
+    "sources": ["/home/runner/work/app/src/a.js", "/home/runner/work/app/src/b.js", "vendor/c.js"],
+    "names": [],
+    "mappings": "AAAA;ACAA;ACAA",
+    "sourceRoot": "/home/runner/work/app/"
+  }"#).unwrap();
+
+  assert_eq!(&*cache.mapping_for_generated_position(1, 0).source, "/home/runner/work/app/src/a.js");
+  assert_eq!(&*cache.mapping_for_generated_position(2, 0).source, "/home/runner/work/app/src/b.js");
+  assert_eq!(&*cache.mapping_for_generated_position(3, 0).source, "vendor/c.js");
+
+  cache.rebase_sources("/home/runner/work/app/", "");
+
+  assert_eq!(&*cache.mapping_for_generated_position(1, 0).source, "src/a.js");
+  assert_eq!(&*cache.mapping_for_generated_position(2, 0).source, "src/b.js");
+  assert_eq!(&*cache.mapping_for_generated_position(3, 0).source, "vendor/c.js");
+  assert_eq!(cache.sources(), ["src/a.js", "src/b.js", "vendor/c.js"]);
+  assert_eq!(cache.source_root, "");
+}
+
+#[test]
+fn it_filters_mappings_by_source() {
+  let json = r#"{
+    "version": 3,
+    "file": "foo.js",
+    "sources": ["app.js", "sdk/a.js", "sdk/b.js", "lib.js"],
+    "names": ["appName", "sdkName", "libName"],
+    "mappings": "AAAAA,ECAAC;ACAA,C,ECAAC;AAAA"
+  }"#;
+  let cache = consume(json).unwrap();
+  let filtered = cache.filter_sources(|source| !source.starts_with("sdk/"), false);
+
+  assert_eq!(filtered.sources(), ["app.js", "lib.js"]);
+  assert_eq!(filtered.names(), ["appName", "libName"]);
+
+  // Retained regions resolve identically
+  for &(line, column) in &[(1, 0), (2, 3), (3, 0)] {
+    assert_equal_mappings!(filtered.mapping_for_generated_position(line, column), cache.mapping_for_generated_position(line, column));
+  }
+
+  // Removed regions return nothing, rather than the next retained mapping
+  for &(line, column) in &[(1, 1), (1, 2), (2, 0)] {
+    let mapping = filtered.mapping_for_generated_position(line, column);
+    assert!(mapping.source.is_empty(), "{}:{} maps to {}", line, column, mapping.source);
+    assert!(mapping.name.is_empty());
+  }
+  assert_eq!(filtered.mapping_for_generated_position(2, 0).generated, CodePosition { line: 2, column: 0 });
+}
+
+#[test]
+fn it_optionally_keeps_unmapped_mappings_when_filtering() {
+  let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA,C" }"#).unwrap();
+  assert_eq!(cache.filter_sources(|_| true, true).generated_mappings.len(), 2);
+  assert_eq!(cache.filter_sources(|_| true, false).generated_mappings.len(), 1);
+}
+
+#[test]
+fn it_does_not_panic_when_filtering_out_everything() {
+  let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA" }"#).unwrap();
+  let filtered = cache.filter_sources(|_| false, false);
+  assert_eq!(&*filtered.mapping_for_generated_position(1, 0).source, "");
+  let empty = filtered.filter_sources(|_| true, false);
+  assert!(empty.is_empty());
+  assert_eq!(&*empty.mapping_for_generated_position(1, 0).source, "");
+}
+
+#[test]
+fn it_accepts_the_url_safe_base64_alphabet() {
+  let standard = consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA,+BAAA,/AAAA" }"#).unwrap();
+  let mixed = consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA,-BAAA,_AAAA" }"#).unwrap();
+  assert_eq!(standard.generated_mappings, mixed.generated_mappings);
+  assert_eq!(mixed.generated_mappings[1].generated.column, 16);
+  assert_eq!(mixed.generated_mappings[2].generated.column, 31);
+}
+
+#[test]
+fn it_explains_confusable_characters_in_mappings() {
+  match consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA=" }"#) {
+    Err(s) => assert_eq!(s.to_string(), "Invalid VLQ mapping field: unexpected character '=' (base 64 padding is not allowed in mappings)"),
+    _ => panic!("Invalid source map should fail to consume")
+  };
+  match consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA, AAAA" }"#) {
+    Err(s) => assert_eq!(s.to_string(), "Invalid VLQ mapping field: unexpected character ' ' (whitespace is not allowed in mappings)"),
+    _ => panic!("Invalid source map should fail to consume")
+  };
+}
+
+#[cfg(all(feature = "serde", feature = "serde-json"))]
+#[test]
+fn it_serializes_mappings() {
+  let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": ["x"], "mappings": "CAAAA" }"#).unwrap();
+  let mapping = cache.mapping_for_generated_position(1, 1);
+  let json = serde_json::to_string(&mapping).unwrap();
+  assert_eq!(json, r#"{"generated":{"line":1,"column":1},"original":{"line":1,"column":0},"source":"a.js","name":"x"}"#);
+  assert_eq!(serde_json::from_str::<Mapping>(&json).unwrap(), mapping);
+}
+
+#[cfg(all(feature = "serde", feature = "serde-json"))]
+#[test]
+fn it_serializes_cache_summaries() {
+  let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": ["x"], "mappings": "CAAAA,C", "sourceRoot": "/" }"#).unwrap();
+  assert_eq!(
+    serde_json::to_string(&cache.summary()).unwrap(),
+    r#"{"sources":["a.js"],"names":["x"],"source_root":"/","mapping_count":2}"#
+  );
+}
+
+#[test]
+fn it_sorts_mappings_canonically() {
+  let mapping = |generated: (u32, u32), original: (u32, u32), source: &str| Mapping {
+    generated: CodePosition { line: generated.0, column: generated.1 },
+    original: CodePosition { line: original.0, column: original.1 },
+    source: source.into(),
+    name: "".into()
+  };
+  let mut mappings = vec![
+    mapping((2, 0), (1, 0), "a.js"),
+    mapping((1, 0), (3, 0), "a.js"),
+    mapping((1, 0), (1, 2), "b.js"),
+    mapping((1, 0), (1, 2), "a.js"),
+    mapping((1, 0), (3, 0), "a.js")
+  ];
+  assert!(!is_sorted_by_generated(&mappings));
+
+  sort_mappings(&mut mappings);
+  dedup_mappings(&mut mappings);
+  assert!(is_sorted_by_generated(&mappings));
+  assert_eq!(mappings, vec![
+    mapping((1, 0), (1, 2), "a.js"),
+    mapping((1, 0), (1, 2), "b.js"),
+    mapping((1, 0), (3, 0), "a.js"),
+    mapping((2, 0), (1, 0), "a.js")
+  ]);
+}
+
+#[test]
+fn it_builds_caches_from_mappings() {
+  let mapping = |generated: (u32, u32), source: &str, name: &str| Mapping {
+    generated: CodePosition { line: generated.0, column: generated.1 },
+    original: CodePosition { line: 1, column: 0 },
+    source: source.into(),
+    name: name.into()
+  };
+  let cache = Cache::from_mappings(vec![
+    mapping((2, 0), "b.js", "y"),
+    mapping((1, 4), "a.js", ""),
+    mapping((1, 0), "b.js", "x")
+  ], "http://example.com").unwrap();
+
+  assert_eq!(cache.sources(), ["b.js", "a.js"]);
+  assert_eq!(cache.names(), ["x", "y"]);
+  assert_eq!(cache.source_root, "http://example.com");
+  assert_equal_mappings!(cache.mapping_for_generated_position(1, 4), mapping((1, 4), "a.js", ""));
+}
+
+#[test]
+fn it_rejects_invalid_mappings_when_building_caches() {
+  assert!(Cache::from_mappings(vec![], "").is_err());
+
+  let mapping = Mapping {
+    generated: CodePosition { line: 0, column: 0 },
+    original: CodePosition { line: 1, column: 0 },
+    source: "a.js".into(),
+    name: "".into()
+  };
+  assert!(Cache::from_mappings(vec![mapping], "").is_err());
+}
+
+#[test]
+fn it_only_retains_sources_content_on_request() {
+  let json = r#"{ "version": 3, "sources": ["a.js", "b.js", "c.js"], "sourcesContent": ["let a;", null], "names": [], "mappings": "AAAA;ACAA" }"#;
+  assert_eq!(consume(json).unwrap().source_content("a.js"), None);
+
+  let mut cache = consume_with_sources_content(json).unwrap();
+  assert_eq!(cache.source_content("a.js"), Some("let a;"));
+  assert_eq!(cache.source_content("b.js"), None);
+  assert_eq!(cache.source_content("c.js"), None);
+
+  cache.map_sources(|source| format!("src/{}", source));
+  assert_eq!(cache.source_content("src/a.js"), Some("let a;"));
+  assert_eq!(cache.filter_sources(|source| source != "src/a.js", true).source_content("src/a.js"), None);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn it_clears_names() {
+  let mut cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": ["x", "y"], "mappings": "AAAAA,EAAEC;AACAD" }"#).unwrap();
+  cache.clear_names();
+  assert!(cache.names().is_empty());
+  assert!(cache.mappings().iter().all(|mapping| mapping.name.is_empty()));
+  let mapping = cache.mapping_for_generated_position(2, 0);
+  assert_eq!((&*mapping.source, mapping.original.line, mapping.original.column), ("a.js", 2, 2));
+  assert_eq!(cache.to_json().unwrap(), r#"{"version":3,"sources":["a.js"],"names":[],"mappings":"AAAA,EAAE;AACA"}"#);
+}
+
+#[test]
+fn it_reads_the_ignore_list() {
+  let mut cache = consume(r#"{ "version": 3, "sources": ["app.js", "vendor.js", "lib.js"], "names": [], "mappings": "AAAA;ACAA;ACAA", "ignoreList": [2, 1, 7] }"#).unwrap();
+  assert_eq!(cache.ignored_sources(), ["vendor.js", "lib.js"]);
+  assert!(!cache.is_ignored("app.js"));
+
+  cache.set_ignored("vendor.js", false);
+  cache.map_sources(|source| format!("/{}", source));
+  assert_eq!(cache.ignored_sources(), ["/lib.js"]);
+
+  let legacy = consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA", "x_google_ignoreList": [0] }"#).unwrap();
+  assert!(legacy.is_ignored("a.js"));
+}
+
+#[test]
+fn it_rejects_truncated_vlqs() {
+  match consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA;AACg" }"#) {
+    Err(s) => assert_eq!(s.to_string(), "Truncated VLQ mapping field at the end of segment \"AACg\" on generated line 2"),
+    _ => panic!("Truncated source map should fail to consume")
+  };
+  // Too large rather than truncated
+  match consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "gggggggB" }"#) {
+    Err(s) => assert_eq!(s.to_string(), "Invalid VLQ mapping field"),
     _ => panic!("Invalid source map should fail to consume")
   };
 }
+
+#[cfg(feature = "std")]
+#[test]
+fn it_reads_the_file() {
+  let cache = consume(r#"{ "version": 3, "file": "bundle.min.js", "sources": ["a.js"], "names": [], "mappings": "AAAA" }"#).unwrap();
+  assert_eq!(cache.file, "bundle.min.js");
+  assert!(cache.to_json().unwrap().contains(r#""file":"bundle.min.js""#));
+}
+
+#[test]
+fn it_returns_the_mappings_on_a_generated_line() {
+  let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA,CAAC;;AACA,CAAC,CAAC" }"#).unwrap();
+  assert_eq!(cache.mappings_for_generated_line(1).len(), 2);
+  assert!(cache.mappings_for_generated_line(2).is_empty());
+  assert_eq!(cache.mappings_for_generated_line(3).iter().map(|mapping| mapping.generated.column).collect::<Vec<_>>(), [0, 1, 2]);
+  assert!(cache.mappings_for_generated_line(4).is_empty());
+}
+
+#[test]
+fn it_consumes_index_maps() {
+  let cache = consume_with_sources_content(r#"{
+    "version": 3,
+    "file": "bundle.js",
+    "sections": [
+      { "offset": { "line": 0, "column": 0 }, "map": { "version": 3, "sources": ["a.js"], "sourcesContent": ["let a;"], "names": ["x"], "mappings": "AAAAA;AACA" } },
+      { "offset": { "line": 1, "column": 10 }, "map": { "version": 3, "sourceRoot": "lib", "sources": ["b.js"], "names": [], "mappings": "AAAA;AACA", "ignoreList": [0] } },
+      { "offset": { "line": 5, "column": 0 }, "map": { "version": 3, "sources": [], "names": [], "mappings": "" } }
+    ]
+  }"#).unwrap();
+
+  assert_eq!(cache.file, "bundle.js");
+  assert_eq!(cache.source_root, "");
+  assert_eq!(cache.sources(), ["a.js", "lib/b.js"]);
+  assert_eq!(cache.source_content("a.js"), Some("let a;"));
+  assert_eq!(cache.ignored_sources(), ["lib/b.js"]);
+  let positions: Vec<(u32, u32, &str)> = cache.mappings().iter()
+    .map(|mapping| (mapping.generated.line, mapping.generated.column, &*mapping.source))
+    .collect();
+  assert_eq!(positions, [(1, 0, "a.js"), (2, 0, "a.js"), (2, 10, "lib/b.js"), (3, 0, "lib/b.js")]);
+}
+
+#[test]
+fn it_rejects_invalid_index_maps() {
+  let section = |line: u32| format!(r#"{{ "offset": {{ "line": {}, "column": 0 }}, "map": {{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA" }} }}"#, line);
+  assert!(consume(&format!(r#"{{ "version": 3, "sections": [{}, {}] }}"#, section(1), section(1))).is_err());
+  assert!(consume(r#"{ "version": 3, "sections": [{ "offset": { "line": 0, "column": 0 }, "url": "a.js.map" }] }"#).unwrap_err().to_string().contains("a.js.map"));
+  assert!(consume(r#"{ "version": 3, "sections": [] }"#).is_err());
+  assert!(consume(&format!(r#"{{ "version": 3, "sections": [{}, {}] }}"#, section(0), section(1))).is_ok());
+}
+
+#[cfg(all(feature = "log", feature = "std"))]
+#[test]
+fn it_logs_skipped_ignore_list_indices() {
+  use test::captured_logs;
+
+  captured_logs();
+  let cache = consume(r#"{ "version": 3, "sources": ["logged.js"], "names": [], "mappings": "AAAA", "ignoreList": [0, 7] }"#).unwrap();
+  assert!(cache.is_ignored("logged.js"));
+  let expected = "WARN Skipping ignoreList index 7, which is outside the sources table of 1 sources";
+  assert!(captured_logs().iter().any(|message| message == expected), "{:?}", captured_logs());
+}
+
+#[test]
+fn it_iterates_over_mappings() {
+  let cache = consume(r#"{ "version": 3, "sources": ["a.js", "b.js"], "names": [], "mappings": "AAAA,CCAA;AAAA" }"#).unwrap();
+  let positions: Vec<(u32, u32)> = (&cache).into_iter().map(|mapping| (mapping.generated.line, mapping.generated.column)).collect();
+  assert_eq!(positions, [(1, 0), (1, 1), (2, 0)]);
+  assert_eq!((cache.len(), cache.is_empty()), (3, false));
+
+  let filtered = cache.filter_sources(|_| true, true);
+  assert_eq!((filtered.len(), filtered.is_empty()), (3, false));
+  // A cache of unmapped mappings only is empty once they are dropped
+  let unmapped = consume(r#"{ "version": 3, "sources": [], "names": [], "mappings": "A,C" }"#).unwrap();
+  let filtered = unmapped.filter_sources(|_| true, false);
+  assert_eq!((filtered.len(), filtered.is_empty()), (0, true));
+  assert_eq!((&filtered).into_iter().count(), 0);
+}
+
+#[test]
+fn it_hands_over_the_mappings() {
+  let json = r#"{ "version": 3, "file": "app.js", "sourceRoot": "src", "sources": ["a.js", "b.js"], "names": ["x"], "mappings": "AAAAA,CCAA;AAAA" }"#;
+  let cache = consume(json).unwrap();
+  let mappings = cache.mappings().to_vec();
+  assert_eq!(cache.clone().into_mappings(), mappings);
+  assert_eq!(Vec::from(cache.clone()), mappings);
+
+  let parts = cache.clone().into_parts();
+  assert_eq!(parts.mappings, mappings);
+  assert_eq!((parts.sources, parts.names), (vec!["a.js".to_owned(), "b.js".to_owned()], vec!["x".to_owned()]));
+  assert_eq!((parts.source_root.as_str(), parts.file.as_str()), ("src", "app.js"));
+
+  let rebuilt = Cache::from_mappings(cache.clone().into_mappings(), cache.source_root.clone()).unwrap();
+  assert_eq!(rebuilt, cache);
+}
+
+#[test]
+fn it_shares_sources_and_names_between_mappings() {
+  let mut cache = consume(r#"{ "version": 3, "sources": ["a.js", "b.js", "a.js"], "names": ["x"], "mappings": "AAAAA,CAAA,CCAAA;ACAA" }"#).unwrap();
+  let mappings = cache.mappings();
+  assert!(Arc::ptr_eq(&mappings[0].source, &mappings[1].source));
+  // Duplicate entries of the sources table are shared too
+  assert!(Arc::ptr_eq(&mappings[0].source, &mappings[3].source));
+  assert!(Arc::ptr_eq(&mappings[0].name, &mappings[2].name));
+  assert!(Arc::ptr_eq(&mappings[1].name, &mappings[3].name) && mappings[1].name.is_empty());
+
+  cache.map_sources(|source| source.to_uppercase());
+  let mappings = cache.mappings();
+  assert_eq!(&*mappings[0].source, "A.JS");
+  assert!(Arc::ptr_eq(&mappings[0].source, &mappings[1].source));
+}
+
+#[test]
+fn it_estimates_its_memory_usage() {
+  let source = "a-rather-long-path/to/the/original/source.js";
+  let json = format!(r#"{{ "version": 3, "sources": ["{}"], "names": [], "mappings": "{}" }}"#, source, "AAAA;".repeat(1000));
+  let cache = consume(&json).unwrap();
+  let usage = cache.memory_usage();
+  assert!(usage.mappings >= 1000 * mem::size_of::<Mapping>());
+  assert!(usage.index >= 1000 * mem::size_of::<u32>());
+  assert_eq!(usage.sources_content, 0);
+  assert_eq!(usage.total, usage.mappings + usage.strings + usage.sources_content + usage.index + mem::size_of::<Cache>());
+  // The source is stored twice, in the table and shared by the mappings
+  assert!(usage.strings >= 2 * source.len() && usage.strings < 4 * source.len() + 200, "{:?}", usage);
+
+  // Mappings with a copy of the source each take far more
+  let unshared: Vec<Mapping> = cache.mappings().iter()
+    .map(|mapping| Mapping { source: (*mapping.source).into(), name: (*mapping.name).into(), ..mapping.clone() })
+    .collect();
+  let unshared = Cache::from_mappings(unshared, "").unwrap().memory_usage();
+  assert!(unshared.strings > usage.strings + 999 * source.len());
+
+  let with_content = consume_with_sources_content(&json.replace(r#""names""#, r#""sourcesContent": ["let a;"], "names""#)).unwrap();
+  assert!(with_content.memory_usage().sources_content >= source.len() + "let a;".len());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn it_drops_unreferenced_sources_and_names() {
+  // Sources 1, 3 and 4 and name 0 are unreferenced, and source 5 repeats source 0
+  let json = r#"{
+    "version": 3,
+    "sources": ["a.js", "shaken.js", "b.js", "", "shaken.css", "a.js"],
+    "sourcesContent": ["a();", "shaken();", "b();", null, null, null],
+    "names": ["unused", "x", "y"],
+    "ignoreList": [1, 2],
+    "mappings": "AAAAC,CEAAC;AGAAA"
+  }"#;
+  let mut cache = consume_with_sources_content(json).unwrap();
+  let mappings = cache.mappings().to_vec();
+  let usage = cache.memory_usage();
+  cache.drop_unreferenced();
+
+  assert_eq!(cache.mappings(), &mappings[..]);
+  assert_eq!(cache.sources(), ["a.js", "b.js"]);
+  assert_eq!(cache.names(), ["x", "y"]);
+  assert_eq!(cache.declared_sources(), [Some("a.js"), None, Some("b.js"), None, None, None]);
+  assert_eq!(cache.declared_names(), [None, Some("x"), Some("y")]);
+  assert_eq!((cache.source_content("a.js"), cache.source_content("shaken.js")), (Some("a();"), None));
+  assert_eq!(cache.ignored_sources(), ["b.js"]);
+  assert!(cache.memory_usage().strings < usage.strings);
+  let written = consume(&cache.to_json().unwrap()).unwrap();
+  assert_eq!((written.sources(), written.mappings()), (cache.sources(), &mappings[..]));
+
+  // Dropping again keeps the indices of the first declaration
+  let mut filtered = cache.filter_sources(|source| source == "b.js", false);
+  assert_eq!(filtered.declared_sources(), [Some("b.js")]);
+  cache.clear_names();
+  cache.drop_unreferenced();
+  assert_eq!(cache.declared_sources(), [Some("a.js"), None, Some("b.js"), None, None, None]);
+  assert_eq!(cache.declared_names(), [None, None, None]);
+  assert_eq!(cache.offset_generated(1, 0).unwrap().declared_sources(), cache.declared_sources());
+  filtered.drop_unreferenced();
+  assert_eq!(filtered.declared_sources(), [Some("b.js")]);
+}
+
+#[test]
+fn it_converts_json_into_caches() {
+  let json = r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AACA" }"#;
+  assert_eq!(Cache::try_from(json).unwrap().mappings(), consume(json).unwrap().mappings());
+  assert_eq!(json.parse::<Cache>().unwrap().mappings(), consume(json).unwrap().mappings());
+  assert_eq!(Cache::try_from(r#"{ "version": 2 }"#).unwrap_err().to_string(), consume(r#"{ "version": 2 }"#).unwrap_err().to_string());
+  assert!("".parse::<Cache>().is_err());
+}
+
+#[test]
+fn it_parses_mappings_without_json() {
+  let json = r#"{ "version": 3, "sources": ["a.js", "b.js"], "names": ["x"], "sourceRoot": "src", "mappings": "AAAAA,EAAE;ACCA" }"#;
+  let sources = vec!["a.js".to_owned(), "b.js".to_owned()];
+  let names = vec!["x".to_owned()];
+  let cache = parse_mappings_str("AAAAA,EAAE;ACCA", &sources, &names, "src").unwrap();
+  let consumed = consume(json).unwrap();
+  assert_eq!(cache.mappings(), consumed.mappings());
+  assert_eq!((cache.sources(), cache.names(), cache.source_root.as_str()), (consumed.sources(), consumed.names(), "src"));
+
+  assert_eq!(
+    parse_mappings_str("ACAA", &sources[..1], &names, "").unwrap_err().to_string(),
+    "Invalid source map: reference to source index 1 when source list length is 1"
+  );
+  assert_eq!(
+    parse_mappings_str("AAAAA", &sources, &[], "").unwrap_err().to_string(),
+    "Invalid source map: reference to name index 0 when name list length is 0"
+  );
+  assert!(parse_mappings_str("AA", &sources, &names, "").is_err());
+  assert!(parse_mappings_str("AAA", &sources, &names, "").is_err());
+  assert!(parse_mappings_str("", &sources, &names, "").unwrap().mappings().is_empty());
+}
+
+#[test]
+fn it_parses_headers_without_mappings() {
+  let header = parse_header(r#"{ "version": 3, "file": "app.js", "sourceRoot": "src", "sources": ["a.js", "b.js"], "names": ["x"], "mappings": "not decoded", "x_google_ignoreList": [1] }"#).unwrap();
+  assert_eq!(header, RawSourceMap {
+    version: 3,
+    file: Some("app.js".into()),
+    source_root: Some("src".into()),
+    sources: vec!["a.js".into(), "b.js".into()],
+    names: vec!["x".into()],
+    ignore_list: vec![1],
+    has_sources_content: false
+  });
+
+  let header = parse_header(r#"{ "version": 2, "sources": [], "names": [], "sourcesContent": [null] }"#).unwrap();
+  assert_eq!((header.version, header.file, header.has_sources_content), (2, None, true));
+  assert!(!parse_header(r#"{ "version": 3, "sources": [], "names": [], "sourcesContent": null }"#).unwrap().has_sources_content);
+  assert!(parse_header(r#"{ "version": 3, "names": [] }"#).is_err());
+}
+
+#[test]
+fn it_visits_mappings_without_collecting_them() {
+  let json = r#"{ "version": 3, "sources": ["a.js", "b.js"], "names": ["x"], "mappings": "AAAAA,CCAA;;ADCA,EAAA" }"#;
+  let mut visited: Vec<Mapping> = Vec::new();
+  let summary = consume_with_visitor(json, |mapping| {
+    visited.push(mapping.clone());
+    ControlFlow::Continue(())
+  }).unwrap();
+  assert_eq!(visited, consume(json).unwrap().mappings());
+  assert_eq!(summary, ConsumeSummary { mapping_count: 4, line_count: 3, stopped_early: false });
+
+  // Decoding stops at the break, before the malformed segment
+  let malformed = r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA;AACA;!!!!" }"#;
+  assert!(consume(malformed).is_err());
+  let summary = consume_with_visitor(malformed, |mapping| {
+    if mapping.generated.line == 2 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+  }).unwrap();
+  assert_eq!(summary, ConsumeSummary { mapping_count: 2, line_count: 2, stopped_early: true });
+  assert!(consume_with_visitor(malformed, |_| ControlFlow::Continue(())).is_err());
+  assert!(consume_with_visitor(r#"{ "version": 2, "sources": [], "names": [], "mappings": "" }"#, |_| ControlFlow::Continue(())).is_err());
+
+  let index_map = r#"{ "version": 3, "sections": [
+    { "offset": { "line": 0, "column": 0 }, "map": { "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA" } },
+    { "offset": { "line": 2, "column": 0 }, "map": { "version": 3, "sources": ["b.js"], "names": [], "mappings": "AAAA" } }
+  ] }"#;
+  let mut sources: Vec<String> = Vec::new();
+  let summary = consume_with_visitor(index_map, |mapping| {
+    sources.push(mapping.source.to_string());
+    ControlFlow::Continue(())
+  }).unwrap();
+  assert_eq!(sources, ["a.js", "b.js"]);
+  assert_eq!(summary, ConsumeSummary { mapping_count: 2, line_count: 3, stopped_early: false });
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn it_orders_mappings_canonically() {
+  use std::collections::{BTreeSet, HashSet};
+
+  let mapping = |line: u32, column: u32, source: &str| Mapping {
+    generated: CodePosition { line, column },
+    original: CodePosition { line: 1, column: 0 },
+    source: source.into(),
+    name: "".into()
+  };
+  assert!(CodePosition { line: 1, column: 9 } < CodePosition { line: 2, column: 0 });
+  assert!(CodePosition { line: 2, column: 0 } < CodePosition { line: 2, column: 1 });
+
+  let mappings = vec![mapping(2, 0, "a.js"), mapping(1, 5, "b.js"), mapping(1, 5, "a.js"), mapping(1, 0, "a.js")];
+  let mut sorted = mappings.clone();
+  sorted.sort_by(compare_mappings);
+  assert_eq!(mappings.iter().cloned().collect::<BTreeSet<_>>().into_iter().collect::<Vec<_>>(), sorted);
+  assert_eq!(mappings.iter().collect::<HashSet<_>>().len(), 4);
+  assert_eq!(mappings.iter().chain(&mappings).collect::<HashSet<_>>().len(), 4);
+}
+
+#[test]
+fn it_parses_positions() {
+  assert_eq!("10:42".parse::<CodePosition>().unwrap(), CodePosition { line: 10, column: 42 });
+  assert_eq!("10,42".parse::<CodePosition>().unwrap(), CodePosition { line: 10, column: 42 });
+  let message = |position: &str| position.parse::<CodePosition>().unwrap_err().to_string();
+  assert_eq!(message("10"), r#"invalid position "10", expected <line>:<column>"#);
+  assert_eq!(message("10:"), r#"invalid position "10:", the column is not a number"#);
+  assert_eq!(message("a:1"), r#"invalid position "a:1", the line is not a number"#);
+  assert_eq!(message("1:-1"), r#"invalid position "1:-1", the column is not a number"#);
+  assert_eq!(message("0:42"), r#"invalid position "0:42", lines start from 1"#);
+  for position in ["1:0", "1523:88", "4294967295:4294967295"] {
+    assert_eq!(position.parse::<CodePosition>().unwrap().to_string(), position);
+  }
+}
+
+#[cfg(all(test, feature = "std"))]
+use std::thread;
+
+#[test]
+fn it_is_send_and_sync() {
+  fn assert_send_sync<T: Send + Sync>() {}
+  assert_send_sync::<Cache>();
+  assert_send_sync::<Mapping>();
+  assert_send_sync::<Error>();
+}
+
+#[test]
+fn it_compares_clones_and_summarizes_caches() {
+  let cache = consume(r#"{ "version": 3, "sources": ["a.js", "b.js"], "names": ["x"], "mappings": "AAAAA,CCAA;ADCA" }"#).unwrap();
+  // The same mappings, with the tables and the segments of the first line in another order
+  let reordered = consume(r#"{ "version": 3, "sources": ["b.js", "a.js"], "names": ["x"], "mappings": "CAAA,DCAAA;AACA" }"#).unwrap();
+  assert_ne!(cache.sources(), reordered.sources());
+  assert_eq!(cache, reordered);
+
+  let mut rooted = cache.clone();
+  assert_eq!(rooted, cache);
+  rooted.source_root = "src".into();
+  assert_ne!(rooted, cache);
+  assert_ne!(cache.offset_generated(1, 0).unwrap(), cache);
+
+  let debug = format!("{:?}", cache);
+  assert!(debug.starts_with(r#"Cache { mapping_count: 3, source_count: 2, name_count: 1, source_root: "", file: "", first_mapping: Some(Mapping { generated: CodePosition { line: 1, column: 0 }"#));
+  assert!(debug.contains("last_mapping: Some(Mapping { generated: CodePosition { line: 2, column: 0 }"));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn it_answers_concurrent_lookups() {
+  let lines: Vec<String> = (0..200).map(|_| "AAAA,EAAE,EACA,EAAC".to_owned()).collect();
+  let json = format!(r#"{{ "version": 3, "sources": ["a.js", "b.js"], "names": ["x"], "mappings": "{}" }}"#, lines.join(";AACA,"));
+  let cache = Arc::new(consume(&json).unwrap());
+  let expected: Vec<Mapping> = (1..=200).flat_map(|line| (0..8).map(move |column| (line, column)))
+    .map(|(line, column)| cache.mapping_for_generated_position(line, column))
+    .collect();
+  let expected = Arc::new(expected);
+
+  let threads: Vec<_> = (0..16).map(|thread| {
+    let cache = Arc::clone(&cache);
+    let expected = Arc::clone(&expected);
+    thread::spawn(move || {
+      for round in 0..50 {
+        // Each thread walks the positions from a different offset
+        for index in 0..expected.len() {
+          let mapping = &expected[(index + thread * 97 + round) % expected.len()];
+          assert_eq!(&cache.mapping_for_generated_position(mapping.generated.line, mapping.generated.column), mapping);
+        }
+      }
+    })
+  }).collect();
+  for thread in threads {
+    thread.join().unwrap();
+  }
+}
+
+#[test]
+fn it_sorts_mappings_only_when_they_are_out_of_order() {
+  use base64_vlq;
+
+  // Encodes lines of (column, original line) segments in the order given
+  let encode = |lines: &[Vec<(i32, i32)>]| {
+    let mut original_line = 0;
+    lines.iter().map(|segments| {
+      let mut column = 0;
+      segments.iter().map(|&(next_column, next_original_line)| {
+        let mut segment = base64_vlq::encode64((next_column - column) as i64);
+        segment.extend(b"A");
+        segment.extend(base64_vlq::encode64((next_original_line - original_line) as i64));
+        segment.extend(b"A");
+        column = next_column;
+        original_line = next_original_line;
+        String::from_utf8(segment).unwrap()
+      }).collect::<Vec<_>>().join(",")
+    }).collect::<Vec<_>>().join(";")
+  };
+  let sources = ["a.js".to_owned()];
+
+  let in_order: Vec<Vec<(i32, i32)>> = (0..20).map(|line| (0..50).map(|column| (column, line * 50 + column)).collect()).collect();
+  let expected = parse_mappings_str(&encode(&in_order), &sources, &[], "").unwrap();
+  assert!(is_sorted_by_generated(&expected.generated_mappings));
+  assert_eq!(expected.len(), 1000);
+
+  // The columns of each line shuffled, which decode out of order
+  let shuffled: Vec<Vec<(i32, i32)>> = in_order.iter()
+    .map(|segments| (0..50).map(|index| segments[index * 37 % 50]).collect())
+    .collect();
+  let cache = parse_mappings_str(&encode(&shuffled), &sources, &[], "").unwrap();
+  assert_eq!(cache.generated_mappings, expected.generated_mappings);
+  assert_eq!(cache.mapping_for_generated_position(7, 12), expected.mapping_for_generated_position(7, 12));
+
+  // Segments at the same generated position are sorted by original position too
+  let cache = parse_mappings_str(&encode(&[vec![(0, 2), (0, 1)]]), &sources, &[], "").unwrap();
+  assert_eq!(cache.generated_mappings.iter().map(|mapping| mapping.original.line).collect::<Vec<_>>(), [2, 3]);
+}
+
+#[test]
+fn it_sorts_mappings_like_a_sequential_stable_sort() {
+  // Many mappings compare equal, and each has strings of its own, so that a sort that
+  // reordered equal mappings would be told apart by the addresses of their strings
+  let mut state = 0x2545_f491u32;
+  let mut random = move |range: u32| {
+    state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+    (state >> 8) % range
+  };
+  let mut mappings: Vec<Mapping> = (0..200_000).map(|_| Mapping {
+    generated: CodePosition { line: 1 + random(50), column: random(20) },
+    original: CodePosition { line: 1 + random(3), column: random(3) },
+    source: ["a.js", "b.js", ""][random(3) as usize].into(),
+    name: ["x", ""][random(2) as usize].into()
+  }).collect();
+  let mut expected = mappings.clone();
+  expected.sort_by(compare_mappings);
+
+  sort_mappings(&mut mappings);
+  assert_eq!(mappings, expected);
+  let addresses = |mappings: &[Mapping]| mappings.iter().map(|mapping| (Arc::as_ptr(&mapping.source), Arc::as_ptr(&mapping.name))).collect::<Vec<_>>();
+  assert!(addresses(&mappings) == addresses(&expected));
+}
@@ -0,0 +1,199 @@
+use base64;
+use consume::{Cache, consume};
+use error::{Error, ErrorCode};
+use generate::Generator;
+
+const DATA_URI_PREFIX: &str = "data:application/json;charset=utf-8;base64,";
+
+/**
+ * Returns the source map as a `data:` URI, suitable for inlining the source map in the
+ * generated file.
+ */
+fn to_data_uri(json: &str) -> String {
+  format!("{}{}", DATA_URI_PREFIX, base64::encode_bytes(json.as_bytes()))
+}
+
+fn source_mapping_url_comment(data_uri: &str) -> String {
+  format!("//# sourceMappingURL={}", data_uri)
+}
+
+impl Cache {
+  /**
+   * Serializes the cache into a base 64 `data:` URI.
+   *
+   * # Examples
+   *
+   * ```
+   * use js_source_mapper::{consume, consume_data_uri};
+   *
+   * let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA" }"#).unwrap();
+   * let uri = cache.to_data_uri().unwrap();
+   * assert!(uri.starts_with("data:application/json;charset=utf-8;base64,"));
+   * assert!(&*consume_data_uri(&uri).unwrap().mapping_for_generated_position(1, 0).source == "a.js");
+   * ```
+   */
+  pub fn to_data_uri(&self) -> Result<String, Error> {
+    Ok(to_data_uri(&self.to_json()?))
+  }
+
+  /**
+   * Returns a `//# sourceMappingURL=` comment inlining the cache as a `data:` URI, to be
+   * appended to the generated file.
+   */
+  pub fn source_mapping_url_comment(&self) -> Result<String, Error> {
+    Ok(source_mapping_url_comment(&self.to_data_uri()?))
+  }
+}
+
+impl Generator {
+  /**
+   * Serializes the source map into a base 64 `data:` URI.
+   */
+  pub fn to_data_uri(&self) -> Result<String, Error> {
+    Ok(to_data_uri(&self.to_json()?))
+  }
+
+  /**
+   * Returns a `//# sourceMappingURL=` comment inlining the source map as a `data:` URI,
+   * to be appended to the generated file.
+   */
+  pub fn source_mapping_url_comment(&self) -> Result<String, Error> {
+    Ok(source_mapping_url_comment(&self.to_data_uri()?))
+  }
+}
+
+/**
+ * Parses a source map inlined as a `data:` URI, like the ones produced by
+ * `Cache::to_data_uri`.
+ *
+ * Both base 64 and percent-encoded payloads are supported.
+ */
+pub fn consume_data_uri(data_uri: &str) -> Result<Cache, Error> {
+  let json = decode_data_uri(data_uri)?;
+  consume(&json)
+}
+
+/**
+ * Decodes a source map inlined as a `data:` URI into its JSON text, without parsing it.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::decode_data_uri;
+ *
+ * assert!(decode_data_uri("data:application/json;base64,e30=").unwrap() == "{}");
+ * ```
+ */
+pub fn decode_data_uri(data_uri: &str) -> Result<String, Error> {
+  let rest = match data_uri.get(..5) {
+    Some(scheme) if scheme.eq_ignore_ascii_case("data:") => &data_uri[5..],
+    _ => return Err(Error::invalid(ErrorCode::InvalidDataUri, "Not a data URI"))
+  };
+  let comma = rest.find(',').ok_or_else(|| Error::invalid(ErrorCode::InvalidDataUri, "Invalid data URI: missing ','"))?;
+  let (metadata, payload) = (&rest[..comma], &rest[comma + 1..]);
+
+  let mut parameters = metadata.split(';');
+  let media_type = parameters.next().unwrap_or("").trim();
+  if !media_type.is_empty() && !media_type.eq_ignore_ascii_case("application/json") {
+    return Err(Error::invalid(ErrorCode::InvalidDataUri, format!("Unsupported data URI media type '{}'", media_type)));
+  }
+  let is_base64 = parameters.any(|parameter| parameter.trim().eq_ignore_ascii_case("base64"));
+
+  let bytes = if is_base64 {
+    base64::decode_bytes(payload.trim().as_bytes()).ok_or_else(|| Error::invalid(ErrorCode::InvalidDataUri, "Invalid data URI: invalid base 64 payload"))?
+  } else {
+    percent_decode(payload)?
+  };
+  String::from_utf8(bytes).map_err(|_| Error::invalid(ErrorCode::InvalidDataUri, "Invalid data URI: payload is not valid UTF-8"))
+}
+
+/**
+ * Returns the URL of the source map referenced by the last `//# sourceMappingURL=`
+ * comment of a generated file, which may be a `data:` URI. The legacy `//@` and the
+ * `/*# ... */` forms of the comment are recognized as well.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::source_mapping_url;
+ *
+ * let code = "console.log(1);\n//# sourceMappingURL=bundle.min.js.map\n";
+ * assert!(source_mapping_url(code) == Some("bundle.min.js.map"));
+ * ```
+ */
+pub fn source_mapping_url(code: &str) -> Option<&str> {
+  code.lines().rev().filter_map(|line| {
+    let line = line.trim();
+    let url = line.strip_prefix("//# sourceMappingURL=")
+      .or_else(|| line.strip_prefix("//@ sourceMappingURL="))
+      .or_else(|| line.strip_prefix("/*# sourceMappingURL=").and_then(|rest| rest.strip_suffix("*/")))?
+      .trim();
+    if url.is_empty() { None } else { Some(url) }
+  }).next()
+}
+
+fn percent_decode(payload: &str) -> Result<Vec<u8>, Error> {
+  let bytes = payload.as_bytes();
+  let mut decoded = Vec::with_capacity(bytes.len());
+  let mut index = 0;
+  while index < bytes.len() {
+    if bytes[index] == b'%' {
+      let hex = payload.get(index + 1..index + 3).ok_or_else(|| Error::invalid(ErrorCode::InvalidDataUri, "Invalid data URI: truncated percent-encoding"))?;
+      let byte = u8::from_str_radix(hex, 16).map_err(|_| Error::invalid(ErrorCode::InvalidDataUri, "Invalid data URI: invalid percent-encoding"))?;
+      decoded.push(byte);
+      index += 3;
+    } else {
+      decoded.push(bytes[index]);
+      index += 1;
+    }
+  }
+  Ok(decoded)
+}
+
+#[cfg(test)]
+use consume::CodePosition;
+
+#[test]
+fn it_round_trips_caches_through_data_uris() {
+  let cache = consume(r#"{
+    "version": 3,
+    "sources": ["source.js", "ünïcode.js"],
+    "names": ["name1", "name2"],
+    "mappings": ";EAACA,CCAC;;IAEEC",
+    "sourceRoot": "http://example.com"
+  }"#).unwrap();
+  let round_tripped = consume_data_uri(&cache.to_data_uri().unwrap()).unwrap();
+  assert_eq!(round_tripped.mappings(), cache.mappings());
+  assert_eq!(round_tripped.source_root, cache.source_root);
+}
+
+#[test]
+fn it_writes_source_mapping_url_comments() {
+  let mut generator = Generator::new();
+  generator.add_mapping(CodePosition { line: 1, column: 0 }, CodePosition { line: 1, column: 0 }, "a.js", "");
+  assert_eq!(
+    generator.source_mapping_url_comment().unwrap(),
+    "//# sourceMappingURL=data:application/json;charset=utf-8;base64,eyJ2ZXJzaW9uIjozLCJzb3VyY2VzIjpbImEuanMiXSwibmFtZXMiOltdLCJtYXBwaW5ncyI6IkFBQUEifQ=="
+  );
+}
+
+#[test]
+fn it_consumes_percent_encoded_data_uris() {
+  let cache = consume_data_uri(r#"data:application/json,{"version":3,"sources":["a%20b.js"],"names":[],"mappings":"AAAA"}"#).unwrap();
+  assert_eq!(cache.sources(), ["a b.js"]);
+}
+
+#[test]
+fn it_rejects_invalid_data_uris() {
+  assert!(consume_data_uri("http://example.com/foo.js.map").is_err());
+  assert!(consume_data_uri("data:text/plain;base64,e30=").is_err());
+  assert!(consume_data_uri("data:application/json;base64,!!!").is_err());
+}
+
+#[test]
+fn it_finds_source_mapping_urls() {
+  assert_eq!(source_mapping_url("a();\n//# sourceMappingURL=a.js.map"), Some("a.js.map"));
+  assert_eq!(source_mapping_url("a();\r\n//@ sourceMappingURL=old.js.map\r\n//# sourceMappingURL=new.js.map \r\n"), Some("new.js.map"));
+  assert_eq!(source_mapping_url("a{}\n/*# sourceMappingURL=a.css.map */\n"), Some("a.css.map"));
+  assert_eq!(source_mapping_url("var s = '//# sourceMappingURL=a.js.map';\n//# sourceMappingURL=\n"), None);
+}
@@ -0,0 +1,179 @@
+use consume::{Cache, Mapping, compare_mappings};
+
+/**
+ * A mapping whose generated position resolves differently in two caches.
+ */
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct MappingChange {
+  /** The mapping in the old cache */
+  pub old: Mapping,
+  /** The mapping in the new cache */
+  pub new: Mapping
+}
+
+/**
+ * The differences between two caches, compared by generated position.
+ */
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct CacheDiff {
+  /** Mappings at generated positions that are only mapped in the new cache */
+  pub added: Vec<Mapping>,
+  /** Mappings at generated positions that are only mapped in the old cache */
+  pub removed: Vec<Mapping>,
+  /** Mappings at generated positions that resolve differently in the new cache */
+  pub changed: Vec<MappingChange>,
+  /** The number of mappings that are identical in both caches */
+  pub unchanged: usize
+}
+
+impl CacheDiff {
+  /**
+   * Returns true if both caches contain the same mappings.
+   */
+  pub fn is_empty(&self) -> bool {
+    self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+  }
+}
+
+/**
+ * Compares the mappings of two caches by generated position.
+ *
+ * Both caches are walked in generated order in lockstep, so comparing two large caches
+ * takes linear time. Mappings sharing a generated position are compared regardless of
+ * their order in the source map.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::{consume, diff};
+ *
+ * let old = consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA,CAAC" }"#).unwrap();
+ * let new = consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA,CAAE,CAAC" }"#).unwrap();
+ *
+ * let changes = diff(&old, &new);
+ * assert!(changes.added.len() == 1);
+ * assert!(changes.changed.len() == 1);
+ * assert!(changes.unchanged == 1);
+ * ```
+ */
+pub fn diff(old: &Cache, new: &Cache) -> CacheDiff {
+  let mut result = CacheDiff::default();
+  let mut old_groups = PositionGroups { mappings: old.mappings() };
+  let mut new_groups = PositionGroups { mappings: new.mappings() };
+  let mut old_group = old_groups.next();
+  let mut new_group = new_groups.next();
+
+  loop {
+    match (old_group.take(), new_group.take()) {
+      (None, None) => break,
+      (Some(old_mappings), None) => {
+        result.removed.extend(old_mappings);
+        old_group = old_groups.next();
+      },
+      (None, Some(new_mappings)) => {
+        result.added.extend(new_mappings);
+        new_group = new_groups.next();
+      },
+      (Some(old_mappings), Some(new_mappings)) => {
+        let old_position = (old_mappings[0].generated.line, old_mappings[0].generated.column);
+        let new_position = (new_mappings[0].generated.line, new_mappings[0].generated.column);
+        if old_position < new_position {
+          result.removed.extend(old_mappings);
+          old_group = old_groups.next();
+          new_group = Some(new_mappings);
+        } else if new_position < old_position {
+          result.added.extend(new_mappings);
+          new_group = new_groups.next();
+          old_group = Some(old_mappings);
+        } else {
+          diff_group(old_mappings, new_mappings, &mut result);
+          old_group = old_groups.next();
+          new_group = new_groups.next();
+        }
+      }
+    }
+  }
+
+  result
+}
+
+// Compares mappings sharing a generated position, ignoring their order
+fn diff_group(mut old: Vec<Mapping>, mut new: Vec<Mapping>, result: &mut CacheDiff) {
+  if old.len() > 1 || new.len() > 1 {
+    old.sort_by(compare_mappings);
+    new.sort_by(compare_mappings);
+  }
+
+  let mut old = old.into_iter();
+  let mut new = new.into_iter();
+  loop {
+    match (old.next(), new.next()) {
+      (None, None) => break,
+      (Some(old_mapping), None) => result.removed.push(old_mapping),
+      (None, Some(new_mapping)) => result.added.push(new_mapping),
+      (Some(old_mapping), Some(new_mapping)) => {
+        if old_mapping == new_mapping {
+          result.unchanged += 1;
+        } else {
+          result.changed.push(MappingChange { old: old_mapping, new: new_mapping });
+        }
+      }
+    }
+  }
+}
+
+// Iterates over runs of mappings sharing a generated position
+struct PositionGroups<'a> {
+  mappings: &'a [Mapping]
+}
+
+impl<'a> Iterator for PositionGroups<'a> {
+  type Item = Vec<Mapping>;
+
+  fn next(&mut self) -> Option<Vec<Mapping>> {
+    let first = self.mappings.first()?;
+    let length = self.mappings.iter()
+      .take_while(|mapping| mapping.generated == first.generated)
+      .count();
+    let (group, rest) = self.mappings.split_at(length);
+    self.mappings = rest;
+    Some(group.to_vec())
+  }
+}
+
+#[cfg(test)]
+use consume::consume;
+
+#[test]
+fn it_finds_no_differences_between_identical_caches() {
+  let json = r#"{ "version": 3, "sources": ["a.js"], "names": ["x"], "mappings": "AAAAA,CAAC;AACA" }"#;
+  let result = diff(&consume(json).unwrap(), &consume(json).unwrap());
+  assert!(result.is_empty());
+  assert_eq!(result.unchanged, 3);
+}
+
+#[test]
+fn it_finds_no_differences_between_reordered_caches() {
+  let old = consume(r#"{ "version": 3, "sources": ["a.js", "b.js"], "names": [], "mappings": "AAAA,ECAA,ADAA" }"#).unwrap();
+  let new = consume(r#"{ "version": 3, "sources": ["b.js", "a.js"], "names": [], "mappings": "ACAA,EDAA,ACAA" }"#).unwrap();
+  assert!(diff(&old, &new).is_empty());
+}
+
+#[test]
+fn it_finds_added_removed_and_changed_mappings() {
+  let old = consume(r#"{ "version": 3, "sources": ["a.js"], "names": ["x"], "mappings": "AAAA,CAAC;AACA" }"#).unwrap();
+  let new = consume(r#"{ "version": 3, "sources": ["a.js"], "names": ["x"], "mappings": "AAAA,CAACA,C;;AAEA" }"#).unwrap();
+
+  let result = diff(&old, &new);
+  assert_eq!(result.unchanged, 1);
+
+  let added: Vec<(u32, u32)> = result.added.iter().map(|m| (m.generated.line, m.generated.column)).collect();
+  assert_eq!(added, vec![(1, 2), (3, 0)]);
+
+  let removed: Vec<(u32, u32)> = result.removed.iter().map(|m| (m.generated.line, m.generated.column)).collect();
+  assert_eq!(removed, vec![(2, 0)]);
+
+  assert_eq!(result.changed.len(), 1);
+  assert_eq!(&*result.changed[0].old.name, "");
+  assert_eq!(&*result.changed[0].new.name, "x");
+}
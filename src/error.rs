@@ -0,0 +1,249 @@
+use core::fmt;
+#[cfg(feature = "std")]
+use std::error;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(not(feature = "std"))]
+use alloc::{borrow::ToOwned, string::String};
+
+#[cfg(feature = "serde-json")]
+extern crate serde_json;
+
+/**
+ * The error returned when a source map, data URI or binary cache cannot be parsed,
+ * serialized or read.
+ *
+ * With the `std` feature it implements `std::error::Error`, so it converts into
+ * `Box<dyn Error>` and the error types of crates like `anyhow` with `?`:
+ *
+ * ```
+ * # #[cfg(feature = "std")] {
+ * use std::error::Error;
+ * use js_source_mapper::consume;
+ *
+ * fn original_line(json: &str) -> Result<u32, Box<dyn Error>> {
+ *   let cache = consume(json)?;
+ *   Ok(cache.mapping_for_generated_position(1, 0).original.line)
+ * }
+ *
+ * assert!(original_line(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AACA" }"#).unwrap() == 2);
+ * assert!(original_line("{").is_err());
+ * # }
+ * ```
+ */
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+  /**
+   * The JSON is malformed, or does not have the fields of a source map. Errors of the
+   * `mini-json` parser are `Invalid` errors with the `Json` code instead.
+   */
+  #[cfg(feature = "serde-json")]
+  Json(serde_json::Error),
+  /** Reading a file failed */
+  #[cfg(feature = "std")]
+  Io(io::Error),
+  /**
+   * The input is well-formed but invalid, for instance a malformed `mappings` field.
+   * The code tells kinds of invalid input apart without parsing the message.
+   */
+  Invalid(ErrorCode, String)
+}
+
+/**
+ * A stable numeric identity for each kind of error, for bindings and for aggregating
+ * errors in logs. The values never change, even when messages are reworded, and new
+ * kinds of errors get new values.
+ *
+ * | Code | Kind |
+ * |------|------|
+ * | 1 | `Json`: the JSON is malformed, or does not have the fields of a source map |
+ * | 2 | `UnsupportedVersion`: the source map is not of version 3 |
+ * | 3 | `InvalidVlq`: a field of the mappings is not a valid base 64 VLQ |
+ * | 4 | `SourceIndexOutOfRange`: a mapping refers to a source outside the `sources` table |
+ * | 5 | `NameIndexOutOfRange`: a mapping refers to a name outside the `names` table |
+ * | 6 | `InvalidSegment`: a segment of the mappings has the wrong number of fields, or is out of order |
+ * | 7 | `NoMappings`: the source map has no mappings |
+ * | 8 | `InvalidPosition`: a line or column is out of range, or a position is malformed |
+ * | 9 | `InvalidIndexMap`: the sections of an index map are malformed |
+ * | 10 | `InvalidDataUri`: a `data:` URI is malformed |
+ * | 11 | `InvalidBinaryCache`: a serialized cache is malformed or of another format version |
+ * | 12 | `Fetch`: a source map referred to by URL cannot be fetched |
+ * | 13 | `Io`: reading a file failed |
+ * | 255 | `Other`: any other error |
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::{ErrorCode, consume};
+ *
+ * let err = consume(r#"{ "version": 2, "sources": [], "names": [], "mappings": "" }"#).unwrap_err();
+ * assert!(err.code() == ErrorCode::UnsupportedVersion && err.code() as u32 == 2);
+ * ```
+ */
+#[non_exhaustive]
+#[repr(u32)]
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum ErrorCode {
+  /** The JSON is malformed, or does not have the fields of a source map */
+  Json = 1,
+  /** The source map is not of version 3 */
+  UnsupportedVersion = 2,
+  /** A field of the mappings is not a valid base 64 VLQ */
+  InvalidVlq = 3,
+  /** A mapping refers to a source outside the `sources` table */
+  SourceIndexOutOfRange = 4,
+  /** A mapping refers to a name outside the `names` table */
+  NameIndexOutOfRange = 5,
+  /** A segment of the mappings has the wrong number of fields, or is out of order */
+  InvalidSegment = 6,
+  /** The source map has no mappings */
+  NoMappings = 7,
+  /** A line or column is out of range, or a position is malformed */
+  InvalidPosition = 8,
+  /** The sections of an index map are malformed */
+  InvalidIndexMap = 9,
+  /** A `data:` URI is malformed */
+  InvalidDataUri = 10,
+  /** A serialized cache is malformed or of another format version */
+  InvalidBinaryCache = 11,
+  /** A source map referred to by URL cannot be fetched */
+  Fetch = 12,
+  /** Reading a file failed */
+  Io = 13,
+  /** Any other error */
+  Other = 255
+}
+
+impl Error {
+  /**
+   * Returns the stable code of the kind of error.
+   */
+  pub fn code(&self) -> ErrorCode {
+    match *self {
+      #[cfg(feature = "serde-json")]
+      Error::Json(_) => ErrorCode::Json,
+      #[cfg(feature = "std")]
+      Error::Io(_) => ErrorCode::Io,
+      Error::Invalid(code, _) => code
+    }
+  }
+
+  pub(crate) fn invalid<S: Into<String>>(code: ErrorCode, message: S) -> Error {
+    Error::Invalid(code, message.into())
+  }
+
+  // Prefixes the message with where the error happened, keeping the code
+  pub(crate) fn in_context(self, context: &str) -> Error {
+    let code = self.code();
+    Error::Invalid(code, format!("{}: {}", context, self))
+  }
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    match *self {
+      #[cfg(feature = "serde-json")]
+      Error::Json(ref err) => err.fmt(formatter),
+      #[cfg(feature = "std")]
+      Error::Io(ref err) => err.fmt(formatter),
+      Error::Invalid(_, ref message) => formatter.write_str(message)
+    }
+  }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for Error {
+  fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+    match *self {
+      Error::Json(ref err) => Some(err),
+      Error::Io(ref err) => Some(err),
+      Error::Invalid(..) => None
+    }
+  }
+}
+
+#[cfg(feature = "serde-json")]
+impl From<serde_json::Error> for Error {
+  fn from(err: serde_json::Error) -> Error {
+    Error::Json(err)
+  }
+}
+
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+  fn from(err: io::Error) -> Error {
+    Error::Io(err)
+  }
+}
+
+impl From<String> for Error {
+  fn from(message: String) -> Error {
+    Error::Invalid(ErrorCode::Other, message)
+  }
+}
+
+impl<'a> From<&'a str> for Error {
+  fn from(message: &'a str) -> Error {
+    Error::Invalid(ErrorCode::Other, message.to_owned())
+  }
+}
+
+#[cfg(all(test, feature = "std"))]
+use std::error::Error as StdError;
+
+#[cfg(feature = "std")]
+#[test]
+fn it_chains_error_sources() {
+  let json_error = serde_json::from_str::<u32>("x").unwrap_err();
+  let message = json_error.to_string();
+  let err = Error::from(json_error);
+  assert_eq!(err.to_string(), message);
+  assert!(err.source().is_some());
+
+  let err = Error::from(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+  assert_eq!(err.to_string(), "no such file");
+  assert!(err.source().is_some());
+
+  let err = Error::from("Source Map contains no mappings");
+  assert_eq!(err.to_string(), "Source Map contains no mappings");
+  assert!(err.source().is_none());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn it_gives_each_kind_of_error_a_distinct_documented_code() {
+  use consume::consume;
+
+  let codes = [
+    (ErrorCode::Json, 1), (ErrorCode::UnsupportedVersion, 2), (ErrorCode::InvalidVlq, 3),
+    (ErrorCode::SourceIndexOutOfRange, 4), (ErrorCode::NameIndexOutOfRange, 5), (ErrorCode::InvalidSegment, 6),
+    (ErrorCode::NoMappings, 7), (ErrorCode::InvalidPosition, 8), (ErrorCode::InvalidIndexMap, 9),
+    (ErrorCode::InvalidDataUri, 10), (ErrorCode::InvalidBinaryCache, 11), (ErrorCode::Fetch, 12),
+    (ErrorCode::Io, 13), (ErrorCode::Other, 255)
+  ];
+  let documentation = include_str!("error.rs");
+  for (index, &(code, value)) in codes.iter().enumerate() {
+    assert_eq!(code as u32, value);
+    assert!(codes[..index].iter().all(|&(other, _)| other as u32 != value), "{:?}", code);
+    assert!(documentation.contains(&format!(" * | {} | `{:?}`: ", value, code)), "{:?} is not documented", code);
+  }
+
+  let code = |json: &str| consume(json).unwrap_err().code();
+  assert_eq!(code("{"), ErrorCode::Json);
+  assert_eq!(code(r#"{ "version": 2, "sources": [], "names": [], "mappings": "AAAA" }"#), ErrorCode::UnsupportedVersion);
+  assert_eq!(code(r#"{ "version": 3, "sources": [], "names": [], "mappings": "A!" }"#), ErrorCode::InvalidVlq);
+  assert_eq!(code(r#"{ "version": 3, "sources": [], "names": [], "mappings": "AAAA" }"#), ErrorCode::SourceIndexOutOfRange);
+  assert_eq!(code(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAAA" }"#), ErrorCode::NameIndexOutOfRange);
+  assert_eq!(code(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AA" }"#), ErrorCode::InvalidSegment);
+  assert_eq!(code(r#"{ "version": 3, "sources": [], "names": [], "mappings": "" }"#), ErrorCode::NoMappings);
+  assert_eq!(code(r#"{ "version": 3, "sections": [{ "offset": { "line": 0, "column": 0 } }] }"#), ErrorCode::InvalidIndexMap);
+  assert_eq!("0:1".parse::<::consume::CodePosition>().unwrap_err().code(), ErrorCode::InvalidPosition);
+  assert_eq!(Error::from(io::Error::new(io::ErrorKind::NotFound, "no such file")).code(), ErrorCode::Io);
+  assert_eq!(Error::from("anything else").code(), ErrorCode::Other);
+
+  // Context added to an error keeps its code
+  let nested = r#"{ "version": 3, "sections": [{ "offset": { "line": 0, "column": 0 }, "map": { "version": 3, "sources": [], "names": [], "mappings": "AAAA" } }] }"#;
+  let err = consume(nested).unwrap_err();
+  assert_eq!((err.code(), err.to_string().starts_with("Invalid index map: section 0: ")), (ErrorCode::SourceIndexOutOfRange, true));
+}
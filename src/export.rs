@@ -0,0 +1,152 @@
+use std::io;
+use std::io::Write;
+
+#[cfg(feature = "serde")]
+extern crate serde_json;
+
+use consume::{Cache, Mapping};
+
+const CSV_COLUMNS: [&str; 6] = ["generated_line", "generated_column", "original_line", "original_column", "source", "name"];
+
+impl Cache {
+  /**
+   * Writes the mappings as CSV, one row per mapping in generated order, after a header
+   * row naming the columns: generated_line, generated_column, original_line,
+   * original_column, source and name.
+   *
+   * Sources and names containing the delimiter, quotes or line breaks are quoted. The
+   * original position, source and name of mappings without a source are left empty.
+   * Rows are written one at a time, so wrapping `writer` in an `io::BufWriter` is
+   * recommended.
+   *
+   * # Examples
+   *
+   * ```
+   * use js_source_mapper::consume;
+   *
+   * let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": ["x"], "mappings": "AAAAA" }"#).unwrap();
+   * let mut tsv = Vec::new();
+   * cache.write_csv(&mut tsv, b'\t').unwrap();
+   * assert!(tsv.ends_with(b"1\t0\t1\t0\ta.js\tx\n"));
+   * ```
+   */
+  pub fn write_csv<W: Write>(&self, writer: W, delimiter: u8) -> io::Result<()> {
+    write_mappings_csv(self.mappings(), writer, delimiter)
+  }
+
+  /**
+   * Serializes the mappings as a JSON array of objects, in generated order, using the
+   * `Serialize` implementation of `Mapping`. Requires the `serde` feature.
+   *
+   * # Examples
+   *
+   * ```
+   * use js_source_mapper::consume;
+   *
+   * let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA" }"#).unwrap();
+   * assert_eq!(
+   *   cache.mappings_to_json(),
+   *   r#"[{"generated":{"line":1,"column":0},"original":{"line":1,"column":0},"source":"a.js","name":""}]"#
+   * );
+   * ```
+   */
+  #[cfg(feature = "serde")]
+  pub fn mappings_to_json(&self) -> String {
+    serde_json::to_string(self.mappings()).expect("mappings should serialize to JSON")
+  }
+}
+
+/**
+ * Writes a selection of mappings as CSV, in the same format as `Cache::write_csv`:
+ * a header row followed by one row per mapping, in the order given.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::{consume, write_mappings_csv};
+ *
+ * let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": ["x"], "mappings": "AAAAA;AACA" }"#).unwrap();
+ * let mut csv = Vec::new();
+ * write_mappings_csv(cache.mappings_for_generated_line(2), &mut csv, b',').unwrap();
+ * assert_eq!(csv, b"generated_line,generated_column,original_line,original_column,source,name\n2,0,2,0,a.js,\n");
+ * ```
+ */
+pub fn write_mappings_csv<'a, W: Write, I: IntoIterator<Item = &'a Mapping>>(mappings: I, mut writer: W, delimiter: u8) -> io::Result<()> {
+  for (index, column) in CSV_COLUMNS.iter().enumerate() {
+    if index > 0 {
+      writer.write_all(&[delimiter])?;
+    }
+    writer.write_all(column.as_bytes())?;
+  }
+  writer.write_all(b"\n")?;
+
+  for mapping in mappings {
+    write_csv_row(&mut writer, mapping, delimiter)?;
+  }
+  writer.flush()
+}
+
+fn write_csv_row<W: Write>(writer: &mut W, mapping: &Mapping, delimiter: u8) -> io::Result<()> {
+  write!(writer, "{}", mapping.generated.line)?;
+  writer.write_all(&[delimiter])?;
+  write!(writer, "{}", mapping.generated.column)?;
+  writer.write_all(&[delimiter])?;
+  if !mapping.source.is_empty() {
+    write!(writer, "{}", mapping.original.line)?;
+  }
+  writer.write_all(&[delimiter])?;
+  if !mapping.source.is_empty() {
+    write!(writer, "{}", mapping.original.column)?;
+  }
+  writer.write_all(&[delimiter])?;
+  write_csv_field(writer, &mapping.source, delimiter)?;
+  writer.write_all(&[delimiter])?;
+  write_csv_field(writer, &mapping.name, delimiter)?;
+  writer.write_all(b"\n")
+}
+
+fn write_csv_field<W: Write>(writer: &mut W, field: &str, delimiter: u8) -> io::Result<()> {
+  let needs_quotes = field.bytes().any(|byte| byte == delimiter || byte == b'"' || byte == b'\n' || byte == b'\r');
+  if !needs_quotes {
+    return writer.write_all(field.as_bytes());
+  }
+  writer.write_all(b"\"")?;
+  writer.write_all(field.replace('"', "\"\"").as_bytes())?;
+  writer.write_all(b"\"")
+}
+
+#[cfg(test)]
+use consume::consume;
+
+#[test]
+fn it_writes_mappings_as_csv() {
+  let cache = consume(r#"{
+    "version": 3,
+    "sources": ["a.js", "b, \"c\".js"],
+    "names": ["x"],
+    "mappings": "AAAAA,C;ACCA"
+  }"#).unwrap();
+
+  let mut csv = Vec::new();
+  cache.write_csv(&mut csv, b',').unwrap();
+  assert_eq!(String::from_utf8(csv).unwrap(), concat!(
+    "generated_line,generated_column,original_line,original_column,source,name\n",
+    "1,0,1,0,a.js,x\n",
+    "1,1,,,,\n",
+    "2,0,2,0,\"b, \"\"c\"\".js\",\n"
+  ));
+
+  let mut tsv = Vec::new();
+  cache.write_csv(&mut tsv, b'\t').unwrap();
+  assert!(String::from_utf8(tsv).unwrap().ends_with("2\t0\t2\t0\t\"b, \"\"c\"\".js\"\t\n"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn it_dumps_mappings_as_json() {
+  let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": ["x"], "mappings": "AAAAA,C" }"#).unwrap();
+  assert_eq!(cache.mappings_to_json(), concat!(
+    r#"[{"generated":{"line":1,"column":0},"original":{"line":1,"column":0},"source":"a.js","name":"x"},"#,
+    r#"{"generated":{"line":1,"column":1},"original":{"line":0,"column":0},"source":"","name":""}]"#
+  ));
+}
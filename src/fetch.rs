@@ -0,0 +1,283 @@
+#[cfg(feature = "std")]
+use std::fs;
+#[cfg(feature = "std")]
+use std::path::PathBuf;
+#[cfg(not(feature = "std"))]
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+
+use consume::{Cache, Scratch, consume_json};
+#[cfg(feature = "std")]
+use data_uri::{decode_data_uri, source_mapping_url};
+use error::{Error, ErrorCode};
+use resolve::has_scheme;
+
+// How many source maps may refer to each other by url, counting the first one
+const MAX_FETCH_DEPTH: usize = 8;
+
+/**
+ * Fetches the source maps that generated files and index map sections refer to by URL,
+ * for `consume_from_generated` and `consume_with_fetcher`. `FileFetcher` reads them from
+ * the filesystem, and other fetchers can get them over HTTP or from an artifact store.
+ * Closures taking the URL implement it too.
+ *
+ * Relative URLs of index map sections are resolved against the URL of the index map
+ * before they are fetched, and references that form a cycle or nest more than 8 maps
+ * deep are rejected, so fetchers only need to fetch.
+ */
+pub trait MapFetcher {
+  /** Returns the source map JSON at a URL */
+  fn fetch(&self, url: &str) -> Result<String, Error>;
+}
+
+impl<F: Fn(&str) -> Result<String, Error>> MapFetcher for F {
+  fn fetch(&self, url: &str) -> Result<String, Error> {
+    self(url)
+  }
+}
+
+/**
+ * Fetches source maps from the filesystem, resolving relative URLs against a base
+ * directory, which is usually the directory of the generated file. Absolute paths and
+ * `file://` URLs are read as they are, while URLs with other schemes are rejected.
+ *
+ * # Examples
+ *
+ * ```no_run
+ * use std::fs;
+ * use js_source_mapper::{FileFetcher, consume_from_generated};
+ *
+ * let code = fs::read_to_string("dist/bundle.min.js").unwrap();
+ * let cache = consume_from_generated(&code, &FileFetcher::new("dist")).unwrap();
+ * ```
+ */
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+pub struct FileFetcher {
+  base_dir: PathBuf
+}
+
+#[cfg(feature = "std")]
+impl FileFetcher {
+  /**
+   * Creates a fetcher resolving relative URLs against `base_dir`.
+   */
+  pub fn new<P: Into<PathBuf>>(base_dir: P) -> FileFetcher {
+    FileFetcher { base_dir: base_dir.into() }
+  }
+}
+
+#[cfg(feature = "std")]
+impl MapFetcher for FileFetcher {
+  fn fetch(&self, url: &str) -> Result<String, Error> {
+    let path = match url.strip_prefix("file://") {
+      Some(path) => path,
+      None if has_scheme(url) => return Err(Error::invalid(ErrorCode::Fetch, format!("Cannot fetch {} from the filesystem", url))),
+      None => url
+    };
+    // Query strings and fragments are for caching, not part of the file name
+    let path = &path[..path.find(['?', '#']).unwrap_or(path.len())];
+    Ok(fs::read_to_string(self.base_dir.join(path))?)
+  }
+}
+
+// The maps fetched so far, from the outermost to the one being parsed
+pub(crate) struct Fetch<'a> {
+  fetcher: &'a dyn MapFetcher,
+  urls: Vec<String>
+}
+
+impl<'a> Fetch<'a> {
+  // Fetches and parses the map at a url, relative to the map being parsed
+  pub(crate) fn consume(&self, url: &str, with_sources_content: bool, scratch: &mut Scratch) -> Result<Cache, Error> {
+    let url = match self.urls.last() {
+      Some(base) => resolve_url(base, url),
+      None => url.to_owned()
+    };
+    if self.urls.contains(&url) {
+      return Err(Error::invalid(ErrorCode::Fetch, format!("{} refers to itself through its sections", url)));
+    }
+    if self.urls.len() >= MAX_FETCH_DEPTH {
+      return Err(Error::invalid(ErrorCode::Fetch, format!("Source maps nest more than {} deep at {}", MAX_FETCH_DEPTH, url)));
+    }
+
+    let json = self.fetch(&url)?;
+    let mut urls = self.urls.clone();
+    urls.push(url);
+    consume_json(&json, with_sources_content, Some(&Fetch { fetcher: self.fetcher, urls }), scratch)
+  }
+
+  #[cfg(feature = "std")]
+  fn fetch(&self, url: &str) -> Result<String, Error> {
+    if url.get(..5).is_some_and(|scheme| scheme.eq_ignore_ascii_case("data:")) {
+      decode_data_uri(url)
+    } else {
+      self.fetcher.fetch(url)
+    }
+  }
+
+  #[cfg(not(feature = "std"))]
+  fn fetch(&self, url: &str) -> Result<String, Error> {
+    self.fetcher.fetch(url)
+  }
+}
+
+// Resolves a url relative to the url of the map referring to it. Urls starting with a
+// slash keep the scheme and host of the base, if it has them
+fn resolve_url(base: &str, url: &str) -> String {
+  if has_scheme(url) {
+    return url.to_owned();
+  }
+  if url.starts_with('/') {
+    let origin = match base.find("://") {
+      Some(separator) if has_scheme(base) => {
+        let host_start = separator + 3;
+        let path_start = base[host_start..].find('/').map_or(base.len(), |slash| host_start + slash);
+        if url.starts_with("//") { &base[..separator + 1] } else { &base[..path_start] }
+      },
+      _ => ""
+    };
+    return format!("{}{}", origin, url);
+  }
+  match base.rfind('/') {
+    Some(slash) => format!("{}{}", &base[..slash + 1], url),
+    None => url.to_owned()
+  }
+}
+
+/**
+ * Like `consume`, but fetches the maps of index map sections that refer to them by
+ * `url` with `fetcher`.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::{Error, consume_with_fetcher};
+ *
+ * let index_map = r#"{ "version": 3, "sections": [{ "offset": { "line": 0, "column": 0 }, "url": "maps/a.js.map" }] }"#;
+ * let fetcher = |url: &str| -> Result<String, Error> {
+ *   assert!(url == "maps/a.js.map");
+ *   Ok(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA" }"#.into())
+ * };
+ * let cache = consume_with_fetcher(index_map, &fetcher).unwrap();
+ * assert!(cache.sources() == ["a.js"]);
+ * ```
+ */
+pub fn consume_with_fetcher(source_map_json: &str, fetcher: &dyn MapFetcher) -> Result<Cache, Error> {
+  let fetch = Fetch { fetcher, urls: Vec::new() };
+  non_empty(consume_json(source_map_json, false, Some(&fetch), &mut Scratch::default())?)
+}
+
+/**
+ * Parses the source map of a generated file, which its last `//# sourceMappingURL=`
+ * comment either inlines as a `data:` URI or refers to by URL, in which case it is
+ * fetched with `fetcher`. Index map sections with a `url` are fetched as well, relative
+ * to the URL of their index map.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::{Error, consume_from_generated};
+ *
+ * let code = "a();\n//# sourceMappingURL=bundle.js.map\n";
+ * let fetcher = |url: &str| -> Result<String, Error> {
+ *   assert!(url == "bundle.js.map");
+ *   Ok(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA" }"#.into())
+ * };
+ * assert!(consume_from_generated(code, &fetcher).unwrap().sources() == ["a.js"]);
+ * ```
+ */
+#[cfg(feature = "std")]
+pub fn consume_from_generated(code: &str, fetcher: &dyn MapFetcher) -> Result<Cache, Error> {
+  let url = source_mapping_url(code).ok_or_else(|| Error::invalid(ErrorCode::Fetch, "The generated file has no sourceMappingURL comment"))?;
+  let fetch = Fetch { fetcher, urls: Vec::new() };
+  non_empty(fetch.consume(url, false, &mut Scratch::default())?)
+}
+
+fn non_empty(cache: Cache) -> Result<Cache, Error> {
+  if cache.is_empty() {
+    return Err(Error::invalid(ErrorCode::NoMappings, "Source Map contains no mappings"));
+  }
+  Ok(cache)
+}
+
+#[cfg(all(test, feature = "std"))]
+use core::cell::RefCell;
+#[cfg(all(test, not(feature = "std")))]
+use alloc::string::ToString;
+
+#[cfg(all(test, feature = "std"))]
+const SECTION: &str = r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA" }"#;
+
+#[cfg(test)]
+fn index_map(urls: &[&str]) -> String {
+  let sections: Vec<String> = urls.iter().enumerate()
+    .map(|(line, url)| format!(r#"{{ "offset": {{ "line": {}, "column": 0 }}, "url": "{}" }}"#, line, url))
+    .collect();
+  format!(r#"{{ "version": 3, "sections": [{}] }}"#, sections.join(", "))
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn it_fetches_index_map_sections_relative_to_their_map() {
+  let fetched = RefCell::new(Vec::new());
+  let fetcher = |url: &str| -> Result<String, Error> {
+    fetched.borrow_mut().push(url.to_owned());
+    match url {
+      "https://cdn.example/maps/index.map" => Ok(index_map(&["a.map", "nested/index.map", "/b.map"])),
+      "https://cdn.example/maps/nested/index.map" => Ok(index_map(&["../c.map", "https://other.example/d.map"])),
+      _ => Ok(SECTION.replace("a.js", &url[url.rfind('/').unwrap() + 1..]))
+    }
+  };
+
+  let cache = consume_from_generated("//# sourceMappingURL=https://cdn.example/maps/index.map", &fetcher).unwrap();
+  assert_eq!(cache.sources(), ["a.map", "c.map", "d.map", "b.map"]);
+  assert_eq!(*fetched.borrow(), [
+    "https://cdn.example/maps/index.map",
+    "https://cdn.example/maps/a.map",
+    "https://cdn.example/maps/nested/index.map",
+    "https://cdn.example/maps/nested/../c.map",
+    "https://other.example/d.map",
+    "https://cdn.example/b.map"
+  ]);
+
+  let inline = "//# sourceMappingURL=data:application/json;base64,eyJ2ZXJzaW9uIjozLCJzb3VyY2VzIjpbImEuanMiXSwibmFtZXMiOltdLCJtYXBwaW5ncyI6IkFBQUEifQ==";
+  assert_eq!(consume_from_generated(inline, &fetcher).unwrap().sources(), ["a.js"]);
+  assert!(consume_from_generated("a();", &fetcher).is_err());
+  assert!(::consume::consume(&index_map(&["a.map"])).unwrap_err().to_string().contains("need a MapFetcher"));
+}
+
+#[test]
+fn it_rejects_cyclic_and_deeply_nested_sections() {
+  let cyclic = |url: &str| -> Result<String, Error> {
+    Ok(if url == "a.map" { index_map(&["b.map"]) } else { index_map(&["a.map"]) })
+  };
+  let err = consume_with_fetcher(&index_map(&["a.map"]), &cyclic).unwrap_err().to_string();
+  assert!(err.ends_with("a.map refers to itself through its sections"), "{}", err);
+
+  let deep = |url: &str| -> Result<String, Error> { Ok(index_map(&[&format!("{}a", url)])) };
+  let err = consume_with_fetcher(&index_map(&["a"]), &deep).unwrap_err().to_string();
+  assert!(err.ends_with("Source maps nest more than 8 deep at aaaaaaaaa"), "{}", err);
+}
+
+#[test]
+fn it_resolves_urls_against_the_url_of_their_map() {
+  assert_eq!(resolve_url("dist/index.map", "a.map"), "dist/a.map");
+  assert_eq!(resolve_url("index.map", "a.map"), "a.map");
+  assert_eq!(resolve_url("dist/index.map", "/srv/a.map"), "/srv/a.map");
+  assert_eq!(resolve_url("https://cdn.example", "/a.map"), "https://cdn.example/a.map");
+  assert_eq!(resolve_url("https://cdn.example/maps/index.map", "//other.example/a.map"), "https://other.example/a.map");
+  assert_eq!(resolve_url("file:///srv/index.map", "/a.map"), "file:///a.map");
+  assert_eq!(resolve_url("file:///srv/index.map", "s3://bucket/a.map"), "s3://bucket/a.map");
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn it_fetches_maps_from_the_filesystem() {
+  use std::path::Path;
+
+  let fetcher = FileFetcher::new(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("cli"));
+  let cache = consume_from_generated("//# sourceMappingURL=app.js.map?v=2", &fetcher).unwrap();
+  assert!(!cache.is_empty());
+  assert!(fetcher.fetch("https://cdn.example/app.js.map").is_err());
+  assert!(matches!(fetcher.fetch("missing.js.map"), Err(Error::Io(_))));
+}
@@ -0,0 +1,758 @@
+use std::collections::{HashMap, HashSet};
+
+extern crate serde_json;
+
+use base64_vlq;
+use consume::{Cache, CodePosition};
+use error::{Error, ErrorCode};
+
+const SOURCE_MAP_VERSION: u32 = 3;
+
+#[allow(non_snake_case)]
+#[derive(Serialize, Debug)]
+struct SourceMapOutput {
+  version: u32,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  file: Option<String>,
+  #[serde(skip_serializing_if = "String::is_empty")]
+  sourceRoot: String,
+  sources: Vec<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  sourcesContent: Option<Vec<Option<String>>>,
+  names: Vec<String>,
+  mappings: String,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  ignoreList: Vec<u32>
+}
+
+#[derive(Serialize, Debug)]
+struct IndexMapOutput {
+  version: u32,
+  sections: Vec<SectionOutput>
+}
+
+#[derive(Serialize, Debug)]
+struct SectionOutput {
+  offset: OffsetOutput,
+  map: SourceMapOutput
+}
+
+#[derive(Serialize, Debug)]
+struct OffsetOutput {
+  line: u32,
+  column: u32
+}
+
+/**
+ * Generator builds a source map from individual mappings.
+ *
+ * Sources and names are interned into the `sources` and `names` tables as they are first
+ * used, so the same sequence of calls always produces the same output.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::{consume, Generator, CodePosition};
+ *
+ * let mut generator = Generator::new();
+ * generator.set_file("out.js");
+ * generator.add_mapping(
+ *   CodePosition { line: 1, column: 4 },
+ *   CodePosition { line: 2, column: 0 },
+ *   "foo.js",
+ *   "bar"
+ * );
+ *
+ * let json = generator.to_json().unwrap();
+ * let cache = consume(&json).unwrap();
+ * assert!(&*cache.mapping_for_generated_position(1, 4).name == "bar");
+ * ```
+ */
+#[derive(Debug, Default)]
+pub struct Generator {
+  file: Option<String>,
+  source_root: String,
+  mappings: Vec<GeneratorMapping>,
+  sources: Vec<String>,
+  names: Vec<String>,
+  source_indices: HashMap<String, u32>,
+  name_indices: HashMap<String, u32>,
+  sources_content: HashMap<String, String>,
+  ignored_sources: HashSet<u32>,
+  skip_sources_content: bool,
+  skip_duplicates: bool,
+  skip_redundant: bool,
+  relativize_sources: bool
+}
+
+// A mapping with its source and name interned into the generator's tables
+#[derive(Clone, Eq, PartialEq, Debug)]
+struct GeneratorMapping {
+  generated: CodePosition,
+  original: CodePosition,
+  source: Option<u32>,
+  name: Option<u32>
+}
+
+impl Generator {
+  /**
+   * Creates an empty generator.
+   */
+  pub fn new() -> Generator {
+    Generator::default()
+  }
+
+  /**
+   * Sets the name of the generated file the source map is associated with.
+   */
+  pub fn set_file<S: Into<String>>(&mut self, file: S) {
+    self.file = Some(file.into());
+  }
+
+  /**
+   * Sets the URL root from which all sources are relative.
+   */
+  pub fn set_source_root<S: Into<String>>(&mut self, source_root: S) {
+    self.source_root = source_root.into();
+  }
+
+  /**
+   * Rewrites sources to be relative to the source root on output. Off by default.
+   *
+   * Sources that start with the source root have it removed, so the output does not
+   * depend on where the sources were on the machine that generated it. Sources outside
+   * the source root are left as they are, and reported by `warnings`.
+   */
+  pub fn set_relativize_sources(&mut self, relativize_sources: bool) {
+    self.relativize_sources = relativize_sources;
+  }
+
+  /**
+   * Returns warnings about the output of the generator, such as sources that could not be
+   * made relative to the source root.
+   */
+  pub fn warnings(&self) -> Vec<String> {
+    if !self.relativize_sources || self.source_root.is_empty() {
+      return Vec::new();
+    }
+    self.sources.iter()
+      .filter(|source| self.relative_source(source).is_none())
+      .map(|source| format!("Source {:?} is outside the source root {:?} and was left as is", source, self.source_root))
+      .collect()
+  }
+
+  // The source relative to the source root, or None if it is outside the source root
+  fn relative_source<'a>(&self, source: &'a str) -> Option<&'a str> {
+    let relative = source.strip_prefix(self.source_root.as_str())?;
+    if self.source_root.ends_with('/') {
+      Some(relative)
+    } else {
+      relative.strip_prefix('/')
+    }
+  }
+
+  fn output_sources(&self) -> Vec<String> {
+    if !self.relativize_sources || self.source_root.is_empty() {
+      return self.sources.clone();
+    }
+    self.sources.iter()
+      .map(|source| self.relative_source(source).unwrap_or(source).to_owned())
+      .collect()
+  }
+
+  /**
+   * Sets the original text of a source, which is embedded in the `sourcesContent` of the
+   * output.
+   */
+  pub fn set_source_content<S: Into<String>, C: Into<String>>(&mut self, source: S, content: C) {
+    self.sources_content.insert(source.into(), content.into());
+  }
+
+  /**
+   * Whether the output includes a `sourcesContent` array with the source contents that
+   * were set. On by default; turn it off to emit a smaller map with mappings only.
+   */
+  pub fn set_include_sources_content(&mut self, include_sources_content: bool) {
+    self.skip_sources_content = !include_sources_content;
+  }
+
+  /**
+   * Drops mappings identical to the previous mapping from the output. Off by default.
+   */
+  pub fn set_skip_duplicates(&mut self, skip_duplicates: bool) {
+    self.skip_duplicates = skip_duplicates;
+  }
+
+  /**
+   * Drops mappings that do not change the result of any lookup from the output. Off by
+   * default.
+   *
+   * Like terser does, a mapping is redundant when it repeats the source, original
+   * position and name of the previous mapping on the same generated line. Unlike terser,
+   * it is only dropped when the next mapping on the line repeats them as well, so that
+   * every column resolves to the same original position both in `Cache` lookups, which
+   * resolve a position to the mapping at or after it, and in browsers, which resolve it
+   * to the mapping at or before it.
+   */
+  pub fn set_skip_redundant(&mut self, skip_redundant: bool) {
+    self.skip_redundant = skip_redundant;
+  }
+
+  /**
+   * Returns the index of a source in the `sources` table, adding it to the end of the
+   * table if it is not there yet.
+   *
+   * Sources are added automatically by `add_mapping`, so this is only needed to control
+   * the order of the table.
+   */
+  pub fn source_index(&mut self, source: &str) -> u32 {
+    intern(&mut self.sources, &mut self.source_indices, source)
+  }
+
+  /**
+   * Returns the index of a name in the `names` table, adding it to the end of the table if
+   * it is not there yet.
+   *
+   * Names are added automatically by `add_mapping`, so this is only needed to control the
+   * order of the table.
+   */
+  pub fn name_index(&mut self, name: &str) -> u32 {
+    intern(&mut self.names, &mut self.name_indices, name)
+  }
+
+  /**
+   * Adds a source to the `ignoreList`, so that debuggers hide it. The source is added to
+   * the `sources` table if it is not there yet.
+   */
+  pub fn add_to_ignore_list(&mut self, source: &str) {
+    let index = self.source_index(source);
+    self.ignored_sources.insert(index);
+  }
+
+  /**
+   * Adds a mapping to the source map.
+   *
+   * An empty `source` produces a mapping without an original position, and an empty `name`
+   * produces a mapping without a name, mirroring how `consume` represents them.
+   */
+  pub fn add_mapping(&mut self, generated: CodePosition, original: CodePosition, source: &str, name: &str) {
+    let source = if source.is_empty() { None } else { Some(self.source_index(source)) };
+    let name = if name.is_empty() || source.is_none() { None } else { Some(self.name_index(name)) };
+    self.mappings.push(GeneratorMapping { generated, original, source, name });
+  }
+
+  /**
+   * Serializes the source map into a JSON string.
+   */
+  pub fn to_json(&self) -> Result<String, Error> {
+    let output = self.build()?;
+    serde_json::to_string(&output).map_err(Error::from)
+  }
+
+  fn build(&self) -> Result<SourceMapOutput, Error> {
+    let mut mappings: Vec<&GeneratorMapping> = self.mappings.iter().collect();
+    // Sort like compare_mappings does, comparing sources and names by value
+    let source = |mapping: &GeneratorMapping| mapping.source.map_or("", |index| self.sources[index as usize].as_str());
+    let name = |mapping: &GeneratorMapping| mapping.name.map_or("", |index| self.names[index as usize].as_str());
+    mappings.sort_by(|a, b| {
+      (&a.generated, &a.original, source(a), name(a)).cmp(&(&b.generated, &b.original, source(b), name(b)))
+    });
+    if self.skip_duplicates {
+      mappings.dedup();
+    }
+    if self.skip_redundant {
+      // The first and last mappings of a run are kept, so mappings are compared with
+      // their neighbours in the input rather than with the ones kept
+      mappings = mappings.iter().enumerate()
+        .filter(|&(index, mapping)| {
+          index == 0 || index + 1 == mappings.len()
+            || !(continues(mappings[index - 1], mapping) && continues(mapping, mappings[index + 1]))
+        })
+        .map(|(_, &mapping)| mapping)
+        .collect();
+    }
+
+    let mut encoded: Vec<u8> = Vec::new();
+    let mut previous_generated_line: u32 = 1;
+    let mut previous_generated_column: u32 = 0;
+    let mut previous_original_line: u32 = 0;
+    let mut previous_original_column: u32 = 0;
+    let mut previous_source: u32 = 0;
+    let mut previous_name: u32 = 0;
+
+    for mapping in mappings {
+      if mapping.generated.line < 1 {
+        return Err(Error::invalid(ErrorCode::InvalidPosition, "Invalid mapping: generated line numbers start from 1"));
+      }
+
+      if mapping.generated.line == previous_generated_line {
+        if !encoded.is_empty() && encoded[encoded.len() - 1] != b';' {
+          encoded.push(b',');
+        }
+      } else {
+        while previous_generated_line < mapping.generated.line {
+          encoded.push(b';');
+          previous_generated_line += 1;
+        }
+        previous_generated_column = 0;
+      }
+
+      encode_delta(&mut encoded, mapping.generated.column as i64, previous_generated_column as i64)?;
+      previous_generated_column = mapping.generated.column;
+
+      let source = match mapping.source {
+        Some(source) => source,
+        None => continue
+      };
+
+      if mapping.original.line < 1 {
+        return Err(Error::invalid(ErrorCode::InvalidPosition, "Invalid mapping: original line numbers start from 1"));
+      }
+
+      encode_delta(&mut encoded, source as i64, previous_source as i64)?;
+      previous_source = source;
+
+      // Lines are stored 0-based
+      let original_line = mapping.original.line - 1;
+      encode_delta(&mut encoded, original_line as i64, previous_original_line as i64)?;
+      previous_original_line = original_line;
+
+      encode_delta(&mut encoded, mapping.original.column as i64, previous_original_column as i64)?;
+      previous_original_column = mapping.original.column;
+
+      let name = match mapping.name {
+        Some(name) => name,
+        None => continue
+      };
+
+      encode_delta(&mut encoded, name as i64, previous_name as i64)?;
+      previous_name = name;
+    }
+
+    let sources_content = if self.skip_sources_content {
+      None
+    } else {
+      let contents: Vec<Option<String>> = self.sources.iter().map(|source| self.sources_content.get(source).cloned()).collect();
+      if contents.iter().any(Option::is_some) { Some(contents) } else { None }
+    };
+
+    let mut ignore_list: Vec<u32> = self.ignored_sources.iter().cloned().collect();
+    ignore_list.sort_unstable();
+
+    Ok(SourceMapOutput {
+      version: SOURCE_MAP_VERSION,
+      file: self.file.clone(),
+      sourceRoot: self.source_root.clone(),
+      sources: self.output_sources(),
+      sourcesContent: sources_content,
+      names: self.names.clone(),
+      // The encoder only ever produces base 64 digits and separators
+      mappings: String::from_utf8(encoded).expect("mappings should be ASCII"),
+      ignoreList: ignore_list
+    })
+  }
+}
+
+fn intern(table: &mut Vec<String>, indices: &mut HashMap<String, u32>, entry: &str) -> u32 {
+  if let Some(&index) = indices.get(entry) {
+    return index;
+  }
+  let index = table.len() as u32;
+  table.push(entry.to_owned());
+  indices.insert(entry.to_owned(), index);
+  index
+}
+
+// Whether `mapping` is on the same generated line as the `previous` mapping and resolves
+// to the same original position
+fn continues(previous: &GeneratorMapping, mapping: &GeneratorMapping) -> bool {
+  previous.generated.line == mapping.generated.line
+    && previous.original == mapping.original
+    && previous.source == mapping.source
+    && previous.name == mapping.name
+}
+
+fn encode_delta(encoded: &mut Vec<u8>, value: i64, previous: i64) -> Result<(), Error> {
+  let delta = value - previous;
+  if delta < (i32::MIN as i64) || delta > (i32::MAX as i64) {
+    return Err(Error::invalid(ErrorCode::InvalidVlq, format!("Mapping field delta {} does not fit in a VLQ", delta)));
+  }
+  let digits = base64_vlq::encode(delta as i32).ok_or_else(|| Error::invalid(ErrorCode::InvalidVlq, "Invalid VLQ mapping field"))?;
+  encoded.extend_from_slice(&digits);
+  Ok(())
+}
+
+impl Cache {
+  /**
+   * Serializes the cache back into a source map JSON string.
+   *
+   * The `sources` and `names` tables only contain the entries that are referenced by a
+   * mapping, in the order they are first referenced, and the `ignoreList` refers to the
+   * same table. Source contents retained by `consume_with_sources_content` are written
+   * to `sourcesContent`.
+   */
+  pub fn to_json(&self) -> Result<String, Error> {
+    self.generator().to_json()
+  }
+
+  /**
+   * Like `to_json`, but omits the `sourcesContent` array, which often makes up most of the
+   * size of a source map.
+   */
+  pub fn to_json_without_sources_content(&self) -> Result<String, Error> {
+    let mut generator = self.generator();
+    generator.set_include_sources_content(false);
+    generator.to_json()
+  }
+
+  fn generator(&self) -> Generator {
+    let mut generator = Generator::new();
+    generator.set_source_root(self.source_root.clone());
+    if !self.file.is_empty() {
+      generator.set_file(self.file.clone());
+    }
+    generator.sources_content = self.sources_content().clone();
+    for mapping in self.mappings() {
+      generator.add_mapping(mapping.generated.clone(), mapping.original.clone(), &mapping.source, &mapping.name);
+    }
+    for source in self.ignored_sources() {
+      // Only sources that are still referenced are in the table
+      if generator.source_indices.contains_key(source) {
+        generator.add_to_ignore_list(source);
+      }
+    }
+    generator
+  }
+}
+
+/**
+ * Merges several caches into a single [index map][index-map], serialized as a JSON string.
+ *
+ * Each part is the generated position where the part starts in the combined file, along
+ * with the cache for that part. Parts must be ordered by their starting position, and a
+ * part must not start before the last mapping of the previous part.
+ *
+ * [index-map]: https://tc39.es/source-map/#index-map
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::{consume, merge_to_index_map, CodePosition};
+ *
+ * let a = consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA" }"#).unwrap();
+ * let b = consume(r#"{ "version": 3, "sources": ["b.js"], "names": [], "mappings": "AAAA" }"#).unwrap();
+ *
+ * let json = merge_to_index_map(&[
+ *   (CodePosition { line: 1, column: 0 }, &a),
+ *   (CodePosition { line: 10, column: 0 }, &b)
+ * ]).unwrap();
+ * assert!(json.contains(r#""offset":{"line":9,"column":0}"#));
+ * ```
+ */
+pub fn merge_to_index_map(parts: &[(CodePosition, &Cache)]) -> Result<String, Error> {
+  let generators: Vec<(&CodePosition, Generator)> = parts.iter()
+    .map(|&(ref offset, cache)| (offset, cache.generator()))
+    .collect();
+  index_map(generators.iter().map(|&(offset, ref generator)| (offset, generator)))
+}
+
+/**
+ * Emits an [index map][index-map] with one section per generator, serialized as a JSON
+ * string.
+ *
+ * Each section is the generated position where the section starts in the combined file,
+ * along with the generator for that section. Sections must be ordered by their starting
+ * position, and a section must not start before the last mapping of the previous section.
+ *
+ * [index-map]: https://tc39.es/source-map/#index-map
+ */
+pub fn emit_index_map(sections: &[(CodePosition, &Generator)]) -> Result<String, Error> {
+  index_map(sections.iter().map(|&(ref offset, generator)| (offset, generator)))
+}
+
+fn index_map<'a, I: Iterator<Item = (&'a CodePosition, &'a Generator)>>(parts: I) -> Result<String, Error> {
+  let mut sections: Vec<SectionOutput> = Vec::new();
+  let mut previous_offset: Option<(u32, u32)> = None;
+  let mut previous_end: Option<(u32, u32)> = None;
+
+  for (offset, generator) in parts {
+    if offset.line < 1 {
+      return Err(Error::invalid(ErrorCode::InvalidIndexMap, "Invalid index map section: line numbers start from 1"));
+    }
+
+    let start = (offset.line, offset.column);
+    if previous_offset.is_some_and(|previous| start <= previous) || previous_end.is_some_and(|end| start <= end) {
+      return Err(Error::invalid(ErrorCode::InvalidIndexMap, format!(
+        "Invalid index map section at {}:{}: sections must be sorted and must not overlap",
+        offset.line, offset.column
+      )));
+    }
+
+    // The last generated position of the section within the combined file
+    let last = generator.mappings.iter().map(|mapping| (mapping.generated.line, mapping.generated.column)).max();
+    previous_end = last.map(|(line, column)| {
+      if line <= 1 {
+        (offset.line, offset.column.saturating_add(column))
+      } else {
+        ((offset.line - 1).saturating_add(line), column)
+      }
+    });
+    previous_offset = Some(start);
+
+    sections.push(SectionOutput {
+      offset: OffsetOutput {
+        line: offset.line - 1,
+        column: offset.column
+      },
+      map: generator.build()?
+    });
+  }
+
+  let output = IndexMapOutput {
+    version: SOURCE_MAP_VERSION,
+    sections
+  };
+  serde_json::to_string(&output).map_err(Error::from)
+}
+
+#[cfg(test)]
+use consume::{consume, consume_with_sources_content, Mapping};
+#[cfg(test)]
+use compact::CompactCache;
+#[cfg(test)]
+use binary::MappedCache;
+
+#[test]
+fn it_round_trips_through_consume() {
+  let json = r#"{
+    "version": 3,
+    "file": "foo.js",
+    "sources": ["source.js", "other.js"],
+    "names": ["name1", "name2"],
+    "mappings": ";EAACA,CCAC;;IAEEC,C;;MAEE",
+    "sourceRoot": "http://example.com"
+  }"#;
+  let cache = consume(json).unwrap();
+  let round_tripped = consume(&cache.to_json().unwrap()).unwrap();
+  assert_eq!(cache.mappings(), round_tripped.mappings());
+  assert_eq!(round_tripped.source_root, "http://example.com");
+}
+
+#[test]
+fn it_encodes_mappings_without_sources_or_names() {
+  let mut generator = Generator::new();
+  generator.add_mapping(CodePosition { line: 1, column: 1 }, CodePosition { line: 0, column: 0 }, "", "");
+  generator.add_mapping(CodePosition { line: 3, column: 2 }, CodePosition { line: 1, column: 1 }, "a.js", "");
+  assert_eq!(
+    generator.to_json().unwrap(),
+    r#"{"version":3,"sources":["a.js"],"names":[],"mappings":"C;;EAAC"}"#
+  );
+}
+
+#[test]
+fn it_merges_caches_into_an_index_map() {
+  let a = consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA;AACA" }"#).unwrap();
+  let b = consume(r#"{ "version": 3, "sources": ["b.js"], "names": ["x"], "mappings": "EAAAA" }"#).unwrap();
+
+  let json = merge_to_index_map(&[
+    (CodePosition { line: 1, column: 0 }, &a),
+    (CodePosition { line: 2, column: 10 }, &b)
+  ]).unwrap();
+
+  assert_eq!(json, concat!(
+    r#"{"version":3,"sections":["#,
+    r#"{"offset":{"line":0,"column":0},"map":{"version":3,"sources":["a.js"],"names":[],"mappings":"AAAA;AACA"}},"#,
+    r#"{"offset":{"line":1,"column":10},"map":{"version":3,"sources":["b.js"],"names":["x"],"mappings":"EAAAA"}}"#,
+    r#"]}"#
+  ));
+}
+
+#[test]
+fn it_rejects_unsorted_index_map_sections() {
+  let a = consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA" }"#).unwrap();
+  let result = merge_to_index_map(&[
+    (CodePosition { line: 5, column: 0 }, &a),
+    (CodePosition { line: 2, column: 0 }, &a)
+  ]);
+  assert!(result.is_err());
+}
+
+#[test]
+fn it_rejects_overlapping_index_map_sections() {
+  let a = consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA;AACA" }"#).unwrap();
+  let result = merge_to_index_map(&[
+    (CodePosition { line: 1, column: 0 }, &a),
+    (CodePosition { line: 2, column: 0 }, &a)
+  ]);
+  assert!(result.is_err());
+}
+
+#[test]
+fn it_serializes_rebased_sources() {
+  let mut cache = consume(r#"{ "version": 3, "sources": ["/ci/src/a.js"], "names": [], "mappings": "AAAA" }"#).unwrap();
+  cache.rebase_sources("/ci/", "./");
+  assert_eq!(
+    cache.to_json().unwrap(),
+    r#"{"version":3,"sources":["./src/a.js"],"names":[],"mappings":"AAAA"}"#
+  );
+}
+
+#[test]
+fn it_optionally_skips_duplicate_and_redundant_mappings() {
+  let mut generator = Generator::new();
+  for &(column, original_column) in &[(0, 0), (0, 0), (2, 5), (4, 5), (6, 5), (8, 9)] {
+    generator.add_mapping(CodePosition { line: 1, column }, CodePosition { line: 1, column: original_column }, "a.js", "");
+  }
+  let full = generator.to_json().unwrap();
+
+  generator.set_skip_duplicates(true);
+  let deduplicated = generator.to_json().unwrap();
+  generator.set_skip_redundant(true);
+  let minimal = generator.to_json().unwrap();
+
+  assert_eq!(deduplicated, r#"{"version":3,"sources":["a.js"],"names":[],"mappings":"AAAA,EAAK,EAAA,EAAA,EAAI"}"#);
+  assert_eq!(minimal, r#"{"version":3,"sources":["a.js"],"names":[],"mappings":"AAAA,EAAK,IAAA,EAAI"}"#);
+
+  // Every column, including those of the dropped mappings, resolves to the same original
+  // position in each of the caches
+  let resolve = |mapping: Mapping| (mapping.original, mapping.source, mapping.name);
+  let (full_cache, minimal_cache) = (consume(&full).unwrap(), consume(&minimal).unwrap());
+  let (full_compact, minimal_compact) = (CompactCache::from_json(&full).unwrap(), CompactCache::from_json(&minimal).unwrap());
+  let full_mapped = MappedCache::from_bytes(full_cache.serialize_binary()).unwrap();
+  let minimal_mapped = MappedCache::from_bytes(minimal_cache.serialize_binary()).unwrap();
+  for column in 0..10 {
+    let expected = resolve(full_cache.mapping_for_generated_position(1, column));
+    assert_eq!(resolve(minimal_cache.mapping_for_generated_position(1, column)), expected, "column {}", column);
+    assert_eq!(resolve(full_compact.mapping_for_generated_position(1, column)), expected, "column {}", column);
+    assert_eq!(resolve(minimal_compact.mapping_for_generated_position(1, column)), expected, "column {}", column);
+    assert_eq!(resolve(full_mapped.mapping_for_generated_position(1, column)), expected, "column {}", column);
+    assert_eq!(resolve(minimal_mapped.mapping_for_generated_position(1, column)), expected, "column {}", column);
+  }
+
+  // Mappings that differ in their name from a neighbour are kept
+  let mut named = Generator::new();
+  named.set_skip_redundant(true);
+  named.add_mapping(CodePosition { line: 1, column: 0 }, CodePosition { line: 1, column: 0 }, "a.js", "");
+  named.add_mapping(CodePosition { line: 1, column: 2 }, CodePosition { line: 1, column: 0 }, "a.js", "");
+  named.add_mapping(CodePosition { line: 1, column: 4 }, CodePosition { line: 1, column: 0 }, "a.js", "x");
+  assert_eq!(named.to_json().unwrap(), r#"{"version":3,"sources":["a.js"],"names":["x"],"mappings":"AAAA,EAAA,EAAAA"}"#);
+}
+
+#[test]
+fn it_emits_index_maps_from_generators() {
+  let start = CodePosition { line: 1, column: 0 };
+  let mut first = Generator::new();
+  first.add_mapping(CodePosition { line: 1, column: 0 }, start.clone(), "a.js", "");
+  first.add_mapping(CodePosition { line: 3, column: 4 }, start.clone(), "a.js", "");
+  let mut second = Generator::new();
+  second.add_mapping(CodePosition { line: 1, column: 2 }, start, "b.js", "");
+
+  let json = emit_index_map(&[
+    (CodePosition { line: 1, column: 0 }, &first),
+    (CodePosition { line: 3, column: 5 }, &second)
+  ]).unwrap();
+  assert_eq!(json, concat!(
+    r#"{"version":3,"sections":["#,
+    r#"{"offset":{"line":0,"column":0},"map":{"version":3,"sources":["a.js"],"names":[],"mappings":"AAAA;;IAAA"}},"#,
+    r#"{"offset":{"line":2,"column":5},"map":{"version":3,"sources":["b.js"],"names":[],"mappings":"EAAA"}}"#,
+    r#"]}"#
+  ));
+
+  // The first section ends at 3:4, so the second cannot start there
+  assert!(emit_index_map(&[
+    (CodePosition { line: 1, column: 0 }, &first),
+    (CodePosition { line: 3, column: 4 }, &second)
+  ]).is_err());
+}
+
+#[test]
+fn it_rejects_index_map_sections_with_the_same_offset() {
+  let empty = Generator::new();
+  assert!(emit_index_map(&[
+    (CodePosition { line: 1, column: 0 }, &empty),
+    (CodePosition { line: 1, column: 0 }, &empty)
+  ]).is_err());
+}
+
+#[test]
+fn it_optionally_includes_sources_content() {
+  let cache = consume_with_sources_content(r#"{
+    "version": 3,
+    "sources": ["a.js", "b.js", "unused.js"],
+    "sourcesContent": [null, "let b;", "let unused;"],
+    "names": [],
+    "mappings": "AAAA;ACAA"
+  }"#).unwrap();
+  assert_eq!(
+    cache.to_json().unwrap(),
+    r#"{"version":3,"sources":["a.js","b.js"],"sourcesContent":[null,"let b;"],"names":[],"mappings":"AAAA;ACAA"}"#
+  );
+  assert_eq!(
+    cache.to_json_without_sources_content().unwrap(),
+    r#"{"version":3,"sources":["a.js","b.js"],"names":[],"mappings":"AAAA;ACAA"}"#
+  );
+
+  let round_tripped = consume_with_sources_content(&cache.to_json().unwrap()).unwrap();
+  assert_eq!(round_tripped.source_content("b.js"), Some("let b;"));
+}
+
+#[test]
+fn it_interns_sources_and_names_in_first_use_order() {
+  let mut generator = Generator::new();
+  assert_eq!(generator.source_index("z.js"), 0);
+  generator.add_mapping(CodePosition { line: 2, column: 0 }, CodePosition { line: 1, column: 0 }, "b.js", "y");
+  generator.add_mapping(CodePosition { line: 1, column: 0 }, CodePosition { line: 1, column: 0 }, "a.js", "x");
+  generator.add_mapping(CodePosition { line: 1, column: 5 }, CodePosition { line: 1, column: 0 }, "b.js", "x");
+  assert_eq!(generator.source_index("b.js"), 1);
+  assert_eq!(generator.source_index("a.js"), 2);
+  assert_eq!(generator.name_index("x"), 1);
+
+  assert_eq!(
+    generator.to_json().unwrap(),
+    r#"{"version":3,"sources":["z.js","b.js","a.js"],"names":["y","x"],"mappings":"AEAAC,KDAAA;AAAAD"}"#
+  );
+}
+
+#[test]
+fn it_optionally_relativizes_sources_to_the_source_root() {
+  let mut generator = Generator::new();
+  generator.set_source_root("/home/ci/app");
+  for (line, source) in (1..).zip(&["/home/ci/app/src/a.js", "/home/ci/application.js", "/usr/lib/b.js"]) {
+    generator.add_mapping(CodePosition { line, column: 0 }, CodePosition { line: 1, column: 0 }, source, "");
+  }
+  assert!(generator.to_json().unwrap().contains(r#""sources":["/home/ci/app/src/a.js","/home/ci/application.js","/usr/lib/b.js"]"#));
+  assert!(generator.warnings().is_empty());
+
+  generator.set_relativize_sources(true);
+  assert!(generator.to_json().unwrap().contains(r#""sources":["src/a.js","/home/ci/application.js","/usr/lib/b.js"]"#));
+  assert_eq!(generator.warnings().len(), 2);
+  assert!(generator.warnings()[1].contains("/usr/lib/b.js"));
+
+  generator.set_source_root("/home/ci/app/");
+  assert!(generator.to_json().unwrap().contains(r#""sources":["src/a.js","#));
+}
+
+#[test]
+fn it_emits_the_ignore_list() {
+  let mut generator = Generator::new();
+  generator.add_mapping(CodePosition { line: 1, column: 0 }, CodePosition { line: 1, column: 0 }, "app.js", "");
+  generator.add_to_ignore_list("vendor.js");
+  generator.add_mapping(CodePosition { line: 2, column: 0 }, CodePosition { line: 1, column: 0 }, "vendor.js", "");
+  assert_eq!(
+    generator.to_json().unwrap(),
+    r#"{"version":3,"sources":["app.js","vendor.js"],"names":[],"mappings":"AAAA;ACAA","ignoreList":[1]}"#
+  );
+}
+
+#[test]
+fn it_recomputes_the_ignore_list_of_filtered_caches() {
+  let cache = consume(r#"{ "version": 3, "sources": ["sdk.js", "app.js", "vendor.js"], "names": [], "mappings": "AAAA;ACAA;ACAA", "ignoreList": [0, 2] }"#).unwrap();
+  assert_eq!(
+    cache.to_json().unwrap(),
+    r#"{"version":3,"sources":["sdk.js","app.js","vendor.js"],"names":[],"mappings":"AAAA;ACAA;ACAA","ignoreList":[0,2]}"#
+  );
+  assert_eq!(
+    cache.filter_sources(|source| source != "sdk.js", true).to_json().unwrap(),
+    r#"{"version":3,"sources":["app.js","vendor.js"],"names":[],"mappings":"A;AAAA;ACAA","ignoreList":[1]}"#
+  );
+}
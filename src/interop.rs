@@ -0,0 +1,224 @@
+extern crate sourcemap;
+
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use self::sourcemap::{SourceMap, SourceMapBuilder};
+
+use consume::{Cache, CodePosition, Interner, Mapping, sort_mappings};
+use error::{Error, ErrorCode};
+
+/**
+ * Converts a `sourcemap::SourceMap` into a cache without serializing it to JSON. Requires
+ * the `sourcemap-interop` feature.
+ *
+ * The lines of the `sourcemap` crate start from 0 and are converted to lines starting
+ * from 1. Sources are stored without the source root, which `sourcemap` prefixes them
+ * with, and source contents are kept. Fails if a line does not fit in a `u32` once
+ * converted, or if a token refers to an entry outside the tables.
+ *
+ * # Examples
+ *
+ * ```
+ * # extern crate sourcemap;
+ * # extern crate js_source_mapper;
+ * use std::convert::TryFrom;
+ * use js_source_mapper::Cache;
+ *
+ * let map = sourcemap::SourceMap::from_slice(br#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": ";AACA" }"#).unwrap();
+ * let cache = Cache::try_from(&map).unwrap();
+ * let mapping = cache.mapping_for_generated_position(2, 0);
+ * assert!(&*mapping.source == "a.js" && mapping.original.line == 2);
+ * ```
+ */
+impl<'a> TryFrom<&'a SourceMap> for Cache {
+  type Error = Error;
+
+  fn try_from(map: &'a SourceMap) -> Result<Cache, Error> {
+    let source_root = map.get_source_root().unwrap_or("");
+    let sources: Vec<String> = map.sources().map(|source| without_source_root(source, source_root).to_owned()).collect();
+    let names: Vec<String> = map.names().map(|name| name.to_owned()).collect();
+    let mut interner = Interner::default();
+    let shared_sources: Vec<Arc<str>> = sources.iter().map(|source| interner.intern(source)).collect();
+    let empty = interner.intern("");
+
+    let mut mappings = Vec::with_capacity(map.get_token_count() as usize);
+    for token in map.tokens() {
+      let generated = CodePosition {
+        line: line_from_zero_based(token.get_dst_line())?,
+        column: token.get_dst_col()
+      };
+      let mut mapping = Mapping {
+        generated,
+        original: CodePosition { line: 0, column: 0 },
+        source: empty.clone(),
+        name: empty.clone()
+      };
+      if token.has_source() {
+        let source_id = token.get_src_id();
+        mapping.source = shared_sources.get(source_id as usize).cloned()
+          .ok_or_else(|| Error::invalid(ErrorCode::SourceIndexOutOfRange, format!("Invalid source map: reference to source index {} when source list length is {}", source_id, sources.len())))?;
+        mapping.original = CodePosition {
+          line: line_from_zero_based(token.get_src_line())?,
+          column: token.get_src_col()
+        };
+        if token.get_name_id() != !0 {
+          mapping.name = interner.intern(token.get_name()
+            .ok_or_else(|| Error::invalid(ErrorCode::NameIndexOutOfRange, format!("Invalid source map: reference to name index {} when name list length is {}", token.get_name_id(), names.len())))?);
+        }
+      }
+      mappings.push(mapping);
+    }
+    sort_mappings(&mut mappings);
+
+    let sources_content = (0..).zip(&sources)
+      .filter_map(|(index, source)| map.get_source_contents(index).map(|content| (source.clone(), content.to_owned())))
+      .collect();
+    let mut cache = Cache::from_parts(mappings, sources, names, source_root.to_owned());
+    cache.set_sources_content(sources_content);
+    cache.file = map.get_file().unwrap_or("").to_owned();
+    Ok(cache)
+  }
+}
+
+/**
+ * Converts a cache into a `sourcemap::SourceMapBuilder`, keeping the order of its tables,
+ * for adding more mappings or source contents before building a `sourcemap::SourceMap`.
+ * Requires the `sourcemap-interop` feature.
+ *
+ * Lines are converted to lines starting from 0, the convention of the `sourcemap` crate.
+ * Mappings without a source become tokens without a source, and the ignore list is
+ * dropped, since `sourcemap` does not support it.
+ */
+impl<'a> From<&'a Cache> for SourceMapBuilder {
+  fn from(cache: &'a Cache) -> SourceMapBuilder {
+    let mut builder = SourceMapBuilder::new(None);
+    if !cache.file.is_empty() {
+      builder.set_file(Some(cache.file.as_str()));
+    }
+    if !cache.source_root.is_empty() {
+      builder.set_source_root(Some(cache.source_root.as_str()));
+    }
+    for source in cache.sources() {
+      let source_id = builder.add_source(source);
+      if let Some(content) = cache.source_content(source) {
+        builder.set_source_contents(source_id, Some(content));
+      }
+    }
+    for name in cache.names() {
+      builder.add_name(name);
+    }
+
+    // Lines in caches start from 1, apart from the original line of mappings without a source
+    for mapping in cache.mappings() {
+      let generated_line = mapping.generated.line - 1;
+      if mapping.source.is_empty() {
+        builder.add_raw(generated_line, mapping.generated.column, 0, 0, None, None, false);
+      } else {
+        let name = if mapping.name.is_empty() { None } else { Some(&*mapping.name) };
+        builder.add(generated_line, mapping.generated.column, mapping.original.line - 1, mapping.original.column, Some(&mapping.source), name, false);
+      }
+    }
+    builder
+  }
+}
+
+/**
+ * Converts a cache into a `sourcemap::SourceMap`, see the conversion into
+ * `sourcemap::SourceMapBuilder`. Requires the `sourcemap-interop` feature.
+ *
+ * # Examples
+ *
+ * ```
+ * # extern crate sourcemap;
+ * # extern crate js_source_mapper;
+ * use js_source_mapper::consume;
+ *
+ * let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": ["x"], "mappings": ";AACAA" }"#).unwrap();
+ * let map = sourcemap::SourceMap::from(&cache);
+ * let token = map.lookup_token(1, 0).unwrap();
+ * assert!(token.get_src() == (1, 0) && token.get_name() == Some("x"));
+ * ```
+ */
+impl<'a> From<&'a Cache> for SourceMap {
+  fn from(cache: &'a Cache) -> SourceMap {
+    SourceMapBuilder::from(cache).into_sourcemap()
+  }
+}
+
+fn line_from_zero_based(line: u32) -> Result<u32, Error> {
+  line.checked_add(1).ok_or_else(|| Error::invalid(ErrorCode::InvalidPosition, "Line number overflowed"))
+}
+
+// Undoes the prefixing of sources with the source root done by sourcemap::SourceMap,
+// which joins them with a single slash and leaves absolute sources untouched
+fn without_source_root<'a>(source: &'a str, source_root: &str) -> &'a str {
+  let source_root = source_root.strip_suffix('/').unwrap_or(source_root);
+  if source_root.is_empty() {
+    return source;
+  }
+  source.strip_prefix(source_root)
+    .and_then(|source| source.strip_prefix('/'))
+    .unwrap_or(source)
+}
+
+#[cfg(test)]
+const FIXTURE: &str = r#"{
+  "version": 3,
+  "file": "app.js",
+  "sourceRoot": "src",
+  "sources": ["a.js", "b.js"],
+  "sourcesContent": ["let a;", null],
+  "names": ["x", "y"],
+  "mappings": "A,CAAAA;ACCA,EAAEC;;IDEA"
+}"#;
+
+#[test]
+fn it_converts_sourcemap_maps_into_caches() {
+  let map = SourceMap::from_slice(FIXTURE.as_bytes()).unwrap();
+  let cache = Cache::try_from(&map).unwrap();
+  let consumed = ::consume::consume_with_sources_content(FIXTURE).unwrap();
+  assert_eq!(cache.mappings(), consumed.mappings());
+  assert_eq!((cache.sources(), cache.names()), (consumed.sources(), consumed.names()));
+  assert_eq!((cache.source_root.as_str(), cache.file.as_str()), ("src", "app.js"));
+  assert_eq!((cache.source_content("a.js"), cache.source_content("b.js")), (Some("let a;"), None));
+
+  // sourcemap counts lines from 0, caches from 1
+  let token = map.lookup_token(3, 4).unwrap();
+  assert_eq!((token.get_dst(), token.get_src(), token.get_source()), ((3, 4), (3, 2), Some("src/a.js")));
+  let mapping = cache.mapping_for_generated_position(4, 4);
+  assert_eq!((mapping.original.line, mapping.original.column, &*mapping.source), (4, 2, "a.js"));
+}
+
+#[test]
+fn it_converts_caches_into_sourcemap_maps() {
+  let cache = ::consume::consume_with_sources_content(FIXTURE).unwrap();
+  let map = SourceMap::from(&cache);
+  assert_eq!(map.sources().collect::<Vec<_>>(), ["src/a.js", "src/b.js"]);
+  assert_eq!((map.get_file(), map.get_source_root()), (Some("app.js"), Some("src")));
+  assert_eq!(map.get_source_contents(0), Some("let a;"));
+
+  let tokens: Vec<_> = map.tokens()
+    .map(|token| (token.get_dst(), token.get_src(), token.get_source(), token.get_name()))
+    .collect();
+  assert_eq!(tokens, [
+    ((0, 0), (0, 0), None, None),
+    ((0, 1), (0, 0), Some("src/a.js"), Some("x")),
+    ((1, 0), (1, 0), Some("src/b.js"), None),
+    ((1, 2), (1, 2), Some("src/b.js"), Some("y")),
+    ((3, 4), (3, 2), Some("src/a.js"), None)
+  ]);
+
+  let round_tripped = Cache::try_from(&map).unwrap();
+  assert_eq!(round_tripped.mappings(), cache.mappings());
+  assert_eq!(round_tripped.sources(), cache.sources());
+}
+
+#[test]
+fn it_rejects_lines_that_overflow() {
+  let mut builder = SourceMapBuilder::new(None);
+  builder.add(u32::MAX, 0, 0, 0, Some("a.js"), None, false);
+  assert_eq!(Cache::try_from(&builder.into_sourcemap()).unwrap_err().to_string(), "Line number overflowed");
+  assert_eq!(without_source_root("src/a.js", "src/"), "a.js");
+  assert_eq!(without_source_root("/abs/a.js", "src"), "/abs/a.js");
+}
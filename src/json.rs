@@ -0,0 +1,93 @@
+extern crate serde_json;
+
+#[cfg(not(feature = "std"))]
+use alloc::{string::{String, ToString}, vec::Vec};
+
+use consume::{Document, IndexMap, RawSourceMap, Section, is_present};
+use error::Error;
+
+#[allow(non_snake_case)]
+#[derive(Deserialize, Debug)]
+struct SourceMap {
+  version: u32,
+  sources: Vec<String>,
+  names: Vec<String>,
+  sourceRoot: Option<String>,
+  mappings: String,
+  file: Option<String>,
+  #[serde(default, alias = "x_google_ignoreList")]
+  ignoreList: Vec<u32>,
+  // We skip sourcesContent here. Keeping megabytes of data that we do not care about
+  // in memory seems reckless to caches, so it is only parsed on request, see
+  // consume_with_sources_content.
+  #[serde(default, deserialize_with = "is_present")]
+  sourcesContent: bool
+}
+
+#[allow(non_snake_case)]
+#[derive(Deserialize, Debug)]
+struct SourcesContent {
+  sourcesContent: Option<Vec<Option<String>>>
+}
+
+#[derive(Deserialize, Debug)]
+struct JsonIndexMap {
+  version: u32,
+  file: Option<String>,
+  sections: Vec<JsonSection>
+}
+
+#[derive(Deserialize, Debug)]
+struct JsonSection {
+  offset: SectionOffset,
+  map: Option<serde_json::Value>,
+  url: Option<String>
+}
+
+#[derive(Deserialize, Debug)]
+struct SectionOffset {
+  line: u32,
+  column: u32
+}
+
+// Parses a regular source map, or an index map, which lacks the fields of regular
+// source maps
+pub(crate) fn parse_document(json: &str) -> Result<Document, Error> {
+  let source_map: SourceMap = match serde_json::from_str(json) {
+    Ok(source_map) => source_map,
+    Err(err) => {
+      return match serde_json::from_str::<JsonIndexMap>(json) {
+        Ok(index_map) => Ok(Document::IndexMap(IndexMap {
+          version: index_map.version,
+          file: index_map.file,
+          sections: index_map.sections.into_iter().map(|section| Section {
+            offset: (section.offset.line, section.offset.column),
+            map: section.map.map(|map| map.to_string()),
+            url: section.url
+          }).collect()
+        })),
+        Err(_) => Err(Error::Json(err))
+      };
+    }
+  };
+
+  let header = RawSourceMap {
+    version: source_map.version,
+    file: source_map.file,
+    source_root: source_map.sourceRoot,
+    sources: source_map.sources,
+    names: source_map.names,
+    ignore_list: source_map.ignoreList,
+    has_sources_content: source_map.sourcesContent
+  };
+  Ok(Document::SourceMap(header, source_map.mappings))
+}
+
+pub(crate) fn parse_header(json: &str) -> Result<RawSourceMap, Error> {
+  serde_json::from_str(json).map_err(Error::from)
+}
+
+pub(crate) fn parse_sources_content(json: &str) -> Result<Vec<Option<String>>, Error> {
+  let sources_content: SourcesContent = serde_json::from_str(json)?;
+  Ok(sources_content.sourcesContent.unwrap_or_default())
+}
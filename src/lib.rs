@@ -1,10 +1,119 @@
-mod base64;
-mod base64_vlq;
+// Without the std feature only consume, Cache and the base64 modules are built, and
+// they only need alloc
+#![cfg_attr(not(feature = "std"), no_std)]
+// Lints that the oldest code and tests of the crate trip, which keep their style
+#![allow(
+  clippy::char_lit_as_u8,
+  clippy::partialeq_to_none,
+  clippy::redundant_static_lifetimes,
+  clippy::assertions_on_constants,
+  clippy::identity_op,
+  clippy::single_match
+)]
+
+#[cfg(not(feature = "std"))]
+#[macro_use] extern crate alloc;
+// The cdylib needs the panic handler and allocator of std, so std is linked on targets
+// that have an operating system even without the feature. The cdylib is not built for
+// targets without one
+#[cfg(all(not(feature = "std"), not(target_os = "none")))]
+extern crate std;
+#[cfg(feature = "std")]
+extern crate core;
+
+#[cfg(feature = "log")]
+#[macro_use] extern crate log;
+
+// Without the log feature the warnings are discarded, and the log crate is not a
+// dependency at all
+#[cfg(not(feature = "log"))]
+macro_rules! warn {
+  ($($arg:tt)*) => { if false { let _ = format_args!($($arg)*); } }
+}
+#[cfg(not(feature = "log"))]
+macro_rules! debug {
+  ($($arg:tt)*) => { if false { let _ = format_args!($($arg)*); } }
+}
+
+pub mod base64;
+pub mod base64_vlq;
+pub mod mappings;
 mod consume;
+mod line_index;
+mod scan;
+#[cfg(all(feature = "serde-json", not(feature = "mini-json")))]
+mod json;
+#[cfg(any(feature = "mini-json", not(feature = "serde-json")))]
+mod mini_json;
+mod error;
+mod resolve;
+mod lookup;
+mod compact;
+mod parser;
+mod fetch;
+#[cfg(feature = "std")]
+mod generate;
+#[cfg(feature = "std")]
+mod binary;
+#[cfg(feature = "std")]
+mod diff;
+#[cfg(feature = "std")]
+mod compose;
+#[cfg(feature = "std")]
+mod data_uri;
+#[cfg(feature = "std")]
+mod repair;
+#[cfg(feature = "std")]
+mod export;
+#[cfg(feature = "std")]
+mod stack_trace;
+#[cfg(feature = "std")]
+mod validate;
+#[cfg(feature = "sourcemap-interop")]
+mod interop;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "capi")]
+mod capi;
 
 #[macro_use] extern crate serde_derive;
 
-pub use consume::{Cache, Mapping, CodePosition, consume};
+pub use error::{Error, ErrorCode};
+pub use consume::{Cache, CacheParts, CacheSummary, MemoryUsage, Mapping, CodePosition, consume, consume_with_sources_content, parse_mappings_str};
+pub use consume::{RawSourceMap, parse_header};
+pub use consume::{ConsumeSummary, consume_with_visitor};
+pub use resolve::{DefaultSourceResolver, SourceResolver};
+pub use lookup::MappingLookup;
+pub use compact::CompactCache;
+pub use parser::Parser;
+pub use fetch::{MapFetcher, consume_with_fetcher};
+#[cfg(feature = "std")]
+pub use fetch::{FileFetcher, consume_from_generated};
+pub use consume::{compare_mappings, sort_mappings, dedup_mappings, is_sorted_by_generated};
+#[cfg(feature = "std")]
+pub use generate::{Generator, emit_index_map, merge_to_index_map};
+#[cfg(feature = "std")]
+pub use binary::MappedCache;
+#[cfg(feature = "std")]
+pub use diff::{CacheDiff, MappingChange, diff};
+#[cfg(feature = "std")]
+pub use compose::{Composition, compose};
+#[cfg(feature = "std")]
+pub use data_uri::{consume_data_uri, decode_data_uri, source_mapping_url};
+#[cfg(feature = "std")]
+pub use export::write_mappings_csv;
+#[cfg(feature = "std")]
+pub use repair::{IndexRepair, RepairAction, RepairOptions, repair};
+#[cfg(feature = "std")]
+pub use stack_trace::{StackFrame, StackTraceParser, AnyStackTraceParser, V8StackTraceParser, FirefoxStackTraceParser, SafariStackTraceParser};
+#[cfg(feature = "std")]
+pub use validate::{Finding, Severity, validate};
+#[cfg(feature = "wasm")]
+pub use wasm::WasmCache;
 
-#[cfg(test)]
+// The property tests and the conformance suite read files and generate maps, so they
+// need std
+#[cfg(all(test, feature = "std"))]
 mod test;
+#[cfg(all(test, feature = "std"))]
+mod conformance;
@@ -0,0 +1,170 @@
+use core::cmp::Ordering;
+use core::mem;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+
+use consume::Mapping;
+
+/**
+ * The range of mappings on each generated line, so that lookups only search the
+ * mappings of one line. Built when a cache is created, from its sorted mappings.
+ */
+#[derive(Clone, Debug)]
+pub(crate) enum LineIndex {
+  // The index of the first mapping on or after each line, from line 0 to the line after
+  // the last one
+  Dense(Vec<u32>),
+  // The first line and the index of its first mapping, for each line that has mappings,
+  // when most lines have none
+  Sparse(Vec<(u32, u32)>),
+  // The line and the column of each mapping, when all mappings are on one line, as in
+  // minified bundles. Searching the columns alone reads a twelfth of the memory a search
+  // of the mappings does
+  SingleLine(u32, Vec<u32>)
+}
+
+impl LineIndex {
+  pub(crate) fn new(mappings: &[Mapping]) -> LineIndex {
+    let last_line = mappings.last().map_or(0, |mapping| mapping.generated.line);
+    if mappings.first().is_some_and(|first| first.generated.line == last_line) {
+      return LineIndex::SingleLine(last_line, mappings.iter().map(|mapping| mapping.generated.column).collect());
+    }
+    // A dense index takes 4 bytes per line, which is little next to the mappings as long
+    // as there are more mappings than lines
+    if last_line as usize / 4 > mappings.len() {
+      let mut starts: Vec<(u32, u32)> = Vec::new();
+      for (index, mapping) in mappings.iter().enumerate() {
+        if starts.last().is_none_or(|&(line, _)| line != mapping.generated.line) {
+          starts.push((mapping.generated.line, index as u32));
+        }
+      }
+      return LineIndex::Sparse(starts);
+    }
+
+    let mut starts: Vec<u32> = Vec::with_capacity(last_line as usize + 2);
+    for (index, mapping) in mappings.iter().enumerate() {
+      while starts.len() <= mapping.generated.line as usize {
+        starts.push(index as u32);
+      }
+    }
+    starts.push(mappings.len() as u32);
+    LineIndex::Dense(starts)
+  }
+
+  // The bytes the index holds, by the capacity of its vector
+  pub(crate) fn memory_usage(&self) -> usize {
+    match *self {
+      LineIndex::Dense(ref starts) => starts.capacity() * mem::size_of::<u32>(),
+      LineIndex::Sparse(ref starts) => starts.capacity() * mem::size_of::<(u32, u32)>(),
+      LineIndex::SingleLine(_, ref columns) => columns.capacity() * mem::size_of::<u32>()
+    }
+  }
+
+  // Returns the range of the mappings on a line within mappings, which the index was
+  // built from. The range is empty and starts at the next line's mappings if the line
+  // has none
+  pub(crate) fn line_range(&self, mappings: &[Mapping], line: u32) -> (usize, usize) {
+    let end_of_mappings = mappings.len();
+    match *self {
+      LineIndex::Dense(ref starts) => {
+        let start = starts.get(line as usize).map_or(end_of_mappings, |&start| start as usize);
+        let end = starts.get(line as usize + 1).map_or(end_of_mappings, |&end| end as usize);
+        (start, end)
+      },
+      LineIndex::Sparse(ref starts) => {
+        let next = starts.partition_point(|&(start_line, _)| start_line < line);
+        let start = starts.get(next).map_or(end_of_mappings, |&(_, start)| start as usize);
+        let end = match starts.get(next) {
+          Some(&(start_line, _)) if start_line == line => starts.get(next + 1).map_or(end_of_mappings, |&(_, end)| end as usize),
+          _ => start
+        };
+        (start, end)
+      },
+      LineIndex::SingleLine(single_line, _) => match line.cmp(&single_line) {
+        Ordering::Less => (0, 0),
+        Ordering::Equal => (0, end_of_mappings),
+        Ordering::Greater => (end_of_mappings, end_of_mappings)
+      }
+    }
+  }
+
+  // Returns the index of the mapping at a position within mappings, which the index was
+  // built from, or of the first mapping after it, which is mappings.len() past the last
+  pub(crate) fn search(&self, mappings: &[Mapping], line: u32, column: u32) -> usize {
+    let (start, end) = self.line_range(mappings, line);
+    let found = match *self {
+      // The same comparisons as those of a search of the mappings, so that of mappings at
+      // the same position, the same one is found
+      LineIndex::SingleLine(_, ref columns) => columns[start..end].binary_search(&column),
+      _ => mappings[start..end].binary_search_by(|mapping| mapping.generated.column.cmp(&column))
+    };
+    match found {
+      Ok(offset) | Err(offset) => start + offset
+    }
+  }
+}
+
+#[cfg(test)]
+use consume::{Cache, CodePosition};
+
+// The lookup before there was an index, a binary search of all mappings
+#[cfg(test)]
+fn search_all(mappings: &[Mapping], line: u32, column: u32) -> &Mapping {
+  let index = match mappings.binary_search_by(|mapping| (mapping.generated.line, mapping.generated.column).cmp(&(line, column))) {
+    Ok(index) | Err(index) => index
+  };
+  &mappings[index.min(mappings.len() - 1)]
+}
+
+#[cfg(test)]
+fn cache_with_lines(lines: &[u32]) -> Cache {
+  let mut mappings = Vec::new();
+  for (index, &line) in lines.iter().enumerate() {
+    for column in 0..(index as u32 % 4 + 1) {
+      mappings.push(Mapping {
+        generated: CodePosition { line, column: column * 3 },
+        original: CodePosition { line: index as u32 + 1, column },
+        source: "a.js".into(),
+        name: "".into()
+      });
+    }
+  }
+  Cache::from_mappings(mappings, "").unwrap()
+}
+
+#[test]
+fn it_finds_the_same_mappings_as_a_search_of_all_mappings() {
+  let dense = cache_with_lines(&[1, 2, 3, 5, 6, 9, 10, 11, 12, 14]);
+  let sparse = cache_with_lines(&[2, 40, 41, 900, 100_000]);
+  assert!(matches!(dense.line_index, LineIndex::Dense(_)));
+  assert!(matches!(sparse.line_index, LineIndex::Sparse(_)));
+  // Mappings at the same positions, on a line other than the first, so that lines before
+  // it are looked up too
+  let single_line = cache_with_lines(&[7; 9]);
+  let minified = cache_with_lines(&[1; 40]);
+  assert!(matches!(single_line.line_index, LineIndex::SingleLine(7, _)));
+  assert!(matches!(minified.line_index, LineIndex::SingleLine(1, _)));
+
+  for (cache, lines) in [(&dense, 0..20), (&sparse, 0..100_010), (&single_line, 0..10), (&minified, 0..3)] {
+    let mappings = cache.mappings();
+    for line in lines.chain([u32::MAX - 1, u32::MAX]) {
+      for column in [0, 1, 3, 4, 9, 10, u32::MAX] {
+        assert_eq!(cache.mapping_for_generated_position(line, column), *search_all(mappings, line, column), "{}:{}", line, column);
+      }
+      let on_line: Vec<&Mapping> = mappings.iter().filter(|mapping| mapping.generated.line == line).collect();
+      assert_eq!(cache.mappings_for_generated_line(line).iter().collect::<Vec<_>>(), on_line);
+    }
+  }
+}
+
+#[test]
+fn it_indexes_derived_caches() {
+  let cache = cache_with_lines(&[1, 2, 4]);
+  let offset = cache.offset_generated(3, 5).unwrap();
+  assert_eq!(offset.mappings_for_generated_line(4).len(), 1);
+  assert_eq!(offset.mapping_for_generated_position(4, 5).generated, CodePosition { line: 4, column: 5 });
+  let filtered = cache.filter_sources(|_| false, false);
+  assert!(filtered.mappings_for_generated_line(1).iter().all(|mapping| mapping.source.is_empty()));
+  assert!(filtered.filter_sources(|_| true, false).mappings_for_generated_line(1).is_empty());
+  assert_eq!(LineIndex::new(&[]).line_range(&[], 1), (0, 0));
+}
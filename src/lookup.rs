@@ -0,0 +1,94 @@
+use consume::{Cache, Mapping};
+
+/**
+ * Looks up mappings by generated position. Implemented by `Cache`, `MappedCache` and
+ * `CompactCache`, so code that only queries mappings can accept any of them, or a fake
+ * in tests, through a generic or `&dyn MappingLookup`.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::{MappingLookup, consume};
+ *
+ * fn original_line(lookup: &dyn MappingLookup, line: u32, column: u32) -> Option<u32> {
+ *   lookup.mapping_for_generated_position(line, column).map(|mapping| mapping.original.line)
+ * }
+ *
+ * let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AACA" }"#).unwrap();
+ * assert!(original_line(&cache, 1, 0) == Some(2));
+ * ```
+ */
+pub trait MappingLookup {
+  /**
+   * Returns the mapping for a generated position, with the semantics of
+   * `Cache::mapping_for_generated_position`, or `None` if there are no mappings to
+   * return.
+   */
+  fn mapping_for_generated_position(&self, line: u32, column: u32) -> Option<Mapping>;
+}
+
+impl MappingLookup for Cache {
+  fn mapping_for_generated_position(&self, line: u32, column: u32) -> Option<Mapping> {
+    if self.is_empty() {
+      None
+    } else {
+      Some(Cache::mapping_for_generated_position(self, line, column))
+    }
+  }
+}
+
+impl<L: MappingLookup + ?Sized> MappingLookup for &L {
+  fn mapping_for_generated_position(&self, line: u32, column: u32) -> Option<Mapping> {
+    (**self).mapping_for_generated_position(line, column)
+  }
+}
+
+#[cfg(all(test, feature = "std"))]
+use std::collections::HashMap;
+#[cfg(all(test, feature = "std"))]
+use consume::{CodePosition, consume};
+#[cfg(all(test, feature = "std"))]
+use binary::MappedCache;
+
+// Application code that only needs lookups
+#[cfg(all(test, feature = "std"))]
+fn describe<L: MappingLookup>(lookup: L, line: u32, column: u32) -> String {
+  match lookup.mapping_for_generated_position(line, column) {
+    Some(mapping) => format!("{}:{}:{}", mapping.source, mapping.original.line, mapping.original.column),
+    None => "unmapped".into()
+  }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn it_looks_up_mappings_through_any_implementation() {
+  let cache = consume(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA,EACE" }"#).unwrap();
+  let mapped = MappedCache::from_bytes(cache.serialize_binary()).unwrap();
+  assert_eq!(describe(&cache, 1, 2), "a.js:2:2");
+  assert_eq!(describe(&mapped, 1, 2), "a.js:2:2");
+  let lookups: [&dyn MappingLookup; 2] = [&cache, &mapped];
+  for lookup in &lookups {
+    assert_eq!(describe(lookup, 1, 0), "a.js:1:0");
+  }
+
+  // Removed mappings are replaced by unmapped ones, which go when unmapped mappings do
+  let empty = cache.filter_sources(|_| false, false).filter_sources(|_| true, false);
+  assert_eq!(describe(&empty, 1, 0), "unmapped");
+  assert_eq!(describe(MappedCache::from_bytes(empty.serialize_binary()).unwrap(), 1, 0), "unmapped");
+
+  struct Fake(HashMap<(u32, u32), Mapping>);
+  impl MappingLookup for Fake {
+    fn mapping_for_generated_position(&self, line: u32, column: u32) -> Option<Mapping> {
+      self.0.get(&(line, column)).cloned()
+    }
+  }
+  let mut fake = Fake(HashMap::new());
+  fake.0.insert((3, 4), Mapping {
+    generated: CodePosition { line: 3, column: 4 },
+    original: CodePosition { line: 10, column: 0 },
+    source: "b.js".into(),
+    name: "".into()
+  });
+  assert_eq!(describe(&fake, 3, 4), "b.js:10:0");
+  assert_eq!(describe(&fake, 1, 0), "unmapped");
+}
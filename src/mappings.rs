@@ -0,0 +1,308 @@
+/*!
+ * Decoding of the `mappings` field of source maps into its segments, for tools that need
+ * the encoded structure rather than resolved mappings, e.g. to re-emit a map with minimal
+ * differences. `consume` decodes mappings with the same decoder.
+ */
+
+#[cfg(not(feature = "std"))]
+use alloc::{string::String, vec::Vec};
+
+use base64;
+use base64_vlq;
+use error::{Error, ErrorCode};
+use scan;
+
+/**
+ * A segment of the mappings, with its fields as encoded: the generated column is relative
+ * to the previous segment on the same line, and the other fields are relative to the
+ * last segment that had them, on any line.
+ */
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DecodedSegment {
+  /** The generated line of the segment, starting from 1 */
+  pub generated_line: u32,
+  /**
+   * The deltas of the generated column, source index, original line, original column
+   * and name index, of which the first `field_count` are set and the rest are 0
+   */
+  pub fields: [i32; 5],
+  /** The number of fields, which is 1, 4 or 5 */
+  pub field_count: u8
+}
+
+impl DecodedSegment {
+  /**
+   * Returns the fields that are set.
+   */
+  pub fn fields(&self) -> &[i32] {
+    &self.fields[..self.field_count as usize]
+  }
+}
+
+/**
+ * An iterator over the segments of a `mappings` field, in the order they are encoded.
+ * Empty segments are skipped. An invalid segment is returned as an error, after which
+ * the iterator ends.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::mappings::MappingsDecoder;
+ *
+ * let segments: Vec<(u32, Vec<i32>)> = MappingsDecoder::new("AAAAA,EAAE;;AACA")
+ *   .map(|segment| segment.map(|segment| (segment.generated_line, segment.fields().to_vec())))
+ *   .collect::<Result<_, _>>()
+ *   .unwrap();
+ * assert!(segments == [(1, vec![0, 0, 0, 0, 0]), (1, vec![2, 0, 0, 2]), (3, vec![0, 0, 1, 0])]);
+ * assert!(MappingsDecoder::new("AAAA,AA").nth(1).unwrap().is_err());
+ * ```
+ */
+#[derive(Clone, Debug)]
+pub struct MappingsDecoder<'a> {
+  mappings: &'a [u8],
+  position: usize,
+  generated_line: u32,
+  failed: bool
+}
+
+impl<'a> MappingsDecoder<'a> {
+  /**
+   * Creates a decoder for a `mappings` field.
+   */
+  pub fn new(mappings: &'a str) -> MappingsDecoder<'a> {
+    MappingsDecoder { mappings: mappings.as_bytes(), position: 0, generated_line: 1, failed: false }
+  }
+
+  // Resumes decoding at an offset and generated line that a decoder of the same mappings
+  // reached before
+  pub(crate) fn resume(mappings: &'a str, offset: usize, generated_line: u32) -> MappingsDecoder<'a> {
+    MappingsDecoder { mappings: mappings.as_bytes(), position: offset, generated_line, failed: false }
+  }
+
+  /**
+   * Returns the generated line the decoder has reached, which is the number of lines of
+   * the mappings once the decoder is exhausted.
+   */
+  pub fn generated_line(&self) -> u32 {
+    self.generated_line
+  }
+
+  /**
+   * Returns the byte offset in the mappings the decoder has reached: the end of the last
+   * segment returned, or the start of the field that could not be decoded after an error.
+   *
+   * # Examples
+   *
+   * ```
+   * use js_source_mapper::mappings::MappingsDecoder;
+   *
+   * let mut decoder = MappingsDecoder::new("AAAA;AA!A");
+   * assert!(decoder.next().unwrap().is_ok() && decoder.offset() == 4);
+   * assert!(decoder.next().unwrap().is_err() && decoder.offset() == 7);
+   * ```
+   */
+  pub fn offset(&self) -> usize {
+    self.position
+  }
+
+  // Decodes the segment starting at the cursor, leaving the cursor after it, or at the
+  // start of the field that is invalid
+  fn decode_segment(&mut self) -> Result<DecodedSegment, Error> {
+    let start = self.position;
+    let mut decoded = DecodedSegment { generated_line: self.generated_line, fields: [0; 5], field_count: 0 };
+    let mut field_count = 0;
+    while let Some(&byte) = self.mappings.get(self.position) {
+      if byte == b',' || byte == b';' {
+        break;
+      }
+      let (value, field_length) = match base64_vlq::decode(&self.mappings[self.position..]) {
+        Some(field) => field,
+        None => {
+          let segment = self.segment_from(start);
+          return Err(Error::invalid(ErrorCode::InvalidVlq, invalid_vlq_error(segment, self.position - start, self.generated_line)));
+        }
+      };
+      if field_count == decoded.fields.len() {
+        let segment = self.segment_from(start);
+        return Err(Error::invalid(ErrorCode::InvalidSegment, format!("Found more than five fields in segment {:?} on generated line {}", String::from_utf8_lossy(segment), self.generated_line)));
+      }
+      decoded.fields[field_count] = value;
+      field_count += 1;
+      self.position += field_length;
+    }
+
+    match field_count {
+      2 => Err(Error::invalid(ErrorCode::InvalidSegment, "Found a source, but no line and column")),
+      3 => Err(Error::invalid(ErrorCode::InvalidSegment, "Found a source and line, but no column")),
+      _ => {
+        decoded.field_count = field_count as u8;
+        Ok(decoded)
+      }
+    }
+  }
+
+  // The whole segment starting at a byte offset, for error messages
+  fn segment_from(&self, start: usize) -> &'a [u8] {
+    let rest = &self.mappings[start..];
+    &rest[..scan::find_separator(rest).unwrap_or(rest.len())]
+  }
+}
+
+impl<'a> Iterator for MappingsDecoder<'a> {
+  type Item = Result<DecodedSegment, Error>;
+
+  fn next(&mut self) -> Option<Result<DecodedSegment, Error>> {
+    if self.failed {
+      return None;
+    }
+    // A single forward scan: separators move the cursor and the line, and anything else
+    // starts a segment
+    loop {
+      match *self.mappings.get(self.position)? {
+        b';' => {
+          self.generated_line += 1;
+          self.position += 1;
+        },
+        b',' => self.position += 1,
+        _ => {
+          let decoded = self.decode_segment();
+          self.failed = decoded.is_err();
+          return Some(decoded);
+        }
+      }
+    }
+  }
+}
+
+pub(crate) fn invalid_vlq_error(segment: &[u8], field_start: usize, generated_line: u32) -> String {
+  let field = &segment[field_start..];
+  let mut digits = Vec::new();
+  match base64::decode_slice(field, &mut digits) {
+    Err(offset) => {
+      let character = field[offset];
+      let hint = match character {
+        b'=' => " (base 64 padding is not allowed in mappings)",
+        b' ' | b'\t' | b'\r' | b'\n' => " (whitespace is not allowed in mappings)",
+        _ => ""
+      };
+      format!("Invalid VLQ mapping field: unexpected character {:?}{}", character as char, hint)
+    },
+    // Every digit has the continuation bit set, so the input ended mid-value
+    Ok(()) if digits.iter().all(|&digit| digit & 0b100000 != 0) => format!(
+      "Truncated VLQ mapping field at the end of segment {:?} on generated line {}",
+      String::from_utf8_lossy(segment), generated_line
+    ),
+    Ok(()) => "Invalid VLQ mapping field".into()
+  }
+}
+
+
+#[cfg(test)]
+use consume::{consume, parse_mappings_str};
+#[cfg(all(test, not(feature = "std")))]
+use alloc::{borrow::ToOwned, string::ToString};
+
+#[test]
+fn it_decodes_segments_as_deltas() {
+  let segments: Vec<DecodedSegment> = MappingsDecoder::new(";CAAC,,GACAC;").collect::<Result<_, _>>().unwrap();
+  assert_eq!(segments, [
+    DecodedSegment { generated_line: 2, fields: [1, 0, 0, 1, 0], field_count: 4 },
+    DecodedSegment { generated_line: 2, fields: [3, 0, 1, 0, 1], field_count: 5 }
+  ]);
+
+  let mut decoder = MappingsDecoder::new("A;;");
+  assert_eq!(decoder.next().unwrap().unwrap().fields(), [0]);
+  assert!(decoder.next().is_none());
+  assert_eq!(decoder.generated_line(), 3);
+  assert_eq!(MappingsDecoder::new("").generated_line(), 1);
+}
+
+#[test]
+fn it_stops_at_the_first_invalid_segment() {
+  let mut decoder = MappingsDecoder::new("AAAA,A!AA,AAAA");
+  assert!(decoder.next().unwrap().is_ok());
+  let err = decoder.next().unwrap().unwrap_err();
+  assert_eq!((err.code(), err.to_string()), (ErrorCode::InvalidVlq, "Invalid VLQ mapping field: unexpected character '!'".to_owned()));
+  assert!(decoder.next().is_none());
+
+  let err = MappingsDecoder::new("AAA").next().unwrap().unwrap_err();
+  assert_eq!((err.code(), err.to_string()), (ErrorCode::InvalidSegment, "Found a source and line, but no column".to_owned()));
+  let err = MappingsDecoder::new(";AAAAA,AAAAAC").nth(1).unwrap().unwrap_err();
+  assert_eq!((err.code(), err.to_string()), (ErrorCode::InvalidSegment, "Found more than five fields in segment \"AAAAAC\" on generated line 2".to_owned()));
+}
+
+#[test]
+fn it_agrees_with_consume() {
+  let mappings = "AAAAA,EAAE;;ACAA,CDCC;AACAA,gBAAgB";
+  let json = format!(r#"{{ "version": 3, "sources": ["a.js", "b.js"], "names": ["x"], "mappings": "{}" }}"#, mappings);
+  let cache = consume(&json).unwrap();
+  let segments: Vec<DecodedSegment> = MappingsDecoder::new(mappings).collect::<Result<_, _>>().unwrap();
+  assert_eq!(segments.len(), cache.len());
+  for (segment, mapping) in segments.iter().zip(cache.mappings()) {
+    assert_eq!(segment.generated_line, mapping.generated.line);
+    assert_eq!(segment.field_count > 1, !mapping.source.is_empty());
+    assert_eq!(segment.field_count > 4, !mapping.name.is_empty());
+  }
+
+  let names = ["x".to_owned()];
+  assert_eq!(
+    parse_mappings_str("AA", &[], &names, "").unwrap_err().to_string(),
+    MappingsDecoder::new("AA").next().unwrap().unwrap_err().to_string()
+  );
+}
+
+// Decodes like consume did before it used the decoder, allocating the fields of each segment
+#[cfg(test)]
+fn decode_into_vecs(mappings: &str) -> Vec<(u32, Vec<i32>)> {
+  let mut segments = Vec::new();
+  for (generated_line, line) in (1u32..).zip(mappings.as_bytes().split(|&x| x == b';')) {
+    for segment in line.split(|&x| x == b',') {
+      let mut fields: Vec<i32> = Vec::new();
+      let mut character_index = 0;
+      while character_index < segment.len() {
+        let (value, field_length) = base64_vlq::decode(&segment[character_index..]).unwrap();
+        fields.push(value);
+        character_index += field_length;
+      }
+      if !fields.is_empty() {
+        segments.push((generated_line, fields));
+      }
+    }
+  }
+  segments
+}
+
+#[test]
+fn it_decodes_large_mappings_like_the_allocating_decoder() {
+  // A fixed linear congruential sequence of segments of 1, 4 and 5 fields, with empty
+  // lines and segments in between
+  let mut state = 0x9e37_79b9u32;
+  let mut random = move |range: u32| {
+    state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+    (state >> 8) % range
+  };
+  let mut mappings = String::new();
+  for _ in 0..100_000 {
+    match random(20) {
+      0 => mappings.push(';'),
+      1 => mappings.push(','),
+      _ => {
+        let field_count = [1, 4, 5][random(3) as usize];
+        for _ in 0..field_count {
+          let value = random(2_000_000) as i32 - 1_000_000;
+          let value = if random(4) == 0 { value } else { value / 1000 };
+          mappings.push_str(core::str::from_utf8(&base64_vlq::encode(value).unwrap()).unwrap());
+        }
+        mappings.push(if random(8) == 0 { ';' } else { ',' });
+      }
+    }
+  }
+
+  let segments: Vec<(u32, Vec<i32>)> = MappingsDecoder::new(&mappings)
+    .map(|segment| segment.map(|segment| (segment.generated_line, segment.fields().to_vec())))
+    .collect::<Result<_, _>>()
+    .unwrap();
+  assert!(segments.len() > 80_000);
+  assert_eq!(segments, decode_into_vecs(&mappings));
+}
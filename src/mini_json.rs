@@ -0,0 +1,535 @@
+// The JSON front-end of the mini-json feature, and of builds without serde-json, a parser
+// for exactly the JSON source maps are made of, so that the crate builds without a JSON
+// dependency. Fields are read into the same types as the serde_json front-end gives,
+// values of unknown fields are checked and skipped, and errors have the Json code.
+
+#[cfg(not(feature = "std"))]
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+
+use consume::{Document, IndexMap, RawSourceMap, Section};
+use error::{Error, ErrorCode};
+
+// Like serde_json, nesting of skipped values is limited to keep the stack bounded
+const MAX_DEPTH: usize = 128;
+
+// The fields of a regular source map or an index map, whichever the JSON turns out to be
+#[derive(Default)]
+struct Fields {
+  version: Option<u32>,
+  file: Option<Option<String>>,
+  source_root: Option<Option<String>>,
+  sources: Option<Vec<String>>,
+  names: Option<Vec<String>>,
+  mappings: Option<String>,
+  ignore_list: Option<Vec<u32>>,
+  has_sources_content: Option<bool>,
+  sources_content: Option<Option<Vec<Option<String>>>>,
+  sections: Option<Vec<Section>>
+}
+
+// Which of the large fields to keep rather than skip
+#[derive(Clone, Copy)]
+struct Keep {
+  mappings: bool,
+  sources_content: bool
+}
+
+fn parse_fields(json: &str, keep: Keep) -> Result<(Fields, Reader<'_>), Error> {
+  let mut reader = Reader { json, position: 0 };
+  let mut fields = Fields::default();
+  reader.object(|reader, key| match key {
+    "version" => set(&mut fields.version, reader.u32()?, reader, key),
+    "file" => set(&mut fields.file, reader.optional_string()?, reader, key),
+    "sourceRoot" => set(&mut fields.source_root, reader.optional_string()?, reader, key),
+    "sources" => set(&mut fields.sources, reader.strings()?, reader, key),
+    "names" => set(&mut fields.names, reader.strings()?, reader, key),
+    "mappings" if keep.mappings => set(&mut fields.mappings, reader.string()?, reader, key),
+    "ignoreList" | "x_google_ignoreList" => set(&mut fields.ignore_list, reader.u32s()?, reader, "ignoreList"),
+    "sourcesContent" if keep.sources_content => {
+      let sources_content = if reader.null()? { None } else { Some(reader.optional_strings()?) };
+      set(&mut fields.sources_content, sources_content, reader, key)
+    },
+    "sourcesContent" => {
+      let is_present = !reader.null()?;
+      if is_present {
+        reader.skip_value(0)?;
+      }
+      set(&mut fields.has_sources_content, is_present, reader, key)
+    },
+    "sections" => set(&mut fields.sections, reader.sections()?, reader, key),
+    _ => reader.skip_value(0).map(|_| ())
+  })?;
+  reader.end()?;
+  Ok((fields, reader))
+}
+
+// Fails on fields that appear twice, like serde_json
+fn set<T>(slot: &mut Option<T>, value: T, reader: &Reader, key: &str) -> Result<(), Error> {
+  if slot.is_some() {
+    return Err(reader.error(&format!("duplicate field `{}`", key)));
+  }
+  *slot = Some(value);
+  Ok(())
+}
+
+fn required<T>(value: Option<T>, reader: &Reader, key: &str) -> Result<T, Error> {
+  value.ok_or_else(|| reader.error(&format!("missing field `{}`", key)))
+}
+
+pub(crate) fn parse_document(json: &str) -> Result<Document, Error> {
+  let (fields, reader) = parse_fields(json, Keep { mappings: true, sources_content: false })?;
+  // Index maps lack the fields of regular source maps
+  if fields.mappings.is_none() && fields.sources.is_none() && fields.names.is_none() {
+    if let Some(sections) = fields.sections {
+      return Ok(Document::IndexMap(IndexMap {
+        version: required(fields.version, &reader, "version")?,
+        file: fields.file.unwrap_or_default(),
+        sections
+      }));
+    }
+  }
+
+  let header = RawSourceMap {
+    version: required(fields.version, &reader, "version")?,
+    file: fields.file.unwrap_or_default(),
+    source_root: fields.source_root.unwrap_or_default(),
+    sources: required(fields.sources, &reader, "sources")?,
+    names: required(fields.names, &reader, "names")?,
+    ignore_list: fields.ignore_list.unwrap_or_default(),
+    has_sources_content: fields.has_sources_content.unwrap_or(false)
+  };
+  Ok(Document::SourceMap(header, required(fields.mappings, &reader, "mappings")?))
+}
+
+pub(crate) fn parse_header(json: &str) -> Result<RawSourceMap, Error> {
+  let (fields, reader) = parse_fields(json, Keep { mappings: false, sources_content: false })?;
+  Ok(RawSourceMap {
+    version: required(fields.version, &reader, "version")?,
+    file: fields.file.unwrap_or_default(),
+    source_root: fields.source_root.unwrap_or_default(),
+    sources: required(fields.sources, &reader, "sources")?,
+    names: required(fields.names, &reader, "names")?,
+    ignore_list: fields.ignore_list.unwrap_or_default(),
+    has_sources_content: fields.has_sources_content.unwrap_or(false)
+  })
+}
+
+pub(crate) fn parse_sources_content(json: &str) -> Result<Vec<Option<String>>, Error> {
+  let (fields, _) = parse_fields(json, Keep { mappings: false, sources_content: true })?;
+  Ok(fields.sources_content.unwrap_or_default().unwrap_or_default())
+}
+
+struct Reader<'a> {
+  json: &'a str,
+  position: usize
+}
+
+impl<'a> Reader<'a> {
+  // Errors name the line and column like those of serde_json
+  fn error(&self, message: &str) -> Error {
+    let before = &self.json.as_bytes()[..self.position.min(self.json.len())];
+    let line = before.iter().filter(|&&byte| byte == b'\n').count() + 1;
+    let column = before.len() - before.iter().rposition(|&byte| byte == b'\n').map_or(0, |newline| newline + 1);
+    Error::invalid(ErrorCode::Json, format!("{} at line {} column {}", message, line, column))
+  }
+
+  // Skips whitespace and returns the next byte without consuming it
+  fn peek(&mut self) -> Option<u8> {
+    let bytes = self.json.as_bytes();
+    while let Some(&byte) = bytes.get(self.position) {
+      if !matches!(byte, b' ' | b'\t' | b'\n' | b'\r') {
+        return Some(byte);
+      }
+      self.position += 1;
+    }
+    None
+  }
+
+  fn expect(&mut self, expected: u8, description: &str) -> Result<(), Error> {
+    match self.peek() {
+      Some(byte) if byte == expected => {
+        self.position += 1;
+        Ok(())
+      },
+      Some(_) => Err(self.error(&format!("expected {}", description))),
+      None => Err(self.error("EOF while parsing a value"))
+    }
+  }
+
+  fn end(&mut self) -> Result<(), Error> {
+    match self.peek() {
+      Some(_) => Err(self.error("trailing characters")),
+      None => Ok(())
+    }
+  }
+
+  // Consumes a literal such as null if it is next
+  fn literal(&mut self, literal: &str) -> Result<bool, Error> {
+    if self.peek() != Some(literal.as_bytes()[0]) {
+      return Ok(false);
+    }
+    if !self.json[self.position..].starts_with(literal) {
+      return Err(self.error("expected ident"));
+    }
+    self.position += literal.len();
+    Ok(true)
+  }
+
+  fn null(&mut self) -> Result<bool, Error> {
+    self.literal("null")
+  }
+
+  // Calls field with each key of an object, which has to consume the value
+  fn object<F: FnMut(&mut Reader<'a>, &str) -> Result<(), Error>>(&mut self, mut field: F) -> Result<(), Error> {
+    self.expect(b'{', "an object")?;
+    if self.peek() == Some(b'}') {
+      self.position += 1;
+      return Ok(());
+    }
+    loop {
+      if self.peek() != Some(b'"') {
+        return Err(self.error("key must be a string"));
+      }
+      let key = self.string()?;
+      self.expect(b':', "`:`")?;
+      field(self, &key)?;
+      match self.peek() {
+        Some(b',') => self.position += 1,
+        Some(b'}') => {
+          self.position += 1;
+          return Ok(());
+        },
+        Some(_) => return Err(self.error("expected `,` or `}`")),
+        None => return Err(self.error("EOF while parsing an object"))
+      }
+    }
+  }
+
+  // Calls element for each element of an array, which has to consume it
+  fn array<F: FnMut(&mut Reader<'a>) -> Result<(), Error>>(&mut self, mut element: F) -> Result<(), Error> {
+    self.expect(b'[', "a sequence")?;
+    if self.peek() == Some(b']') {
+      self.position += 1;
+      return Ok(());
+    }
+    loop {
+      element(self)?;
+      match self.peek() {
+        Some(b',') => self.position += 1,
+        Some(b']') => {
+          self.position += 1;
+          return Ok(());
+        },
+        Some(_) => return Err(self.error("expected `,` or `]`")),
+        None => return Err(self.error("EOF while parsing a list"))
+      }
+    }
+  }
+
+  fn string(&mut self) -> Result<String, Error> {
+    self.expect(b'"', "a string")?;
+    let bytes = self.json.as_bytes();
+    let mut string = String::new();
+    loop {
+      // Copies everything up to the next quote or escape at once
+      let start = self.position;
+      while let Some(&byte) = bytes.get(self.position) {
+        if byte == b'"' || byte == b'\\' || byte < 0x20 {
+          break;
+        }
+        self.position += 1;
+      }
+      string.push_str(&self.json[start..self.position]);
+
+      match bytes.get(self.position) {
+        Some(b'"') => {
+          self.position += 1;
+          return Ok(string);
+        },
+        Some(b'\\') => {
+          self.position += 1;
+          let escape = match bytes.get(self.position) {
+            Some(&escape) => escape,
+            None => return Err(self.error("EOF while parsing a string"))
+          };
+          self.position += 1;
+          match escape {
+            b'"' => string.push('"'),
+            b'\\' => string.push('\\'),
+            b'/' => string.push('/'),
+            b'b' => string.push('\u{8}'),
+            b'f' => string.push('\u{c}'),
+            b'n' => string.push('\n'),
+            b'r' => string.push('\r'),
+            b't' => string.push('\t'),
+            b'u' => string.push(self.unicode_escape()?),
+            _ => {
+              self.position -= 1;
+              return Err(self.error("invalid escape"));
+            }
+          }
+        },
+        Some(_) => return Err(self.error("control character (\\u0000-\\u001F) found while parsing a string")),
+        None => return Err(self.error("EOF while parsing a string"))
+      }
+    }
+  }
+
+  // Decodes the hex digits after \u, and the low surrogate after a high surrogate
+  fn unicode_escape(&mut self) -> Result<char, Error> {
+    let first = self.hex_digits()?;
+    let code_point = match first {
+      0xD800..=0xDBFF => {
+        if !self.json[self.position..].starts_with("\\u") {
+          return Err(self.error("lone leading surrogate in hex escape"));
+        }
+        self.position += 2;
+        let second = self.hex_digits()?;
+        if !(0xDC00..=0xDFFF).contains(&second) {
+          return Err(self.error("lone leading surrogate in hex escape"));
+        }
+        0x10000 + ((first - 0xD800) << 10) + (second - 0xDC00)
+      },
+      0xDC00..=0xDFFF => return Err(self.error("lone trailing surrogate in hex escape")),
+      _ => first
+    };
+    // Surrogates are handled above, so every remaining code point is a char
+    char::from_u32(code_point).ok_or_else(|| self.error("invalid unicode code point"))
+  }
+
+  fn hex_digits(&mut self) -> Result<u32, Error> {
+    let digits = match self.json.get(self.position..self.position + 4) {
+      Some(digits) => digits,
+      None => return Err(self.error("EOF while parsing a string"))
+    };
+    if !digits.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+      return Err(self.error("invalid escape"));
+    }
+    self.position += 4;
+    u32::from_str_radix(digits, 16).map_err(|_| self.error("invalid escape"))
+  }
+
+  fn optional_string(&mut self) -> Result<Option<String>, Error> {
+    if self.null()? { Ok(None) } else { self.string().map(Some) }
+  }
+
+  fn strings(&mut self) -> Result<Vec<String>, Error> {
+    let mut strings = Vec::new();
+    self.array(|reader| {
+      strings.push(reader.string()?);
+      Ok(())
+    })?;
+    Ok(strings)
+  }
+
+  fn optional_strings(&mut self) -> Result<Vec<Option<String>>, Error> {
+    let mut strings = Vec::new();
+    self.array(|reader| {
+      strings.push(reader.optional_string()?);
+      Ok(())
+    })?;
+    Ok(strings)
+  }
+
+  fn u32(&mut self) -> Result<u32, Error> {
+    let bytes = self.json.as_bytes();
+    let start = self.position;
+    match self.peek() {
+      Some(b'0'..=b'9') => {},
+      Some(b'-') => return Err(self.error("invalid value: integer, expected u32")),
+      Some(_) => return Err(self.error("invalid type, expected u32")),
+      None => return Err(self.error("EOF while parsing a value"))
+    }
+    let digits_start = self.position;
+    while bytes.get(self.position).is_some_and(u8::is_ascii_digit) {
+      self.position += 1;
+    }
+    let digits = &self.json[digits_start..self.position];
+    if digits.len() > 1 && digits.starts_with('0') {
+      return Err(self.error("invalid number"));
+    }
+    if matches!(bytes.get(self.position), Some(b'.' | b'e' | b'E')) {
+      self.position = start;
+      return Err(self.error("invalid type: floating point, expected u32"));
+    }
+    digits.parse().map_err(|_| self.error("invalid value: integer, expected u32"))
+  }
+
+  fn u32s(&mut self) -> Result<Vec<u32>, Error> {
+    let mut values = Vec::new();
+    self.array(|reader| {
+      values.push(reader.u32()?);
+      Ok(())
+    })?;
+    Ok(values)
+  }
+
+  fn sections(&mut self) -> Result<Vec<Section>, Error> {
+    let mut sections = Vec::new();
+    self.array(|reader| {
+      let (mut offset, mut map, mut url) = (None, None, None);
+      reader.object(|reader, key| match key {
+        "offset" => set(&mut offset, reader.offset()?, reader, key),
+        "map" => {
+          let map_json = if reader.null()? { None } else { Some(reader.skip_value(0)?.to_owned()) };
+          set(&mut map, map_json, reader, key)
+        },
+        "url" => set(&mut url, reader.optional_string()?, reader, key),
+        _ => reader.skip_value(0).map(|_| ())
+      })?;
+      sections.push(Section {
+        offset: required(offset, reader, "offset")?,
+        map: map.unwrap_or_default(),
+        url: url.unwrap_or_default()
+      });
+      Ok(())
+    })?;
+    Ok(sections)
+  }
+
+  fn offset(&mut self) -> Result<(u32, u32), Error> {
+    let (mut line, mut column) = (None, None);
+    self.object(|reader, key| match key {
+      "line" => set(&mut line, reader.u32()?, reader, key),
+      "column" => set(&mut column, reader.u32()?, reader, key),
+      _ => reader.skip_value(0).map(|_| ())
+    })?;
+    Ok((required(line, self, "line")?, required(column, self, "column")?))
+  }
+
+  // Checks and skips any value, returning its JSON
+  fn skip_value(&mut self, depth: usize) -> Result<&'a str, Error> {
+    if depth >= MAX_DEPTH {
+      return Err(self.error("recursion limit exceeded"));
+    }
+    let start = match self.peek() {
+      Some(_) => self.position,
+      None => return Err(self.error("EOF while parsing a value"))
+    };
+    match self.json.as_bytes()[start] {
+      b'{' => self.object(|reader, _| reader.skip_value(depth + 1).map(|_| ()))?,
+      b'[' => self.array(|reader| reader.skip_value(depth + 1).map(|_| ()))?,
+      b'"' => {
+        self.string()?;
+      },
+      b'-' | b'0'..=b'9' => self.number()?,
+      _ => {
+        if !(self.literal("null")? || self.literal("true")? || self.literal("false")?) {
+          return Err(self.error("expected value"));
+        }
+      }
+    }
+    Ok(&self.json[start..self.position])
+  }
+
+  // Skips a number, which may have a sign, a fraction and an exponent
+  fn number(&mut self) -> Result<(), Error> {
+    let bytes = self.json.as_bytes();
+    let digits = |reader: &mut Reader| {
+      let start = reader.position;
+      while bytes.get(reader.position).is_some_and(u8::is_ascii_digit) {
+        reader.position += 1;
+      }
+      reader.position - start
+    };
+    if bytes.get(self.position) == Some(&b'-') {
+      self.position += 1;
+    }
+    let integer_start = self.position;
+    match digits(self) {
+      0 => return Err(self.error("invalid number")),
+      length if length > 1 && bytes[integer_start] == b'0' => return Err(self.error("invalid number")),
+      _ => {}
+    }
+    if bytes.get(self.position) == Some(&b'.') {
+      self.position += 1;
+      if digits(self) == 0 {
+        return Err(self.error("invalid number"));
+      }
+    }
+    if matches!(bytes.get(self.position), Some(b'e' | b'E')) {
+      self.position += 1;
+      if matches!(bytes.get(self.position), Some(b'+' | b'-')) {
+        self.position += 1;
+      }
+      if digits(self) == 0 {
+        return Err(self.error("invalid number"));
+      }
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+use consume::consume;
+#[cfg(all(test, not(feature = "std")))]
+use alloc::string::ToString;
+
+#[test]
+fn it_decodes_escapes_in_strings() {
+  let json = r#"{ "version": 3, "sources": ["a\"\\\/\b\f\n\r\t.js", "é中.js", "😀.js", "ä😀.js"], "names": [], "mappings": "AAAA,CCAA,CCAA,CCAA" }"#;
+  let cache = consume(json).unwrap();
+  assert_eq!(cache.sources(), ["a\"\\/\u{8}\u{c}\n\r\t.js", "é中.js", "😀.js", "ä😀.js"]);
+
+  for (string, message) in [
+    (r#""\ud83d.js""#, "lone leading surrogate in hex escape"),
+    (r#""\ud83dA.js""#, "lone leading surrogate in hex escape"),
+    (r#""\ude00.js""#, "lone trailing surrogate in hex escape"),
+    (r#""\u12g4""#, "invalid escape"),
+    (r#""\x""#, "invalid escape"),
+    ("\"a\nb\"", "control character (\\u0000-\\u001F) found while parsing a string")
+  ] {
+    let json = format!(r#"{{ "version": 3, "sources": [{}], "names": [], "mappings": "AAAA" }}"#, string);
+    let err = consume(&json).unwrap_err();
+    assert_eq!(err.code(), ErrorCode::Json);
+    assert!(err.to_string().starts_with(message), "{}: {}", string, err);
+  }
+  assert_eq!(consume(r#"{ "version": 3, "sources": ["a.js"#).unwrap_err().to_string(), "EOF while parsing a string at line 1 column 33");
+}
+
+#[test]
+fn it_skips_unknown_fields_and_rejects_malformed_json() {
+  let json = r#" { "x_extra": [1, -2.5e+3, true, false, null, { "a": [] }, "}"], "version": 3, "sources": ["a.js"],
+    "names": [], "mappings": "AAAA", "sourcesContent": [null], "ignoreList": [0] } "#;
+  let header = parse_header(json).unwrap();
+  assert_eq!((header.ignore_list, header.has_sources_content), (vec![0], true));
+  assert!(consume(json).is_ok());
+
+  for (json, message) in [
+    (format!("{} x", json), "trailing characters at line 2 column 84"),
+    (json.replace("\"version\": 3", "\"version\": 3.0"), "invalid type: floating point, expected u32"),
+    (json.replace("\"version\": 3", "\"version\": -3"), "invalid value: integer, expected u32"),
+    (json.replace("\"version\": 3", "\"version\": 3, \"version\": 3"), "duplicate field `version`"),
+    (json.replace("\"ignoreList\"", "\"x_google_ignoreList\": [], \"ignoreList\""), "duplicate field `ignoreList`"),
+    (json.replace("\"mappings\": \"AAAA\",", ""), "missing field `mappings`"),
+    (json.replace("-2.5e+3", "01"), "invalid number"),
+    (json.replace("true", "tru"), "expected ident"),
+    (json.replace("[\"a.js\"]", "[\"a.js\",]"), "expected a string"),
+    (json.replace(", \"ignoreList\": [0] }", ""), "EOF while parsing an object"),
+    (format!("{}{}{}", "[".repeat(200), json, "]".repeat(200)), "expected an object")
+  ] {
+    let err = consume(&json).unwrap_err();
+    assert!(err.to_string().starts_with(message), "expected {:?}, got {}", message, err);
+  }
+  let nested = format!(r#"{{ "x": {}{} }}"#, "[".repeat(200), "]".repeat(200));
+  assert!(parse_header(&nested).unwrap_err().to_string().starts_with("recursion limit exceeded"));
+}
+
+#[test]
+fn it_parses_index_maps_and_sources_content() {
+  let json = r#"{ "version": 3, "file": "app.js", "sections": [
+    { "offset": { "line": 0, "column": 0 }, "map": { "version": 3, "sources": ["a.js"], "sourcesContent": ["a!"], "names": [], "mappings": "AAAA" } },
+    { "offset": { "column": 0, "line": 1 }, "url": "b.js.map" }
+  ] }"#;
+  match parse_document(json).unwrap() {
+    Document::IndexMap(index_map) => {
+      assert_eq!((index_map.version, index_map.file.as_deref()), (3, Some("app.js")));
+      assert_eq!(index_map.sections[0].offset, (0, 0));
+      assert!(index_map.sections[0].map.as_deref().unwrap().ends_with(r#""mappings": "AAAA" }"#));
+      assert_eq!((index_map.sections[1].offset, index_map.sections[1].url.as_deref()), ((1, 0), Some("b.js.map")));
+    },
+    document => panic!("expected an index map, got {:?}", document)
+  }
+  assert_eq!(parse_sources_content(r#"{ "sourcesContent": ["a", null] }"#).unwrap(), [Some("a".into()), None]);
+  assert_eq!(parse_sources_content(r#"{ "sourcesContent": null }"#).unwrap(), []);
+}
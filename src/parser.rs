@@ -0,0 +1,119 @@
+use consume::{Cache, Scratch, consume_json};
+use error::{Error, ErrorCode};
+
+/**
+ * Parses source maps like `consume`, keeping the buffers that parsing only needs while
+ * it runs from one source map to the next. A service parsing many source maps can keep
+ * a parser around, so that each of them allocates less; `consume` parses with a parser
+ * of its own, which is dropped afterwards.
+ *
+ * The parser also keeps the sources and names of the caches it has returned, for as long
+ * as those caches are alive or until the next call, so that the strings recurring from
+ * one source map to the next, such as those of the same bundle from one build to
+ * another, are shared between caches rather than allocated again. Strings no cache
+ * refers to anymore are let go of after each call.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::Parser;
+ *
+ * let mut parser = Parser::new();
+ * for source in ["a.js", "b.js"] {
+ *   let json = format!(r#"{{ "version": 3, "sources": ["{}"], "names": [], "mappings": "AAAA" }}"#, source);
+ *   let cache = parser.consume(&json).unwrap();
+ *   assert!(&*cache.mapping_for_generated_position(1, 0).source == source);
+ * }
+ * ```
+ */
+#[derive(Default, Debug)]
+pub struct Parser {
+  /**
+   * Whether the `sourcesContent` of source maps is retained, like
+   * `consume_with_sources_content` does. Off by default.
+   */
+  pub sources_content: bool,
+  /**
+   * Whether the sources and names no mapping refers to are removed from the tables of
+   * the caches, with `Cache::drop_unreferenced`. Off by default.
+   */
+  pub drop_unreferenced: bool,
+  scratch: Scratch
+}
+
+impl Parser {
+  /**
+   * Returns a parser with empty buffers, which grow as source maps are parsed.
+   */
+  pub fn new() -> Parser {
+    Parser::default()
+  }
+
+  /**
+   * Parses a source map, or an index map, into a cache, like `consume`.
+   */
+  pub fn consume(&mut self, source_map_json: &str) -> Result<Cache, Error> {
+    let mut cache = consume_json(source_map_json, self.sources_content, None, &mut self.scratch)?;
+    if cache.is_empty() {
+      return Err(Error::invalid(ErrorCode::NoMappings, "Source Map contains no mappings"));
+    }
+    if self.drop_unreferenced {
+      cache.drop_unreferenced();
+    }
+    Ok(cache)
+  }
+}
+
+#[cfg(test)]
+use consume::consume;
+#[cfg(all(test, not(feature = "std")))]
+use alloc::{borrow::ToOwned, string::ToString};
+
+#[test]
+fn it_parses_like_consume_from_one_map_to_the_next() {
+  let maps = [
+    r#"{ "version": 3, "sources": ["a.js", "b.js"], "names": ["x", "y"], "mappings": "AAAAA,CCCCC;ADCAD" }"#,
+    r#"{ "version": 3, "sources": ["c.js"], "sourcesContent": ["c();"], "names": [], "mappings": "AAAA" }"#,
+    r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAAC" }"#,
+    r#"{ "version": 3, "sections": [{ "offset": { "line": 1, "column": 0 }, "map": { "version": 3, "sources": ["d.js"], "names": ["z"], "mappings": "AAAAA" } }] }"#,
+    r#"{ "version": 3, "sources": [], "names": [], "mappings": "" }"#,
+    r#"{ "version": 3, "sources": ["e.js", "a.js"], "names": ["y"], "mappings": "ACAAA" }"#
+  ];
+  let mut parser = Parser::new();
+  // Twice over, so that every map is parsed after every other
+  for json in maps.iter().chain(&maps) {
+    match (parser.consume(json), consume(json)) {
+      (Ok(cache), Ok(expected)) => assert_eq!(cache, expected, "{}", json),
+      (Err(err), Err(expected)) => assert_eq!(err.to_string(), expected.to_string(), "{}", json),
+      (result, expected) => panic!("{} gave {:?}, and consume {:?}", json, result, expected)
+    }
+  }
+
+  parser.sources_content = true;
+  assert_eq!(parser.consume(maps[1]).unwrap().source_content("c.js"), Some("c();"));
+  parser.drop_unreferenced = true;
+  let cache = parser.consume(maps[3]).unwrap();
+  assert_eq!(cache.declared_sources(), [Some("d.js")]);
+  let cache = parser.consume(maps[5]).unwrap();
+  assert_eq!((cache.sources(), cache.declared_sources()), (&["a.js".to_owned()][..], vec![None, Some("a.js")]));
+}
+
+#[test]
+fn it_shares_strings_between_the_caches_it_returns() {
+  #[cfg(feature = "std")]
+  use std::sync::Arc;
+  #[cfg(not(feature = "std"))]
+  use alloc::sync::Arc;
+
+  let mut parser = Parser::new();
+  let first = parser.consume(r#"{ "version": 3, "sources": ["a.js", "b.js"], "names": ["x"], "mappings": "AAAAA,CCAA" }"#).unwrap();
+  let second = parser.consume(r#"{ "version": 3, "sources": ["b.js", "c.js"], "names": ["x"], "mappings": "AAAAA,CCAA" }"#).unwrap();
+  let mapping = |cache: &Cache, column| cache.mapping_for_generated_position(1, column);
+  assert!(Arc::ptr_eq(&mapping(&first, 1).source, &mapping(&second, 0).source));
+  assert!(Arc::ptr_eq(&mapping(&first, 0).name, &mapping(&second, 0).name));
+  // Strings are only kept for as long as a cache refers to them
+  drop(first);
+  parser.consume(r#"{ "version": 3, "sources": ["c.js"], "names": [], "mappings": "AAAA" }"#).unwrap();
+  // The empty string, and the strings of the second cache
+  assert_eq!(parser.scratch.shared_strings(), 1 + ["b.js", "c.js", "x"].len());
+}
@@ -0,0 +1,400 @@
+extern crate serde_json;
+
+use std::sync::Arc;
+
+use base64_vlq;
+use consume::{CodePosition, Interner, Mapping, compare_mappings};
+use error::{Error, ErrorCode};
+use generate::Generator;
+
+#[allow(non_snake_case)]
+#[derive(Deserialize, Debug)]
+struct RepairInput {
+  version: u32,
+  #[serde(default)]
+  sources: Vec<Option<String>>,
+  #[serde(default)]
+  names: Vec<String>,
+  sourceRoot: Option<String>,
+  sourcesContent: Option<Vec<Option<String>>>,
+  #[serde(default)]
+  mappings: String,
+  file: Option<String>
+}
+
+/**
+ * How `repair` treats source and name indices that are out of range of their table.
+ */
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum IndexRepair {
+  /** Replace the index by the last index of the table */
+  Clamp,
+  /** Remove the source or name from the mapping */
+  Drop
+}
+
+/**
+ * The fixes `repair` is allowed to apply. Defects without an enabled fix make `repair`
+ * fail. By default all fixes are enabled, and bad indices are dropped.
+ */
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RepairOptions {
+  /** Sort segments that are out of order within a line */
+  pub sort: bool,
+  /** Remove duplicate identical segments */
+  pub dedup: bool,
+  /** How to fix out of range source and name indices, if at all */
+  pub bad_indices: Option<IndexRepair>,
+  /** Remove undecodable text after the last `;` of the mappings */
+  pub drop_trailing_garbage: bool
+}
+
+impl Default for RepairOptions {
+  fn default() -> RepairOptions {
+    RepairOptions {
+      sort: true,
+      dedup: true,
+      bad_indices: Some(IndexRepair::Drop),
+      drop_trailing_garbage: true
+    }
+  }
+}
+
+/**
+ * A fix applied by `repair`.
+ */
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RepairAction {
+  /** The segments of a generated line were sorted by column */
+  SortedLine { line: u32 },
+  /** A segment identical to another segment was removed */
+  RemovedDuplicate { generated: CodePosition },
+  /** An out of range source index was replaced by the last source index */
+  ClampedSourceIndex { generated: CodePosition, index: u32, clamped_to: u32 },
+  /** An out of range name index was replaced by the last name index */
+  ClampedNameIndex { generated: CodePosition, index: u32, clamped_to: u32 },
+  /** An out of range source index was removed, leaving the segment unmapped */
+  DroppedSourceIndex { generated: CodePosition, index: u32 },
+  /** An out of range name index was removed from the segment */
+  DroppedNameIndex { generated: CodePosition, index: u32 },
+  /**
+   * A name index was removed from a segment whose source index was dropped, since a
+   * segment without a source cannot have a name
+   */
+  DroppedNameWithSource { generated: CodePosition, index: u32 },
+  /** Undecodable text after the last `;` of the mappings was removed */
+  RemovedTrailingGarbage { line: u32, garbage: String }
+}
+
+/**
+ * Parses a source map leniently, fixes the defects selected by `options` and serializes
+ * a clean source map.
+ *
+ * Returns the repaired source map JSON along with the list of fixes that were applied,
+ * which is empty if the source map had no defects. The `file`, `sourceRoot` and
+ * `sourcesContent` of the source map are preserved.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::{repair, RepairOptions, RepairAction};
+ *
+ * // The second segment refers to a name past the end of the names table
+ * let json = r#"{ "version": 3, "sources": ["a.js"], "names": ["x"], "mappings": "AAAAA,CAAAC" }"#;
+ * let (repaired, actions) = repair(json, &RepairOptions::default()).unwrap();
+ * assert!(repaired.contains(r#""mappings":"AAAAA,CAAA""#));
+ * assert!(actions.len() == 1);
+ * ```
+ */
+pub fn repair(json: &str, options: &RepairOptions) -> Result<(String, Vec<RepairAction>), Error> {
+  let input: RepairInput = serde_json::from_str(json)?;
+  if input.version != 3 {
+    return Err(Error::invalid(ErrorCode::UnsupportedVersion, "Only Source Map version 3 is implemented"));
+  }
+
+  let mut actions: Vec<RepairAction> = Vec::new();
+  let mut mappings = decode_leniently(&input, options, &mut actions)?;
+  if mappings.is_empty() {
+    return Err(Error::invalid(ErrorCode::NoMappings, "Source Map contains no mappings"));
+  }
+
+  if !options.sort {
+    // The generator always writes mappings in order, so unsorted lines cannot be kept
+    if let Some(pair) = mappings.windows(2).find(|pair| is_out_of_order(&pair[0], &pair[1])) {
+      return Err(Error::invalid(ErrorCode::InvalidSegment, format!("Segments of generated line {} are out of order", pair[1].generated.line)));
+    }
+  } else {
+    let mut unsorted_lines: Vec<u32> = mappings.windows(2)
+      .filter(|pair| is_out_of_order(&pair[0], &pair[1]))
+      .map(|pair| pair[1].generated.line)
+      .collect();
+    unsorted_lines.dedup();
+    actions.extend(unsorted_lines.into_iter().map(|line| RepairAction::SortedLine { line }));
+  }
+  mappings.sort_by(compare_mappings);
+
+  if options.dedup {
+    let mut deduplicated: Vec<Mapping> = Vec::with_capacity(mappings.len());
+    for mapping in mappings {
+      if deduplicated.last() == Some(&mapping) {
+        actions.push(RepairAction::RemovedDuplicate { generated: mapping.generated });
+      } else {
+        deduplicated.push(mapping);
+      }
+    }
+    mappings = deduplicated;
+  }
+
+  let mut generator = Generator::new();
+  if let Some(file) = input.file {
+    generator.set_file(file);
+  }
+  if let Some(source_root) = input.sourceRoot {
+    generator.set_source_root(source_root);
+  }
+  let sources_content = input.sourcesContent.unwrap_or_default();
+  for (source, content) in input.sources.iter().zip(sources_content) {
+    if let (Some(source), Some(content)) = (source, content) {
+      generator.set_source_content(source.as_str(), content);
+    }
+  }
+  for mapping in mappings {
+    generator.add_mapping(mapping.generated, mapping.original, &mapping.source, &mapping.name);
+  }
+
+  for action in &actions {
+    warn!("Repaired a defect of the source map: {:?}", action);
+  }
+  Ok((generator.to_json()?, actions))
+}
+
+fn is_out_of_order(previous: &Mapping, next: &Mapping) -> bool {
+  previous.generated.line == next.generated.line && previous.generated.column > next.generated.column
+}
+
+// Decodes the mappings like `consume`, but fixes bad indices and trailing garbage when
+// the options allow it. Mappings are returned in the order they appear.
+fn decode_leniently(input: &RepairInput, options: &RepairOptions, actions: &mut Vec<RepairAction>) -> Result<Vec<Mapping>, Error> {
+  let mut interner = Interner::default();
+  let sources: Vec<Arc<str>> = input.sources.iter().map(|source| interner.intern(source.as_ref().map_or("", |source| source.as_str()))).collect();
+  let names: Vec<Arc<str>> = input.names.iter().map(|name| interner.intern(name)).collect();
+  let empty = interner.intern("");
+  let mut mappings: Vec<Mapping> = Vec::new();
+
+  let mut previous_original_line: i64 = 0;
+  let mut previous_original_column: i64 = 0;
+  let mut previous_source: i64 = 0;
+  let mut previous_name: i64 = 0;
+
+  let lines: Vec<&str> = input.mappings.split(';').collect();
+  for (generated_line, line) in (1u32..).zip(lines.iter()) {
+    let is_last_line = generated_line as usize == lines.len();
+    let mut previous_generated_column: i64 = 0;
+    let mut line_mappings: Vec<Mapping> = Vec::new();
+    let mut result: Result<(), Error> = Ok(());
+
+    for segment in line.split(',') {
+      let fields = match decode_segment(segment.as_bytes()) {
+        Ok(fields) => fields,
+        Err(err) => {
+          result = Err(err);
+          break;
+        }
+      };
+      if fields.is_empty() {
+        continue;
+      }
+      if fields.len() == 2 || fields.len() == 3 {
+        result = Err(Error::invalid(ErrorCode::InvalidSegment, format!("Invalid segment {:?} on generated line {}", segment, generated_line)));
+        break;
+      }
+
+      previous_generated_column += fields[0];
+      let generated = CodePosition { line: generated_line, column: to_u32(previous_generated_column)? };
+      let mut mapping = Mapping {
+        generated: generated.clone(),
+        original: CodePosition { line: 0, column: 0 },
+        source: empty.clone(),
+        name: empty.clone()
+      };
+
+      if fields.len() > 1 {
+        previous_source += fields[1];
+        previous_original_line += fields[2];
+        previous_original_column += fields[3];
+
+        let source = to_u32(previous_source)?;
+        match repair_index(source, sources.len(), options.bad_indices) {
+          Some(index) => {
+            if index != source {
+              actions.push(RepairAction::ClampedSourceIndex { generated: generated.clone(), index: source, clamped_to: index });
+            }
+            mapping.source = sources[index as usize].clone();
+            // Lines are stored 0-based
+            mapping.original.line = to_u32(previous_original_line)?.checked_add(1).ok_or_else(|| Error::invalid(ErrorCode::InvalidPosition, "Line number overflowed"))?;
+            mapping.original.column = to_u32(previous_original_column)?;
+          },
+          None if options.bad_indices.is_some() => {
+            actions.push(RepairAction::DroppedSourceIndex { generated: generated.clone(), index: source });
+          },
+          None => return Err(Error::invalid(ErrorCode::SourceIndexOutOfRange, format!("Invalid source map: reference to source index {} when source list length is {}", source, sources.len())))
+        }
+      }
+
+      if fields.len() > 4 {
+        previous_name += fields[4];
+        let name = to_u32(previous_name)?;
+        match repair_index(name, input.names.len(), options.bad_indices) {
+          Some(_) if mapping.source.is_empty() => {
+            actions.push(RepairAction::DroppedNameWithSource { generated, index: name });
+          },
+          Some(index) => {
+            if index != name {
+              actions.push(RepairAction::ClampedNameIndex { generated: generated.clone(), index: name, clamped_to: index });
+            }
+            mapping.name = names[index as usize].clone();
+          },
+          None if options.bad_indices.is_some() => {
+            actions.push(RepairAction::DroppedNameIndex { generated, index: name });
+          },
+          None => return Err(Error::invalid(ErrorCode::NameIndexOutOfRange, format!("Invalid source map: reference to name index {} when name list length is {}", name, input.names.len())))
+        }
+      }
+
+      line_mappings.push(mapping);
+    }
+
+    match result {
+      Ok(()) => mappings.extend(line_mappings),
+      Err(_) if is_last_line && options.drop_trailing_garbage => {
+        actions.push(RepairAction::RemovedTrailingGarbage { line: generated_line, garbage: (*line).to_owned() });
+      },
+      Err(err) => return Err(err)
+    }
+  }
+
+  Ok(mappings)
+}
+
+fn decode_segment(segment: &[u8]) -> Result<Vec<i64>, Error> {
+  let mut fields: Vec<i64> = Vec::new();
+  let mut index = 0;
+  while index < segment.len() {
+    match base64_vlq::decode(&segment[index..]) {
+      Some((value, length)) => {
+        fields.push(value as i64);
+        index += length;
+      },
+      None => return Err(Error::invalid(ErrorCode::InvalidVlq, format!("Invalid VLQ mapping field in segment {:?}", String::from_utf8_lossy(segment))))
+    }
+  }
+  Ok(fields)
+}
+
+fn to_u32(value: i64) -> Result<u32, Error> {
+  if value < 0 || value > (u32::MAX as i64) {
+    return Err(Error::invalid(ErrorCode::InvalidPosition, format!("Mapping field {} is out of range", value)));
+  }
+  Ok(value as u32)
+}
+
+// Returns the index to use, or None if the index should be dropped or is an error
+fn repair_index(index: u32, table_length: usize, repair: Option<IndexRepair>) -> Option<u32> {
+  if (index as usize) < table_length {
+    return Some(index);
+  }
+  match repair {
+    Some(IndexRepair::Clamp) if table_length > 0 => Some(table_length as u32 - 1),
+    _ => None
+  }
+}
+
+#[cfg(test)]
+use consume::consume;
+
+#[cfg(test)]
+fn position(line: u32, column: u32) -> CodePosition {
+  CodePosition { line, column }
+}
+
+#[test]
+fn it_leaves_valid_source_maps_unchanged() {
+  let json = r#"{"version":3,"file":"out.js","sourceRoot":"/","sources":["a.js"],"sourcesContent":["let a;"],"names":["x"],"mappings":"AAAAA,CAAC;AACA"}"#;
+  let (repaired, actions) = repair(json, &RepairOptions::default()).unwrap();
+  assert_eq!(repaired, json);
+  assert!(actions.is_empty());
+}
+
+#[test]
+fn it_sorts_and_deduplicates_segments() {
+  let json = r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "EAAE,DAAD,AAAA;AACA,AAAA" }"#;
+  let (repaired, actions) = repair(json, &RepairOptions::default()).unwrap();
+  assert_eq!(actions, vec![
+    RepairAction::SortedLine { line: 1 },
+    RepairAction::RemovedDuplicate { generated: position(1, 1) },
+    RepairAction::RemovedDuplicate { generated: position(2, 0) }
+  ]);
+  assert_eq!(repaired, r#"{"version":3,"sources":["a.js"],"names":[],"mappings":"CAAC,CAAC;AACD"}"#);
+
+  let strict = RepairOptions { sort: false, ..RepairOptions::default() };
+  assert!(repair(json, &strict).is_err());
+}
+
+#[test]
+fn it_clamps_or_drops_bad_indices() {
+  let json = r#"{ "version": 3, "sources": ["a.js"], "names": ["x"], "mappings": "AAAAC,ACAA" }"#;
+
+  let clamp = RepairOptions { bad_indices: Some(IndexRepair::Clamp), ..RepairOptions::default() };
+  let (repaired, actions) = repair(json, &clamp).unwrap();
+  assert_eq!(actions, vec![
+    RepairAction::ClampedNameIndex { generated: position(1, 0), index: 1, clamped_to: 0 },
+    RepairAction::ClampedSourceIndex { generated: position(1, 0), index: 1, clamped_to: 0 }
+  ]);
+  let cache = consume(&repaired).unwrap();
+  assert_eq!(&*cache.mapping_for_generated_position(1, 0).name, "x");
+
+  let (repaired, actions) = repair(json, &RepairOptions::default()).unwrap();
+  assert_eq!(actions, vec![
+    RepairAction::DroppedNameIndex { generated: position(1, 0), index: 1 },
+    RepairAction::DroppedSourceIndex { generated: position(1, 0), index: 1 }
+  ]);
+  assert_eq!(repaired, r#"{"version":3,"sources":["a.js"],"names":[],"mappings":"A,AAAA"}"#);
+
+  let strict = RepairOptions { bad_indices: None, ..RepairOptions::default() };
+  assert!(repair(json, &strict).is_err());
+
+  // A valid name goes along with a dropped source
+  let json = r#"{ "version": 3, "sources": ["a.js"], "names": ["x"], "mappings": "ACAAA" }"#;
+  let (repaired, actions) = repair(json, &RepairOptions::default()).unwrap();
+  assert_eq!(actions, vec![
+    RepairAction::DroppedSourceIndex { generated: position(1, 0), index: 1 },
+    RepairAction::DroppedNameWithSource { generated: position(1, 0), index: 0 }
+  ]);
+  assert_eq!(repaired, r#"{"version":3,"sources":[],"names":[],"mappings":"A"}"#);
+}
+
+#[test]
+fn it_removes_trailing_garbage() {
+  let json = r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA;AACA;\n" }"#;
+  let (repaired, actions) = repair(json, &RepairOptions::default()).unwrap();
+  assert_eq!(actions, vec![RepairAction::RemovedTrailingGarbage { line: 3, garbage: "\n".into() }]);
+  assert_eq!(repaired, r#"{"version":3,"sources":["a.js"],"names":[],"mappings":"AAAA;AACA"}"#);
+
+  // Garbage before the last line is not fixable
+  assert!(repair(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AA!A;AACA" }"#, &RepairOptions::default()).is_err());
+}
+
+#[cfg(feature = "log")]
+#[test]
+fn it_logs_repairs() {
+  use test::captured_logs;
+
+  captured_logs();
+  let json = r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA;AACA;AAAA,AAAA" }"#;
+  repair(json, &RepairOptions::default()).unwrap();
+  let expected = "WARN Repaired a defect of the source map: RemovedDuplicate { generated: CodePosition { line: 3, column: 0 } }";
+  assert!(captured_logs().iter().any(|message| message == expected), "{:?}", captured_logs());
+}
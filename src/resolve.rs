@@ -0,0 +1,133 @@
+use core::fmt;
+#[cfg(feature = "std")]
+use std::sync::Arc;
+#[cfg(not(feature = "std"))]
+use alloc::{borrow::ToOwned, string::String, sync::Arc};
+
+use consume::{Cache, join_source_root};
+
+/**
+ * Turns the `sources` entries of a source map into the paths or URLs they refer to.
+ * Attach one to a cache with `Cache::set_source_resolver` to change how
+ * `Cache::resolved_source` resolves sources, for instance to map them into a local
+ * checkout. Closures taking the source root and the source implement it too.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::consume;
+ *
+ * let mut cache = consume(r#"{ "version": 3, "sourceRoot": "webpack:///", "sources": ["app/a.js"], "names": [], "mappings": "AAAA" }"#).unwrap();
+ * assert!(cache.resolved_source("app/a.js") == "webpack:///app/a.js");
+ * cache.set_source_resolver(|_: &str, source: &str| format!("/home/me/checkout/{}", source));
+ * assert!(cache.resolved_source("app/a.js") == "/home/me/checkout/app/a.js");
+ * ```
+ */
+pub trait SourceResolver: Send + Sync {
+  /** Resolves a source against the source root of its source map, which may be empty */
+  fn resolve(&self, source_root: &str, source: &str) -> String;
+}
+
+impl<F: Fn(&str, &str) -> String + Send + Sync> SourceResolver for F {
+  fn resolve(&self, source_root: &str, source: &str) -> String {
+    self(source_root, source)
+  }
+}
+
+impl fmt::Debug for dyn SourceResolver {
+  fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+    formatter.write_str("SourceResolver")
+  }
+}
+
+/**
+ * The resolver caches use unless another one is attached. Sources that are absolute
+ * URLs are kept as they are, and other sources are prefixed with the source root,
+ * separated by a slash unless the source root already ends with one.
+ */
+#[derive(Clone, Copy, Default, Debug)]
+pub struct DefaultSourceResolver;
+
+impl SourceResolver for DefaultSourceResolver {
+  fn resolve(&self, source_root: &str, source: &str) -> String {
+    if has_scheme(source) {
+      source.to_owned()
+    } else {
+      join_source_root(source_root, source)
+    }
+  }
+}
+
+// Whether a source starts with a URL scheme such as http: or webpack:
+pub(crate) fn has_scheme(source: &str) -> bool {
+  match source.find(':') {
+    Some(colon) => {
+      let scheme = &source[..colon];
+      scheme.starts_with(|character: char| character.is_ascii_alphabetic()) &&
+        scheme.chars().all(|character| character.is_ascii_alphanumeric() || "+-.".contains(character))
+    },
+    None => false
+  }
+}
+
+impl Cache {
+  /**
+   * Attaches a resolver, which `resolved_source` consults from then on. Caches derived
+   * from this one with `filter_sources` or `offset_generated` share it.
+   */
+  pub fn set_source_resolver<R: SourceResolver + 'static>(&mut self, resolver: R) {
+    self.resolver = Some(Arc::new(resolver));
+  }
+
+  /**
+   * Resolves a source of the cache, as it appears in its mappings, against the source
+   * root, using the attached resolver or `DefaultSourceResolver`.
+   *
+   * # Examples
+   *
+   * ```
+   * use js_source_mapper::consume;
+   *
+   * let cache = consume(r#"{ "version": 3, "sourceRoot": "src", "sources": ["a.js", "https://cdn.example/b.js"], "names": [], "mappings": "AAAA,CCAA" }"#).unwrap();
+   * assert!(cache.resolved_source("a.js") == "src/a.js");
+   * assert!(cache.resolved_source("https://cdn.example/b.js") == "https://cdn.example/b.js");
+   * ```
+   */
+  pub fn resolved_source(&self, source: &str) -> String {
+    match self.resolver {
+      Some(ref resolver) => resolver.resolve(&self.source_root, source),
+      None => DefaultSourceResolver.resolve(&self.source_root, source)
+    }
+  }
+}
+
+#[test]
+fn it_resolves_sources_against_the_source_root() {
+  let resolve = |source_root: &str, source: &str| DefaultSourceResolver.resolve(source_root, source);
+  assert_eq!(resolve("", "a.js"), "a.js");
+  assert_eq!(resolve("src", "a.js"), "src/a.js");
+  assert_eq!(resolve("http://example.com/", "a.js"), "http://example.com/a.js");
+  assert_eq!(resolve("src", "webpack:///a.js"), "webpack:///a.js");
+  assert_eq!(resolve("src", "./a:b.js"), "src/./a:b.js");
+  assert_eq!(resolve("src", "1a:b.js"), "src/1a:b.js");
+}
+
+#[test]
+fn it_shares_custom_resolvers_with_derived_caches() {
+  use consume::consume;
+
+  struct Checkout;
+  impl SourceResolver for Checkout {
+    fn resolve(&self, _: &str, source: &str) -> String {
+      format!("/checkout/{}", source.trim_start_matches("webpack:///"))
+    }
+  }
+
+  let mut cache = consume(r#"{ "version": 3, "sourceRoot": "webpack:///", "sources": ["a.js", "b.js"], "names": [], "mappings": "AAAA;ACAA" }"#).unwrap();
+  assert_eq!(cache.resolved_source("a.js"), "webpack:///a.js");
+  cache.set_source_resolver(Checkout);
+  assert_eq!(cache.resolved_source("a.js"), "/checkout/a.js");
+  assert_eq!(cache.filter_sources(|source| source == "b.js", false).resolved_source("b.js"), "/checkout/b.js");
+  assert_eq!(cache.offset_generated(1, 0).unwrap().resolved_source("b.js"), "/checkout/b.js");
+  assert!(format!("{:?}", cache).contains("resolver: Some(SourceResolver)"));
+}
@@ -0,0 +1,448 @@
+use consume::Cache;
+
+// The layouts of stack trace frames printed by the major JavaScript engines
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+enum FrameLayout {
+  // V8 (Chrome, Node.js): "    at function (file:line:column)" or "    at file:line:column"
+  V8,
+  // SpiderMonkey (Firefox) and JavaScriptCore (Safari): "function@file:line:column",
+  // where the function may be empty
+  AtSign,
+  // JavaScriptCore frames of anonymous functions: "file:line:column"
+  Location
+}
+
+impl FrameLayout {
+  // Detects the layout of a line of a stack trace, returning the line without the
+  // whitespace around it
+  fn detect(line: &str) -> (FrameLayout, &str) {
+    let trimmed = line.trim();
+    let layout = if trimmed.starts_with("at ") {
+      FrameLayout::V8
+    } else if trimmed.contains('@') {
+      FrameLayout::AtSign
+    } else {
+      FrameLayout::Location
+    };
+    (layout, trimmed)
+  }
+}
+
+/**
+ * A frame of a JavaScript stack trace that refers to a position in a generated file,
+ * such as `    at handleClick (https://cdn.example.com/bundle.min.js:1:283744)` in
+ * Chrome or `handleClick@https://cdn.example.com/bundle.min.js:1:283744` in Firefox
+ * and Safari.
+ *
+ * Lines and columns are kept as printed by the JavaScript engine, where both start
+ * from 1. With the `serde` feature frames can be read from and written to structured
+ * formats, where `raw` may be omitted.
+ */
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct StackFrame {
+  /** The name of the function, if the frame has one */
+  pub function: Option<String>,
+  /** The file or URL of the generated file */
+  pub file: String,
+  /** Line number in the generated file, starting from 1 */
+  pub line: u32,
+  /** Column number in the generated file, starting from 1 */
+  pub column: u32,
+  /**
+   * The line of the stack trace the frame was parsed from, which `symbolicate` keeps
+   * the layout and indentation of. Frames without one are written in the layout of V8.
+   */
+  #[cfg_attr(feature = "serde", serde(default))]
+  pub raw: String
+}
+
+/**
+ * Parses the lines of a stack trace into frames. Implemented for the formats of the
+ * major JavaScript engines by `V8StackTraceParser`, `FirefoxStackTraceParser` and
+ * `SafariStackTraceParser`, and by `AnyStackTraceParser`, which detects the format of
+ * each line.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::{FirefoxStackTraceParser, StackTraceParser};
+ *
+ * let frames = FirefoxStackTraceParser.parse_frames("handleClick@https://cdn.example.com/bundle.min.js:1:283744\n@https://cdn.example.com/bundle.min.js:1:9");
+ * assert!(frames.len() == 2);
+ * assert!(frames[0].function.as_deref() == Some("handleClick") && frames[1].function.is_none());
+ * ```
+ */
+pub trait StackTraceParser {
+  /**
+   * Parses a single line of a stack trace, returning `None` for lines that are not
+   * frames in the format, or that do not refer to a position in a file.
+   */
+  fn parse_frame(&self, line: &str) -> Option<StackFrame>;
+
+  /**
+   * Parses the frames of a stack trace, skipping the lines that are not frames, such as
+   * the error message.
+   */
+  fn parse_frames(&self, trace: &str) -> Vec<StackFrame> {
+    trace.lines().filter_map(|line| self.parse_frame(line)).collect()
+  }
+}
+
+/**
+ * Parses the frames of V8 (Chrome, Node.js): `at function (file:line:column)` or
+ * `at file:line:column`.
+ */
+#[derive(Clone, Copy, Default, Debug)]
+pub struct V8StackTraceParser;
+
+impl StackTraceParser for V8StackTraceParser {
+  fn parse_frame(&self, line: &str) -> Option<StackFrame> {
+    let rest = line.trim().strip_prefix("at ")?;
+    let (function, location) = if rest.ends_with(')') {
+      let open = matching_open_paren(rest)?;
+      (Some(rest[..open].trim_end()), &rest[open + 1..rest.len() - 1])
+    } else {
+      (None, rest)
+    };
+    frame(line, function, location)
+  }
+}
+
+/**
+ * Parses the frames of SpiderMonkey (Firefox): `function@file:line:column`, with an
+ * empty function for anonymous functions.
+ */
+#[derive(Clone, Copy, Default, Debug)]
+pub struct FirefoxStackTraceParser;
+
+impl StackTraceParser for FirefoxStackTraceParser {
+  fn parse_frame(&self, line: &str) -> Option<StackFrame> {
+    let rest = line.trim();
+    let at = rest.find('@')?;
+    frame(line, Some(&rest[..at]), &rest[at + 1..])
+  }
+}
+
+/**
+ * Parses the frames of JavaScriptCore (Safari): `function@file:line:column`, or just
+ * `file:line:column` for anonymous functions.
+ */
+#[derive(Clone, Copy, Default, Debug)]
+pub struct SafariStackTraceParser;
+
+impl StackTraceParser for SafariStackTraceParser {
+  fn parse_frame(&self, line: &str) -> Option<StackFrame> {
+    match FrameLayout::detect(line) {
+      (FrameLayout::AtSign, _) => FirefoxStackTraceParser.parse_frame(line),
+      (_, rest) => frame(line, None, rest)
+    }
+  }
+}
+
+/**
+ * Parses the frames of any of the formats of `V8StackTraceParser`,
+ * `FirefoxStackTraceParser` and `SafariStackTraceParser`, detecting the format of each
+ * line, for stack traces of unknown origin.
+ */
+#[derive(Clone, Copy, Default, Debug)]
+pub struct AnyStackTraceParser;
+
+impl StackTraceParser for AnyStackTraceParser {
+  fn parse_frame(&self, line: &str) -> Option<StackFrame> {
+    match FrameLayout::detect(line).0 {
+      FrameLayout::V8 => V8StackTraceParser.parse_frame(line),
+      FrameLayout::AtSign | FrameLayout::Location => SafariStackTraceParser.parse_frame(line)
+    }
+  }
+}
+
+// Builds a frame from the parts of a line, rejecting locations that are not positions
+fn frame(raw: &str, function: Option<&str>, location: &str) -> Option<StackFrame> {
+  let (file, line, column) = parse_location(location)?;
+  Some(StackFrame {
+    function: function.filter(|function| !function.is_empty()).map(|function| function.to_owned()),
+    file: file.to_owned(),
+    line,
+    column,
+    raw: raw.to_owned()
+  })
+}
+
+impl StackFrame {
+  /**
+   * Parses a single line of a stack trace with `AnyStackTraceParser`, which detects the
+   * format from the line:
+   *
+   * * V8 (Chrome, Node.js): `at function (file:line:column)` or `at file:line:column`.
+   * * SpiderMonkey (Firefox) and JavaScriptCore (Safari): `function@file:line:column`,
+   *   with an empty function for anonymous functions, or just `file:line:column`.
+   *
+   * Returns `None` for lines that are not frames, or that do not refer to a position in
+   * a file, such as `at new Promise (<anonymous>)`, `forEach@[native code]` or frames
+   * in code run by `eval`.
+   *
+   * # Examples
+   *
+   * ```
+   * use js_source_mapper::StackFrame;
+   *
+   * let frame = StackFrame::parse("    at handleClick (https://cdn.example.com/bundle.min.js:1:283744)").unwrap();
+   * assert!(frame.function.as_deref() == Some("handleClick"));
+   * assert!(frame.file == "https://cdn.example.com/bundle.min.js");
+   * assert!((frame.line, frame.column) == (1, 283744));
+   * ```
+   */
+  pub fn parse(line: &str) -> Option<StackFrame> {
+    AnyStackTraceParser.parse_frame(line)
+  }
+
+  /**
+   * Returns true if the frame refers to the given generated file, comparing the last
+   * path segments of the frame's URL (without query string or fragment) against `file`.
+   */
+  pub fn is_in_file(&self, file: &str) -> bool {
+    let path = match self.file.find(['?', '#']) {
+      Some(end) => &self.file[..end],
+      None => self.file.as_str()
+    };
+    let file = file.trim_start_matches("./");
+    path == file || (path.ends_with(file) && path[..path.len() - file.len()].ends_with('/'))
+  }
+
+  /**
+   * Rewrites the frame with the original source, line, column and name of its generated
+   * position, in the layout of `raw`. Returns `None` if the position is not mapped to an
+   * original position.
+   *
+   * # Examples
+   *
+   * ```
+   * use js_source_mapper::{consume, StackFrame};
+   *
+   * let cache = consume(r#"{ "version": 3, "sources": ["src/app.js"], "names": ["handleClick"], "mappings": "AAyDYA" }"#).unwrap();
+   * let frame = StackFrame::parse("    at r (https://cdn.example.com/bundle.min.js:1:1)").unwrap();
+   * assert_eq!(frame.symbolicate(&cache).unwrap(), "    at handleClick (src/app.js:58:13)");
+   * ```
+   */
+  pub fn symbolicate(&self, cache: &Cache) -> Option<String> {
+    let mut frame = self.clone();
+    if !frame.remap(cache) {
+      return None;
+    }
+
+    let indent = &self.raw[..self.raw.len() - self.raw.trim_start().len()];
+    let layout = if self.raw.is_empty() { FrameLayout::V8 } else { FrameLayout::detect(&self.raw).0 };
+    let location = format!("{}:{}:{}", frame.file, frame.line, frame.column);
+    Some(match (layout, frame.function.as_deref()) {
+      (FrameLayout::V8, Some(function)) => format!("{}at {} ({})", indent, function, location),
+      (FrameLayout::V8, None) => format!("{}at {}", indent, location),
+      (FrameLayout::AtSign, function) | (FrameLayout::Location, function @ Some(_)) => {
+        format!("{}{}@{}", indent, function.unwrap_or(""), location)
+      },
+      (FrameLayout::Location, None) => format!("{}{}", indent, location)
+    })
+  }
+
+  // Replaces the position with the original one, returning false if it is not mapped
+  fn remap(&mut self, cache: &Cache) -> bool {
+    let mapping = cache.mapping_for_generated_position(self.line, self.column.saturating_sub(1));
+    if mapping.source.is_empty() || mapping.generated.line != self.line {
+      return false;
+    }
+    if !mapping.name.is_empty() {
+      self.function = Some(mapping.name.to_string());
+    }
+    self.file = mapping.source.to_string();
+    self.line = mapping.original.line;
+    self.column = mapping.original.column + 1;
+    true
+  }
+}
+
+impl Cache {
+  /**
+   * Rewrites the frames in the generated file of the cache with their original source,
+   * line, column and function name, like `StackFrame::symbolicate`, and returns how many
+   * were rewritten. If the cache does not name its generated file, every frame is looked
+   * up. Frames in other files, and frames at unmapped positions, are left unchanged,
+   * and `raw` always keeps the line the frame was parsed from.
+   *
+   * # Examples
+   *
+   * ```
+   * use js_source_mapper::{AnyStackTraceParser, StackTraceParser, consume};
+   *
+   * let cache = consume(r#"{ "version": 3, "file": "bundle.min.js", "sources": ["src/app.js"], "names": ["handleClick"], "mappings": "AAyDYA" }"#).unwrap();
+   * let mut frames = AnyStackTraceParser.parse_frames("    at r (https://cdn.example.com/bundle.min.js:1:1)\n    at https://cdn.example.com/vendor.js:1:1");
+   * assert!(cache.remap_frames(&mut frames) == 1);
+   * assert!(frames[0].function.as_deref() == Some("handleClick") && frames[0].file == "src/app.js");
+   * assert!((frames[0].line, frames[0].column) == (58, 13));
+   * assert!(frames[1].file == "https://cdn.example.com/vendor.js");
+   * ```
+   */
+  pub fn remap_frames(&self, frames: &mut [StackFrame]) -> usize {
+    frames.iter_mut()
+      .filter(|frame| self.file.is_empty() || frame.is_in_file(&self.file))
+      .map(|frame| frame.remap(self))
+      .filter(|&remapped| remapped)
+      .count()
+  }
+}
+
+// Finds the parenthesis opening the group closed by the last character
+fn matching_open_paren(text: &str) -> Option<usize> {
+  let mut depth = 0;
+  for (index, character) in text.char_indices().rev() {
+    match character {
+      ')' => depth += 1,
+      '(' => {
+        depth -= 1;
+        if depth == 0 {
+          return Some(index);
+        }
+      },
+      _ => {}
+    }
+  }
+  None
+}
+
+// Splits "file:line:column", rejecting locations like "eval at f (a.js:1:2), <anonymous>"
+fn parse_location(location: &str) -> Option<(&str, u32, u32)> {
+  let mut parts = location.rsplitn(3, ':');
+  let column = parts.next()?.parse().ok()?;
+  let line = parts.next()?.parse().ok()?;
+  let file = parts.next()?;
+  if file.is_empty() || file.contains(|character: char| character.is_whitespace() || character == '(' || character == ')') {
+    return None;
+  }
+  Some((file, line, column))
+}
+
+#[cfg(test)]
+use consume::consume;
+
+#[test]
+fn it_parses_v8_frames() {
+  let frame = StackFrame::parse("    at Object.<anonymous> (/srv/app/dist/server.js:10:7)").unwrap();
+  assert_eq!(frame.function.as_deref(), Some("Object.<anonymous>"));
+  assert_eq!((frame.file.as_str(), frame.line, frame.column), ("/srv/app/dist/server.js", 10, 7));
+
+  let frame = StackFrame::parse("  at https://cdn.example.com/bundle.min.js:1:2837").unwrap();
+  assert_eq!(frame.function, None);
+  assert_eq!((frame.file.as_str(), frame.line, frame.column), ("https://cdn.example.com/bundle.min.js", 1, 2837));
+
+  assert_eq!(StackFrame::parse("TypeError: Cannot read properties of undefined (reading 'x')"), None);
+  assert_eq!(StackFrame::parse("    at new Promise (<anonymous>)"), None);
+  assert_eq!(StackFrame::parse("    at async Promise.all (index 0)"), None);
+  assert_eq!(StackFrame::parse("    at eval (eval at run (https://cdn.example.com/bundle.min.js:1:5), <anonymous>:1:1)"), None);
+}
+
+#[test]
+fn it_parses_firefox_and_safari_frames() {
+  let frame = StackFrame::parse("handleClick/<@https://cdn.example.com/bundle.min.js:1:2837").unwrap();
+  assert_eq!(frame.function.as_deref(), Some("handleClick/<"));
+  assert_eq!((frame.file.as_str(), frame.line, frame.column), ("https://cdn.example.com/bundle.min.js", 1, 2837));
+
+  let frame = StackFrame::parse("@https://cdn.example.com/bundle.min.js:1:2837").unwrap();
+  assert_eq!(frame.function, None);
+
+  let frame = StackFrame::parse("https://cdn.example.com/bundle.min.js:1:2837").unwrap();
+  assert_eq!((frame.function.as_deref(), frame.line, frame.column), (None, 1, 2837));
+
+  assert_eq!(StackFrame::parse("forEach@[native code]"), None);
+  assert_eq!(StackFrame::parse("eval code"), None);
+  assert_eq!(StackFrame::parse("f@https://cdn.example.com/bundle.min.js line 2 > eval:1:5"), None);
+}
+
+#[test]
+fn it_only_parses_the_frames_of_the_format() {
+  let v8 = "    at f (https://cdn.example.com/bundle.min.js:1:2)";
+  let firefox = "f@https://cdn.example.com/bundle.min.js:1:2";
+  let safari = "https://cdn.example.com/bundle.min.js:1:2";
+  let parsed = |parser: &dyn StackTraceParser| [v8, firefox, safari].iter().map(|line| parser.parse_frame(line).is_some()).collect::<Vec<_>>();
+  assert_eq!(parsed(&V8StackTraceParser), [true, false, false]);
+  assert_eq!(parsed(&FirefoxStackTraceParser), [false, true, false]);
+  assert_eq!(parsed(&SafariStackTraceParser), [false, true, true]);
+  assert_eq!(parsed(&AnyStackTraceParser), [true, true, true]);
+
+  let frames = V8StackTraceParser.parse_frames("Error: oops\n    at f (a.js:1:2)\n    at new Promise (<anonymous>)\n    at a.js:3:4\n");
+  assert_eq!(frames, [
+    StackFrame { function: Some("f".into()), file: "a.js".into(), line: 1, column: 2, raw: "    at f (a.js:1:2)".into() },
+    StackFrame { function: None, file: "a.js".into(), line: 3, column: 4, raw: "    at a.js:3:4".into() }
+  ]);
+}
+
+#[test]
+fn it_matches_frames_by_file() {
+  let frame = StackFrame::parse("    at f (https://cdn.example.com/app/bundle.min.js?v=3:1:1)").unwrap();
+  assert!(frame.is_in_file("bundle.min.js"));
+  assert!(frame.is_in_file("app/bundle.min.js"));
+  assert!(!frame.is_in_file("min.js"));
+  assert!(!frame.is_in_file("vendor.js"));
+}
+
+#[test]
+fn it_symbolicates_v8_frames() {
+  let cache = consume(r#"{ "version": 3, "sources": ["src/app.js"], "names": ["handleClick"], "mappings": "AAAA,MAyDYA;;AAEA" }"#).unwrap();
+  let symbolicate = |line| StackFrame::parse(line).unwrap().symbolicate(&cache);
+  assert_eq!(symbolicate("    at r (bundle.min.js:1:7)"), Some("    at handleClick (src/app.js:58:13)".into()));
+  assert_eq!(symbolicate("    at bundle.min.js:1:1"), Some("    at src/app.js:1:1".into()));
+  assert_eq!(symbolicate("    at r (bundle.min.js:1:1)"), Some("    at r (src/app.js:1:1)".into()));
+  // Line 2 has no mappings, and the next mapping is on line 3
+  assert_eq!(symbolicate("    at r (bundle.min.js:2:1)"), None);
+}
+
+#[test]
+fn it_remaps_frames_in_the_generated_file() {
+  let mut cache = consume(r#"{ "version": 3, "file": "bundle.min.js", "sources": ["src/app.js"], "names": ["handleClick"], "mappings": "AAAA,MAyDYA;;AAEA" }"#).unwrap();
+  let mut frames = vec![
+    StackFrame { function: Some("r".into()), file: "https://cdn.example.com/bundle.min.js".into(), line: 1, column: 7, raw: "".into() },
+    StackFrame { function: Some("r".into()), file: "https://cdn.example.com/bundle.min.js".into(), line: 2, column: 1, raw: "".into() },
+    StackFrame { function: None, file: "https://cdn.example.com/vendor.js".into(), line: 1, column: 1, raw: "".into() }
+  ];
+  let untouched = frames.clone();
+  assert_eq!(frames[0].symbolicate(&cache), Some("at handleClick (src/app.js:58:13)".into()));
+  assert_eq!(cache.remap_frames(&mut frames), 1);
+  assert_eq!(frames[0], StackFrame { function: Some("handleClick".into()), file: "src/app.js".into(), line: 58, column: 13, raw: "".into() });
+  assert_eq!(frames[1..], untouched[1..]);
+
+  // Without a generated file, frames in any file are looked up
+  cache.file.clear();
+  let mut frames = untouched;
+  assert_eq!(cache.remap_frames(&mut frames), 2);
+  assert_eq!((frames[2].file.as_str(), frames[2].line, frames[2].column), ("src/app.js", 1, 1));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn it_reads_frames_from_json() {
+  extern crate serde_json;
+
+  let frames: Vec<StackFrame> = serde_json::from_str(r#"[{ "function": null, "file": "bundle.min.js", "line": 1, "column": 7 }]"#).unwrap();
+  assert_eq!(frames[0], StackFrame { function: None, file: "bundle.min.js".into(), line: 1, column: 7, raw: "".into() });
+  let json = serde_json::to_string(&StackFrame::parse("f@a.js:1:2").unwrap()).unwrap();
+  assert_eq!(json, r#"{"function":"f","file":"a.js","line":1,"column":2,"raw":"f@a.js:1:2"}"#);
+}
+
+#[test]
+fn it_symbolicates_stack_trace_fixtures() {
+  use std::fs;
+  use std::path::Path;
+
+  let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("stack-traces");
+  let cache = consume(&fs::read_to_string(dir.join("bundle.min.js.map")).unwrap()).unwrap();
+  for engine in &["v8", "firefox", "safari"] {
+    let trace = fs::read_to_string(dir.join(format!("{}.txt", engine))).unwrap();
+    let expected = fs::read_to_string(dir.join(format!("{}.expected.txt", engine))).unwrap();
+
+    let symbolicated: Vec<String> = trace.lines().map(|line| {
+      StackFrame::parse(line)
+        .filter(|frame| frame.is_in_file(&cache.file))
+        .and_then(|frame| frame.symbolicate(&cache))
+        .unwrap_or_else(|| line.to_owned())
+    }).collect();
+    assert_eq!(symbolicated, expected.lines().collect::<Vec<_>>(), "{} stack trace", engine);
+  }
+}
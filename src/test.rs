@@ -1,21 +1,222 @@
 extern crate quickcheck;
 
-use self::quickcheck::quickcheck;
+use self::quickcheck::{quickcheck, Arbitrary, Gen};
 
+use base64;
 use base64_vlq;
+use compact::CompactCache;
+use consume::{consume, sort_mappings, Cache, CodePosition, Mapping};
+use generate::Generator;
+
+// A logger keeping the messages of every test, which tests look for their own messages in
+#[cfg(feature = "log")]
+struct CapturingLogger(::std::sync::Mutex<Vec<String>>);
+
+#[cfg(feature = "log")]
+impl ::log::Log for CapturingLogger {
+  fn enabled(&self, _: &::log::Metadata) -> bool {
+    true
+  }
+
+  fn log(&self, record: &::log::Record) {
+    self.0.lock().unwrap().push(format!("{} {}", record.level(), record.args()));
+  }
+
+  fn flush(&self) {}
+}
+
+#[cfg(feature = "log")]
+static LOGGER: CapturingLogger = CapturingLogger(::std::sync::Mutex::new(Vec::new()));
+
+/**
+ * Installs the capturing logger, and returns the messages logged so far by all tests.
+ */
+#[cfg(feature = "log")]
+pub fn captured_logs() -> Vec<String> {
+  static INSTALL: ::std::sync::Once = ::std::sync::Once::new();
+  INSTALL.call_once(|| {
+    ::log::set_logger(&LOGGER).unwrap();
+    ::log::set_max_level(::log::LevelFilter::Trace);
+  });
+  LOGGER.0.lock().unwrap().clone()
+}
+
+const SOURCES: [&str; 4] = ["a.js", "b.js", "lib/c.js", "ünïcode.js"];
+const NAMES: [&str; 3] = ["x", "y", "z"];
+
+// Positions are kept small, so that generated mappings share lines and columns
+impl Arbitrary for CodePosition {
+  fn arbitrary<G: Gen>(g: &mut G) -> CodePosition {
+    CodePosition { line: u8::arbitrary(g) as u32 % 16 + 1, column: u16::arbitrary(g) as u32 }
+  }
+
+  fn shrink(&self) -> Box<dyn Iterator<Item = CodePosition>> {
+    Box::new((self.line - 1, self.column).shrink().map(|(line, column)| CodePosition { line: line + 1, column }))
+  }
+}
+
+// A mapping has a source from SOURCES and maybe a name from NAMES, or neither, in which
+// case its original position is 0:0
+impl Arbitrary for Mapping {
+  fn arbitrary<G: Gen>(g: &mut G) -> Mapping {
+    let generated = CodePosition::arbitrary(g);
+    match SOURCES.get(usize::arbitrary(g) % (SOURCES.len() + 1)) {
+      Some(source) => Mapping {
+        generated,
+        original: CodePosition::arbitrary(g),
+        source: (*source).into(),
+        name: NAMES.get(usize::arbitrary(g) % (NAMES.len() + 1)).map_or("", |name| name).into()
+      },
+      None => Mapping {
+        generated,
+        original: CodePosition { line: 0, column: 0 },
+        source: "".into(),
+        name: "".into()
+      }
+    }
+  }
+
+  fn shrink(&self) -> Box<dyn Iterator<Item = Mapping>> {
+    let mapping = self.clone();
+    Box::new(self.generated.shrink().map(move |generated| Mapping { generated, ..mapping.clone() }))
+  }
+}
+
+/**
+ * Canonically sorted mappings, never empty, with the `sources` and `names` tables a
+ * cache built from them has: the entries in the order they are first referenced.
+ */
+#[derive(Clone, Debug)]
+pub struct ValidMappings {
+  pub mappings: Vec<Mapping>,
+  pub sources: Vec<String>,
+  pub names: Vec<String>
+}
+
+impl ValidMappings {
+  fn new(mut mappings: Vec<Mapping>) -> ValidMappings {
+    sort_mappings(&mut mappings);
+    let table = |entries: Vec<&str>| -> Vec<String> {
+      let mut table: Vec<String> = Vec::new();
+      for entry in entries {
+        if !entry.is_empty() && !table.iter().any(|existing| existing == entry) {
+          table.push(entry.to_owned());
+        }
+      }
+      table
+    };
+    let sources = table(mappings.iter().map(|mapping| &*mapping.source).collect());
+    let names = table(mappings.iter().map(|mapping| &*mapping.name).collect());
+    ValidMappings { mappings, sources, names }
+  }
+}
+
+impl Arbitrary for ValidMappings {
+  fn arbitrary<G: Gen>(g: &mut G) -> ValidMappings {
+    let mut mappings: Vec<Mapping> = Arbitrary::arbitrary(g);
+    mappings.push(Mapping::arbitrary(g));
+    ValidMappings::new(mappings)
+  }
+
+  fn shrink(&self) -> Box<dyn Iterator<Item = ValidMappings>> {
+    Box::new(self.mappings.shrink().filter(|mappings| !mappings.is_empty()).map(ValidMappings::new))
+  }
+}
+
+// quickcheck only generates small numbers, so shift them across the whole i32 range,
+// and always include the extremes
+fn spread_i32(x: i32, shift: u8) -> i32 {
+  match shift % 34 {
+    32 => i32::MIN,
+    33 => i32::MAX,
+    shift => x.rotate_left(shift as u32)
+  }
+}
 
 #[test]
 fn quickcheck_base64_vlq_converts_to_vql_and_back() {
-  fn prop(x: i32) -> bool {
-    base64_vlq::from_vql(base64_vlq::to_vql(x)) == x
+  fn prop(x: i32, shift: u8) -> bool {
+    let x = spread_i32(x, shift);
+    base64_vlq::from_vql(base64_vlq::to_vql(x)) == x as i64
   }
-  quickcheck(prop as fn(i32) -> bool);
+  quickcheck(prop as fn(i32, u8) -> bool);
 }
 
 #[test]
 fn quickcheck_base64_vlq_encodes_and_decodes_some_numbers() {
-  fn prop(x: i32) -> bool {
+  fn prop(x: i32, shift: u8) -> bool {
+    let x = spread_i32(x, shift);
     base64_vlq::encode(x).and_then(|x| base64_vlq::decode(&x)).unwrap().0 == x
   }
-  quickcheck(prop as fn(i32) -> bool);
+  quickcheck(prop as fn(i32, u8) -> bool);
+}
+
+#[test]
+fn quickcheck_base64_vlq_encodes_and_decodes_64_bit_numbers() {
+  fn prop(x: i64) -> bool {
+    let encoded = base64_vlq::encode64(x);
+    base64_vlq::decode64(&encoded) == Some((x, encoded.len()))
+  }
+  quickcheck(prop as fn(i64) -> bool);
+}
+
+#[test]
+fn quickcheck_base64_encodes_and_decodes_bytes() {
+  fn prop(bytes: Vec<u8>) -> bool {
+    base64::decode_bytes(base64::encode_bytes(&bytes).as_bytes()) == Some(bytes)
+  }
+  quickcheck(prop as fn(Vec<u8>) -> bool);
+}
+
+#[test]
+fn quickcheck_generated_maps_round_trip_through_consume() {
+  fn prop(valid: ValidMappings) -> bool {
+    let mut generator = Generator::new();
+    for mapping in &valid.mappings {
+      generator.add_mapping(mapping.generated.clone(), mapping.original.clone(), &mapping.source, &mapping.name);
+    }
+    let cache = consume(&generator.to_json().unwrap()).unwrap();
+    cache.mappings() == &valid.mappings[..] && cache.sources() == &valid.sources[..] && cache.names() == &valid.names[..]
+  }
+  quickcheck(prop as fn(ValidMappings) -> bool);
+}
+
+#[test]
+fn quickcheck_lookups_return_one_of_the_mappings() {
+  fn prop(valid: ValidMappings, position: CodePosition) -> bool {
+    let cache = Cache::from_mappings(valid.mappings.clone(), "").unwrap();
+    let found = cache.mapping_for_generated_position(position.line, position.column);
+    let exact = valid.mappings.iter().any(|mapping| mapping.generated == position);
+    valid.mappings.contains(&found) && (!exact || found.generated == position)
+  }
+  quickcheck(prop as fn(ValidMappings, CodePosition) -> bool);
+}
+
+#[test]
+fn quickcheck_caches_have_the_tables_of_their_mappings() {
+  fn prop(valid: ValidMappings) -> bool {
+    let cache = Cache::from_mappings(valid.mappings.clone(), "").unwrap();
+    cache.mappings() == &valid.mappings[..] && cache.sources() == &valid.sources[..] && cache.names() == &valid.names[..]
+  }
+  quickcheck(prop as fn(ValidMappings) -> bool);
+}
+
+#[test]
+fn quickcheck_compact_caches_answer_like_caches() {
+  fn prop(valid: ValidMappings, interval: u8) -> bool {
+    let mut generator = Generator::new();
+    for mapping in &valid.mappings {
+      generator.add_mapping(mapping.generated.clone(), mapping.original.clone(), &mapping.source, &mapping.name);
+    }
+    let json = generator.to_json().unwrap();
+    let cache = consume(&json).unwrap();
+    let compact = CompactCache::with_checkpoint_interval(&json, interval as usize % 8 + 1).unwrap();
+    // Every line, and every column around the mappings
+    (0..19).all(|line| {
+      compact.mappings_for_generated_line(line) == cache.mappings_for_generated_line(line) &&
+        valid.mappings.iter().flat_map(|mapping| [mapping.generated.column.saturating_sub(1), mapping.generated.column, mapping.generated.column + 1])
+          .all(|column| compact.mapping_for_generated_position(line, column) == cache.mapping_for_generated_position(line, column))
+    })
+  }
+  quickcheck(prop as fn(ValidMappings, u8) -> bool);
 }
@@ -0,0 +1,371 @@
+extern crate serde_json;
+
+use self::serde_json::Value;
+
+use base64_vlq;
+use mappings::invalid_vlq_error;
+
+/**
+ * How serious a problem found by `validate` is.
+ */
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Severity {
+  /** The source map is accepted, but likely to be mapped wrongly by some consumers */
+  Warning,
+  /** The source map is invalid and rejected by `consume` */
+  Error
+}
+
+/**
+ * A problem found by `validate`.
+ */
+#[derive(Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Finding {
+  /** How serious the problem is */
+  pub severity: Severity,
+  /** The generated line of the problem, for problems in the mappings */
+  pub line: Option<u32>,
+  /** The index of the segment within its generated line, starting from 0 */
+  pub segment: Option<u32>,
+  /** A description of the problem */
+  pub message: String
+}
+
+/**
+ * Checks a source map and returns every problem found, in the order they appear, instead
+ * of stopping at the first one like `consume` does. A source map without findings of
+ * `Severity::Error` is accepted by `consume`.
+ *
+ * The checks cover the JSON structure of the source map, the VLQ encoding of the
+ * mappings, source and name indices, the order of the segments within each line, and
+ * the alignment of `sourcesContent` and `ignoreList` with the `sources` table. The
+ * sections of index maps are checked recursively. Mappings
+ * after an undecodable segment are not checked, as the relative fields that follow it
+ * cannot be resolved.
+ *
+ * # Examples
+ *
+ * ```
+ * use js_source_mapper::{validate, Severity};
+ *
+ * let findings = validate(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA;ACAA" }"#);
+ * assert!(findings.len() == 1);
+ * assert!(findings[0].severity == Severity::Error);
+ * assert!(findings[0].line == Some(2));
+ * ```
+ */
+pub fn validate(json: &str) -> Vec<Finding> {
+  let mut findings: Vec<Finding> = Vec::new();
+  let source_map: Value = match serde_json::from_str(json) {
+    Ok(source_map) => source_map,
+    Err(err) => {
+      findings.push(error(None, None, format!("Invalid JSON: {}", err)));
+      return findings;
+    }
+  };
+  if check_source_map(&source_map, &mut findings) == Some(0) {
+    findings.push(error(None, None, "Source Map contains no mappings".into()));
+  }
+  findings
+}
+
+// Checks a source map or index map, and returns its number of mappings if they could be
+// checked
+fn check_source_map(source_map: &Value, findings: &mut Vec<Finding>) -> Option<usize> {
+  if !source_map.is_object() {
+    findings.push(error(None, None, "The source map is not a JSON object".into()));
+    return None;
+  }
+
+  match source_map.get("version") {
+    Some(version) if version.as_u64() == Some(3) => {},
+    Some(version) => findings.push(error(None, None, format!("Unsupported version {}, only version 3 is implemented", version))),
+    None => findings.push(error(None, None, "Missing version".into()))
+  }
+  for field in &["file", "sourceRoot"] {
+    if source_map.get(*field).is_some_and(|value| !value.is_string() && !value.is_null()) {
+      findings.push(error(None, None, format!("{} is not a string", field)));
+    }
+  }
+  if let Some(sections) = source_map.get("sections") {
+    return check_sections(sections, findings);
+  }
+
+  let sources_length = string_table(source_map, "sources", findings);
+  let names_length = string_table(source_map, "names", findings);
+  if let Some(sources_length) = sources_length {
+    check_sources_content(source_map, sources_length, findings);
+    check_ignore_list(source_map, sources_length, findings);
+  }
+
+  match source_map.get("mappings") {
+    Some(Value::String(mappings)) => match (sources_length, names_length) {
+      (Some(sources_length), Some(names_length)) => check_mappings(mappings, sources_length, names_length, findings),
+      _ => None
+    },
+    Some(_) => {
+      findings.push(error(None, None, "mappings is not a string".into()));
+      None
+    },
+    None => {
+      findings.push(error(None, None, "Missing mappings".into()));
+      None
+    }
+  }
+}
+
+// Checks the sections of an index map. Findings in the map of a section are prefixed with
+// the index of the section, and their lines are relative to the section.
+fn check_sections(sections: &Value, findings: &mut Vec<Finding>) -> Option<usize> {
+  let sections = match sections.as_array() {
+    Some(sections) => sections,
+    None => {
+      findings.push(error(None, None, "sections is not an array".into()));
+      return None;
+    }
+  };
+
+  let mut mapping_count = Some(0);
+  let mut previous_offset: Option<(u64, u64)> = None;
+  for (index, section) in sections.iter().enumerate() {
+    let offset = section.get("offset").and_then(|offset| Some((offset.get("line")?.as_u64()?, offset.get("column")?.as_u64()?)));
+    match offset {
+      Some(offset) if previous_offset.is_some_and(|previous| offset <= previous) => {
+        findings.push(error(None, None, format!("sections[{}] does not start after the previous section", index)));
+      },
+      Some(offset) => previous_offset = Some(offset),
+      None => findings.push(error(None, None, format!("sections[{}] has no valid offset", index)))
+    }
+
+    let map = match (section.get("map"), section.get("url")) {
+      (Some(map), _) => map,
+      (None, Some(_)) => {
+        findings.push(error(None, None, format!("sections[{}] refers to a url, which is not supported", index)));
+        mapping_count = None;
+        continue;
+      },
+      (None, None) => {
+        findings.push(error(None, None, format!("sections[{}] has no map", index)));
+        mapping_count = None;
+        continue;
+      }
+    };
+    let mut section_findings: Vec<Finding> = Vec::new();
+    let section_mapping_count = check_source_map(map, &mut section_findings);
+    mapping_count = mapping_count.and_then(|count| Some(count + section_mapping_count?));
+    findings.extend(section_findings.into_iter().map(|finding| Finding {
+      message: format!("sections[{}]: {}", index, finding.message),
+      ..finding
+    }));
+  }
+  mapping_count
+}
+
+fn error(line: Option<u32>, segment: Option<u32>, message: String) -> Finding {
+  Finding { severity: Severity::Error, line, segment, message }
+}
+
+fn warning(line: Option<u32>, segment: Option<u32>, message: String) -> Finding {
+  Finding { severity: Severity::Warning, line, segment, message }
+}
+
+// Checks that a table is an array of strings, and returns its length
+fn string_table(source_map: &Value, field: &str, findings: &mut Vec<Finding>) -> Option<usize> {
+  let entries = match source_map.get(field) {
+    Some(Value::Array(entries)) => entries,
+    Some(_) => {
+      findings.push(error(None, None, format!("{} is not an array", field)));
+      return None;
+    },
+    None => {
+      findings.push(error(None, None, format!("Missing {}", field)));
+      return None;
+    }
+  };
+
+  for (index, entry) in entries.iter().enumerate() {
+    if !entry.is_string() {
+      findings.push(error(None, None, format!("{}[{}] is not a string", field, index)));
+    }
+  }
+  Some(entries.len())
+}
+
+fn check_sources_content(source_map: &Value, sources_length: usize, findings: &mut Vec<Finding>) {
+  match source_map.get("sourcesContent") {
+    Some(Value::Array(contents)) => {
+      if contents.len() != sources_length {
+        findings.push(warning(None, None, format!("sourcesContent has {} entries, but there are {} sources", contents.len(), sources_length)));
+      }
+      for (index, content) in contents.iter().enumerate() {
+        if !content.is_string() && !content.is_null() {
+          findings.push(warning(None, None, format!("sourcesContent[{}] is not a string", index)));
+        }
+      }
+    },
+    Some(Value::Null) | None => {},
+    Some(_) => findings.push(warning(None, None, "sourcesContent is not an array".into()))
+  }
+}
+
+fn check_ignore_list(source_map: &Value, sources_length: usize, findings: &mut Vec<Finding>) {
+  let ignore_list = match source_map.get("ignoreList").or_else(|| source_map.get("x_google_ignoreList")) {
+    Some(Value::Array(ignore_list)) => ignore_list,
+    Some(_) => {
+      findings.push(error(None, None, "ignoreList is not an array".into()));
+      return;
+    },
+    None => return
+  };
+  for entry in ignore_list {
+    match entry.as_u64() {
+      Some(index) if (index as usize) < sources_length => {},
+      Some(index) => findings.push(warning(None, None, format!("ignoreList refers to source index {} when source list length is {}", index, sources_length))),
+      None => findings.push(error(None, None, format!("ignoreList entry {} is not a source index", entry)))
+    }
+  }
+}
+
+fn check_mappings(mappings: &str, sources_length: usize, names_length: usize, findings: &mut Vec<Finding>) -> Option<usize> {
+  let mut previous_original_line: i64 = 0;
+  let mut previous_original_column: i64 = 0;
+  let mut previous_source: i64 = 0;
+  let mut previous_name: i64 = 0;
+  let mut mapping_count = 0;
+
+  for (generated_line, line) in (1u32..).zip(mappings.as_bytes().split(|&x| x == b';')) {
+    let mut previous_generated_column: i64 = 0;
+    let mut previous_fields: Option<Vec<i64>> = None;
+
+    for (segment_index, segment) in (0u32..).zip(line.split(|&x| x == b',')) {
+      let at = (Some(generated_line), Some(segment_index));
+      if segment.is_empty() {
+        if !line.is_empty() {
+          findings.push(warning(at.0, at.1, "Empty segment".into()));
+        }
+        continue;
+      }
+
+      let mut fields: Vec<i64> = Vec::new();
+      let mut field_start = 0;
+      while field_start < segment.len() {
+        match base64_vlq::decode(&segment[field_start..]) {
+          Some((value, field_length)) => {
+            fields.push(value as i64);
+            field_start += field_length;
+          },
+          None => {
+            findings.push(error(at.0, at.1, invalid_vlq_error(segment, field_start, generated_line)));
+            return None;
+          }
+        }
+      }
+      if fields.len() != 1 && fields.len() != 4 && fields.len() != 5 {
+        findings.push(error(at.0, at.1, format!("Segment {:?} has {} fields, expected 1, 4 or 5", String::from_utf8_lossy(segment), fields.len())));
+        return None;
+      }
+      mapping_count += 1;
+
+      let generated_column = previous_generated_column + fields[0];
+      if generated_column < 0 {
+        findings.push(error(at.0, at.1, format!("Negative generated column {}", generated_column)));
+      } else if fields[0] < 0 {
+        findings.push(warning(at.0, at.1, format!("Segment at column {} is out of order, after column {}", generated_column, previous_generated_column)));
+      } else if fields[0] == 0 && previous_fields.as_ref().is_some_and(|previous| previous.len() == fields.len() && previous[1..].iter().all(|&field| field == 0) && fields[1..].iter().all(|&field| field == 0)) {
+        findings.push(warning(at.0, at.1, format!("Duplicate segment at column {}", generated_column)));
+      }
+      previous_generated_column = generated_column;
+
+      if fields.len() > 1 {
+        previous_source += fields[1];
+        previous_original_line += fields[2];
+        previous_original_column += fields[3];
+        if previous_source < 0 || previous_source as usize >= sources_length {
+          findings.push(error(at.0, at.1, format!("Reference to source index {} when source list length is {}", previous_source, sources_length)));
+        }
+        if previous_original_line < 0 {
+          findings.push(error(at.0, at.1, format!("Negative original line {}", previous_original_line)));
+        }
+        if previous_original_column < 0 {
+          findings.push(error(at.0, at.1, format!("Negative original column {}", previous_original_column)));
+        }
+      }
+      if fields.len() > 4 {
+        previous_name += fields[4];
+        if previous_name < 0 || previous_name as usize >= names_length {
+          findings.push(error(at.0, at.1, format!("Reference to name index {} when name list length is {}", previous_name, names_length)));
+        }
+      }
+      previous_fields = Some(fields);
+    }
+  }
+
+  Some(mapping_count)
+}
+
+#[cfg(test)]
+fn messages(json: &str) -> Vec<(Severity, Option<u32>, Option<u32>, String)> {
+  validate(json).into_iter().map(|finding| (finding.severity, finding.line, finding.segment, finding.message)).collect()
+}
+
+#[test]
+fn it_accepts_valid_source_maps() {
+  assert_eq!(validate(r#"{ "version": 3, "sources": ["a.js"], "sourcesContent": ["let a;"], "names": ["x"], "mappings": "AAAAA,CAAC;;AACA", "ignoreList": [0] }"#), []);
+}
+
+#[test]
+fn it_reports_structural_problems() {
+  assert_eq!(messages(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA""#).len(), 1);
+  assert_eq!(messages(r#"{ "version": 2, "sources": ["a.js", 1], "mappings": 3, "file": false }"#), [
+    (Severity::Error, None, None, "Unsupported version 2, only version 3 is implemented".into()),
+    (Severity::Error, None, None, "file is not a string".into()),
+    (Severity::Error, None, None, "sources[1] is not a string".into()),
+    (Severity::Error, None, None, "Missing names".into()),
+    (Severity::Error, None, None, "mappings is not a string".into())
+  ]);
+  assert_eq!(messages(r#"{ "version": 3, "sources": ["a.js", "b.js"], "sourcesContent": ["let a;"], "names": [], "mappings": "AAAA", "ignoreList": [2] }"#), [
+    (Severity::Warning, None, None, "sourcesContent has 1 entries, but there are 2 sources".into()),
+    (Severity::Warning, None, None, "ignoreList refers to source index 2 when source list length is 2".into())
+  ]);
+}
+
+#[test]
+fn it_reports_problems_in_the_mappings() {
+  assert_eq!(messages(r#"{ "version": 3, "sources": ["a.js"], "names": ["x"], "mappings": "EAAA,DAAA,AAAA;AACAC,AAAA,,F" }"#), [
+    (Severity::Warning, Some(1), Some(1), "Segment at column 1 is out of order, after column 2".into()),
+    (Severity::Warning, Some(1), Some(2), "Duplicate segment at column 1".into()),
+    (Severity::Error, Some(2), Some(0), "Reference to name index 1 when name list length is 1".into()),
+    (Severity::Warning, Some(2), Some(2), "Empty segment".into()),
+    (Severity::Error, Some(2), Some(3), "Negative generated column -2".into())
+  ]);
+  assert_eq!(messages(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA;AA;AAAA" }"#), [
+    (Severity::Error, Some(2), Some(0), "Segment \"AA\" has 2 fields, expected 1, 4 or 5".into())
+  ]);
+  assert_eq!(messages(r#"{ "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA;Ag" }"#), [
+    (Severity::Error, Some(2), Some(0), "Truncated VLQ mapping field at the end of segment \"Ag\" on generated line 2".into())
+  ]);
+  assert_eq!(messages(r#"{ "version": 3, "sources": [], "names": [], "mappings": ";;" }"#), [
+    (Severity::Error, None, None, "Source Map contains no mappings".into())
+  ]);
+}
+
+#[test]
+fn it_checks_index_map_sections() {
+  assert_eq!(validate(r#"{ "version": 3, "sections": [
+    { "offset": { "line": 0, "column": 0 }, "map": { "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA" } },
+    { "offset": { "line": 1, "column": 0 }, "map": { "version": 3, "sources": [], "names": [], "mappings": "" } }
+  ] }"#), []);
+  assert_eq!(messages(r#"{ "version": 3, "sections": [
+    { "offset": { "line": 1, "column": 0 }, "map": { "version": 3, "sources": ["a.js"], "names": [], "mappings": "AAAA;ACAA" } },
+    { "offset": { "line": 0, "column": 0 }, "url": "b.js.map" }
+  ] }"#), [
+    (Severity::Error, Some(2), Some(0), "sections[0]: Reference to source index 1 when source list length is 1".into()),
+    (Severity::Error, None, None, "sections[1] does not start after the previous section".into()),
+    (Severity::Error, None, None, "sections[1] refers to a url, which is not supported".into())
+  ]);
+  assert_eq!(messages(r#"{ "version": 3, "sections": [] }"#), [
+    (Severity::Error, None, None, "Source Map contains no mappings".into())
+  ]);
+}
@@ -0,0 +1,53 @@
+extern crate serde_wasm_bindgen;
+extern crate wasm_bindgen;
+
+use self::wasm_bindgen::prelude::*;
+
+use consume::{Cache, consume};
+
+/**
+ * A `Cache` for JavaScript hosts, such as browser extensions, built with the `wasm`
+ * feature and `wasm-pack`.
+ *
+ * Errors are thrown as JavaScript `Error`s, and mappings are returned as plain objects
+ * of the same shape as the `serde` serialization of `Mapping`:
+ *
+ * ```js
+ * const cache = new WasmCache(json);
+ * cache.mappingForGeneratedPosition(2, 2);
+ * // => { generated: { line: 2, column: 2 }, original: { line: 1, column: 1 },
+ * //      source: "source.js", name: "name1" }
+ * ```
+ */
+#[wasm_bindgen]
+pub struct WasmCache {
+  cache: Cache
+}
+
+#[wasm_bindgen]
+impl WasmCache {
+  /**
+   * Parses a source map from its JSON, like `consume`.
+   */
+  #[wasm_bindgen(constructor)]
+  pub fn new(source_map_json: &str) -> Result<WasmCache, JsError> {
+    consume(source_map_json).map(|cache| WasmCache { cache }).map_err(JsError::from)
+  }
+
+  /**
+   * Returns the mapping of a generated position, like
+   * `Cache::mapping_for_generated_position`. Lines start from 1 and columns from 0.
+   */
+  #[wasm_bindgen(js_name = mappingForGeneratedPosition)]
+  pub fn mapping_for_generated_position(&self, line: u32, column: u32) -> Result<JsValue, JsError> {
+    let mapping = self.cache.mapping_for_generated_position(line, column);
+    serde_wasm_bindgen::to_value(&mapping).map_err(|err| JsError::new(&err.to_string()))
+  }
+
+  /**
+   * Returns the `sources` table of the source map.
+   */
+  pub fn sources(&self) -> Vec<String> {
+    self.cache.sources().to_vec()
+  }
+}
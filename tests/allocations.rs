@@ -0,0 +1,64 @@
+//! Counts the allocations of parsing the same source map over and over, with `consume`
+//! and with a `Parser` kept between calls. A test binary of its own, since it installs a
+//! counting global allocator.
+extern crate js_source_mapper;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use js_source_mapper::{Parser, consume};
+
+struct CountingAllocator;
+
+thread_local! {
+  // Counted per thread, so that the test harness allocating on other threads does not
+  // skew the counts
+  static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+    System.alloc(layout)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    System.dealloc(ptr, layout)
+  }
+
+  unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+    ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+    System.realloc(ptr, layout, new_size)
+  }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations<F: FnMut()>(mut f: F) -> usize {
+  let before = ALLOCATIONS.with(Cell::get);
+  f();
+  ALLOCATIONS.with(Cell::get) - before
+}
+
+#[test]
+fn it_allocates_less_parsing_with_a_parser() {
+  // A bundle of 300 modules, with a few mappings in each
+  let sources: Vec<String> = (0..301).map(|index| format!(r#""webpack://app/src/module{}.js""#, index)).collect();
+  let names: Vec<String> = (0..301).map(|index| format!(r#""name{}""#, index)).collect();
+  let mappings = vec!["AAAAA,CAACC,CCAAA"; 300].join(";");
+  let json = format!(r#"{{ "version": 3, "sources": [{}], "names": [{}], "mappings": "{}" }}"#, sources.join(","), names.join(","), mappings);
+
+  const CALLS: usize = 20;
+  let mut parser = Parser::new();
+  // The first call sizes the buffers of the parser
+  parser.consume(&json).unwrap();
+  let with_parser = allocations(|| for _ in 0..CALLS {
+    parser.consume(&json).unwrap();
+  });
+  let with_consume = allocations(|| for _ in 0..CALLS {
+    consume(&json).unwrap();
+  });
+  println!("Allocations per call: {} with consume, {} with a parser", with_consume / CALLS, with_parser / CALLS);
+  assert!(with_parser < with_consume, "{} allocations with a parser, {} with consume", with_parser, with_consume);
+}
@@ -0,0 +1,30 @@
+// Builds tests/capi/lookup.c against the cdylib and the generated header, and runs it
+#![cfg(feature = "capi")]
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn it_looks_up_positions_from_c() {
+  // The cdylib the test was built with is next to it, in target/<profile>/deps
+  let deps_dir: PathBuf = env::current_exe().unwrap().parent().unwrap().to_owned();
+  let program = deps_dir.join("capi-lookup");
+  let compiled = Command::new(env::var("CC").unwrap_or_else(|_| "cc".into()))
+    .args(["tests/capi/lookup.c", "-Iinclude", "-o"])
+    .arg(&program)
+    .arg("-L")
+    .arg(&deps_dir)
+    .arg(format!("-Wl,-rpath,{}", deps_dir.display()))
+    .args(["-ljs_source_mapper", "-Wall", "-Werror"])
+    .status()
+    .unwrap();
+  assert!(compiled.success());
+
+  // cargo points LD_LIBRARY_PATH at target/<profile>, which may hold a build without
+  // the feature
+  let output = Command::new(&program).arg("tests/cli/app.js.map").env("LD_LIBRARY_PATH", &deps_dir).output().unwrap();
+  assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+  assert_eq!(String::from_utf8(output.stdout).unwrap(), "1:9 -> src/app.js:1:9 (handleClick)\n");
+  assert!(output.status.success());
+}
@@ -0,0 +1,61 @@
+/* Looks up a position in the map given as the first argument through the C API. */
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "js_source_mapper.h"
+
+static char *read_file(const char *path, size_t *len) {
+  FILE *file = fopen(path, "rb");
+  if (file == NULL) {
+    return NULL;
+  }
+  fseek(file, 0, SEEK_END);
+  *len = (size_t)ftell(file);
+  fseek(file, 0, SEEK_SET);
+  char *contents = malloc(*len);
+  if (contents != NULL && fread(contents, 1, *len, file) != *len) {
+    free(contents);
+    contents = NULL;
+  }
+  fclose(file);
+  return contents;
+}
+
+int main(int argc, char **argv) {
+  size_t len = 0;
+  char *json = argc > 1 ? read_file(argv[1], &len) : NULL;
+  if (json == NULL) {
+    fprintf(stderr, "usage: lookup <map-file>\n");
+    return 2;
+  }
+
+  jsm_error err = {0};
+  jsm_cache *cache = jsm_consume(json, len, &err);
+  free(json);
+  if (cache == NULL) {
+    fprintf(stderr, "error %u: %s\n", err.code, jsm_error_message(&err));
+    jsm_string_free(err.message);
+    return 1;
+  }
+
+  jsm_mapping mapping;
+  if (!jsm_lookup(cache, 1, 9, &mapping)) {
+    fprintf(stderr, "error: 1:9 is not mapped\n");
+    jsm_cache_free(cache);
+    return 1;
+  }
+  printf("%u:%u -> %s:%u:%u (%s)\n", mapping.generated_line, mapping.generated_column,
+    mapping.source, mapping.original_line, mapping.original_column, mapping.name);
+
+  /* Null arguments are rejected rather than dereferenced */
+  jsm_error null_err = {0};
+  if (jsm_consume(NULL, 0, &null_err) != NULL || null_err.status != JSM_NULL_ARGUMENT || jsm_lookup(NULL, 1, 0, &mapping)) {
+    fprintf(stderr, "error: null arguments were not rejected\n");
+    return 1;
+  }
+  jsm_string_free(null_err.message);
+
+  jsm_cache_free(cache);
+  return 0;
+}
@@ -0,0 +1,140 @@
+//! Runs the command line tool against the fixture maps in tests/cli.
+extern crate assert_cmd;
+
+use assert_cmd::Command;
+
+fn js_source_mapper() -> Command {
+  Command::cargo_bin("js-source-mapper").unwrap()
+}
+
+#[test]
+fn it_looks_up_positions() {
+  js_source_mapper()
+    .args(["tests/cli/app.js.map", "1:9", "2:1"])
+    .assert()
+    .success()
+    .stdout("1:9 -> src/app.js:1:9 (handleClick)\n2:1 -> src/app.js:2:3 (save)\n");
+  // lookup is also the default command
+  js_source_mapper()
+    .args(["lookup", "tests/cli/app.js.map", "1:9"])
+    .assert()
+    .success()
+    .stdout("1:9 -> src/app.js:1:9 (handleClick)\n");
+}
+
+#[test]
+fn it_finds_maps_through_source_mapping_url() {
+  js_source_mapper()
+    .args(["tests/cli/app.js", "2:1"])
+    .assert()
+    .success()
+    .stdout("2:1 -> src/app.js:2:3 (save)\n");
+  js_source_mapper()
+    .args(["--map", "tests/cli/app.changed.js.map", "tests/cli/app.js", "2:2"])
+    .assert()
+    .success()
+    .stdout("2:2 -> src/app.js:2:2\n");
+}
+
+#[test]
+fn it_applies_global_options_anywhere() {
+  js_source_mapper()
+    .args(["dump", "tests/cli/app.js.map", "--source-root", "/checkout/", "--line", "2"])
+    .assert()
+    .success()
+    .stdout("2:0 -> /checkout/src/app.js:2:2\n2:2 -> /checkout/src/app.js:2:3 (save)\n");
+  js_source_mapper()
+    .args(["lookup", "--json", "tests/cli/app.js.map", "1:9"])
+    .assert()
+    .success()
+    .stdout(concat!(
+      r#"{"generated":{"line":1,"column":9},"original":{"line":1,"column":9},"#,
+      r#""source":"src/app.js","name":"handleClick","matched":true}"#, "\n"
+    ));
+}
+
+#[test]
+fn it_looks_up_names() {
+  js_source_mapper()
+    .args(["lookup-name", "tests/cli/app.js.map", "save"])
+    .assert()
+    .success()
+    .stdout("2:2 -> src/app.js:2:3 (save)\n");
+  js_source_mapper()
+    .args(["--json", "lookup-name", "tests/cli/app.js.map", "handleClick", "--source", "src/app.js"])
+    .assert()
+    .success()
+    .stdout(concat!(
+      r#"[{"generated":{"line":1,"column":9},"original":{"line":1,"column":9},"#,
+      r#""source":"src/app.js","name":"handleClick"}]"#, "\n"
+    ));
+  js_source_mapper()
+    .args(["lookup-name", "tests/cli/app.js.map", "save", "--source", "src/other.js"])
+    .assert()
+    .code(1);
+}
+
+#[test]
+fn it_prints_stats_as_json() {
+  let output = js_source_mapper()
+    .args(["--json", "stats", "tests/cli/app.js.map"])
+    .assert()
+    .success()
+    .get_output()
+    .stdout
+    .clone();
+  let output = String::from_utf8(output).unwrap();
+  assert!(output.starts_with(r#"{"mappings":4,"generated_lines":2,"#), "{}", output);
+}
+
+#[test]
+fn it_exits_with_the_validation_result() {
+  js_source_mapper()
+    .args(["validate", "tests/cli/app.js.map"])
+    .assert()
+    .code(0)
+    .stdout("tests/cli/app.js.map is valid\n");
+  js_source_mapper()
+    .args(["validate", "tests/cli/invalid.js.map"])
+    .assert()
+    .code(2);
+}
+
+#[test]
+fn it_exits_with_the_diff_result() {
+  js_source_mapper()
+    .args(["diff", "tests/cli/app.js.map", "tests/cli/app.js.map"])
+    .assert()
+    .code(0);
+  js_source_mapper()
+    .args(["diff", "tests/cli/app.js.map", "tests/cli/app.changed.js.map"])
+    .assert()
+    .code(1)
+    .stdout("mappings: 0 added, 1 removed, 0 changed, 3 unchanged\n");
+}
+
+#[test]
+fn it_reports_errors_consistently() {
+  js_source_mapper()
+    .args(["tests/cli/missing.js.map", "1:0"])
+    .assert()
+    .code(1)
+    .stdout("");
+  let output = js_source_mapper()
+    .args(["--json", "tests/cli/missing.js.map", "1:0"])
+    .assert()
+    .code(1)
+    .get_output()
+    .stderr
+    .clone();
+  let output = String::from_utf8(output).unwrap();
+  assert!(output.starts_with(r#"{"error":"could not read tests/cli/missing.js.map: "#), "{}", output);
+  js_source_mapper()
+    .args(["--json", "symbolicate", "tests/cli/app.js.map"])
+    .assert()
+    .code(2);
+  js_source_mapper()
+    .args(["dump", "--bogus", "tests/cli/app.js.map"])
+    .assert()
+    .code(2);
+}
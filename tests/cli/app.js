@@ -0,0 +1,3 @@
+function a(){
+b()}
+//# sourceMappingURL=app.js.map
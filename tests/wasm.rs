@@ -0,0 +1,27 @@
+// Run with wasm-pack test --node -- --features wasm
+#![cfg(all(feature = "wasm", target_arch = "wasm32"))]
+
+extern crate js_source_mapper;
+extern crate serde_wasm_bindgen;
+extern crate wasm_bindgen_test;
+
+use js_source_mapper::{CodePosition, Mapping, WasmCache};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test]
+fn it_looks_up_positions_from_javascript() {
+  let cache = WasmCache::new(include_str!("cli/app.js.map")).unwrap();
+  let mapping: Mapping = serde_wasm_bindgen::from_value(cache.mapping_for_generated_position(1, 9).unwrap()).unwrap();
+  assert_eq!(mapping, Mapping {
+    generated: CodePosition { line: 1, column: 9 },
+    original: CodePosition { line: 1, column: 9 },
+    source: "src/app.js".into(),
+    name: "handleClick".into()
+  });
+  assert_eq!(cache.sources(), ["src/app.js"]);
+}
+
+#[wasm_bindgen_test]
+fn it_throws_on_invalid_maps() {
+  assert!(WasmCache::new(r#"{ "version": 2 }"#).is_err());
+}